@@ -1,5 +1,9 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use crate::application::cli::commands::Command;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
 use std::path::PathBuf;
+use crate::services::filesystem::file_compression::{self, ArchiveFormat};
+use crate::utils::display::{SizeStyle, TimeStyle};
 
 #[derive(Debug, Clone)]
 pub enum OperationMode {
@@ -7,29 +11,287 @@ pub enum OperationMode {
         items: Vec<PathBuf>,
         name: Option<String>,
         copy: bool,
+        hardlink: bool,
+        hardlink_or_copy: bool,
+        no_hash: bool,
+        compress: bool,
+        strict: bool,
+        ignore_hidden: bool,
+        include_hidden: bool,
+        min_age: Option<chrono::Duration>,
+        max_age: Option<chrono::Duration>,
+        no_reflink: bool,
+        sort_items_by: Option<ItemSort>,
+        quiet: bool,
+        force: bool,
+        restore_message: Option<String>,
+        meta: Vec<(String, String)>,
+        meta_file: Option<PathBuf>,
+        checksum_file: Option<Option<PathBuf>>,
+        pre_command: Option<String>,
+        verbose: bool,
+        version_tag: Option<usize>,
+        jobs: Option<usize>,
+        format: OutputFormat,
     },
     Pop {
         identifier: Option<String>,
         copy: bool,
         force: bool,
         restore: bool,
+        missing_ok: bool,
+        verify_dest: bool,
+        git_aware: bool,
+        post_command: Option<String>,
+        no_reflink: bool,
+        quiet: bool,
+        only: Vec<glob::Pattern>,
+        strict: bool,
+        backup: bool,
+        resolve_to: Option<String>,
+        version: Option<u32>,
+        jobs: Option<usize>,
+        format: OutputFormat,
+    },
+    PopToArchive {
+        identifier: Option<String>,
+        archive: PathBuf,
+        format: ArchiveFormat,
+        force: bool,
+    },
+    Dump {
+        to: Option<PathBuf>,
+        delete: bool,
+    },
+    List {
+        watch: bool,
+        since_last_pop: bool,
+        since_last_git_commit: bool,
+        page: Option<usize>,
+        per_page: usize,
+        columns: Vec<ListColumn>,
+        disk_usage: bool,
+        verbose: bool,
+        time: TimeStyle,
+        sizes: SizeStyle,
+        filter_meta: Vec<(String, String)>,
+        total: bool,
+        format: OutputFormat,
+    },
+    ExportPaths {
+        entry: Option<String>,
+        filter: Option<glob::Pattern>,
+        format: OutputFormat,
+    },
+    Search {
+        pattern: String,
+        time: TimeStyle,
+        sizes: SizeStyle,
+        format: OutputFormat,
+    },
+    Find {
+        path: PathBuf,
+        format: OutputFormat,
     },
-    Dump,
-    List,
-    Search(String),
     Info {
         identifier: Option<String>,
+        hash_check: bool,
+        disk_usage: bool,
+        time: TimeStyle,
+        sizes: SizeStyle,
+        strict: bool,
+        format: OutputFormat,
+    },
+    Peek {
+        identifier: Option<String>,
+        force: bool,
+        list_only: bool,
+        only: Vec<glob::Pattern>,
+        sizes: SizeStyle,
+    },
+    History {
+        diff: bool,
+    },
+    Clean {
+        days: i64,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+        keep_latest: Option<usize>,
+        filter_meta: Vec<(String, Option<String>)>,
+        dry_run: bool,
+        format: OutputFormat,
     },
-    History,
-    Clean(i64),
     Rename {
         old: String,
         new: String,
     },
-    Tar(PathBuf),
-    Init,
+    RenameUuid {
+        uuid: uuid::Uuid,
+        new: String,
+    },
+    SwapNames {
+        first: String,
+        second: String,
+    },
+    NameHistory {
+        identifier: String,
+    },
+    RevertName {
+        identifier: String,
+    },
+    Edit {
+        identifier: String,
+        path: PathBuf,
+        force: bool,
+    },
+    CheckSpace {
+        identifier: String,
+        destination: Option<PathBuf>,
+        copy: bool,
+    },
+    Tar {
+        path: PathBuf,
+        resume: bool,
+        jobs: usize,
+    },
+    PushClipboard {
+        name: Option<String>,
+    },
+    Import {
+        source: String,
+        sha256: Option<String>,
+    },
+    PruneJournal,
+    Health,
+    Status {
+        time: TimeStyle,
+        sizes: SizeStyle,
+        format: OutputFormat,
+    },
+    MoveStashDir {
+        destination: PathBuf,
+        merge: bool,
+        dry_run: bool,
+    },
+    Rehash(String),
+    /// Push the items of a template entry again from the current directory;
+    /// `push` is the push the rest of the flags describe, minus its items
+    PushFromTemplate {
+        template_name: String,
+        push: Box<OperationMode>,
+    },
+    MarkTemplate {
+        identifier: String,
+        is_template: bool,
+    },
+    Delete {
+        targets: Vec<String>,
+        force: bool,
+    },
+    Sync {
+        remote: String,
+        direction: SyncDirection,
+        dry_run: bool,
+    },
+    Raw(RawTarget),
+    Benchmark {
+        files: usize,
+        size_mb: u64,
+    },
+    Init {
+        local: bool,
+        force: bool,
+    },
+    Config {
+        key: Option<String>,
+        value: Option<String>,
+    },
 }
 
+/// How command results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable, may change between releases
+    Human,
+    /// Stable tab-separated lines for scripts
+    Porcelain,
+    /// JSON document (--clean and --export-paths only)
+    Json,
+}
+
+/// Item orders `--sort-items-by` accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ItemSort {
+    /// By stashed path only
+    Unsorted,
+    /// By original path
+    Name,
+    /// Largest first
+    Size,
+    /// Most recently modified first
+    Mtime,
+}
+
+/// Fields `--list --columns` can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ListColumn {
+    Uuid,
+    Name,
+    Size,
+    /// Time since the entry was pushed (creation time with --porcelain)
+    Age,
+    /// Number of stashed items
+    Items,
+}
+
+/// On-disk state that `--raw` can dump
+#[derive(Debug, Clone, ValueEnum)]
+pub enum RawTarget {
+    Index,
+    Journal,
+    Config,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyncDirection {
+    Push,
+    Pull,
+}
+
+
+impl Cli {
+    pub fn output_format(&self) -> OutputFormat {
+        if self.porcelain {
+            OutputFormat::Porcelain
+        } else if self.json {
+            OutputFormat::Json
+        } else {
+            self.output.unwrap_or(OutputFormat::Human)
+        }
+    }
+
+    /// The time style --relative or --absolute asked for, else the command's own
+    pub fn time_style(&self, default: TimeStyle) -> TimeStyle {
+        if self.relative {
+            TimeStyle::Relative
+        } else if self.absolute {
+            TimeStyle::Absolute
+        } else {
+            default
+        }
+    }
+
+    /// The size style --bytes or --si asked for, else powers of 1024
+    pub fn size_style(&self) -> SizeStyle {
+        if self.bytes {
+            SizeStyle::Bytes
+        } else if self.si {
+            SizeStyle::Si
+        } else {
+            SizeStyle::Human
+        }
+    }
+}
 
 #[derive(Parser)]
 #[command(name = "stash")]
@@ -39,54 +301,445 @@ pub enum OperationMode {
     stash file.txt          # Stash if exists, restore if in stash\n  \
     stash                   # Restore most recent entry\n  \
     stash --name work src/  # Stash with custom name\n  \
-    stash --list            # Show all entries")]
+    stash --list            # Show all entries\n  \
+    stash pop work          # Or name the operation with a command")]
 #[command(version)]
+#[command(args_conflicts_with_subcommands = true)]
 #[command(group(
     clap::ArgGroup::new("operation")
-        .args(&["list", "search", "info", "history", "init", "clean", "rename", "tar", "dump"])
+        .args(&["list", "search", "find", "info", "history", "init", "clean", "rename", "name_history", "revert_name", "edit", "check_space", "tar", "read_from_clipboard", "import", "dump", "health", "move_to_stash_dir", "prune_journal", "sync_push", "sync_pull", "raw", "rehash", "benchmark", "status", "peek", "template", "mark_template", "unmark_template"])
         .required(false)
 ))]
 
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
     /// Files or directories to operate on
     pub items: Vec<PathBuf>,
     /// Initialize stash storage and config
     #[arg(long)]
     pub init: bool,
+    /// With --init, initialize the user's stash in ~/.stash (default)
+    #[arg(long, requires = "init", conflicts_with = "local")]
+    pub global: bool,
+    /// With --init, initialize a project stash in ./.stash
+    #[arg(long, requires = "init")]
+    pub local: bool,
     /// Assign a custom name to a stash entry
     #[arg(short, long)]
     pub name: Option<String>,
     /// Search stash entries by name or pattern
     #[arg(short, long)]
     pub search: Option<String>,
+    /// Find the entries holding an item stashed from PATH
+    #[arg(long, value_name = "PATH")]
+    pub find: Option<PathBuf>,
     /// List all stash entries
     #[arg(short, long)]
     pub list: bool,
+    /// With --list, redraw whenever the stash changes
+    #[arg(long, requires = "list")]
+    pub watch: bool,
+    /// With --list, only show entries stashed since the last pop or dump
+    #[arg(long, requires = "list")]
+    pub since_last_pop: bool,
+    /// With --list, only show entries stashed since the last git commit in
+    /// the current directory (or $STASH_GIT_DIR)
+    #[arg(long, requires = "list")]
+    pub since_last_git_commit: bool,
+    /// With --list, show only this page of entries (starting at 1)
+    #[arg(long, value_name = "N", requires = "list", value_parser = clap::value_parser!(u64).range(1..))]
+    pub page: Option<u64>,
+    /// With --list, number of entries per page (default 20; implies --page 1)
+    #[arg(long, value_name = "M", requires = "list", value_parser = clap::value_parser!(u64).range(1..))]
+    pub per_page: Option<u64>,
+    /// With --list, comma-separated columns to show, in order
+    /// (default name,items,size,age; uuid,name,age,size,items with --porcelain)
+    #[arg(long, value_name = "COLS", requires = "list", value_enum, value_delimiter = ',')]
+    pub columns: Vec<ListColumn>,
+    /// With --list, only show entries whose metadata has KEY set to VALUE;
+    /// with --clean --keep-latest, only rank and remove those entries, and a
+    /// bare KEY keeps the latest N for each of its values (repeatable; every
+    /// filter must match)
+    #[arg(long, value_name = "KEY[=VALUE]", value_parser = parse_meta_filter)]
+    pub filter_meta: Vec<(String, Option<String>)>,
+    /// With --list, end with the number, items and combined size of the entries shown
+    #[arg(long, visible_alias = "summary", requires = "list")]
+    pub total: bool,
+    /// With --list, print the original path of every stashed item, one per line
+    #[arg(long, requires = "list")]
+    pub export_paths: bool,
+    /// With --export-paths, only list the items of this entry
+    #[arg(long, value_name = "ID", requires = "export_paths")]
+    pub entry: Option<String>,
+    /// With --export-paths, only list paths matching GLOB
+    #[arg(long, value_name = "GLOB", requires = "export_paths", value_parser = parse_glob)]
+    pub filter: Option<glob::Pattern>,
     /// Show detailed info about a stash entry
     #[arg(short, long)]
     pub info: bool,
-    /// Remove entries older than the given number of days
-    #[arg(long, value_name = "DAYS", default_missing_value = "30")]
-    pub clean: Option<i64>,
-    /// Rename a stash entry (format: OLD:NEW)
-    #[arg(long, value_name = "OLD:NEW", alias = "rn")]
-    pub rename: Option<String>,
+    /// Copy an entry's files into the current directory, leaving it stashed
+    /// (default: the most recent entry)
+    #[arg(long, value_name = "ENTRY", num_args = 0..=1)]
+    pub peek: Option<Option<String>>,
+    /// With --peek, print the entry's items as a tree instead of copying them
+    #[arg(long, requires = "peek")]
+    pub list_only: bool,
+    /// With --info, re-hash stashed data and compare it to stored hashes
+    #[arg(long, requires = "info")]
+    pub hash_check: bool,
+    /// With --list or --info, also show the space entries actually take on disk
+    #[arg(long)]
+    pub disk_usage: bool,
+    /// Show more detail: with --list --disk-usage, every entry's on-disk
+    /// usage, not just the totals; when pushing, the pre-command and its exit code
+    #[arg(long)]
+    pub verbose: bool,
+    /// With --list, --search or --info, show times as ages ("3 hours ago")
+    #[arg(long, conflicts_with = "absolute")]
+    pub relative: bool,
+    /// With --list, --search or --info, show times as timestamps in the configured date_format
+    #[arg(long)]
+    pub absolute: bool,
+    /// With --list, --search or --info, show sizes as exact byte counts
+    #[arg(long, conflicts_with = "si")]
+    pub bytes: bool,
+    /// With --list, --search or --info, show sizes in powers of 1000 (kB, MB)
+    #[arg(long)]
+    pub si: bool,
+    /// Remove entries older than the given number of days (default 30)
+    #[arg(long, value_name = "DAYS", num_args = 0..=1)]
+    pub clean: Option<Option<i64>>,
+    /// With --clean, remove entries created at or after WHEN (up to now, or
+    /// --until) instead of those older than DAYS: a date (2024-01-31), a date
+    /// and time, or a duration ago (30d, 12h)
+    #[arg(long, value_name = "WHEN", requires = "clean", value_parser = parse_point_in_time)]
+    pub since: Option<DateTime<Utc>>,
+    /// With --clean, remove entries created before WHEN instead of those older
+    /// than DAYS
+    #[arg(long, value_name = "WHEN", requires = "clean", value_parser = parse_point_in_time)]
+    pub until: Option<DateTime<Utc>>,
+    /// With --clean, keep only the N most recently created entries and remove
+    /// the rest, whatever their age
+    #[arg(long, value_name = "N", requires = "clean", conflicts_with_all = ["since", "until"],
+          value_parser = clap::value_parser!(u64).range(1..))]
+    pub keep_latest: Option<u64>,
+    /// Rename a stash entry (format: OLD:NEW, escape colons in names as \:;
+    /// or give no value and use --from-uuid/--to or --swap)
+    #[arg(long, value_name = "OLD:NEW", alias = "rn", num_args = 0..=1)]
+    pub rename: Option<Option<String>>,
+    /// With --rename, the UUID of the entry to rename (use with --to)
+    #[arg(long, value_name = "UUID", requires_all = ["rename", "to"], value_parser = uuid::Uuid::parse_str)]
+    pub from_uuid: Option<uuid::Uuid>,
+    /// With --rename --from-uuid, the new name (taken literally, colons
+    /// included); with --dump, the directory to copy every entry into
+    #[arg(long, value_name = "NAME|DIR")]
+    pub to: Option<String>,
+    /// With --rename, swap the names of two entries
+    #[arg(long, num_args = 2, value_names = ["ID1", "ID2"], requires = "rename", conflicts_with = "from_uuid")]
+    pub swap: Vec<String>,
+    /// Show every name an entry has had, from the journal
+    #[arg(long, value_name = "ENTRY")]
+    pub name_history: Option<String>,
+    /// Give an entry back the name it had before its last rename
+    #[arg(long, value_name = "ENTRY")]
+    pub revert_name: Option<String>,
+    /// Edit a stashed file in $VISUAL/$EDITOR (usage: --edit ENTRY FILE)
+    #[arg(long, value_name = "ENTRY")]
+    pub edit: Option<String>,
+    /// Check there is enough free space to pop an entry
+    #[arg(long, value_name = "ENTRY")]
+    pub check_space: Option<String>,
+    /// With --check-space, the directory to check (default: current directory)
+    #[arg(long, value_name = "PATH", requires = "check_space")]
+    pub dest: Option<PathBuf>,
     /// Export all entries to a tar archive
     #[arg(long, value_name = "FILE")]
     pub tar: Option<PathBuf>,
+    /// With --tar, resume an interrupted export, adding only the entries it is missing
+    #[arg(long = "continue", requires = "tar")]
+    pub resume: bool,
+    /// Threads used to copy and move files when pushing or popping (default:
+    /// push_workers from the config); with --tar, entries read into the
+    /// archive at once (default 4)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub jobs: Option<u64>,
+    /// Stash the clipboard's text as a new entry (use --name to name it)
+    #[arg(long)]
+    pub read_from_clipboard: bool,
+    /// Import entries from a --tar archive (file path, http(s) URL, or - for stdin)
+    #[arg(long, value_name = "FILE|URL|-")]
+    pub import: Option<String>,
+    /// With --import, verify the archive against this SHA-256 checksum
+    #[arg(long, value_name = "HEX", requires = "import")]
+    pub sha256: Option<String>,
     /// Restore or delete all stash entries
     #[arg(long)]
     pub dump: bool,
+    /// Delete the entries given as arguments (names, UUIDs or globs over names);
+    /// with --dump --to, remove the entries from the stash once restored
+    #[arg(long)]
+    pub delete: bool,
     /// Show stash operation history
     #[arg(long)]
     pub history: bool,
+    /// With --history, show how each operation changed the number and size of entries
+    #[arg(long, requires = "history")]
+    pub diff: bool,
+    /// Push the items of the template entry NAME again, from the current directory
+    #[arg(long, value_name = "NAME")]
+    pub template: Option<String>,
+    /// Mark an entry as a template for --template
+    #[arg(long, value_name = "ENTRY")]
+    pub mark_template: Option<String>,
+    /// Stop an entry being a template
+    #[arg(long, value_name = "ENTRY")]
+    pub unmark_template: Option<String>,
+    /// Compute missing hashes for an entry pushed with --no-hash
+    #[arg(long, value_name = "ENTRY")]
+    pub rehash: Option<String>,
+    /// Remove journal operations that refer to deleted entries
+    #[arg(long)]
+    pub prune_journal: bool,
+    /// Run self-diagnostics on the stash and print a health report
+    #[arg(long)]
+    pub health: bool,
+    /// Print a short overview of the stash: entries, size, oldest entry, last push
+    #[arg(long)]
+    pub status: bool,
+    /// Move the global stash to DIR and make it the configured stash_dir
+    #[arg(long, value_name = "DIR")]
+    pub move_to_stash_dir: Option<PathBuf>,
+    /// With --move-to-stash-dir, merge into a stash that already exists there
+    #[arg(long, requires = "move_to_stash_dir")]
+    pub merge: bool,
+    /// Upload and merge the stash into a remote (rsync location, e.g. user@host:path)
+    #[arg(long, value_name = "REMOTE")]
+    pub sync_push: Option<String>,
+    /// Download and merge the stash from a remote (rsync location, e.g. user@host:path)
+    #[arg(long, value_name = "REMOTE")]
+    pub sync_pull: Option<String>,
+    /// Dump parsed on-disk state verbatim (for bug reports)
+    #[arg(long, value_enum, value_name = "STATE", hide = true)]
+    pub raw: Option<RawTarget>,
+    /// Time push, pop, hashing and compression on a scratch dataset
+    #[arg(long, hide = true)]
+    pub benchmark: bool,
+    /// With --benchmark, number of files to generate
+    #[arg(long, value_name = "N", default_value_t = 200, requires = "benchmark", hide = true)]
+    pub bench_files: usize,
+    /// With --benchmark, total size of the dataset in MB
+    #[arg(long, value_name = "MB", default_value_t = 64, requires = "benchmark", hide = true)]
+    pub bench_size: u64,
+    /// Show what would change without changing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    /// When popping or peeking, restore only items whose stashed path matches GLOB
+    /// (repeatable; `*` stops at `/`, `**` crosses it)
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    pub only: Vec<glob::Pattern>,
+    /// Don't print the size and time summary after a push or pop
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Print stable, tab-separated output for scripts
+    #[arg(long)]
+    pub porcelain: bool,
+    /// Output format for command results
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "porcelain")]
+    pub output: Option<OutputFormat>,
+    /// Shorthand for --output json
+    #[arg(long, conflicts_with_all = ["porcelain", "output"])]
+    pub json: bool,
+    /// Skip hash computation when pushing (faster, but disables verification)
+    #[arg(long)]
+    pub no_hash: bool,
+    /// Compress the entry's data into a single archive after pushing
+    #[arg(long)]
+    pub compress: bool,
+    /// Fail when pushing named pipes, sockets or device files instead of skipping
+    /// them; with --pop or --info, refuse a manifest that fails validation
+    #[arg(long)]
+    pub strict: bool,
+    /// Leave dotfiles and dot-directories out of the push
+    #[arg(long)]
+    pub ignore_hidden: bool,
+    /// Push hidden files even if ignore_hidden_by_default is set
+    #[arg(long, conflicts_with = "ignore_hidden")]
+    pub include_hidden: bool,
+    /// Only push items last modified at least this long ago (e.g. 30d, 12h, 2w)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub min_age: Option<chrono::Duration>,
+    /// Only push items last modified at most this long ago
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_age: Option<chrono::Duration>,
+    /// Reminder to show when this entry is popped
+    #[arg(long, value_name = "MSG")]
+    pub message_on_restore: Option<String>,
+    /// Attach KEY=VALUE to the entry's metadata (repeatable)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub meta: Vec<(String, String)>,
+    /// Attach the JSON object in FILE as the entry's metadata; --meta pairs
+    /// are added on top
+    #[arg(long, value_name = "FILE")]
+    pub meta_file: Option<PathBuf>,
+    /// After pushing, write the file hashes in `sha256sum -c` format to PATH
+    /// (default: <name>.sha256sums in the stash directory)
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    pub checksum_file: Option<Option<PathBuf>>,
+    /// Before pushing, run CMD with `sh -c`; if it fails nothing is stashed
+    #[arg(long, value_name = "CMD")]
+    pub pre_command: Option<String>,
+    /// Keep up to N versions under the pushed name: the new entry becomes its
+    /// latest version and the oldest ones beyond N are deleted
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub version_tag: Option<u64>,
+    /// Order of the items in a new entry's manifest (default: sort_items_by from the config)
+    #[arg(long, value_enum, value_name = "ORDER")]
+    pub sort_items_by: Option<ItemSort>,
+    /// Always copy file contents instead of cloning them with reflinks
+    #[arg(long)]
+    pub no_reflink: bool,
     /// Copy files instead of moving them
     #[arg(short, long)]
     pub copy: bool,
-    /// Overwrite existing files when restoring
+    /// Hard-link files into the stash instead of copying them (same filesystem only)
+    #[arg(long, conflicts_with = "copy")]
+    pub hardlink: bool,
+    /// Like --hardlink, but copy items that are on a different filesystem
+    #[arg(long, conflicts_with_all = ["copy", "hardlink"])]
+    pub hardlink_or_copy: bool,
+    /// Overwrite existing files when restoring (when pushing, skip the free-space
+    /// check; with --edit, allow binary files; with --init, recreate missing
+    /// index and journal files)
     #[arg(short, long)]
     pub force: bool,
+    /// With --force, move files the pop would overwrite into a new entry first
+    #[arg(long, requires = "force")]
+    pub backup: bool,
     /// Restore files to their original paths
     #[arg(short, long)]
     pub restore: bool,
+    /// Restore files relative to the directory ENTRY was pushed from, instead
+    /// of the current directory
+    #[arg(long, value_name = "ENTRY", conflicts_with = "restore")]
+    pub resolve_to: Option<String>,
+    /// Instead of restoring, write the entry's items into a new archive at
+    /// FILE (.tar, .tar.gz, .tar.bz2 or .zip) and leave the entry stashed
+    #[arg(long, value_name = "FILE", value_parser = parse_archive_target,
+          conflicts_with_all = ["restore", "resolve_to", "only", "backup"])]
+    pub to_archive: Option<(PathBuf, ArchiveFormat)>,
+    /// Pop version N of a versioned entry instead of its latest
+    #[arg(long, value_name = "N")]
+    pub entry_version: Option<u32>,
+    /// When restoring, skip files whose directory no longer exists
+    #[arg(long)]
+    pub missing_ok: bool,
+    /// Before restoring, check the destination has room for the entry's files
+    #[arg(long)]
+    pub verify_dest: bool,
+    /// When restoring into a git repository, warn about git-ignored targets
+    /// and tracked files with uncommitted changes that would be overwritten
+    #[arg(long)]
+    pub git_aware: bool,
+    /// After a successful pop, run CMD with `sh -c` in the directory the entry
+    /// was pushed from
+    #[arg(long, value_name = "CMD")]
+    pub post_command: Option<String>,
+}
+
+/// Parse a duration like `90s`, `45m`, `12h`, `30d` or `2w`
+pub fn parse_duration(value: &str) -> Result<chrono::Duration, String> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let amount: i64 = number
+        .parse()
+        .map_err(|_| format!("'{}' is not a duration; use e.g. 30d, 12h or 2w", value))?;
+
+    let duration = match unit {
+        "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => chrono::Duration::try_days(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        "" => return Err(format!("'{}' needs a unit: s, m, h, d or w (e.g. {}d)", value, number)),
+        _ => return Err(format!("unknown unit '{}'; use s, m, h, d or w", unit)),
+    };
+
+    duration.ok_or_else(|| format!("'{}' is too long", value))
+}
+
+/// Parse a point in time: `today`, `yesterday`, a date (`2024-01-31`, local
+/// midnight), a local `2024-01-31 14:30`, RFC 3339, or a duration ago (`30d`)
+pub fn parse_point_in_time(value: &str) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    let midnight = |date: NaiveDate| {
+        date.and_hms_opt(0, 0, 0)
+            .and_then(|time| time.and_local_timezone(Local).earliest())
+            .map(|time| time.with_timezone(&Utc))
+            .ok_or_else(|| format!("'{}' doesn't exist in the local time zone", value))
+    };
+
+    match value {
+        "today" => return midnight(Local::now().date_naive()),
+        "yesterday" => return midnight(Local::now().date_naive() - chrono::Duration::days(1)),
+        _ => {}
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return midnight(date);
+    }
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, format) {
+            return time
+                .and_local_timezone(Local)
+                .earliest()
+                .map(|time| time.with_timezone(&Utc))
+                .ok_or_else(|| format!("'{}' doesn't exist in the local time zone", value));
+        }
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    // A bare number could be a year or a count of days; make it explicit
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) {
+        return Err(format!(
+            "'{}' is ambiguous; give a date (2024-01-31) or a duration with a unit (30d)",
+            value
+        ));
+    }
+
+    parse_duration(value)
+        .map(|ago| Utc::now() - ago)
+        .map_err(|_| format!("'{}' is not a date, a time or a duration; use e.g. 2024-01-31 or 30d", value))
+}
+
+pub fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", value)),
+    }
+}
+
+/// `KEY=VALUE`, or a bare `KEY`
+pub fn parse_meta_filter(value: &str) -> Result<(String, Option<String>), String> {
+    if value.contains('=') {
+        return parse_key_value(value).map(|(key, value)| (key, Some(value)));
+    }
+    if value.is_empty() {
+        return Err("expected KEY=VALUE or KEY, got ''".into());
+    }
+    Ok((value.to_string(), None))
+}
+
+pub fn parse_archive_target(value: &str) -> Result<(PathBuf, ArchiveFormat), String> {
+    let path = PathBuf::from(value);
+    match file_compression::format_for_name(&path) {
+        Some(format) => Ok((path, format)),
+        None => Err("the file name must end in .tar, .tar.gz, .tgz, .tar.bz2, .tbz2 or .zip".into()),
+    }
+}
+
+pub fn parse_glob(value: &str) -> Result<glob::Pattern, String> {
+    glob::Pattern::new(value).map_err(|e| e.to_string())
 }
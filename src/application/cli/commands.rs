@@ -0,0 +1,523 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::{Args, Subcommand};
+use std::path::PathBuf;
+use crate::application::cli::arguments::{
+    parse_archive_target, parse_duration, parse_glob, parse_key_value, parse_meta_filter, parse_point_in_time, Cli, ItemSort,
+    ListColumn, OperationMode, OutputFormat,
+};
+use crate::application::cli::inference;
+use crate::services::filesystem::file_compression::ArchiveFormat;
+
+/// Explicit commands, as an alternative to flags and inference. Each one
+/// fills in the flags it stands for, so both forms share the same checks and
+/// produce the same operations.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Stash files or directories
+    Push(PushArgs),
+    /// Restore an entry (default: the most recent)
+    Pop(PopArgs),
+    /// Copy an entry's files into the current directory, leaving it stashed
+    Peek(PeekArgs),
+    /// List stash entries
+    #[command(visible_alias = "ls")]
+    List(ListArgs),
+    /// Show detailed info about an entry
+    Info(InfoArgs),
+    /// Delete entries (names, UUIDs or globs over names)
+    #[command(visible_alias = "delete")]
+    Rm(RmArgs),
+    /// Remove entries older than DAYS days (default 30)
+    Clean(CleanArgs),
+    /// Show stash operation history
+    History(HistoryArgs),
+    /// Export all entries to a tar archive
+    Export(ExportArgs),
+    /// Import entries from an exported archive
+    Import(ImportArgs),
+    /// Show the config, or get or set one of its keys
+    Config(ConfigArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct PushArgs {
+    /// Files or directories to stash
+    #[arg(required = true)]
+    pub items: Vec<PathBuf>,
+    /// Assign a custom name to the entry
+    #[arg(short, long)]
+    pub name: Option<String>,
+    /// Copy files instead of moving them
+    #[arg(short, long)]
+    pub copy: bool,
+    /// Hard-link files into the stash instead of copying them (same filesystem only)
+    #[arg(long, conflicts_with = "copy")]
+    pub hardlink: bool,
+    /// Like --hardlink, but copy items that are on a different filesystem
+    #[arg(long, conflicts_with_all = ["copy", "hardlink"])]
+    pub hardlink_or_copy: bool,
+    /// Skip hash computation (faster, but disables verification)
+    #[arg(long)]
+    pub no_hash: bool,
+    /// Compress the entry's data into a single archive after pushing
+    #[arg(long)]
+    pub compress: bool,
+    /// Fail on named pipes, sockets or device files instead of skipping them
+    #[arg(long)]
+    pub strict: bool,
+    /// Leave dotfiles and dot-directories out
+    #[arg(long)]
+    pub ignore_hidden: bool,
+    /// Push hidden files even if ignore_hidden_by_default is set
+    #[arg(long, conflicts_with = "ignore_hidden")]
+    pub include_hidden: bool,
+    /// Only push items last modified at least this long ago (e.g. 30d, 12h, 2w)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub min_age: Option<chrono::Duration>,
+    /// Only push items last modified at most this long ago
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_age: Option<chrono::Duration>,
+    /// Always copy file contents instead of cloning them with reflinks
+    #[arg(long)]
+    pub no_reflink: bool,
+    /// Order of the items in the manifest (default: sort_items_by from the config)
+    #[arg(long, value_enum, value_name = "ORDER")]
+    pub sort_items_by: Option<ItemSort>,
+    /// Don't print the size and time summary
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Skip the free-space check
+    #[arg(short, long)]
+    pub force: bool,
+    /// Reminder to show when the entry is popped
+    #[arg(long, value_name = "MSG")]
+    pub message_on_restore: Option<String>,
+    /// Attach KEY=VALUE to the entry's metadata (repeatable)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub meta: Vec<(String, String)>,
+    /// Attach the JSON object in FILE as the entry's metadata
+    #[arg(long, value_name = "FILE")]
+    pub meta_file: Option<PathBuf>,
+    /// Write the file hashes in `sha256sum -c` format to PATH
+    /// (default: <name>.sha256sums in the stash directory)
+    #[arg(long, value_name = "PATH", num_args = 0..=1)]
+    pub checksum_file: Option<Option<PathBuf>>,
+    /// Run CMD with `sh -c` first; if it fails nothing is stashed
+    #[arg(long, value_name = "CMD")]
+    pub pre_command: Option<String>,
+    /// Show the pre-command and its exit code
+    #[arg(long)]
+    pub verbose: bool,
+    /// Keep up to N versions under the entry's name
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub version_tag: Option<u64>,
+    /// Threads used to copy and move files (default: push_workers from the config)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub jobs: Option<u64>,
+    /// Print stable, tab-separated output for scripts
+    #[arg(long)]
+    pub porcelain: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PopArgs {
+    /// Name or UUID of the entry
+    pub entry: Option<String>,
+    /// Copy files out, leaving the entry stashed
+    #[arg(short, long)]
+    pub copy: bool,
+    /// Overwrite existing files
+    #[arg(short, long)]
+    pub force: bool,
+    /// With --force, move files the pop would overwrite into a new entry first
+    #[arg(long, requires = "force")]
+    pub backup: bool,
+    /// Restore files to their original paths
+    #[arg(short, long)]
+    pub restore: bool,
+    /// Restore files relative to the directory ENTRY was pushed from
+    #[arg(long, value_name = "ENTRY", conflicts_with = "restore")]
+    pub resolve_to: Option<String>,
+    /// Write the entry's items into a new archive at FILE (.tar, .tar.gz,
+    /// .tar.bz2 or .zip) instead, leaving the entry stashed
+    #[arg(long, value_name = "FILE", value_parser = parse_archive_target,
+          conflicts_with_all = ["restore", "resolve_to", "only", "backup"])]
+    pub to_archive: Option<(PathBuf, ArchiveFormat)>,
+    /// Pop version N of a versioned entry instead of its latest
+    #[arg(long = "version", value_name = "N", visible_alias = "entry-version")]
+    pub entry_version: Option<u32>,
+    /// Restore only items whose stashed path matches GLOB (repeatable)
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    pub only: Vec<glob::Pattern>,
+    /// Skip files whose directory no longer exists
+    #[arg(long)]
+    pub missing_ok: bool,
+    /// Check the destination has room for the entry's files first
+    #[arg(long)]
+    pub verify_dest: bool,
+    /// In a git repository, warn about git-ignored targets and tracked files
+    /// with uncommitted changes that would be overwritten
+    #[arg(long)]
+    pub git_aware: bool,
+    /// Afterwards, run CMD with `sh -c` in the directory the entry was pushed from
+    #[arg(long, value_name = "CMD")]
+    pub post_command: Option<String>,
+    /// Refuse a manifest that fails validation
+    #[arg(long)]
+    pub strict: bool,
+    /// Always copy file contents instead of cloning them with reflinks
+    #[arg(long)]
+    pub no_reflink: bool,
+    /// Don't print the size and time summary
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Threads used to copy and move files (default: push_workers from the config)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub jobs: Option<u64>,
+    /// Print stable, tab-separated output for scripts
+    #[arg(long)]
+    pub porcelain: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct PeekArgs {
+    /// Name or UUID of the entry (default: the most recent)
+    pub entry: Option<String>,
+    /// Print the entry's items as a tree instead of copying them
+    #[arg(long)]
+    pub list_only: bool,
+    /// Overwrite existing files
+    #[arg(short, long)]
+    pub force: bool,
+    /// Copy only items whose stashed path matches GLOB (repeatable; `*`
+    /// stops at `/`, `**` crosses it)
+    #[arg(long, value_name = "GLOB", value_parser = parse_glob)]
+    pub only: Vec<glob::Pattern>,
+    #[command(flatten)]
+    pub sizes: SizeArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+    /// Redraw whenever the stash changes
+    #[arg(long)]
+    pub watch: bool,
+    /// Only show entries stashed since the last pop or dump
+    #[arg(long)]
+    pub since_last_pop: bool,
+    /// Only show entries stashed since the last git commit in the current
+    /// directory (or $STASH_GIT_DIR)
+    #[arg(long)]
+    pub since_last_git_commit: bool,
+    /// Show only this page of entries (starting at 1)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub page: Option<u64>,
+    /// Number of entries per page (default 20; implies --page 1)
+    #[arg(long, value_name = "M", value_parser = clap::value_parser!(u64).range(1..))]
+    pub per_page: Option<u64>,
+    /// Comma-separated columns to show, in order
+    #[arg(long, value_name = "COLS", value_enum, value_delimiter = ',')]
+    pub columns: Vec<ListColumn>,
+    /// Only show entries whose metadata has KEY set to VALUE (repeatable)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_key_value)]
+    pub filter_meta: Vec<(String, String)>,
+    /// End with the number, items and combined size of the entries shown
+    #[arg(long, visible_alias = "summary")]
+    pub total: bool,
+    /// Also show the space entries actually take on disk
+    #[arg(long)]
+    pub disk_usage: bool,
+    /// With --disk-usage, show every entry's on-disk usage, not just the totals
+    #[arg(long, requires = "disk_usage")]
+    pub verbose: bool,
+    #[command(flatten)]
+    pub times: TimeArgs,
+    #[command(flatten)]
+    pub sizes: SizeArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct InfoArgs {
+    /// Name or UUID of the entry (default: the most recent)
+    pub entry: Option<String>,
+    /// Re-hash stashed data and compare it to stored hashes
+    #[arg(long)]
+    pub hash_check: bool,
+    /// Also show the space the entry actually takes on disk
+    #[arg(long)]
+    pub disk_usage: bool,
+    /// Refuse a manifest that fails validation
+    #[arg(long)]
+    pub strict: bool,
+    #[command(flatten)]
+    pub times: TimeArgs,
+    #[command(flatten)]
+    pub sizes: SizeArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct RmArgs {
+    /// Entries to delete
+    #[arg(required = true)]
+    pub targets: Vec<String>,
+    /// Don't ask before deleting
+    #[arg(short, long)]
+    pub force: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CleanArgs {
+    /// Age in days (default 30)
+    #[arg(conflicts_with = "keep_latest")]
+    pub days: Option<i64>,
+    /// Remove entries created at or after WHEN (up to now, or --until)
+    /// instead of those older than DAYS: a date (2024-01-31), a date and
+    /// time, or a duration ago (30d, 12h)
+    #[arg(long, value_name = "WHEN", value_parser = parse_point_in_time)]
+    pub since: Option<DateTime<Utc>>,
+    /// Remove entries created before WHEN instead of those older than DAYS
+    #[arg(long, value_name = "WHEN", value_parser = parse_point_in_time)]
+    pub until: Option<DateTime<Utc>>,
+    /// Keep only the N most recently created entries and remove the rest,
+    /// whatever their age
+    #[arg(long, value_name = "N", conflicts_with_all = ["since", "until"],
+          value_parser = clap::value_parser!(u64).range(1..))]
+    pub keep_latest: Option<u64>,
+    /// With --keep-latest, only rank and remove entries whose metadata has
+    /// KEY set to VALUE; a bare KEY keeps the latest N for each of its values
+    /// (repeatable; every filter must match)
+    #[arg(long, value_name = "KEY[=VALUE]", requires = "keep_latest", value_parser = parse_meta_filter)]
+    pub filter_meta: Vec<(String, Option<String>)>,
+    /// Show what would be removed without removing anything
+    #[arg(long)]
+    pub dry_run: bool,
+    #[command(flatten)]
+    pub output: OutputArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryArgs {
+    /// Show how each operation changed the number and size of entries
+    #[arg(long)]
+    pub diff: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// The tar archive to write
+    pub file: PathBuf,
+    /// Resume an interrupted export, adding only the entries it is missing
+    #[arg(long = "continue")]
+    pub resume: bool,
+    /// Entries read into the archive at once (default 4)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub jobs: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// The archive: a file path, an http(s) URL, or - for stdin
+    #[arg(value_name = "FILE|URL|-")]
+    pub source: String,
+    /// Verify the archive against this SHA-256 checksum
+    #[arg(long, value_name = "HEX")]
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    /// A key, in dot notation for nested tables (e.g. push_workers)
+    pub key: Option<String>,
+    /// Set KEY to this value and save the config file
+    pub value: Option<String>,
+}
+
+/// --relative and --absolute
+#[derive(Debug, Args)]
+pub struct TimeArgs {
+    /// Show times as ages ("3 hours ago")
+    #[arg(long, conflicts_with = "absolute")]
+    pub relative: bool,
+    /// Show times as timestamps in the configured date_format
+    #[arg(long)]
+    pub absolute: bool,
+}
+
+/// --bytes and --si
+#[derive(Debug, Args)]
+pub struct SizeArgs {
+    /// Show sizes as exact byte counts
+    #[arg(long, conflicts_with = "si")]
+    pub bytes: bool,
+    /// Show sizes in powers of 1000 (kB, MB)
+    #[arg(long)]
+    pub si: bool,
+}
+
+/// --porcelain, --output and --json
+#[derive(Debug, Args)]
+pub struct OutputArgs {
+    /// Print stable, tab-separated output for scripts
+    #[arg(long)]
+    pub porcelain: bool,
+    /// Output format for command results
+    #[arg(long, value_enum, value_name = "FORMAT", conflicts_with = "porcelain")]
+    pub output: Option<OutputFormat>,
+    /// Shorthand for --output json
+    #[arg(long, conflicts_with_all = ["porcelain", "output"])]
+    pub json: bool,
+}
+
+impl TimeArgs {
+    fn apply(self, cli: &mut Cli) {
+        cli.relative = self.relative;
+        cli.absolute = self.absolute;
+    }
+}
+
+impl SizeArgs {
+    fn apply(self, cli: &mut Cli) {
+        cli.bytes = self.bytes;
+        cli.si = self.si;
+    }
+}
+
+impl OutputArgs {
+    fn apply(self, cli: &mut Cli) {
+        cli.porcelain = self.porcelain;
+        cli.output = self.output;
+        cli.json = self.json;
+    }
+}
+
+impl Command {
+    /// The operation this command asks for. `cli` has no flags of its own
+    /// set, since they can't be combined with a command.
+    pub fn into_operation(self, mut cli: Cli) -> Result<OperationMode> {
+        match self {
+            Command::Push(args) => {
+                cli.name = args.name;
+                cli.copy = args.copy;
+                cli.hardlink = args.hardlink;
+                cli.hardlink_or_copy = args.hardlink_or_copy;
+                cli.no_hash = args.no_hash;
+                cli.compress = args.compress;
+                cli.strict = args.strict;
+                cli.ignore_hidden = args.ignore_hidden;
+                cli.include_hidden = args.include_hidden;
+                cli.min_age = args.min_age;
+                cli.max_age = args.max_age;
+                cli.no_reflink = args.no_reflink;
+                cli.sort_items_by = args.sort_items_by;
+                cli.quiet = args.quiet;
+                cli.force = args.force;
+                cli.message_on_restore = args.message_on_restore;
+                cli.meta = args.meta;
+                cli.meta_file = args.meta_file;
+                cli.checksum_file = args.checksum_file;
+                cli.pre_command = args.pre_command;
+                cli.verbose = args.verbose;
+                cli.version_tag = args.version_tag;
+                cli.jobs = args.jobs;
+                cli.porcelain = args.porcelain;
+                // Always a push: the items aren't checked against the stash
+                inference::push_mode(&cli, args.items)
+            }
+            Command::Pop(args) => {
+                cli.copy = args.copy;
+                cli.force = args.force;
+                cli.backup = args.backup;
+                cli.restore = args.restore;
+                cli.resolve_to = args.resolve_to;
+                cli.to_archive = args.to_archive;
+                cli.entry_version = args.entry_version;
+                cli.only = args.only;
+                cli.missing_ok = args.missing_ok;
+                cli.verify_dest = args.verify_dest;
+                cli.git_aware = args.git_aware;
+                cli.post_command = args.post_command;
+                cli.strict = args.strict;
+                cli.no_reflink = args.no_reflink;
+                cli.quiet = args.quiet;
+                cli.jobs = args.jobs;
+                cli.porcelain = args.porcelain;
+                // Always a pop, even if a file of the same name exists here
+                Ok(inference::pop_mode(&cli, args.entry))
+            }
+            Command::Peek(args) => {
+                cli.peek = Some(args.entry);
+                cli.list_only = args.list_only;
+                cli.force = args.force;
+                cli.only = args.only;
+                args.sizes.apply(&mut cli);
+                inference::infer_operation(&cli)
+            }
+            Command::List(args) => {
+                cli.list = true;
+                cli.watch = args.watch;
+                cli.since_last_pop = args.since_last_pop;
+                cli.since_last_git_commit = args.since_last_git_commit;
+                cli.page = args.page;
+                cli.per_page = args.per_page;
+                cli.columns = args.columns;
+                cli.filter_meta = args.filter_meta.into_iter().map(|(key, value)| (key, Some(value))).collect();
+                cli.total = args.total;
+                cli.disk_usage = args.disk_usage;
+                cli.verbose = args.verbose;
+                args.times.apply(&mut cli);
+                args.sizes.apply(&mut cli);
+                args.output.apply(&mut cli);
+                inference::infer_operation(&cli)
+            }
+            Command::Info(args) => {
+                cli.info = true;
+                cli.items = args.entry.into_iter().map(PathBuf::from).collect();
+                cli.hash_check = args.hash_check;
+                cli.disk_usage = args.disk_usage;
+                cli.strict = args.strict;
+                args.times.apply(&mut cli);
+                args.sizes.apply(&mut cli);
+                args.output.apply(&mut cli);
+                inference::infer_operation(&cli)
+            }
+            Command::Rm(args) => {
+                cli.delete = true;
+                cli.items = args.targets.into_iter().map(PathBuf::from).collect();
+                cli.force = args.force;
+                inference::infer_operation(&cli)
+            }
+            Command::Clean(args) => {
+                cli.clean = Some(args.days);
+                cli.since = args.since;
+                cli.until = args.until;
+                cli.keep_latest = args.keep_latest;
+                cli.filter_meta = args.filter_meta;
+                cli.dry_run = args.dry_run;
+                args.output.apply(&mut cli);
+                inference::infer_operation(&cli)
+            }
+            Command::History(args) => {
+                cli.history = true;
+                cli.diff = args.diff;
+                inference::infer_operation(&cli)
+            }
+            Command::Export(args) => {
+                cli.tar = Some(args.file);
+                cli.resume = args.resume;
+                cli.jobs = args.jobs;
+                inference::infer_operation(&cli)
+            }
+            Command::Import(args) => {
+                cli.import = Some(args.source);
+                cli.sha256 = args.sha256;
+                inference::infer_operation(&cli)
+            }
+            Command::Config(args) => Ok(OperationMode::Config { key: args.key, value: args.value }),
+        }
+    }
+}
@@ -1,58 +1,199 @@
 use anyhow::Result;
+use std::path::Path;
 use crate::application::cli::arguments::{Cli, OperationMode};
 use crate::application::cli::inference;
 use crate::application::features;
-use crate::utils::paths::AppDirs;
+use crate::utils::display::escape_path;
 
 impl Cli {
-    pub fn run(self) -> Result<()> {
-        let operation = inference::infer_operation(&self)?;
+    pub fn run(mut self) -> Result<()> {
+        match self.command.take() {
+            Some(command) => {
+                warn_if_command_is_a_path();
+                Self::execute(command.into_operation(self)?)
+            }
+            None => Self::execute(inference::infer_operation(&self)?),
+        }
+    }
 
+    fn execute(operation: OperationMode) -> Result<()> {
         match operation {
-            OperationMode::Push { items, name, copy } => {
-                features::push::run(&items, &name, &copy)
+            OperationMode::Push {
+                items, name, copy, hardlink, hardlink_or_copy, no_hash, compress, strict, ignore_hidden, include_hidden,
+                min_age, max_age, no_reflink, sort_items_by, quiet, force, restore_message, meta, meta_file, checksum_file,
+                pre_command, verbose, version_tag, jobs, format,
+            } => {
+                let flags = features::push::PushFlags {
+                    copy, hardlink, hardlink_or_copy, no_hash, compress, strict, ignore_hidden, include_hidden, min_age,
+                    max_age, no_reflink, sort_items_by, quiet, force, restore_message, meta, meta_file, checksum_file,
+                    pre_command, verbose, version_tag, jobs,
+                };
+                features::push::run(&items, &name, &flags, &format)
+            }
+
+            OperationMode::Pop {
+                identifier, copy, force, restore, missing_ok, verify_dest, git_aware, post_command, no_reflink, quiet, only,
+                strict, backup, resolve_to, version, jobs, format,
+            } => {
+                let flags = features::pop::PopFlags {
+                    copy, force, restore, missing_ok, verify_dest, git_aware, post_command, no_reflink, quiet, only, strict,
+                    backup, resolve_to, version, jobs,
+                };
+                features::pop::run(&identifier, &flags, &format)
+            }
+
+            OperationMode::PopToArchive { identifier, archive, format, force } => {
+                features::pop_to_archive::run(&identifier, &archive, &format, &force)
+            }
+
+            OperationMode::Dump { to, delete } => {
+                features::dump::run(&to, &delete)
             }
 
-            OperationMode::Pop { identifier, copy, force, restore } => {
-                features::pop::run(&identifier, &copy, &force, &restore)
+            OperationMode::List {
+                watch, since_last_pop, since_last_git_commit, page, per_page, columns, disk_usage, verbose, time, sizes, filter_meta,
+                total, format,
+            } => {
+                let view = features::list::View {
+                    since_last_pop, since_last_git_commit, page, per_page, columns, disk_usage, verbose, time, sizes, filter_meta,
+                    total,
+                };
+                features::list::run(&watch, &view, &format)
             }
 
-            OperationMode::Dump => {
-                features::dump::run()
+            OperationMode::ExportPaths { entry, filter, format } => {
+                features::export_paths::run(&entry, &filter, &format)
             }
 
-            OperationMode::List => {
-                features::list::run()
+            OperationMode::Search { pattern, time, sizes, format } => {
+                features::search::run(&pattern, &time, &sizes, &format)
             }
 
-            OperationMode::Search(pattern) => {
-                features::search::run(&pattern)
+            OperationMode::Find { path, format } => {
+                features::find::run(&path, &format)
             }
 
-            OperationMode::Info { identifier } => {
-                features::info::run(&identifier)
+            OperationMode::Info { identifier, hash_check, disk_usage, time, sizes, strict, format } => {
+                features::info::run(&identifier, &hash_check, &disk_usage, &time, &sizes, &strict, &format)
             }
 
-            OperationMode::History => {
-                features::history::run()
+            OperationMode::Peek { identifier, force, list_only, only, sizes } => {
+                features::peek::run(&identifier, &force, &list_only, &only, &sizes)
             }
 
-            OperationMode::Clean(days) => {
-                features::clean::run(days)
+            OperationMode::History { diff } => {
+                features::history::run(&diff)
+            }
+
+            OperationMode::Clean { days, since, until, keep_latest, filter_meta, dry_run, format } => {
+                features::clean::run(days, &since, &until, &keep_latest, &filter_meta, &dry_run, &format)
             }
 
             OperationMode::Rename { old, new } => {
                 features::rename::run(&old, &new)
             }
 
-            OperationMode::Tar(path) => {
-                features::tar::run(&path)
+            OperationMode::RenameUuid { uuid, new } => {
+                features::rename::run_by_uuid(&uuid, &new)
+            }
+
+            OperationMode::SwapNames { first, second } => {
+                features::swap_names::run(&first, &second)
+            }
+
+            OperationMode::NameHistory { identifier } => {
+                features::name_history::run(&identifier)
+            }
+
+            OperationMode::RevertName { identifier } => {
+                features::revert_name::run(&identifier)
+            }
+
+            OperationMode::Edit { identifier, path, force } => {
+                features::edit::run(&identifier, &path, &force)
+            }
+            OperationMode::CheckSpace { identifier, destination, copy } => {
+                features::check_space::run(&identifier, &destination, &copy)
+            }
+            OperationMode::Tar { path, resume, jobs } => {
+                features::tar::run(&path, &resume, &jobs)
+            }
+            OperationMode::PushClipboard { name } => {
+                features::clipboard::run(&name)
+            }
+            OperationMode::Import { source, sha256 } => {
+                features::import::run(&source, &sha256)
+            }
+
+            OperationMode::Rehash(identifier) => {
+                features::rehash::run(&identifier)
+            }
+
+            OperationMode::Delete { targets, force } => {
+                features::delete::run(&targets, &force)
+            }
+
+            OperationMode::PushFromTemplate { template_name, mut push } => {
+                if let OperationMode::Push { items, name, .. } = push.as_mut() {
+                    *items = features::template::items(&template_name)?;
+                    name.get_or_insert_with(|| {
+                        format!("{}-{}", template_name, chrono::Local::now().format("%Y-%m-%d-%H%M%S"))
+                    });
+                }
+                Self::execute(*push)
             }
 
-            OperationMode::Init => {
-                AppDirs::new().init()
+            OperationMode::MarkTemplate { identifier, is_template } => {
+                features::template::mark(&identifier, &is_template)
+            }
+
+            OperationMode::PruneJournal => {
+                features::prune::run()
+            }
+            OperationMode::Health => {
+                features::health::run()
+            }
+            OperationMode::Status { time, sizes, format } => {
+                features::status::run(&time, &sizes, &format)
+            }
+            OperationMode::MoveStashDir { destination, merge, dry_run } => {
+                features::move_stash::run(&destination, &merge, &dry_run)
+            }
+
+            OperationMode::Sync { remote, direction, dry_run } => {
+                features::sync::run(&remote, &direction, &dry_run)
+            }
+
+            OperationMode::Raw(target) => {
+                features::raw::run(&target)
+            }
+
+            OperationMode::Benchmark { files, size_mb } => {
+                features::benchmark::run(&files, &size_mb)
+            }
+
+            OperationMode::Init { local, force } => {
+                features::init::run(&local, &force)
+            }
+
+            OperationMode::Config { key, value } => {
+                features::config::run(&key, &value)
             }
         }
     }
 }
 
+/// A command name always runs the command, even when a file of that name is
+/// here; say how to stash the file instead
+fn warn_if_command_is_a_path() {
+    let Some(typed) = std::env::args_os().nth(1) else {
+        return;
+    };
+    let path = Path::new(&typed);
+    if path.symlink_metadata().is_ok() {
+        eprintln!(
+            "Warning: ran the '{0}' command; to stash or pop the path {0} instead, use 'stash -- {0}' or 'stash ./{0}'",
+            escape_path(path)
+        );
+    }
+}
@@ -1,22 +1,93 @@
 use anyhow::{Result, bail};
-use crate::application::cli::arguments::{Cli, OperationMode};
-use std::path::PathBuf;
+use crate::application::cli::arguments::{Cli, OperationMode, OutputFormat, SyncDirection};
+use crate::application::cli::prompt;
+use crate::errors::StashError;
+use crate::models::config::AmbiguityMode;
+use crate::services::storage::ConfigStorage;
+use crate::utils::display::TimeStyle;
+use crate::utils::paths::AppDirs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 
 pub fn infer_operation(cli: &Cli) -> Result<OperationMode> {
+    let mode = infer(cli)?;
+
+    if cli.jobs.is_some()
+        && !matches!(mode, OperationMode::Push { .. } | OperationMode::Pop { .. } | OperationMode::Tar { .. })
+    {
+        bail!(StashError::Usage("--jobs only applies when pushing, popping or exporting with --tar".into()));
+    }
+
+    Ok(mode)
+}
+
+fn infer(cli: &Cli) -> Result<OperationMode> {
     // ========================================================================
     // Priority 1: Explicit, non-inferable operations
     // ========================================================================
 
+    if cli.output_format() == OutputFormat::Json && cli.clean.is_none() && !cli.list && !cli.info {
+        bail!(StashError::Usage("--output json is only supported with --clean, --list and --info".into()));
+    }
+
+    if !cli.filter_meta.is_empty() && !cli.list && cli.keep_latest.is_none() {
+        bail!(StashError::Usage("--filter-meta needs --list or --clean --keep-latest".into()));
+    }
+
+    if cli.to.is_some() && !cli.dump && cli.from_uuid.is_none() {
+        bail!(StashError::Usage("--to needs --rename --from-uuid or --dump".into()));
+    }
+
     if cli.init {
-        return Ok(OperationMode::Init);
+        return Ok(OperationMode::Init {
+            local: cli.local,
+            force: cli.force,
+        });
+    }
+
+    if cli.export_paths {
+        return Ok(OperationMode::ExportPaths {
+            entry: cli.entry.clone(),
+            filter: cli.filter.clone(),
+            format: cli.output_format(),
+        });
     }
 
     if cli.list {
-        return Ok(OperationMode::List);
+        if cli.verbose && !cli.disk_usage {
+            bail!(StashError::Usage("--list --verbose needs --disk-usage".into()));
+        }
+        return Ok(OperationMode::List {
+            watch: cli.watch,
+            since_last_pop: cli.since_last_pop,
+            since_last_git_commit: cli.since_last_git_commit,
+            page: cli.page.or(cli.per_page.map(|_| 1)).map(|p| p as usize),
+            per_page: cli.per_page.unwrap_or(20) as usize,
+            columns: cli.columns.clone(),
+            disk_usage: cli.disk_usage,
+            verbose: cli.verbose,
+            time: cli.time_style(TimeStyle::Relative),
+            sizes: cli.size_style(),
+            filter_meta: exact_meta_filters(&cli.filter_meta)?,
+            total: cli.total,
+            format: cli.output_format(),
+        });
     }
 
     if let Some(pattern) = &cli.search {
-        return Ok(OperationMode::Search(pattern.clone()));
+        return Ok(OperationMode::Search {
+            pattern: pattern.clone(),
+            time: cli.time_style(TimeStyle::Relative),
+            sizes: cli.size_style(),
+            format: cli.output_format(),
+        });
+    }
+
+    if let Some(path) = &cli.find {
+        return Ok(OperationMode::Find {
+            path: path.clone(),
+            format: cli.output_format(),
+        });
     }
 
     if cli.info {
@@ -25,34 +96,199 @@ pub fn infer_operation(cli: &Cli) -> Result<OperationMode> {
             .first()
             .map(|p| p.to_string_lossy().to_string());
 
-        return Ok(OperationMode::Info { identifier });
+        if cli.output_format() == OutputFormat::Json && cli.hash_check {
+            bail!(StashError::Usage("--hash-check can't be combined with --json".into()));
+        }
+
+        return Ok(OperationMode::Info {
+            identifier,
+            hash_check: cli.hash_check,
+            disk_usage: cli.disk_usage,
+            time: cli.time_style(TimeStyle::Both),
+            sizes: cli.size_style(),
+            strict: cli.strict,
+            format: cli.output_format(),
+        });
+    }
+
+    if let Some(identifier) = &cli.peek {
+        return Ok(OperationMode::Peek {
+            identifier: identifier.clone(),
+            force: cli.force,
+            list_only: cli.list_only,
+            only: cli.only.clone(),
+            sizes: cli.size_style(),
+        });
     }
 
     if cli.history {
-        return Ok(OperationMode::History);
+        return Ok(OperationMode::History { diff: cli.diff });
     }
 
     if let Some(days) = cli.clean {
-        return Ok(OperationMode::Clean(days));
+        if days.is_some() && cli.keep_latest.is_some() {
+            bail!(StashError::Usage(
+                "--keep-latest removes entries by count, not age; leave out DAYS".into()
+            ));
+        }
+        if let (Some(since), Some(until)) = (cli.since, cli.until) {
+            if since >= until {
+                bail!(StashError::Usage("--since must be earlier than --until".into()));
+            }
+        }
+        return Ok(OperationMode::Clean {
+            days: days.unwrap_or(30),
+            since: cli.since,
+            until: cli.until,
+            keep_latest: cli.keep_latest.map(|n| n as usize),
+            filter_meta: cli.filter_meta.clone(),
+            dry_run: cli.dry_run,
+            format: cli.output_format(),
+        });
     }
 
     if let Some(spec) = &cli.rename {
-        let (old, new) = spec
-            .split_once(':')
-            .ok_or_else(|| anyhow::anyhow!("--rename must be in OLD:NEW format"))?;
+        return rename_mode(cli, spec.as_deref());
+    }
+
+    if let Some(identifier) = &cli.name_history {
+        return Ok(OperationMode::NameHistory { identifier: identifier.clone() });
+    }
+
+    if let Some(identifier) = &cli.revert_name {
+        return Ok(OperationMode::RevertName { identifier: identifier.clone() });
+    }
+
+    if let Some(identifier) = &cli.edit {
+        let path = cli.items.first().ok_or_else(|| {
+            StashError::Usage("--edit requires a file to edit (usage: --edit ENTRY FILE)".into())
+        })?;
+
+        return Ok(OperationMode::Edit {
+            identifier: identifier.clone(),
+            path: path.clone(),
+            force: cli.force,
+        });
+    }
 
-        return Ok(OperationMode::Rename {
-            old: old.into(),
-            new: new.into(),
+    if let Some(identifier) = &cli.check_space {
+        return Ok(OperationMode::CheckSpace {
+            identifier: identifier.clone(),
+            destination: cli.dest.clone(),
+            copy: cli.copy,
         });
     }
 
     if let Some(path) = &cli.tar {
-        return Ok(OperationMode::Tar(path.clone()));
+        return Ok(OperationMode::Tar {
+            path: path.clone(),
+            resume: cli.resume,
+            jobs: cli.jobs.map_or(4, |n| n as usize),
+        });
+    }
+
+    if cli.read_from_clipboard {
+        return Ok(OperationMode::PushClipboard {
+            name: cli.name.clone(),
+        });
+    }
+
+    if let Some(source) = &cli.import {
+        return Ok(OperationMode::Import {
+            source: source.clone(),
+            sha256: cli.sha256.clone(),
+        });
     }
 
     if cli.dump {
-        return Ok(OperationMode::Dump);
+        if cli.delete && cli.to.is_none() {
+            bail!(StashError::Usage("--dump --delete needs --to; popping already removes the entries".into()));
+        }
+        return Ok(OperationMode::Dump {
+            to: cli.to.as_ref().map(PathBuf::from),
+            delete: cli.delete,
+        });
+    }
+
+    if cli.delete {
+        if cli.items.is_empty() {
+            bail!(StashError::Usage("--delete needs at least one entry name, UUID or glob".into()));
+        }
+        return Ok(OperationMode::Delete {
+            targets: cli.items.iter().map(|item| item.to_string_lossy().to_string()).collect(),
+            force: cli.force,
+        });
+    }
+
+    if let Some(template_name) = &cli.template {
+        if !cli.items.is_empty() {
+            bail!(StashError::Usage("--template pushes the template's items; don't list paths".into()));
+        }
+        return Ok(OperationMode::PushFromTemplate {
+            template_name: template_name.clone(),
+            push: Box::new(push_mode(cli, Vec::new())?),
+        });
+    }
+
+    for (identifier, is_template) in [(&cli.mark_template, true), (&cli.unmark_template, false)] {
+        if let Some(identifier) = identifier {
+            return Ok(OperationMode::MarkTemplate { identifier: identifier.clone(), is_template });
+        }
+    }
+
+    if let Some(identifier) = &cli.rehash {
+        return Ok(OperationMode::Rehash(identifier.clone()));
+    }
+
+    if let Some(destination) = &cli.move_to_stash_dir {
+        return Ok(OperationMode::MoveStashDir {
+            destination: destination.clone(),
+            merge: cli.merge,
+            dry_run: cli.dry_run,
+        });
+    }
+
+    if cli.health {
+        return Ok(OperationMode::Health);
+    }
+
+    if cli.status {
+        return Ok(OperationMode::Status {
+            time: cli.time_style(TimeStyle::Both),
+            sizes: cli.size_style(),
+            format: cli.output_format(),
+        });
+    }
+
+    if cli.prune_journal {
+        return Ok(OperationMode::PruneJournal);
+    }
+
+    if let Some(target) = &cli.raw {
+        return Ok(OperationMode::Raw(target.clone()));
+    }
+
+    if cli.benchmark {
+        return Ok(OperationMode::Benchmark {
+            files: cli.bench_files,
+            size_mb: cli.bench_size,
+        });
+    }
+
+    if let Some(remote) = &cli.sync_push {
+        return Ok(OperationMode::Sync {
+            remote: remote.clone(),
+            direction: SyncDirection::Push,
+            dry_run: cli.dry_run,
+        });
+    }
+
+    if let Some(remote) = &cli.sync_pull {
+        return Ok(OperationMode::Sync {
+            remote: remote.clone(),
+            direction: SyncDirection::Pull,
+            dry_run: cli.dry_run,
+        });
     }
 
     // ========================================================================
@@ -69,71 +305,244 @@ fn infer_from_context(cli: &Cli) -> Result<OperationMode> {
     // Rule 1: No arguments → pop most recent entry
     // ------------------------------------------------------------------------
     if items.is_empty() {
-        return Ok(OperationMode::Pop {
-            identifier: None,
-            copy: cli.copy,
-            force: cli.force,
-            restore: cli.restore,
-        });
+        return Ok(pop_mode(cli, None));
     }
 
     // ------------------------------------------------------------------------
     // Rule 2: All items exist locally → push
     // ------------------------------------------------------------------------
-    let all_exist = items.iter().all(|p| p.exists());
+    let all_exist = items.iter().all(|p| exists_locally(p));
     if all_exist {
-        return Ok(OperationMode::Push {
-            items: items.clone(),
-            name: cli.name.clone(),
-            copy: cli.copy,
-        });
+        return push_mode(cli, items.clone());
     }
 
     // ------------------------------------------------------------------------
     // Rule 3: None exist locally → treat as stash identifier
     // ------------------------------------------------------------------------
-    let none_exist = items.iter().all(|p| !p.exists());
+    let none_exist = items.iter().all(|p| !exists_locally(p));
     if none_exist {
-        if items.len() == 1 {
-            return Ok(OperationMode::Pop {
-                identifier: Some(items[0].to_string_lossy().to_string()),
-                copy: cli.copy,
-                force: cli.force,
-                restore: cli.restore,
-            });
-        }
-
-        bail!(
-            "Cannot restore multiple entries at once: {}\n\
-             Entries are referenced by a single name or ID.\n\
-             Use --list to see available entries.",
-            format_paths(items)
-        );
+        return single_pop(cli, &items.iter().collect::<Vec<_>>());
     }
 
     // ------------------------------------------------------------------------
-    // Rule 4: Mixed existence → ambiguous
+    // Rule 4: Mixed existence → resolved by the configured ambiguity_mode
     // ------------------------------------------------------------------------
-    let existing: Vec<_> = items.iter().filter(|p| p.exists()).collect();
-    let missing: Vec<_> = items.iter().filter(|p| !p.exists()).collect();
-
-    bail!(
-        "Ambiguous operation:\n\
-         - These paths exist locally: {}\n\
-         - These paths do not exist: {}\n\n\
-         Stash infers actions from context.\n\
-         Try separating the operations or using --copy / --force / --restore.",
-        format_paths_refs(&existing),
-        format_paths_refs(&missing)
-    );
+    let existing: Vec<_> = items.iter().filter(|p| exists_locally(p)).collect();
+    let missing: Vec<_> = items.iter().filter(|p| !exists_locally(p)).collect();
+
+    match ambiguity_mode()? {
+        AmbiguityMode::PreferPush => {
+            eprintln!("Warning: ignoring paths that don't exist: {}", format_paths_refs(&missing));
+            push_mode(cli, existing.into_iter().cloned().collect())
+        }
+        AmbiguityMode::PreferPop => {
+            let mode = single_pop(cli, &missing)?;
+            eprintln!("Warning: ignoring paths that exist: {}", format_paths_refs(&existing));
+            Ok(mode)
+        }
+        AmbiguityMode::Ask if std::io::stdin().is_terminal() => {
+            println!(
+                "Some paths exist ({}) and some don't ({}).",
+                format_paths_refs(&existing),
+                format_paths_refs(&missing)
+            );
+            if prompt::prompt_bool("Stash the existing paths and ignore the rest? [y/n]")? {
+                return push_mode(cli, existing.into_iter().cloned().collect());
+            }
+            if missing.len() == 1
+                && prompt::prompt_bool(&format!("Pop {} instead? [y/n]", format_paths_refs(&missing)))?
+            {
+                return single_pop(cli, &missing);
+            }
+            bail!(StashError::Usage("Nothing done".into()))
+        }
+        AmbiguityMode::Ask => bail!(StashError::Usage(format!(
+            "Ambiguous operation:\n\
+             - These paths exist locally: {}\n\
+             - These paths do not exist: {}\n\n\
+             Stash infers actions from context.\n\
+             Try separating the operations, or set ambiguity_mode to \"PreferPush\" or \"PreferPop\".",
+            format_paths_refs(&existing),
+            format_paths_refs(&missing)
+        ))),
+    }
 }
 
-fn format_paths(paths: &[PathBuf]) -> String {
-    paths
+/// `--filter-meta` values for `--list`, which has no use for a bare KEY
+fn exact_meta_filters(filters: &[(String, Option<String>)]) -> Result<Vec<(String, String)>> {
+    filters
         .iter()
-        .map(|p| format!("'{}'", p.display()))
-        .collect::<Vec<_>>()
-        .join(", ")
+        .map(|(key, value)| match value {
+            Some(value) => Ok((key.clone(), value.clone())),
+            None => bail!(StashError::Usage(format!(
+                "--list --filter-meta needs KEY=VALUE; a bare '{}' only groups --clean --keep-latest",
+                key
+            ))),
+        })
+        .collect()
+}
+
+pub fn push_mode(cli: &Cli, items: Vec<PathBuf>) -> Result<OperationMode> {
+    if !cli.only.is_empty() {
+        bail!(StashError::Usage(
+            "--only selects items to restore; it can't be used when stashing".into()
+        ));
+    }
+    if cli.to_archive.is_some() {
+        bail!(StashError::Usage(
+            "--to-archive writes a stashed entry into an archive; it can't be used when stashing".into()
+        ));
+    }
+    if cli.entry_version.is_some() {
+        bail!(StashError::Usage(
+            "--entry-version selects a version to restore; use --version-tag when stashing".into()
+        ));
+    }
+
+    Ok(OperationMode::Push {
+        items,
+        name: cli.name.clone(),
+        copy: cli.copy,
+        hardlink: cli.hardlink,
+        hardlink_or_copy: cli.hardlink_or_copy,
+        no_hash: cli.no_hash,
+        compress: cli.compress,
+        strict: cli.strict,
+        ignore_hidden: cli.ignore_hidden,
+        include_hidden: cli.include_hidden,
+        min_age: cli.min_age,
+        max_age: cli.max_age,
+        no_reflink: cli.no_reflink,
+        sort_items_by: cli.sort_items_by,
+        quiet: cli.quiet,
+        force: cli.force,
+        restore_message: cli.message_on_restore.clone(),
+        meta: cli.meta.clone(),
+        meta_file: cli.meta_file.clone(),
+        checksum_file: cli.checksum_file.clone(),
+        pre_command: cli.pre_command.clone(),
+        verbose: cli.verbose,
+        version_tag: cli.version_tag.map(|n| n as usize),
+        jobs: cli.jobs.map(|n| n as usize),
+        format: cli.output_format(),
+    })
+}
+
+pub fn pop_mode(cli: &Cli, identifier: Option<String>) -> OperationMode {
+    if let Some((archive, format)) = &cli.to_archive {
+        return OperationMode::PopToArchive {
+            identifier,
+            archive: archive.clone(),
+            format: *format,
+            force: cli.force,
+        };
+    }
+
+    OperationMode::Pop {
+        identifier,
+        copy: cli.copy,
+        force: cli.force,
+        restore: cli.restore,
+        missing_ok: cli.missing_ok,
+        verify_dest: cli.verify_dest,
+        git_aware: cli.git_aware,
+        post_command: cli.post_command.clone(),
+        no_reflink: cli.no_reflink,
+        quiet: cli.quiet,
+        only: cli.only.clone(),
+        strict: cli.strict,
+        backup: cli.backup,
+        resolve_to: cli.resolve_to.clone(),
+        version: cli.entry_version,
+        jobs: cli.jobs.map(|n| n as usize),
+        format: cli.output_format(),
+    }
+}
+
+/// Pop the entry named by `identifiers`, which must be exactly one
+fn single_pop(cli: &Cli, identifiers: &[&PathBuf]) -> Result<OperationMode> {
+    if let [identifier] = identifiers {
+        return Ok(pop_mode(cli, Some(identifier.to_string_lossy().to_string())));
+    }
+
+    bail!(StashError::Usage(format!(
+        "Cannot restore multiple entries at once: {}\n\
+         Entries are referenced by a single name or ID.\n\
+         Use --list to see available entries.",
+        format_paths_refs(identifiers)
+    )))
+}
+
+/// The configured `ambiguity_mode`; a missing config file means the default
+fn ambiguity_mode() -> Result<AmbiguityMode> {
+    Ok(ConfigStorage::existing(&AppDirs::new().config_file)?.ambiguity_mode)
+}
+
+/// Whether a path exists without following symlinks, so dangling links count
+fn exists_locally(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok()
+}
+
+/// `--rename` takes either an `OLD:NEW` spec or the explicit
+/// `--from-uuid`/`--to` and `--swap` forms, never both
+fn rename_mode(cli: &Cli, spec: Option<&str>) -> Result<OperationMode> {
+    let explicit = cli.from_uuid.is_some() || !cli.swap.is_empty();
+
+    match spec {
+        Some(_) if explicit => bail!(StashError::Usage(
+            "--rename OLD:NEW can't be combined with --from-uuid or --swap".into()
+        )),
+        Some(spec) => {
+            let (old, new) = parse_rename_spec(spec)?;
+            Ok(OperationMode::Rename { old, new })
+        }
+        None => match (&cli.from_uuid, &cli.to, cli.swap.as_slice()) {
+            (Some(uuid), Some(new), _) => Ok(OperationMode::RenameUuid { uuid: *uuid, new: new.clone() }),
+            (_, _, [first, second]) => Ok(OperationMode::SwapNames { first: first.clone(), second: second.clone() }),
+            _ => bail!(StashError::Usage(
+                "--rename needs OLD:NEW, --from-uuid UUID --to NAME, or --swap ID1 ID2".into()
+            )),
+        },
+    }
+}
+
+/// Split an `OLD:NEW` rename spec on the first unescaped colon.
+/// A colon inside either name can be written as `\:`, and a literal
+/// backslash as `\\`.
+fn parse_rename_spec(spec: &str) -> Result<(String, String)> {
+    let mut old = String::new();
+    let mut new = String::new();
+    let mut in_new = false;
+    let mut chars = spec.chars();
+
+    while let Some(c) = chars.next() {
+        let target = if in_new { &mut new } else { &mut old };
+
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped @ (':' | '\\')) => target.push(escaped),
+                Some(other) => {
+                    target.push('\\');
+                    target.push(other);
+                }
+                None => target.push('\\'),
+            },
+            ':' if !in_new => in_new = true,
+            _ => target.push(c),
+        }
+    }
+
+    if !in_new {
+        bail!(StashError::Usage(
+            "--rename must be in OLD:NEW format (escape colons in names as '\\:')".into()
+        ));
+    }
+
+    if old.is_empty() || new.is_empty() {
+        bail!(StashError::Usage("--rename requires both OLD and NEW names".into()));
+    }
+
+    Ok((old, new))
 }
 
 fn format_paths_refs(paths: &[&PathBuf]) -> String {
@@ -144,3 +553,41 @@ fn format_paths_refs(paths: &[&PathBuf]) -> String {
         .join(", ")
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename(spec: &str) -> (String, String) {
+        parse_rename_spec(spec).unwrap()
+    }
+
+    #[test]
+    fn escaped_colons_stay_in_the_old_name() {
+        assert_eq!(rename(r"a\:b:c"), ("a:b".to_string(), "c".to_string()));
+    }
+
+    #[test]
+    fn later_colons_belong_to_the_new_name() {
+        assert_eq!(rename("a:b:c"), ("a".to_string(), "b:c".to_string()));
+        assert_eq!(rename(r"a:b\:c"), ("a".to_string(), "b:c".to_string()));
+    }
+
+    #[test]
+    fn escaped_backslashes_do_not_escape_the_colon() {
+        assert_eq!(rename(r"a\\:b"), (r"a\".to_string(), "b".to_string()));
+    }
+
+    #[test]
+    fn a_trailing_backslash_is_kept() {
+        assert_eq!(rename(r"a:b\"), ("a".to_string(), r"b\".to_string()));
+        assert_eq!(rename(r"a\b:c"), (r"a\b".to_string(), "c".to_string()));
+    }
+
+    #[test]
+    fn specs_without_both_names_are_rejected() {
+        for spec in ["ab", r"a\:b", ":b", "a:"] {
+            let err = parse_rename_spec(spec).unwrap_err();
+            assert!(matches!(err.downcast_ref::<StashError>(), Some(StashError::Usage(_))), "{}", spec);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 pub mod arguments;
+pub mod commands;
 pub mod dispatch;
 pub mod prompt;
 pub mod inference;
@@ -0,0 +1,274 @@
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use crate::models::config::{HashAlgorithm, SortOrder};
+use crate::models::entry::Entry;
+use crate::models::item::{Item, ItemKind};
+use crate::models::{Operation, OperationKind};
+use crate::services::entry_manager::{EntryManager, PopOptions, PushOptions};
+use crate::services::filesystem::file_compression::{self, CompressionLevel};
+use crate::services::filesystem::{sparse, walker};
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::display::{format_size, SizeStyle};
+use crate::utils::hashing;
+use crate::utils::paths::AppDirs;
+
+/// Files per directory in the generated dataset
+const FILES_PER_DIR: usize = 50;
+
+/// Items in the synthetic manifest used to compare JSON formats
+const MANIFEST_ITEMS: usize = 10_000;
+
+/// Operations in the synthetic journal
+const JOURNAL_OPERATIONS: usize = 50_000;
+
+/// Time the real push, pop, hashing and compression paths on a generated
+/// dataset. Everything happens in a scratch stash under the temp dir, which
+/// is removed afterwards; the user's stash is never opened.
+pub fn run(files: &usize, size_mb: &u64) -> Result<()> {
+    let dirs = AppDirs::new();
+    let config = if dirs.config_file.exists() {
+        ConfigStorage::new(&dirs.config_file)?.get_config().clone()
+    } else {
+        Default::default()
+    };
+
+    let scratch = std::env::temp_dir().join(format!("stash-bench-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&scratch)?;
+
+    let result = benchmark(&scratch, *files, *size_mb * 1024 * 1024, config.push_workers);
+
+    let _ = fs::remove_dir_all(&scratch);
+
+    result
+}
+
+fn benchmark(scratch: &Path, files: usize, total_bytes: u64, workers: usize) -> Result<()> {
+    let work = scratch.join("work");
+    let dataset = work.join("dataset");
+    let stash = AppDirs::global_at(&scratch.join("stash"));
+    fs::create_dir_all(&stash.entries_dir)?;
+
+    let bytes = generate(&dataset, files.max(1), total_bytes)?;
+    println!(
+        "Benchmarking with {} file(s), {} in total, in {}",
+        files.max(1),
+        format_size(bytes, SizeStyle::Human),
+        scratch.display()
+    );
+    println!();
+
+    let mut index_storage = IndexStorage::new(&stash.index_file)?;
+    let mut journal_storage = JournalStorage::new(&stash.journal_file)?;
+    let mut entry_manager = EntryManager::new(&stash.entries_dir, &mut index_storage, &mut journal_storage)?;
+
+    let items = vec![dataset.clone()];
+    let name = Some("benchmark".to_string());
+    let push = |copy: &'static bool| PushOptions {
+        name: &name,
+        copy,
+        hardlink: &false,
+        hardlink_fallback: &false,
+        hash: &true,
+        hash_algorithm: &HashAlgorithm::Sha256,
+        hash_max_size: &None,
+        verify: &true,
+        workers: &workers,
+        compression: &None,
+        strict: &false,
+        sort_items: &SortOrder::Unsorted,
+        ignore_hidden: &false,
+        min_age: &None,
+        max_age: &None,
+        force: &true,
+        restore_message: &None,
+        metadata: &serde_json::Value::Null,
+        recorded_paths: None,
+        versioned: &false,
+        max_versions: &None,
+        pre_command: &None,
+        verbose: &false,
+    };
+
+    let (entry, elapsed) = timed(|| entry_manager.create_entry(&items, push(&false), &work))?;
+    report("push (move)", elapsed, bytes);
+
+    let (_, elapsed) = timed(|| {
+        entry_manager.pop_entry(&entry.uuid, PopOptions {
+            destination: &work,
+            copy: &false,
+            force: &true,
+            missing_ok: &false,
+            preserve_mtime: &true,
+            verify: &true,
+            only: &[],
+            post_command: &None,
+        })
+    })?;
+    report("pop (move)", elapsed, bytes);
+
+    let (entry, elapsed) = timed(|| entry_manager.create_entry(&items, push(&true), &work))?;
+    report("push (copy)", elapsed, bytes);
+    entry_manager.delete_entry(&entry.uuid)?;
+
+    for (label, algorithm) in [("hash (sha256)", HashAlgorithm::Sha256), ("hash (blake3)", HashAlgorithm::Blake3)] {
+        let (_, elapsed) = timed(|| hash_tree(&dataset, algorithm))?;
+        report(label, elapsed, bytes);
+    }
+
+    // The whole dataset as one file, read through a buffer and then mapped
+    let large = scratch.join("large");
+    let large_bytes = generate(&large, 1, total_bytes)?;
+    let large_file = large.join("dir0000").join("file00000.dat");
+    let (_, elapsed) = timed(|| Ok(hashing::hash_reader(fs::File::open(&large_file)?, HashAlgorithm::Sha256)?))?;
+    report("hash 1 file (read)", elapsed, large_bytes);
+    if large_bytes >= hashing::MMAP_THRESHOLD {
+        let (_, elapsed) = timed(|| Ok(hashing::calculate_hash(&large_file, HashAlgorithm::Sha256)?))?;
+        report("hash 1 file (mmap)", elapsed, large_bytes);
+    }
+
+    // The same file copied by the kernel, then through user-space buffers
+    // of a few copy_buffer_kb sizes as hashing copies are
+    let copy = large.join("copy");
+    let (_, elapsed) = timed(|| Ok(fs::copy(&large_file, &copy)?))?;
+    report("copy (kernel)", elapsed, large_bytes);
+    let metadata = fs::metadata(&large_file)?;
+    for kb in [64, 1024, 8192] {
+        fs::remove_file(&copy)?;
+        let (_, elapsed) = timed(|| Ok(sparse::copy_file_with(&large_file, &copy, &metadata, kb * 1024, |_| {})?))?;
+        report(&format!("copy ({} KB buffer)", kb), elapsed, large_bytes);
+    }
+    fs::remove_dir_all(&large)?;
+
+    let levels = [
+        ("compress (fast)", CompressionLevel::Fast),
+        ("compress (medium)", CompressionLevel::Medium),
+        ("compress (maximum)", CompressionLevel::Maximum),
+        ("compress (extreme)", CompressionLevel::Extreme),
+    ];
+    for (label, level) in levels {
+        let (archive, elapsed) = timed(|| file_compression::compress(&dataset, &scratch.join("archive"), level))?;
+        let packed = fs::metadata(&archive)?.len();
+        report(label, elapsed, bytes);
+        println!("{:>22} -> {} ({:.0}%)", "", format_size(packed, SizeStyle::Human), packed as f64 * 100.0 / bytes.max(1) as f64);
+        fs::remove_file(&archive)?;
+    }
+
+    manifest_formats()?;
+    journal_tail(scratch)?;
+
+    Ok(())
+}
+
+/// Read the last few operations of a long journal, as `--history` does,
+/// and then all of them
+fn journal_tail(scratch: &Path) -> Result<()> {
+    let path = scratch.join("journal.log");
+    let mut lines = String::new();
+    for i in 0..JOURNAL_OPERATIONS {
+        let op = Operation::new(OperationKind::Push {
+            entry_id: uuid::Uuid::new_v4(),
+            file_count: i % 100 + 1,
+            name: Some(format!("entry-{}", i)),
+            size_bytes: Some(4096),
+        });
+        lines.push_str(&serde_json::to_string(&op)?);
+        lines.push('\n');
+    }
+    fs::write(&path, lines)?;
+
+    let (_, elapsed) = timed(|| JournalStorage::new(&path)?.recent(20))?;
+    println!("{:<22} {:>8.3}s  of {} operations", "journal (recent 20)", elapsed.as_secs_f64(), JOURNAL_OPERATIONS);
+    let (_, elapsed) = timed(|| Ok(JournalStorage::new(&path)?.operations()?.len()))?;
+    println!("{:<22} {:>8.3}s  of {} operations", "journal (all)", elapsed.as_secs_f64(), JOURNAL_OPERATIONS);
+
+    Ok(())
+}
+
+/// Write, then parse, a 10,000-item manifest indented and compact, the two
+/// forms `compact_json` chooses between
+fn manifest_formats() -> Result<()> {
+    let items = (0..MANIFEST_ITEMS)
+        .map(|i| {
+            let path = PathBuf::from(format!("src/module{:03}/file{:05}.rs", i / 100, i));
+            let hash = format!("sha256:{:064x}", i);
+            Item::new(path.clone(), path, ItemKind::File, 4096, 0o644, chrono::Utc::now(), Some(hash))
+        })
+        .collect();
+    let entry = Entry::new("benchmark".to_string(), items, PathBuf::from("/tmp/benchmark"), true);
+
+    println!();
+    for (label, compact) in [("manifest (pretty)", false), ("manifest (compact)", true)] {
+        let (written, elapsed) = timed(|| {
+            let json = if compact { serde_json::to_string(&entry)? } else { serde_json::to_string_pretty(&entry)? };
+            serde_json::from_str::<Entry>(&json)?;
+            Ok(json.len() as u64)
+        })?;
+        println!(
+            "{:<22} {:>8.3}s  {:>10} for {} items",
+            label,
+            elapsed.as_secs_f64(),
+            format_size(written, SizeStyle::Human),
+            MANIFEST_ITEMS
+        );
+    }
+
+    Ok(())
+}
+
+/// Write `files` files adding up to about `total_bytes`, half random and
+/// half repetitive so compression has something to work with
+fn generate(root: &Path, files: usize, total_bytes: u64) -> Result<u64> {
+    let per_file = (total_bytes / files as u64).max(1) as usize;
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut written = 0u64;
+
+    for i in 0..files {
+        let dir = root.join(format!("dir{:04}", i / FILES_PER_DIR));
+        fs::create_dir_all(&dir)?;
+
+        let mut contents = Vec::with_capacity(per_file);
+        while contents.len() < per_file / 2 {
+            // xorshift64: fast and good enough to defeat compression
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            contents.extend_from_slice(&state.to_le_bytes());
+        }
+        contents.truncate(per_file / 2);
+        let line = format!("line from file {} of the stash benchmark\n", i);
+        while contents.len() < per_file {
+            contents.extend_from_slice(line.as_bytes());
+        }
+        contents.truncate(per_file);
+
+        fs::write(dir.join(format!("file{:05}.dat", i)), &contents)?;
+        written += contents.len() as u64;
+    }
+
+    Ok(written)
+}
+
+fn hash_tree(root: &Path, algorithm: HashAlgorithm) -> Result<()> {
+    for entry in walker::walk(root) {
+        let entry = entry?;
+        if entry.metadata.is_file() {
+            hashing::calculate_hash(&entry.path, algorithm)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn timed<T>(f: impl FnOnce() -> Result<T>) -> Result<(T, Duration)> {
+    let start = Instant::now();
+    let value = f()?;
+    Ok((value, start.elapsed()))
+}
+
+fn report(label: &str, elapsed: Duration, bytes: u64) {
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 { (bytes as f64 / seconds) as u64 } else { 0 };
+    println!("{:<22} {:>8.3}s  {:>10}/s", label, seconds, format_size(rate, SizeStyle::Human));
+}
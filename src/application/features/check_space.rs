@@ -0,0 +1,48 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use crate::errors::StashError;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::display::{format_size, SizeStyle};
+use crate::utils::paths::AppDirs;
+
+/// Report whether popping `identifier` into `destination` would fit
+pub fn run(identifier: &str, destination: &Option<PathBuf>, copy: &bool) -> Result<()> {
+    let destination = match destination {
+        Some(path) => path.clone(),
+        None => std::env::current_dir()?,
+    };
+
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let entry = entry_manager.load_entry_by_identifier(identifier)?;
+    let (needed, available) = entry_manager.check_space(&entry, &destination, *copy)?;
+
+    println!(
+        "Restoring '{}' to {} needs {} ({} available)",
+        entry.display_name(),
+        destination.display(),
+        format_size(needed, SizeStyle::Human),
+        format_size(available, SizeStyle::Human)
+    );
+
+    if needed > available {
+        return Err(StashError::InsufficientSpace {
+            path: destination,
+            needed,
+            available,
+        }
+        .into());
+    }
+
+    println!("OK: enough space to restore.");
+
+    Ok(())
+}
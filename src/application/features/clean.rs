@@ -1,10 +1,32 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+use crate::application::cli::arguments::OutputFormat;
+use crate::errors::StashError;
+use crate::models::EntryMetadata;
+use crate::models::index;
+use crate::models::operation::clean_window;
 use crate::services::entry_manager::EntryManager;
-use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::display;
 use crate::utils::paths::AppDirs;
 
-pub fn run(days: i64) -> Result<()> {
+/// Remove entries older than `days` or in the `since`/`until` window, or
+/// with `keep_latest` all but the newest few. `filter_meta` narrows those
+/// ranked to the entries whose metadata matches; a filter without a value
+/// ranks each of its key's values separately.
+pub fn run(
+    days: i64,
+    since: &Option<DateTime<Utc>>,
+    until: &Option<DateTime<Utc>>,
+    keep_latest: &Option<usize>,
+    filter_meta: &[(String, Option<String>)],
+    dry_run: &bool,
+    format: &OutputFormat,
+) -> Result<()> {
     let dirs = AppDirs::new();
+    let config = ConfigStorage::new(&dirs.config_file)?;
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
     let mut entry_manager = EntryManager::new(
@@ -12,13 +34,114 @@ pub fn run(days: i64) -> Result<()> {
         &mut index_storage,
         &mut journal_storage,
     )?;
+    let window = clean_window(days, *since, *until, *keep_latest);
 
-    let removed = entry_manager.clean_old_entries(days)?;
-
-    if removed.is_empty() {
-        println!("No entries older than {} days.", days);
+    // Metadata only lives in the manifests, so each one has to be read
+    let groups: Option<Vec<Vec<Uuid>>> = if filter_meta.is_empty() {
+        None
     } else {
-        println!("Cleaned {} entries older than {} days.", removed.len(), days);
+        let mut groups: BTreeMap<Vec<String>, Vec<Uuid>> = BTreeMap::new();
+        for meta in entry_manager.list_entries() {
+            let entry = entry_manager.load_entry(&meta.uuid)?;
+            let mut group = Vec::new();
+            let matches = filter_meta.iter().all(|(key, value)| match value {
+                Some(value) => entry.metadata_matches(key, value),
+                None => entry.metadata.get(key).map(|value| group.push(value.to_string())).is_some(),
+            });
+            if matches {
+                groups.entry(group).or_default().push(meta.uuid);
+            }
+        }
+        Some(groups.into_values().collect())
+    };
+
+    let targets: Vec<EntryMetadata> = match keep_latest {
+        Some(n) => entry_manager.all_except_latest(*n, groups.as_deref()),
+        None => entry_manager.created_between(*since, index::clean_window_end(days, *since, *until)),
+    };
+
+    if *dry_run {
+        report(&targets, &window, true, format)?;
+
+        // Non-zero exit lets scripts gate on "nothing to clean"
+        if !targets.is_empty() {
+            return Err(StashError::PendingChanges(format!(
+                "{} entries would be removed",
+                targets.len()
+            ))
+            .into());
+        }
+
+        return Ok(());
+    }
+
+    let outcome = match keep_latest {
+        Some(n) => entry_manager.clean_all_except_latest(*n, groups.as_deref())?,
+        None => entry_manager.clean_old_entries(days, *since, *until)?,
+    };
+    entry_manager.prune_journal_if_over(config.get_config().journal_prune_limits())?;
+
+    let removed: Vec<EntryMetadata> = targets
+        .iter()
+        .filter(|meta| outcome.removed.contains(&meta.uuid))
+        .cloned()
+        .collect();
+    report(&removed, &window, false, format)?;
+
+    for (uuid, err) in &outcome.failed {
+        let name = targets
+            .iter()
+            .find(|meta| &meta.uuid == uuid)
+            .map_or_else(|| uuid.to_string(), |meta| meta.display_name());
+        eprintln!("Failed to clean '{}' ({}): {:#}", name, &uuid.to_string()[..6], err);
+    }
+
+    if !outcome.failed.is_empty() {
+        return Err(anyhow!(
+            "{} entries could not be removed; they stay in the stash, flagged for 'stash --health'",
+            outcome.failed.len()
+        ));
+    }
+
+    Ok(())
+}
+
+fn report(entries: &[EntryMetadata], window: &str, dry_run: bool, format: &OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(entries)?);
+        }
+        OutputFormat::Porcelain => {
+            for meta in entries {
+                println!("{}", display::porcelain_entry(meta));
+            }
+        }
+        OutputFormat::Human if entries.is_empty() => {
+            println!("No entries {}.", window);
+        }
+        OutputFormat::Human if dry_run => {
+            println!("Would clean {} entries {}:", entries.len(), window);
+            for meta in entries {
+                println!(
+                    "  • {} ({}, {}, {})",
+                    meta.display_name(),
+                    &meta.uuid.to_string()[..6],
+                    display::humanize_duration(meta.created),
+                    display::format_size(meta.total_size_bytes, display::SizeStyle::Human)
+                );
+            }
+            let reclaimed = entries
+                .iter()
+                .map(|m| m.compressed_size_bytes.unwrap_or(m.total_size_bytes))
+                .sum();
+            println!("Would free {}.", display::format_size(reclaimed, display::SizeStyle::Human));
+        }
+        OutputFormat::Human => {
+            println!("Cleaned {} entries {}:", entries.len(), window);
+            for meta in entries {
+                println!("  • {} ({})", meta.display_name(), &meta.uuid.to_string()[..6]);
+            }
+        }
     }
 
     Ok(())
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::errors::StashError;
+use crate::models::config::SortOrder;
+use crate::services::entry_manager::{EntryManager, PushOptions};
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::paths::AppDirs;
+
+/// Stands in for a directory in the recorded path of clipboard items
+const CLIPBOARD_DIR: &str = "<clipboard>";
+
+/// Stash the clipboard's text content as a single-file entry
+pub fn run(name: &Option<String>) -> Result<()> {
+    let text = arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .context("Could not read text from the clipboard")?;
+
+    if text.is_empty() {
+        return Err(StashError::NothingMatched("The clipboard is empty".into()).into());
+    }
+    if text.contains('\0') {
+        return Err(StashError::Usage("The clipboard holds binary data, not text".into()).into());
+    }
+
+    let name = name.clone().unwrap_or_else(|| "clipboard".to_string());
+    let file_name = Path::new(&name)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "clipboard".to_string());
+
+    let staging = std::env::temp_dir().join(format!("stash-clipboard-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&staging)?;
+
+    let result = push_text(&text, &Some(name), &file_name, &staging);
+
+    let _ = fs::remove_dir_all(&staging);
+
+    result
+}
+
+fn push_text(text: &str, name: &Option<String>, file_name: &str, staging: &Path) -> Result<()> {
+    let file = staging.join(file_name);
+    fs::write(&file, text)?;
+
+    let dirs = AppDirs::new();
+    let config_storage = ConfigStorage::new(&dirs.config_file)?;
+    let config = config_storage.get_config();
+
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let mut entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let recorded = [PathBuf::from(CLIPBOARD_DIR).join(file_name)];
+
+    // Copy rather than move: the staged file is removed either way, and
+    // there is no original to put back if the push fails
+    let options = PushOptions {
+        name,
+        copy: &true,
+        hardlink: &false,
+        hardlink_fallback: &false,
+        hash: &config.compute_hashes_on_push,
+        hash_algorithm: &config.hash_algorithm,
+        hash_max_size: &config.hash_max_size(),
+        verify: &config.verify_integrity,
+        workers: &1,
+        compression: &None,
+        strict: &false,
+        sort_items: &SortOrder::Unsorted,
+        ignore_hidden: &false,
+        min_age: &None,
+        max_age: &None,
+        force: &false,
+        restore_message: &None,
+        metadata: &serde_json::Value::Null,
+        recorded_paths: Some(&recorded),
+        versioned: &false,
+        max_versions: &None,
+        pre_command: &None,
+        verbose: &false,
+    };
+
+    let entry = entry_manager.create_entry(&[file], options, &std::env::current_dir()?)?;
+
+    println!(
+        "Stashed {} from the clipboard as '{}'",
+        crate::utils::display::format_size(entry.total_size_bytes, crate::utils::display::SizeStyle::Human),
+        entry.display_name()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+use anyhow::Result;
+use crate::errors::StashError;
+use crate::services::storage::ConfigStorage;
+use crate::utils::paths::AppDirs;
+
+/// Print the effective config, print one key of it, or set a key and save
+/// the config file. Only setting a key creates the file.
+pub fn run(key: &Option<String>, value: &Option<String>) -> Result<()> {
+    let dirs = AppDirs::new();
+
+    match (key, value) {
+        (None, _) => {
+            let config = ConfigStorage::existing(&dirs.config_file)?;
+            if dirs.config_file.exists() {
+                println!("# {}", dirs.config_file.display());
+            } else {
+                println!("# {} (missing, showing defaults)", dirs.config_file.display());
+            }
+            print!("{}", toml::to_string_pretty(&config)?);
+        }
+        (Some(key), None) => {
+            let config = ConfigStorage::existing(&dirs.config_file)?;
+            let root = toml::Value::try_from(&config)?;
+            let value = key
+                .split('.')
+                .try_fold(&root, |value, part| value.get(part))
+                .ok_or_else(|| StashError::Usage(format!("No config key '{}'", key)))?;
+
+            match value {
+                toml::Value::String(s) => println!("{}", s),
+                toml::Value::Table(table) => print!("{}", toml::to_string_pretty(table)?),
+                other => println!("{}", other),
+            }
+        }
+        (Some(key), Some(value)) => {
+            let mut config_storage = ConfigStorage::new(&dirs.config_file)?;
+            config_storage.try_set_value(key, value).map_err(StashError::Usage)?;
+            println!("Set {} = {}", key, value);
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,20 @@
 use anyhow::Result;
+use std::io::IsTerminal;
+use uuid::Uuid;
+use crate::application::cli::prompt;
+use crate::errors::StashError;
+use crate::models::EntryMetadata;
 use crate::services::entry_manager::EntryManager;
 use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::display::{format_size, SizeStyle};
+use crate::utils::normalize;
 use crate::utils::paths::AppDirs;
 
-pub fn run(identifier: &str) -> Result<()> {
+/// Delete every entry the targets name. A target is a UUID, a name or a glob
+/// over names. Targets that match nothing, or a name several entries share,
+/// are reported and skipped; the rest are deleted after one confirmation
+/// (skipped with `force`).
+pub fn run(targets: &[String], force: &bool) -> Result<()> {
     let dirs = AppDirs::new();
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
@@ -13,11 +24,122 @@ pub fn run(identifier: &str) -> Result<()> {
         &mut journal_storage,
     )?;
 
-    let entry = entry_manager.load_entry_by_identifier(identifier)?;
+    let mut failures = Vec::new();
+    let mut selected: Vec<EntryMetadata> = Vec::new();
 
-    entry_manager.delete_entry(&entry.uuid)?;
+    for target in targets {
+        match resolve(entry_manager.list_entries(), target) {
+            Ok(matched) => {
+                for meta in matched {
+                    if !selected.iter().any(|s| s.uuid == meta.uuid) {
+                        selected.push(meta.clone());
+                    }
+                }
+            }
+            Err(err) => {
+                eprintln!("Skipping '{}': {}", target, err);
+                failures.push(err);
+            }
+        }
+    }
 
-    println!("Deleted entry '{}' ({} files)", entry.name, entry.items.len());
+    if selected.is_empty() {
+        return Err(failures
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| StashError::NothingMatched("No entries to delete".into()).into()));
+    }
 
-    Ok(())
+    let total: u64 = selected.iter().map(|meta| meta.total_size_bytes).sum();
+    println!(
+        "{} {} entr{} ({}):",
+        if *force { "Deleting" } else { "About to delete" },
+        selected.len(),
+        if selected.len() == 1 { "y" } else { "ies" },
+        format_size(total, SizeStyle::Human)
+    );
+    for meta in &selected {
+        println!(
+            "  • {} ({}, {} files, {})",
+            meta.display_name(),
+            &meta.uuid.to_string()[..6],
+            meta.item_count,
+            format_size(meta.total_size_bytes, SizeStyle::Human)
+        );
+    }
+
+    if !*force {
+        if !std::io::stdin().is_terminal() {
+            return Err(StashError::Usage("Refusing to delete without confirmation; pass --force".into()).into());
+        }
+        if !prompt::prompt_bool("Delete them? [y/n]")? {
+            println!("Nothing deleted.");
+            return Ok(());
+        }
+    }
+
+    let mut deleted = 0;
+    for meta in &selected {
+        match entry_manager.delete_entry(&meta.uuid) {
+            Ok(()) => {
+                deleted += 1;
+                println!("Deleted entry '{}' ({} files)", meta.display_name(), meta.item_count);
+            }
+            Err(err) => {
+                eprintln!("Failed to delete '{}': {:#}", meta.display_name(), err);
+                failures.push(err);
+            }
+        }
+    }
+
+    let Some(first) = failures.into_iter().next() else {
+        return Ok(());
+    };
+
+    // Exit with the first failure's code, saying how much did go through
+    Err(first.context(format!("Deleted {} of {} entries; some targets failed", deleted, selected.len())))
+}
+
+/// The entries one target names: the single entry with that UUID or name
+/// (the latest, for a versioned name), or failing that every entry whose
+/// name matches it as a glob. A name containing `[` or `*` is taken
+/// literally when an entry has it.
+fn resolve<'a>(entries: &'a [EntryMetadata], target: &str) -> Result<Vec<&'a EntryMetadata>> {
+    if let Ok(uuid) = Uuid::parse_str(target) {
+        if let Some(meta) = entries.iter().find(|meta| meta.uuid == uuid) {
+            return Ok(vec![meta]);
+        }
+    }
+
+    let named: Vec<_> = entries.iter().filter(|meta| normalize::same_text(&meta.name, target)).collect();
+    match named.len() {
+        0 if target.contains(['*', '?', '[']) => resolve_glob(entries, target),
+        0 => Err(StashError::NotFound(target.to_string()).into()),
+        1 => Ok(named),
+        // Versions of one name: the name means the latest
+        _ if named.iter().all(|meta| meta.version.is_some()) => {
+            Ok(named.into_iter().max_by_key(|meta| meta.version).into_iter().collect())
+        }
+        n => Err(StashError::Usage(format!(
+            "{} entries are named '{}'; use one of their UUIDs instead: {}",
+            n,
+            target,
+            named.iter().map(|meta| meta.uuid.to_string()).collect::<Vec<_>>().join(", ")
+        ))
+        .into()),
+    }
+}
+
+/// Every entry whose name matches `target` as a glob
+fn resolve_glob<'a>(entries: &'a [EntryMetadata], target: &str) -> Result<Vec<&'a EntryMetadata>> {
+    let pattern = glob::Pattern::new(target)
+        .map_err(|err| StashError::Usage(format!("Invalid glob '{}': {}", target, err)))?;
+    let matched: Vec<_> = entries
+        .iter()
+        .filter(|meta| pattern.matches(&normalize::nfc(&meta.display_name())))
+        .collect();
+    if matched.is_empty() {
+        return Err(StashError::NothingMatched(format!("No entries match '{}'", target)).into());
+    }
+    Ok(matched)
 }
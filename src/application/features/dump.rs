@@ -1,13 +1,18 @@
 use anyhow::Result;
+use std::path::PathBuf;
 
-use crate::services::entry_manager::{EntryManager, PopOptions};
-use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::services::entry_manager::{EntryManager, ManifestChecks, PopOptions};
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
 use crate::utils::paths::AppDirs;
 
-pub fn run() -> Result<()> {
+/// Restore every entry. Without `to` entries are popped into the current
+/// directory; with it each is copied into its own subdirectory of `to`
+/// and stays stashed unless `delete` is set.
+pub fn run(to: &Option<PathBuf>, delete: &bool) -> Result<()> {
     let cwd = std::env::current_dir()?;
     let dirs = AppDirs::new();
 
+    let config = ConfigStorage::new(&dirs.config_file)?;
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
 
@@ -16,6 +21,7 @@ pub fn run() -> Result<()> {
         &mut index_storage,
         &mut journal_storage,
     )?;
+    entry_manager.set_manifest_checks(ManifestChecks::from_flags(config.get_config().verify_integrity, false));
 
     let entries: Vec<_> = entry_manager
         .list_entries()
@@ -28,23 +34,39 @@ pub fn run() -> Result<()> {
         return Ok(());
     }
 
-    println!("Restoring {} entries...", entries.len());
+    match to {
+        Some(to) => println!("Restoring {} entries to {}...", entries.len(), to.display()),
+        None => println!("Restoring {} entries...", entries.len()),
+    }
 
     for uuid in entries {
         let entry = entry_manager.load_entry(&uuid)?;
 
+        // Names may contain separators; keep one directory per entry
+        let destination = match to {
+            Some(to) => to.join(entry.display_name().replace(['/', '\\'], "_")),
+            None => cwd.clone(),
+        };
+        std::fs::create_dir_all(&destination)?;
+
         let options = PopOptions {
-            destination: &cwd,
-            copy: &false,
+            destination: &destination,
+            copy: &(to.is_some() && !*delete),
             force: &true,
+            missing_ok: &false,
+            preserve_mtime: &config.get_config().preserve_mtime,
+            verify: &config.get_config().verify_integrity,
+            only: &[],
+            post_command: &None,
         };
 
         entry_manager.pop_entry(&uuid, options)?;
-        println!("  Restored: {}", entry.name);
+        println!("  Restored: {}", entry.display_name());
     }
 
+    entry_manager.prune_journal_if_over(config.get_config().journal_prune_limits())?;
+
     println!("\nDump complete.");
 
     Ok(())
 }
-
@@ -0,0 +1,120 @@
+use anyhow::{Context, Result, bail};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+use crate::errors::StashError;
+use crate::models::item::ItemKind;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::display::escape_path;
+use crate::utils::paths::AppDirs;
+
+/// Bytes inspected when deciding whether a file is binary
+const BINARY_SNIFF_LEN: usize = 8192;
+
+pub fn run(identifier: &str, path: &Path, force: &bool) -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let mut entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let entry = entry_manager.load_entry_by_identifier(identifier)?;
+    let item = entry_manager.find_item(&entry, path)?;
+
+    if item.kind != ItemKind::File {
+        bail!(StashError::Usage(format!(
+            "{} is not a regular file",
+            escape_path(&item.original_path)
+        )));
+    }
+
+    if entry.total_compressed_size_bytes().is_some() {
+        bail!("Entry '{}' is compressed; pop it to edit its files", entry.display_name());
+    }
+
+    let data_path = entry_manager.item_data_path(&entry.uuid, item);
+    if !data_path.is_file() {
+        bail!("Stashed data for {} not found", escape_path(&item.original_path));
+    }
+
+    if !*force && is_binary(&data_path)? {
+        bail!(StashError::Usage(format!(
+            "{} looks like a binary file. Use --force to edit it anyway.",
+            escape_path(&item.original_path)
+        )));
+    }
+
+    // Edit a copy named like the original so editors pick the right syntax
+    let temp_dir = std::env::temp_dir().join(format!("stash-edit-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&temp_dir)?;
+    let file_name = item.original_path.file_name().unwrap_or(data_path.as_os_str());
+    let temp_file = temp_dir.join(file_name);
+
+    let result = edit_copy(&data_path, &temp_file);
+    let changed = match result {
+        Ok(changed) => changed,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&temp_dir);
+            return Err(e);
+        }
+    };
+
+    // Replace the stashed file rather than writing into it: a hard-linked
+    // entry shares it with the original, which must not change too
+    if changed {
+        let staged = data_path.with_extension("stash-edit.tmp");
+        fs::copy(&temp_file, &staged)?;
+        fs::rename(&staged, &data_path)?;
+    }
+    let _ = fs::remove_dir_all(&temp_dir);
+
+    if !changed {
+        println!("No changes made to {}", escape_path(&item.original_path));
+        return Ok(());
+    }
+
+    let original_path = item.original_path.clone();
+    entry_manager.update_item(&entry.uuid, &original_path)?;
+
+    println!("Updated {} in '{}'", escape_path(&original_path), entry.display_name());
+
+    Ok(())
+}
+
+/// Open a copy of `data_path` in the user's editor.
+/// Returns whether the content changed.
+fn edit_copy(data_path: &Path, temp_file: &Path) -> Result<bool> {
+    fs::copy(data_path, temp_file)?;
+    let before = fs::read(temp_file)?;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    // Allow editors configured with arguments, e.g. "code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or("vi");
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(temp_file)
+        .status()
+        .with_context(|| format!("Failed to launch editor '{}'", editor))?;
+
+    if !status.success() {
+        bail!("Editor '{}' exited with {}; stash left unchanged", editor, status);
+    }
+
+    Ok(fs::read(temp_file)? != before)
+}
+
+fn is_binary(path: &Path) -> Result<bool> {
+    let mut buffer = [0u8; BINARY_SNIFF_LEN];
+    let read = fs::File::open(path)?.read(&mut buffer)?;
+    Ok(buffer[..read].contains(&0))
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+use uuid::Uuid;
+use crate::application::cli::arguments::OutputFormat;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::display::{escape_path, porcelain_field};
+use crate::utils::paths::AppDirs;
+
+#[derive(Serialize)]
+struct StashedPath {
+    entry: String,
+    path: String,
+}
+
+/// Print the original path of every stashed item, or of one entry's items
+pub fn run(entry: &Option<String>, filter: &Option<glob::Pattern>, format: &OutputFormat) -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    // The index caches every entry's item paths; manifests are only read
+    // for entries written before the cache existed
+    let entries: Vec<(Uuid, String, Vec<PathBuf>)> = match entry {
+        Some(ident) => {
+            let entry = entry_manager.load_entry_by_identifier(ident)?;
+            vec![(entry.uuid, entry.display_name(), entry.item_paths())]
+        }
+        None => entry_manager
+            .list_entries()
+            .iter()
+            .map(|meta| match meta.cached_paths() {
+                Some(paths) => Ok((meta.uuid, meta.display_name(), paths.to_vec())),
+                None => Ok((meta.uuid, meta.display_name(), entry_manager.load_entry(&meta.uuid)?.item_paths())),
+            })
+            .collect::<Result<_>>()?,
+    };
+
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+
+    let paths: Vec<_> = entries
+        .iter()
+        .flat_map(|(uuid, name, paths)| paths.iter().map(move |path| (uuid, name, path)))
+        .filter(|(_, _, path)| filter.as_ref().is_none_or(|glob| glob.matches_path_with(path, options)))
+        .collect();
+
+    match format {
+        OutputFormat::Json => {
+            let paths: Vec<_> = paths
+                .iter()
+                .map(|(_, name, path)| StashedPath {
+                    entry: name.to_string(),
+                    path: path.to_string_lossy().into_owned(),
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&paths)?);
+        }
+        OutputFormat::Porcelain => {
+            for (uuid, _, path) in paths {
+                println!("{}\t{}", uuid, porcelain_field(&path.to_string_lossy()));
+            }
+        }
+        OutputFormat::Human => {
+            for (_, _, path) in paths {
+                println!("{}", escape_path(path));
+            }
+        }
+    }
+
+    Ok(())
+}
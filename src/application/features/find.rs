@@ -0,0 +1,52 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use crate::application::cli::arguments::OutputFormat;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::display::{escape_path, porcelain_field};
+use crate::utils::paths::AppDirs;
+
+/// Print the entries holding an item stashed from `path`
+pub fn run(path: &Path, format: &OutputFormat) -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    // The item's own path, which may differ from `path` in Unicode normalization
+    let mut found: Vec<(Uuid, String, PathBuf)> = Vec::new();
+    for uuid in entry_manager.find_entries_containing_path(path)? {
+        let entry = entry_manager.load_entry(&uuid)?;
+        let stashed = entry.get_item(path).map_or_else(|| path.to_path_buf(), |item| item.original_path.clone());
+        found.push((uuid, entry.display_name(), stashed));
+    }
+
+    if *format == OutputFormat::Porcelain {
+        for (uuid, _, path) in found {
+            println!("{}\t{}", uuid, porcelain_field(&path.to_string_lossy()));
+        }
+        return Ok(());
+    }
+
+    if found.is_empty() {
+        println!("No entries hold {}.", escape_path(path));
+        return Ok(());
+    }
+
+    println!(
+        "{} is in {} entr{}:",
+        escape_path(path),
+        found.len(),
+        if found.len() == 1 { "y" } else { "ies" }
+    );
+    for (uuid, name, _) in found {
+        println!("  • {} ({})", name, &uuid.to_string()[..6]);
+    }
+
+    Ok(())
+}
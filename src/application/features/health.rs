@@ -0,0 +1,301 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use crate::models::{Config, Index, Operation};
+use crate::models::entry::Entry;
+use crate::services::storage::JournalStorage;
+use crate::utils::display::{format_size, SizeStyle};
+use crate::utils::paths::AppDirs;
+
+/// Prefixes of the staging directories features create under the system temp dir
+const STAGING_PREFIXES: &[&str] = &[
+    "stash-import-",
+    "stash-export-",
+    "stash-sync-",
+    "stash-edit-",
+    "stash-clipboard-",
+    "stash-bench-",
+];
+
+/// Run every self-check and print a report. Fails if any check fails.
+pub fn run() -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut failures = 0;
+
+    let mut report = |result: Result<String>| match result {
+        Ok(message) => println!("[ OK ] {}", message),
+        Err(err) => {
+            failures += 1;
+            println!("[ FAIL ] {:#}", err);
+        }
+    };
+
+    report(check_dirs(&dirs));
+
+    let index = parse_json::<Index>(&dirs.index_file);
+    report(match &index {
+        Ok(Some(index)) => Ok(format!("Index parses ({} entries)", index.entries.len())),
+        Ok(None) => Ok("No index yet (nothing stashed)".to_string()),
+        Err(err) => Err(anyhow!("{}", err)),
+    });
+
+    let journal = read_journal(&dirs.journal_file);
+    report(match &journal {
+        Ok(Some(journal)) => Ok(format!("Journal parses ({} operations)", journal.len())),
+        Ok(None) => Ok("No journal yet".to_string()),
+        Err(err) => Err(anyhow!("{}", err)),
+    });
+
+    let index = index.ok().flatten().unwrap_or_default();
+    let journal = journal.ok().flatten().unwrap_or_default();
+    report(check_space(&dirs, &index));
+    report(check_orphans(&dirs, &index));
+    report(check_ghosts(&dirs, &index));
+    report(check_failed_removals(&index));
+    report(check_path_cache(&dirs, &index));
+    report(check_leftovers(&dirs));
+    report(check_config(&dirs.config_file));
+    report(check_undoable(&journal));
+
+    if failures > 0 {
+        return Err(anyhow!("{} health check(s) failed", failures));
+    }
+
+    Ok(())
+}
+
+/// Stash directories exist and can be written to
+fn check_dirs(dirs: &AppDirs) -> Result<String> {
+    for dir in [&dirs.data_dir, &dirs.entries_dir, &dirs.config_dir] {
+        let metadata = fs::metadata(dir)
+            .map_err(|e| anyhow!("{} is not accessible: {} (run 'stash --init')", dir.display(), e))?;
+
+        if !metadata.is_dir() {
+            return Err(anyhow!("{} is not a directory", dir.display()));
+        }
+
+        // Permission bits don't account for ACLs or read-only mounts; try it
+        let probe = dir.join(format!(".stash-health-{}", Uuid::new_v4()));
+        fs::write(&probe, b"")
+            .map_err(|e| anyhow!("{} is not writable: {}", dir.display(), e))?;
+        let _ = fs::remove_file(&probe);
+    }
+
+    Ok("Stash directories are accessible and writable".to_string())
+}
+
+/// Parse a JSON file; `None` if it hasn't been written yet
+fn parse_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let json = fs::read_to_string(path)
+        .map_err(|e| anyhow!("Cannot read {}: {}", path.display(), e))?;
+
+    serde_json::from_str(&json)
+        .map(Some)
+        .map_err(|e| anyhow!("{} does not parse: {}", path.display(), e))
+}
+
+/// Every journaled operation, in either of the journal's formats
+fn read_journal(path: &Path) -> Result<Option<Vec<Operation>>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let journal = JournalStorage::new(path)?;
+    let operations = journal
+        .operations()
+        .map_err(|e| anyhow!("{} does not parse: {:#}", path.display(), e))?;
+    Ok(Some(operations.to_vec()))
+}
+
+/// There is room to restore everything in the stash as copies
+fn check_space(dirs: &AppDirs, index: &Index) -> Result<String> {
+    let available = fs2::available_space(&dirs.data_dir)?;
+    let total: u64 = index.entries.iter().map(|e| e.total_size_bytes).sum();
+
+    if available < total {
+        return Err(anyhow!(
+            "Only {} free, but the stash holds {}",
+            format_size(available, SizeStyle::Human),
+            format_size(total, SizeStyle::Human)
+        ));
+    }
+
+    Ok(format!(
+        "{} free for a stash of {}",
+        format_size(available, SizeStyle::Human),
+        format_size(total, SizeStyle::Human)
+    ))
+}
+
+/// Every entry directory is listed in the index
+fn check_orphans(dirs: &AppDirs, index: &Index) -> Result<String> {
+    let known: HashSet<Uuid> = index.entries.iter().map(|e| e.uuid).collect();
+
+    let orphans: Vec<String> = fs::read_dir(&dirs.entries_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let uuid = Uuid::parse_str(&name).ok()?;
+            (!known.contains(&uuid)).then_some(name)
+        })
+        .collect();
+
+    if !orphans.is_empty() {
+        return Err(anyhow!(
+            "{} entry director{} not in the index: {}",
+            orphans.len(),
+            if orphans.len() == 1 { "y" } else { "ies" },
+            orphans.join(", ")
+        ));
+    }
+
+    Ok("No orphaned entry directories".to_string())
+}
+
+/// Every indexed entry has a manifest on disk
+fn check_ghosts(dirs: &AppDirs, index: &Index) -> Result<String> {
+    let ghosts: Vec<String> = index
+        .entries
+        .iter()
+        .filter(|meta| !dirs.entries_dir.join(meta.uuid.to_string()).join("manifest.json").is_file())
+        .map(|meta| format!("'{}' ({})", meta.display_name(), meta.uuid))
+        .collect();
+
+    if !ghosts.is_empty() {
+        return Err(anyhow!(
+            "{} indexed entr{} missing from disk: {}",
+            ghosts.len(),
+            if ghosts.len() == 1 { "y is" } else { "ies are" },
+            ghosts.join(", ")
+        ));
+    }
+
+    Ok("No index entries without data".to_string())
+}
+
+/// The index's cached item paths agree with the manifests. Entries whose
+/// manifest is missing or unreadable are left to the other checks.
+fn check_path_cache(dirs: &AppDirs, index: &Index) -> Result<String> {
+    let stale: Vec<String> = index
+        .entries
+        .iter()
+        .filter(|meta| {
+            let manifest = dirs.entries_dir.join(meta.uuid.to_string()).join("manifest.json");
+            match parse_json::<Entry>(&manifest) {
+                Ok(Some(entry)) => meta.item_paths.as_ref() != Some(&entry.item_paths()),
+                _ => false,
+            }
+        })
+        .map(|meta| format!("'{}' ({})", meta.display_name(), meta.uuid))
+        .collect();
+
+    if !stale.is_empty() {
+        return Err(anyhow!(
+            "{} entr{} a missing or stale item path cache: {} (run 'stash --init --force' to rebuild it)",
+            stale.len(),
+            if stale.len() == 1 { "y has" } else { "ies have" },
+            stale.join(", ")
+        ));
+    }
+
+    Ok("Item path cache matches the manifests".to_string())
+}
+
+/// No entry is left over from a delete or clean that failed part-way
+fn check_failed_removals(index: &Index) -> Result<String> {
+    let flagged: Vec<String> = index
+        .entries
+        .iter()
+        .filter(|meta| meta.removal_failed)
+        .map(|meta| format!("'{}' ({})", meta.display_name(), meta.uuid))
+        .collect();
+
+    if !flagged.is_empty() {
+        return Err(anyhow!(
+            "{} entr{} could not be fully removed; check permissions and delete again: {}",
+            flagged.len(),
+            if flagged.len() == 1 { "y" } else { "ies" },
+            flagged.join(", ")
+        ));
+    }
+
+    Ok("No partially removed entries".to_string())
+}
+
+/// No temporary files were left behind by interrupted operations
+fn check_leftovers(dirs: &AppDirs) -> Result<String> {
+    let mut leftovers = Vec::new();
+    find_temp_files(&dirs.data_dir, &mut leftovers)?;
+
+    if let Ok(entries) = fs::read_dir(std::env::temp_dir()) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if STAGING_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+                leftovers.push(entry.path());
+            }
+        }
+    }
+
+    if !leftovers.is_empty() {
+        let listed: Vec<_> = leftovers.iter().map(|p| p.display().to_string()).collect();
+        return Err(anyhow!(
+            "{} temporary file(s) left by interrupted operations: {}",
+            leftovers.len(),
+            listed.join(", ")
+        ));
+    }
+
+    Ok("No leftover temporary files".to_string())
+}
+
+/// `.tmp` files and the hidden `.<name>.stash-<uuid>` copies made while moving
+fn find_temp_files(dir: &Path, found: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        let is_temp = name.ends_with(".tmp")
+            || (name.starts_with('.')
+                && name
+                    .rsplit_once(".stash-")
+                    .is_some_and(|(_, id)| Uuid::parse_str(id).is_ok()));
+
+        if is_temp {
+            found.push(entry.path());
+        } else if entry.file_type()?.is_dir() {
+            find_temp_files(&entry.path(), found)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The config file, if present, parses; a broken one is otherwise ignored silently
+fn check_config(config_file: &Path) -> Result<String> {
+    if !config_file.exists() {
+        return Ok("No config file; using defaults".to_string());
+    }
+
+    let toml_str = fs::read_to_string(config_file)?;
+    toml::from_str::<Config>(&toml_str)
+        .map_err(|e| anyhow!("{} does not parse: {}", config_file.display(), e.message()))?;
+
+    Ok("Config parses".to_string())
+}
+
+/// The journal records at least one operation that could be undone
+fn check_undoable(operations: &[Operation]) -> Result<String> {
+    let undoable = operations.iter().filter(|op| op.is_undoable()).count();
+
+    if undoable == 0 {
+        return Err(anyhow!("Journal has no undoable operations"));
+    }
+
+    Ok(format!("Journal has {} undoable operation(s)", undoable))
+}
@@ -1,22 +1,225 @@
 use anyhow::Result;
-use crate::services::storage::JournalStorage;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+use crate::models::{EntryMetadata, Operation, OperationKind};
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::display::{format_size, SizeStyle};
 use crate::utils::paths::AppDirs;
 
-pub fn run() -> Result<()> {
+/// Operations shown by `--history`
+const RECENT: usize = 20;
+
+pub fn run(diff: &bool) -> Result<()> {
     let dirs = AppDirs::new();
     let journal_storage = JournalStorage::new(&dirs.journal_file)?;
-    let operations = journal_storage.recent(20)?;
 
-    if operations.is_empty() {
+    if journal_storage.len()? == 0 {
         println!("No operation history.");
         return Ok(());
     }
 
     println!("Operation history:");
-    for op in operations {
-        let timestamp = op.timestamp.format("%Y-%m-%d %H:%M:%S");
-        println!("[{}] {}", timestamp, op.describe());
+
+    if *diff {
+        // The whole journal is replayed; only the end of it is shown
+        let index_storage = IndexStorage::new(&dirs.index_file)?;
+        let lines = diff_lines(journal_storage.operations()?, index_storage.list_all());
+        for line in &lines[lines.len().saturating_sub(RECENT)..] {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    for op in journal_storage.recent(RECENT)? {
+        println!("[{}] {}", timestamp(&op), op.describe());
     }
 
     Ok(())
 }
+
+fn timestamp(op: &Operation) -> String {
+    op.timestamp.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// What the journal knows about an entry while replaying it
+struct Tracked {
+    name: String,
+    files: Option<usize>,
+    size: Option<u64>,
+    pushed: Option<DateTime<Utc>>,
+}
+
+/// Replay the journal and describe how each operation changed the stash.
+///
+/// There are no index snapshots, so the states are approximated: entries
+/// pushed before the journal starts are seeded from the current index, a pop
+/// that doesn't record how many items it left (journals from before that was
+/// recorded) is taken to be partial if the entry is popped again later or is
+/// still indexed, and a clean is taken to have removed the entries old enough for
+/// it that are gone now (or, keeping the latest N, as many of the oldest gone
+/// entries as it removed).
+fn diff_lines(operations: &[Operation], index: &[EntryMetadata]) -> Vec<String> {
+    let current: HashMap<Uuid, &EntryMetadata> = index.iter().map(|meta| (meta.uuid, meta)).collect();
+
+    let mut created = Vec::new();
+    let mut last_pop = HashMap::new();
+    let mut first_name = HashMap::new();
+    for (i, op) in operations.iter().enumerate() {
+        match &op.kind {
+            OperationKind::Push { entry_id, .. }
+            | OperationKind::Copy { entry_id, .. }
+            | OperationKind::Import { entry_id, .. } => created.push(*entry_id),
+            OperationKind::Pop { entry_id, kept: None, .. } => {
+                last_pop.insert(*entry_id, i);
+            }
+            OperationKind::Rename { entry_id, old_name, .. } => {
+                first_name.entry(*entry_id).or_insert_with(|| old_name.clone());
+            }
+            _ => {}
+        }
+    }
+
+    let mut state: HashMap<Uuid, Tracked> = index
+        .iter()
+        .filter(|meta| !created.contains(&meta.uuid))
+        .map(|meta| {
+            let tracked = Tracked {
+                name: first_name.get(&meta.uuid).cloned().unwrap_or_else(|| meta.display_name()),
+                files: Some(meta.item_count),
+                size: Some(meta.total_size_bytes),
+                pushed: Some(meta.created),
+            };
+            (meta.uuid, tracked)
+        })
+        .collect();
+
+    let name_of = |uuid: &Uuid| {
+        first_name
+            .get(uuid)
+            .cloned()
+            .or_else(|| current.get(uuid).map(|meta| meta.display_name()))
+            .unwrap_or_else(|| uuid.to_string()[..6].to_string())
+    };
+
+    let mut lines = Vec::with_capacity(operations.len());
+
+    for (i, op) in operations.iter().enumerate() {
+        let before = state.len();
+
+        let (label, added, removed) = match &op.kind {
+            OperationKind::Push { entry_id, file_count, name, size_bytes }
+            | OperationKind::Copy { entry_id, file_count, name, size_bytes } => {
+                let tracked = Tracked {
+                    name: name.clone().filter(|name| !name.trim().is_empty()).unwrap_or_else(|| name_of(entry_id)),
+                    files: Some(*file_count),
+                    size: size_bytes.or_else(|| current.get(entry_id).map(|meta| meta.total_size_bytes)),
+                    pushed: Some(op.timestamp),
+                };
+                let label = if matches!(op.kind, OperationKind::Push { .. }) { "Push" } else { "Copy" };
+                state.insert(*entry_id, tracked);
+                (label, vec![*entry_id], Vec::new())
+            }
+            OperationKind::Import { entry_id, name } => {
+                let meta = current.get(entry_id);
+                state.insert(*entry_id, Tracked {
+                    name: name.clone(),
+                    files: meta.map(|meta| meta.item_count),
+                    size: meta.map(|meta| meta.total_size_bytes),
+                    pushed: Some(op.timestamp),
+                });
+                ("Import", vec![*entry_id], Vec::new())
+            }
+            OperationKind::Pop { entry_id, kept: None, .. }
+                if last_pop.get(entry_id) == Some(&i) && !current.contains_key(entry_id) =>
+            {
+                ("Pop", Vec::new(), vec![*entry_id])
+            }
+            OperationKind::Pop { entry_id, kept: Some(kept), .. } => {
+                // Still stashed, with fewer items of a size the journal doesn't know
+                if let Some(tracked) = state.get_mut(entry_id) {
+                    tracked.files = Some(*kept);
+                    tracked.size = None;
+                }
+                lines.push(format!("[{}] {} (no change)", timestamp(op), op.describe()));
+                continue;
+            }
+            OperationKind::Drop { entry_id, .. } => ("Drop", Vec::new(), vec![*entry_id]),
+            OperationKind::Clean { removed_count, days, since, until, keep_latest } => {
+                // Keeping the latest N can remove entries of any age, oldest first
+                let cutoff = match keep_latest {
+                    Some(_) => op.timestamp,
+                    None if since.is_some() => until.unwrap_or(op.timestamp),
+                    None => until.unwrap_or(op.timestamp - chrono::Duration::days(*days)),
+                };
+                let mut cleaned: Vec<(Uuid, DateTime<Utc>)> = state
+                    .iter()
+                    .filter(|(uuid, _)| !current.contains_key(uuid))
+                    .filter_map(|(uuid, tracked)| tracked.pushed.map(|pushed| (*uuid, pushed)))
+                    .filter(|(_, pushed)| *pushed < cutoff && since.is_none_or(|since| *pushed >= since))
+                    .collect();
+                if keep_latest.is_some() {
+                    cleaned.sort_by_key(|(_, pushed)| *pushed);
+                    cleaned.truncate(*removed_count);
+                }
+                let cleaned = cleaned.into_iter().map(|(uuid, _)| uuid).collect();
+                ("Clean", Vec::new(), cleaned)
+            }
+            _ => {
+                lines.push(format!("[{}] {} (no change)", timestamp(op), op.describe()));
+                continue;
+            }
+        };
+
+        let gone: Vec<Tracked> = removed.iter().filter_map(|uuid| state.remove(uuid)).collect();
+        if added.is_empty() && gone.is_empty() {
+            let untracked = !removed.is_empty()
+                || matches!(op.kind, OperationKind::Clean { removed_count, .. } if removed_count > 0);
+            let note = if untracked { "stashed before the journal starts; not tracked" } else { "no change" };
+            lines.push(format!("[{}] {} ({})", timestamp(op), op.describe(), note));
+            continue;
+        }
+
+        let changed: Vec<&Tracked> = if added.is_empty() {
+            gone.iter().collect()
+        } else {
+            added.iter().filter_map(|uuid| state.get(uuid)).collect()
+        };
+        let sign = if added.is_empty() { '-' } else { '+' };
+
+        lines.push(format!(
+            "[{}] {} {}{} entr{} {}{}, {} -> {} entries",
+            timestamp(op),
+            label,
+            sign,
+            changed.len(),
+            if changed.len() == 1 { "y" } else { "ies" },
+            changed.iter().map(|t| format!("'{}'", t.name)).collect::<Vec<_>>().join(", "),
+            summary(&changed, sign),
+            before,
+            state.len()
+        ));
+    }
+
+    lines
+}
+
+/// ` (5 files, +12.30 MB)`, leaving out whatever isn't known for every entry
+fn summary(entries: &[&Tracked], sign: char) -> String {
+    let files: Option<usize> = entries.iter().map(|t| t.files).sum();
+    let size: Option<u64> = entries.iter().map(|t| t.size).sum();
+
+    let parts: Vec<String> = [
+        files.map(|files| format!("{} file{}", files, if files == 1 { "" } else { "s" })),
+        size.map(|size| format!("{}{}", sign, format_size(size, SizeStyle::Human))),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if parts.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", parts.join(", "))
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::errors::StashError;
+use crate::services::download;
+use crate::services::entry_manager::EntryManager;
+use crate::services::filesystem::file_compression;
+use crate::models::config::HashAlgorithm;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::hashing;
+use crate::utils::paths::AppDirs;
+
+/// Import entries from an archive produced by `--tar`.
+/// `source` may be a file path, an http(s) URL, or `-` for stdin.
+pub fn run(source: &str, sha256: &Option<String>) -> Result<()> {
+    let staging = std::env::temp_dir().join(format!("stash-import-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&staging)?;
+
+    let result = import(source, sha256.as_deref(), &staging);
+
+    let _ = fs::remove_dir_all(&staging);
+
+    result
+}
+
+fn import(source: &str, sha256: Option<&str>, staging: &Path) -> Result<()> {
+    let archive = fetch_archive(source, staging)?;
+
+    if let Some(expected) = sha256 {
+        verify_sha256(&archive, expected, source)?;
+    }
+
+    let extracted = staging.join("extracted");
+    file_compression::decompress(&archive, &extracted)
+        .with_context(|| format!("Failed to unpack {}", source))?;
+
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let mut entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let mut imported = 0;
+    for dir in fs::read_dir(&extracted)? {
+        let dir = dir?.path();
+        if !dir.join("manifest.json").is_file() {
+            continue;
+        }
+
+        match entry_manager.import_entry(&dir)? {
+            Some(entry) => {
+                println!("  • {}", entry.display_name());
+                imported += 1;
+            }
+            None => println!("  • {} (already stashed, skipped)", dir.file_name().unwrap().to_string_lossy()),
+        }
+    }
+
+    if imported == 0 {
+        return Err(StashError::NothingMatched(format!("No new entries found in {}", source)).into());
+    }
+
+    println!("Imported {} entries from {}", imported, source);
+
+    Ok(())
+}
+
+/// Resolve the source to a local archive file, downloading or buffering stdin as needed
+fn fetch_archive(source: &str, staging: &Path) -> Result<PathBuf> {
+    if download::is_url(source) {
+        let partial = staging.join("download");
+        download::download(source, &partial)?;
+        return Ok(partial);
+    }
+
+    if source == "-" {
+        let partial = staging.join("stdin");
+        let mut file = fs::File::create(&partial)?;
+        std::io::copy(&mut std::io::stdin().lock(), &mut file)?;
+        return Ok(partial);
+    }
+
+    let path = PathBuf::from(source);
+    if !path.is_file() {
+        return Err(StashError::NotFound(source.to_string()).into());
+    }
+
+    Ok(path)
+}
+
+fn verify_sha256(path: &Path, expected: &str, source: &str) -> Result<()> {
+    let hash = hashing::calculate_hash(path, HashAlgorithm::Sha256)?;
+    let actual = hash.trim_start_matches("sha256:");
+    let expected = expected.trim_start_matches("sha256:").to_lowercase();
+
+    if actual != expected {
+        return Err(StashError::Integrity(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            source,
+            expected,
+            actual
+        ))
+        .into());
+    }
+
+    Ok(())
+}
@@ -1,55 +1,170 @@
-use anyhow::{Result, anyhow};
-use crate::services::entry_manager::EntryManager;
-use crate::services::storage::{IndexStorage, JournalStorage};
+use anyhow::{Result, bail};
+use crate::application::cli::arguments::OutputFormat;
+use crate::errors::StashError;
+use crate::services::entry_manager::{EntryManager, HashStatus, ManifestChecks};
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::display::{escape_path, format_size, format_time, SizeStyle, TimeStyle};
 use crate::utils::paths::AppDirs;
 
-pub fn run(identifier: &Option<String>) -> Result<()> {
+pub fn run(
+    identifier: &Option<String>,
+    hash_check: &bool,
+    disk_usage: &bool,
+    time: &TimeStyle,
+    sizes: &SizeStyle,
+    strict: &bool,
+    format: &OutputFormat,
+) -> Result<()> {
     let dirs = AppDirs::new();
+    let date_format = ConfigStorage::existing(&dirs.config_file)?.date_format;
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
-    let entry_manager = EntryManager::new(
+    let mut entry_manager = EntryManager::new(
         &dirs.entries_dir,
         &mut index_storage,
         &mut journal_storage,
     )?;
+    // Without --strict, whatever validation finds is listed below instead
+    entry_manager.set_manifest_checks(ManifestChecks::from_flags(false, *strict));
 
     let entry = if let Some(ident) = identifier {
         entry_manager.load_entry_by_identifier(ident)?
     } else {
         let meta = entry_manager.most_recent_entry()
-            .ok_or_else(|| anyhow!("No stashed entries found"))?;
+            .ok_or_else(|| StashError::NothingMatched("No stashed entries found".into()))?;
         entry_manager.load_entry(&meta.uuid)?
     };
+
+    // The manifest as stored, metadata included
+    if *format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&entry)?);
+        return Ok(());
+    }
+
+    // Capture previous verification times before the check restamps them
+    let last_verified: Vec<_> = entry.items.iter().map(|i| i.hash_verified_at).collect();
+
+    let statuses = if *hash_check {
+        Some(entry_manager.check_hashes(&entry.uuid)?.1)
+    } else {
+        None
+    };
+
     println!("UUID: {}", entry.uuid);
-    println!("Created: {}", entry.created.format("%Y-%m-%d %H:%M:%S"));
-    println!("Working directory: {}", entry.working_directory.display());
-    println!("Total size: {}", humanize_size(entry.total_size_bytes));
+    println!("Created: {}", format_time(entry.created, *time, &date_format));
+    println!("Working directory: {}", escape_path(&entry.working_directory));
+    if let Some(message) = &entry.restore_message {
+        println!("Restore message: {}", message);
+    }
+    match &entry.metadata {
+        serde_json::Value::Null => {}
+        serde_json::Value::Object(fields) => {
+            println!("Metadata:");
+            for (key, value) in fields {
+                match value {
+                    serde_json::Value::String(s) => println!("  {}: {}", key, s),
+                    other => println!("  {}: {}", key, other),
+                }
+            }
+        }
+        other => println!("Metadata: {}", other),
+    }
+    println!(
+        "Source: {}",
+        if entry.was_destructive {
+            "moved (originals removed)"
+        } else if entry.hardlinked {
+            "hard-linked (originals kept; editing them changes the stashed copy too)"
+        } else {
+            "copied (originals kept)"
+        }
+    );
+    let on_disk = entry_manager.disk_usage(&entry.uuid)?;
+    match entry.total_compressed_size_bytes() {
+        _ if *disk_usage => println!(
+            "Total size: {} logical, {} on disk",
+            format_size(entry.total_size_bytes, *sizes),
+            format_size(on_disk, *sizes)
+        ),
+        Some(compressed) if entry.total_size_bytes > 0 => {
+            let reduction = 100.0 - compressed as f64 * 100.0 / entry.total_size_bytes as f64;
+            println!(
+                "Total size: {} ({} compressed, {:.0}% reduction)",
+                format_size(entry.total_size_bytes, *sizes),
+                format_size(compressed, *sizes),
+                reduction
+            );
+        }
+        Some(_) => println!("Total size: {}", format_size(entry.total_size_bytes, *sizes)),
+        None => {
+            if on_disk < entry.total_size_bytes {
+                println!(
+                    "Total size: {} ({} on disk, sparse files)",
+                    format_size(entry.total_size_bytes, *sizes),
+                    format_size(on_disk, *sizes)
+                );
+            } else {
+                println!("Total size: {}", format_size(entry.total_size_bytes, *sizes));
+            }
+        }
+    }
+    let findings = entry.validate();
+    if !findings.is_empty() {
+        println!("Manifest checks:");
+        for finding in &findings {
+            println!("  {}", finding);
+        }
+    }
     println!("Files: {}", entry.items.len());
 
-    for item in &entry.items {
+    let mut failures = 0;
+
+    for (i, item) in entry.items.iter().enumerate() {
         let kind = match item.kind {
             crate::models::item::ItemKind::File => "file",
             crate::models::item::ItemKind::Directory => "dir ",
             crate::models::item::ItemKind::Symlink => "link",
         };
-        println!("  [{}] {}", kind, item.original_path.display());
-    }
 
-    Ok(())
-}
+        let Some(statuses) = &statuses else {
+            println!("  [{}] {}", kind, escape_path(&item.original_path));
+            for special in &item.special_files {
+                println!("         skipped special file {}", escape_path(special));
+            }
+            for hidden in &item.left_in_place {
+                println!("         left hidden {}", escape_path(hidden));
+            }
+            continue;
+        };
 
-fn humanize_size(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
-
-    if bytes >= GB {
-        format!("{:.1}GB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.1}MB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.0}KB", bytes as f64 / KB as f64)
-    } else {
-        format!("{}B", bytes)
+        let verified = last_verified[i]
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "never".to_string());
+
+        let status = match &statuses[i] {
+            HashStatus::Ok => format!("OK (last verified: {})", verified),
+            HashStatus::Mismatch { expected, actual } => {
+                failures += 1;
+                format!(
+                    "MISMATCH (expected {}, got {}; last verified: {})",
+                    expected, actual, verified
+                )
+            }
+            HashStatus::Missing => {
+                failures += 1;
+                "MISSING (data file not found)".to_string()
+            }
+            HashStatus::Unhashed => "no hash stored".to_string(),
+            HashStatus::TooLarge => "not hashed (over hash_max_size_mb; run 'stash --rehash' to hash it)".to_string(),
+        };
+
+        println!("  [{}] {} {}", kind, escape_path(&item.original_path), status);
+    }
+
+    // A plain error exits with 1, as scripts checking a stash expect
+    if failures > 0 {
+        bail!("{} item(s) failed hash verification", failures);
     }
+
+    Ok(())
 }
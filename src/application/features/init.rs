@@ -0,0 +1,156 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::paths::{AppDirs, StashScope};
+
+pub fn run(local: &bool, force: &bool) -> Result<()> {
+    let dirs = if *local {
+        AppDirs::for_project(&std::env::current_dir()?)
+    } else {
+        AppDirs::global()
+    };
+
+    dirs.init()?;
+
+    let config_existed = dirs.config_file.exists();
+    ConfigStorage::new(&dirs.config_file)?;
+    if !config_existed && dirs.config_file.exists() {
+        println!("Wrote default config to {}", dirs.config_file.display());
+    }
+
+    if *force {
+        repair(&dirs)?;
+    }
+
+    match dirs.scope {
+        StashScope::Local => {
+            // Keep stashed files out of the project's repository
+            let gitignore = dirs.data_dir.join(".gitignore");
+            if !gitignore.exists() {
+                fs::write(&gitignore, "*\n")?;
+            }
+        }
+        StashScope::Global => {
+            if let Some(fs_type) = slow_filesystem(&dirs.data_dir) {
+                eprintln!(
+                    "Warning: {} is on a {} filesystem, which is remote or slow; stash operations may be slow",
+                    dirs.data_dir.display(),
+                    fs_type
+                );
+            }
+        }
+    }
+
+    println!("Initialized {} stash at {}", kind(dirs.scope), dirs.data_dir.display());
+
+    // A project stash takes precedence over the global one
+    let active = AppDirs::new();
+    if active.data_dir != dirs.data_dir {
+        println!("Active stash here: {} stash at {}", kind(active.scope), active.data_dir.display());
+    }
+
+    Ok(())
+}
+
+fn kind(scope: StashScope) -> &'static str {
+    match scope {
+        StashScope::Global => "global",
+        StashScope::Local => "local",
+    }
+}
+
+/// Recreate missing state files and refresh the index's item path cache.
+/// Existing data is never overwritten.
+fn repair(dirs: &AppDirs) -> Result<()> {
+    let mut existing = Vec::new();
+
+    if dirs.index_file.exists() {
+        existing.push(dirs.index_file.display().to_string());
+    } else {
+        IndexStorage::new(&dirs.index_file)?.save_packages()?;
+        println!("Created empty {}", dirs.index_file.display());
+    }
+
+    if dirs.journal_file.exists() {
+        existing.push(dirs.journal_file.display().to_string());
+    } else {
+        JournalStorage::new(&dirs.journal_file)?.save_operations()?;
+        println!("Created empty {}", dirs.journal_file.display());
+    }
+
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let rebuilt = EntryManager::new(&dirs.entries_dir, &mut index_storage, &mut journal_storage)?
+        .rebuild_path_cache()?;
+    if rebuilt > 0 {
+        println!("Rebuilt the item path cache for {} entr{}", rebuilt, if rebuilt == 1 { "y" } else { "ies" });
+    }
+
+    let entry_count = fs::read_dir(&dirs.entries_dir)?.count();
+    if entry_count > 0 {
+        existing.push(format!("{} entry directories", entry_count));
+    }
+
+    if !existing.is_empty() {
+        eprintln!("Warning: existing stash data left untouched:");
+        for item in existing {
+            eprintln!("  {}", item);
+        }
+    }
+
+    Ok(())
+}
+
+/// Network filesystems, where every rename and fsync is a round trip
+const REMOTE_FILESYSTEMS: &[&str] = &[
+    "nfs", "nfs4", "cifs", "smbfs", "smb3", "9p", "afs", "ceph", "glusterfs",
+    "fuse.sshfs", "fuse.rclone", "davfs", "fuse.davfs2",
+];
+
+/// Remote filesystems, and FUSE ones, whose every call goes through userspace
+fn is_slow(fs_type: &str) -> bool {
+    REMOTE_FILESYSTEMS.contains(&fs_type) || fs_type == "fuse" || fs_type.starts_with("fuse.")
+}
+
+/// The filesystem type of `path`'s mount, if it is remote or otherwise slow
+#[cfg(target_os = "linux")]
+fn slow_filesystem(path: &Path) -> Option<String> {
+    let mounts = fs::read_to_string("/proc/mounts").ok()?;
+    let path = path.canonicalize().ok()?;
+
+    // The longest mount point that contains the path is the one it lives on
+    let (_, fs_type) = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _device = fields.next()?;
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let fs_type = fields.next()?;
+            path.starts_with(&mount_point).then_some((mount_point.len(), fs_type))
+        })
+        .max_by_key(|(len, _)| *len)?;
+
+    is_slow(fs_type).then(|| fs_type.to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn slow_filesystem(_path: &Path) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_and_fuse_filesystems_are_slow() {
+        for fs_type in ["nfs4", "cifs", "fuse.sshfs", "fuse.gocryptfs", "fuse"] {
+            assert!(is_slow(fs_type), "{}", fs_type);
+        }
+        for fs_type in ["ext4", "btrfs", "tmpfs", "fusectl", "overlay"] {
+            assert!(!is_slow(fs_type), "{}", fs_type);
+        }
+    }
+}
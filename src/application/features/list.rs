@@ -1,41 +1,399 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use console::Term;
+use notify::{RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+use uuid::Uuid;
+use crate::application::cli::arguments::{ListColumn, OutputFormat};
+use crate::models::{EntryMetadata, OperationKind};
 use crate::services::entry_manager::EntryManager;
-use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::{git, pager};
 use crate::utils::paths::AppDirs;
-use crate::utils::display::{humanize_duration, humanize_size};
+use crate::utils::display::{format_size, format_time, porcelain_field, SizeStyle, TimeStyle};
 
-pub fn run() -> Result<()> {
+/// Columns shown without --columns, matching the historical output
+const DEFAULT_COLUMNS: &[ListColumn] = &[
+    ListColumn::Name,
+    ListColumn::Items,
+    ListColumn::Size,
+    ListColumn::Age,
+];
+
+/// The stable porcelain layout, also used without --columns
+const PORCELAIN_COLUMNS: &[ListColumn] = &[
+    ListColumn::Uuid,
+    ListColumn::Name,
+    ListColumn::Age,
+    ListColumn::Size,
+    ListColumn::Items,
+];
+
+/// How long to wait for further changes before redrawing
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Which entries to show, and how
+pub struct View {
+    pub since_last_pop: bool,
+    pub since_last_git_commit: bool,
+    pub page: Option<usize>,
+    pub per_page: usize,
+    pub columns: Vec<ListColumn>,
+    /// Show on-disk usage alongside logical sizes
+    pub disk_usage: bool,
+    /// Show on-disk usage per entry, not just in the totals
+    pub verbose: bool,
+    /// How the age column shows when entries were pushed
+    pub time: TimeStyle,
+    /// How sizes are written
+    pub sizes: SizeStyle,
+    /// KEY=VALUE pairs an entry's metadata must all have
+    pub filter_meta: Vec<(String, String)>,
+    /// End with totals for the entries shown
+    pub total: bool,
+}
+
+/// Totals over the entries a listing shows, after filtering
+#[derive(Serialize)]
+struct Summary {
+    entries: usize,
+    items: usize,
+    total_size_bytes: u64,
+    /// Only counted with --disk-usage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    disk_usage_bytes: Option<u64>,
+}
+
+impl Summary {
+    fn of(entries: &[&EntryMetadata], usage: &HashMap<Uuid, u64>, disk_usage: bool) -> Self {
+        Self {
+            entries: entries.len(),
+            items: entries.iter().map(|meta| meta.item_count).sum(),
+            total_size_bytes: entries.iter().map(|meta| meta.total_size_bytes).sum(),
+            disk_usage_bytes: disk_usage.then(|| usage.values().sum()),
+        }
+    }
+}
+
+pub fn run(watch: &bool, view: &View, format: &OutputFormat) -> Result<()> {
     let dirs = AppDirs::new();
+
+    if !*watch {
+        let output = render(&dirs, view, format)?;
+
+        // Scripts get everything at once; explicit pages are already short
+        if *format == OutputFormat::Human && view.page.is_none() {
+            pager::print_paged(&output);
+        } else {
+            print!("{}", output);
+        }
+
+        return Ok(());
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+
+    // Watch the directory: the index is rewritten rather than modified in place
+    std::fs::create_dir_all(&dirs.data_dir)?;
+    watcher.watch(&dirs.data_dir, RecursiveMode::NonRecursive)?;
+
+    // console's clear_screen rather than crossterm's ClearScreen and
+    // MoveToColumn(0): it does the same, and console is already a dependency
+    let term = Term::stdout();
+
+    redraw_on_changes(&rx, &dirs.index_file, || {
+        term.clear_screen()?;
+        print!("{}", render(&dirs, view, format)?);
+        println!("\nWatching for changes (Ctrl-C to exit)...");
+        Ok(())
+    })
+}
+
+/// Draw, then draw again once per burst of changes to `index_file`, until
+/// the watcher sending the events goes away
+fn redraw_on_changes(
+    rx: &mpsc::Receiver<notify::Result<notify::Event>>,
+    index_file: &Path,
+    mut redraw: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    loop {
+        redraw()?;
+
+        // Block until the index changes, then let rapid successive writes settle
+        loop {
+            let Ok(event) = rx.recv() else {
+                return Ok(());
+            };
+            if event?.paths.iter().any(|p| p == index_file) {
+                break;
+            }
+        }
+        while rx.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+    }
+}
+
+fn render(dirs: &AppDirs, view: &View, format: &OutputFormat) -> Result<String> {
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+
+    // Without a prior pop or dump there is no cutoff, so everything is shown
+    let pop_cutoff = if view.since_last_pop {
+        journal_storage
+            .last_matching(|kind| matches!(kind, OperationKind::Pop { .. } | OperationKind::Dump { .. }))?
+            .map(|op| op.timestamp)
+    } else {
+        None
+    };
+
+    let commit = if view.since_last_git_commit {
+        let dir = git::repository_dir();
+        let commit = git::last_commit(&dir)
+            .ok_or_else(|| anyhow!("No git commit found in {:?}", dir))?;
+        Some(commit)
+    } else {
+        None
+    };
+
+    // With both filters, the later cutoff wins
+    let cutoff = pop_cutoff.max(commit.as_ref().map(|c| c.time));
+
     let entry_manager = EntryManager::new(
         &dirs.entries_dir,
         &mut index_storage,
         &mut journal_storage,
     )?;
 
-    let entries = entry_manager.list_entries();
+    let mut entries: Vec<_> = entry_manager
+        .list_entries()
+        .iter()
+        .filter(|meta| cutoff.is_none_or(|cutoff| meta.created > cutoff))
+        .collect();
+
+    // Metadata only lives in the manifests, so each one has to be read
+    if !view.filter_meta.is_empty() {
+        let mut kept = Vec::with_capacity(entries.len());
+        for meta in entries {
+            let entry = entry_manager.load_entry(&meta.uuid)?;
+            if view.filter_meta.iter().all(|(key, value)| entry.metadata_matches(key, value)) {
+                kept.push(meta);
+            }
+        }
+        entries = kept;
+    }
+
+    let usage: HashMap<Uuid, u64> = if view.disk_usage {
+        entries
+            .iter()
+            .map(|meta| Ok((meta.uuid, entry_manager.disk_usage(&meta.uuid)?)))
+            .collect::<Result<_>>()?
+    } else {
+        HashMap::new()
+    };
+
+    let summary = Summary::of(&entries, &usage, view.disk_usage);
+    let date_format = ConfigStorage::existing(&dirs.config_file)?.date_format;
+    let mut out = String::new();
+
+    if *format == OutputFormat::Json {
+        let mut listing = serde_json::json!({ "entries": entries });
+        if view.total {
+            listing["summary"] = serde_json::to_value(&summary)?;
+        }
+        writeln!(out, "{}", serde_json::to_string_pretty(&listing)?)?;
+        return Ok(out);
+    }
+
+    if *format == OutputFormat::Porcelain {
+        let columns = if view.columns.is_empty() { PORCELAIN_COLUMNS } else { &view.columns };
+        for meta in entries {
+            let mut fields: Vec<_> = columns.iter().map(|c| porcelain_cell(*c, meta)).collect();
+            if let Some(bytes) = usage.get(&meta.uuid) {
+                fields.push(bytes.to_string());
+            }
+            writeln!(out, "{}", fields.join("\t"))?;
+        }
+        if view.total {
+            write!(
+                out,
+                "# total entries={} items={} size_bytes={}",
+                summary.entries, summary.items, summary.total_size_bytes
+            )?;
+            if let Some(bytes) = summary.disk_usage_bytes {
+                write!(out, " disk_bytes={}", bytes)?;
+            }
+            writeln!(out)?;
+        }
+        return Ok(out);
+    }
+
+    if let Some(commit) = &commit {
+        writeln!(
+            out,
+            "Showing entries since last commit: {} at {}",
+            commit.hash,
+            commit.time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")
+        )?;
+    }
 
     if entries.is_empty() {
-        println!("No stashed entries.");
-        return Ok(());
+        if cutoff.is_some() && cutoff == commit.as_ref().map(|c| c.time) {
+            writeln!(out, "No entries stashed since the last commit.")?;
+        } else if cutoff.is_some() {
+            writeln!(out, "No entries stashed since the last pop.")?;
+        } else {
+            writeln!(out, "No stashed entries.")?;
+        }
+        return Ok(out);
     }
 
-    println!("Stashed entries:");
-    for (i, meta) in entries.iter().enumerate() {
+    let total = entries.len();
+    let offset = view.page.map_or(0, |page| (page - 1) * view.per_page);
+    let shown = view.page.map_or(total, |_| view.per_page);
 
-        let age = humanize_duration(meta.created);
-        let size = humanize_size(meta.total_size_bytes);
+    let columns = if view.columns.is_empty() { DEFAULT_COLUMNS } else { &view.columns };
+    let rows: Vec<(usize, Vec<String>)> = entries
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(shown)
+        .map(|(i, meta)| {
+            let cells = columns
+                .iter()
+                .map(|c| {
+                    let disk_usage = usage.get(&meta.uuid).copied().filter(|_| view.verbose);
+                    human_cell(*c, meta, disk_usage, view, &date_format)
+                })
+                .collect();
+            (i + 1, cells)
+        })
+        .collect();
+
+    // Pad every column but the last to its widest cell
+    let mut widths = vec![0; columns.len()];
+    for (_, cells) in &rows {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(console::measure_text_width(cell));
+        }
+    }
+    let number_width = rows.last().map_or(0, |(n, _)| n.to_string().len());
 
-        println!(
-            "{}. {} ({} files, {}, {})",
-            i + 1,
-            meta.name,
-            meta.item_count,
-            size,
-            age
-        );
+    writeln!(out, "Stashed entries:")?;
+    for (n, cells) in &rows {
+        let mut line = format!("{:>width$}.", n, width = number_width);
+        for (i, cell) in cells.iter().enumerate() {
+            line.push_str(if i == 0 { " " } else { "  " });
+            if i + 1 < cells.len() {
+                line.push_str(&console::pad_str(cell, widths[i], console::Alignment::Left, None));
+            } else {
+                line.push_str(cell);
+            }
+        }
+        writeln!(out, "{}", line)?;
     }
 
-    Ok(())
+    if view.total || view.disk_usage {
+        let mut parts = Vec::new();
+        if view.total {
+            parts.push(format!("{} entr{}", summary.entries, if summary.entries == 1 { "y" } else { "ies" }));
+            parts.push(format!("{} files", summary.items));
+        }
+        match summary.disk_usage_bytes {
+            Some(disk) => {
+                parts.push(format!("{} logical", format_size(summary.total_size_bytes, view.sizes)));
+                parts.push(format!("{} on disk", format_size(disk, view.sizes)));
+            }
+            None => parts.push(format_size(summary.total_size_bytes, view.sizes)),
+        }
+        writeln!(out, "\nTotal: {}", parts.join(", "))?;
+    }
+
+    if let Some(page) = view.page {
+        let pages = total.div_ceil(view.per_page);
+        writeln!(out, "\nPage {} of {} ({} entries)", page, pages, total)?;
+    }
+
+    Ok(out)
+}
+
+fn human_cell(column: ListColumn, meta: &EntryMetadata, disk_usage: Option<u64>, view: &View, date_format: &str) -> String {
+    match column {
+        ListColumn::Uuid => meta.uuid.to_string(),
+        ListColumn::Name => {
+            let mut name = meta.display_name();
+            if let Some(version) = meta.version {
+                name = format!("{} v{}", name, version);
+            }
+            if meta.is_template {
+                name.push_str(" [T]");
+            }
+            name
+        }
+        ListColumn::Size => match disk_usage {
+            Some(bytes) => format!(
+                "{} logical, {} on disk",
+                format_size(meta.total_size_bytes, view.sizes),
+                format_size(bytes, view.sizes)
+            ),
+            None => format_size(meta.total_size_bytes, view.sizes),
+        },
+        ListColumn::Age => format_time(meta.created, view.time, date_format),
+        ListColumn::Items => format!("{} files", meta.item_count),
+    }
+}
+
+fn porcelain_cell(column: ListColumn, meta: &EntryMetadata) -> String {
+    match column {
+        ListColumn::Uuid => meta.uuid.to_string(),
+        ListColumn::Name => porcelain_field(&meta.name),
+        ListColumn::Size => meta.total_size_bytes.to_string(),
+        ListColumn::Age => meta.created.to_rfc3339(),
+        ListColumn::Items => meta.item_count.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, thread};
+
+    #[test]
+    fn a_burst_of_index_writes_redraws_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let index_file = dir.path().join("index.json");
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).unwrap();
+        watcher.watch(dir.path(), RecursiveMode::NonRecursive).unwrap();
+
+        let writer = {
+            let index_file = index_file.clone();
+            thread::spawn(move || {
+                // Two bursts, each of writes closer together than WATCH_DEBOUNCE
+                for _ in 0..2 {
+                    for i in 0..5 {
+                        fs::write(&index_file, i.to_string()).unwrap();
+                        thread::sleep(WATCH_DEBOUNCE / 5);
+                    }
+                    thread::sleep(WATCH_DEBOUNCE * 4);
+                }
+                // Ends the redraw loop
+                drop(watcher);
+            })
+        };
+
+        let mut redraws = 0;
+        redraw_on_changes(&rx, &index_file, || {
+            redraws += 1;
+            Ok(())
+        })
+        .unwrap();
+        writer.join().unwrap();
+
+        // The first draw, then one per burst
+        assert_eq!(redraws, 3);
+    }
 }
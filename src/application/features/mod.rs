@@ -1,12 +1,33 @@
 pub mod push;
 pub mod pop;
 pub mod list;
+pub mod move_stash;
+pub mod benchmark;
+pub mod check_space;
 pub mod clean;
+pub mod clipboard;
+pub mod config;
 pub mod delete;
 pub mod dump;
+pub mod edit;
+pub mod export_paths;
+pub mod find;
+pub mod health;
 pub mod history;
+pub mod import;
+pub mod init;
 pub mod info;
+pub mod name_history;
 pub mod peek;
+pub mod pop_to_archive;
+pub mod prune;
+pub mod raw;
+pub mod rehash;
 pub mod rename;
+pub mod revert_name;
 pub mod search;
+pub mod swap_names;
+pub mod status;
+pub mod sync;
 pub mod tar;
+pub mod template;
@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::errors::StashError;
+use crate::services::filesystem::{sparse, walker};
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::lock::StashLock;
+use crate::utils::paths::{AppDirs, StashScope};
+
+/// Move the global stash to `destination` and point `stash_dir` at it.
+/// Manifests only record where files came from, never where the stash
+/// lives, so entries need no rewriting.
+pub fn run(destination: &Path, merge: &bool, dry_run: &bool) -> Result<()> {
+    let source = AppDirs::new();
+    if source.scope == StashScope::Local {
+        return Err(StashError::Usage(
+            "--move-to-stash-dir moves the global stash; run it outside a project stash".into(),
+        )
+        .into());
+    }
+
+    let destination = std::path::absolute(destination)?;
+    if destination == source.data_dir {
+        return Err(StashError::NothingMatched(format!(
+            "The stash is already at {}",
+            destination.display()
+        ))
+        .into());
+    }
+
+    let target = AppDirs::global_at(&destination);
+    let _lock = StashLock::acquire(&source.lock_file)?;
+
+    let entries = entry_dirs(&source.entries_dir)?;
+    let existing = entry_dirs(&target.entries_dir)?.len();
+
+    if existing > 0 && !*merge {
+        return Err(StashError::Usage(format!(
+            "{} already holds a stash with {} entries; use --merge to combine them",
+            destination.display(),
+            existing
+        ))
+        .into());
+    }
+
+    if *dry_run {
+        println!(
+            "Would move {} entries, the index and the journal from {} to {}",
+            entries.len(),
+            source.data_dir.display(),
+            destination.display()
+        );
+        if existing > 0 {
+            println!("Would merge with the {} entries already there", existing);
+        }
+        println!("Would set stash_dir = {:?} in {}", destination, source.config_file.display());
+        return Ok(());
+    }
+
+    target.init()?;
+
+    let mut moved = 0;
+    for entry in &entries {
+        let dest = target.entries_dir.join(entry.file_name().unwrap());
+        if dest.exists() {
+            eprintln!(
+                "Warning: entry {} already exists at the destination; keeping that copy",
+                entry.file_name().unwrap().to_string_lossy()
+            );
+            continue;
+        }
+        move_path(entry, &dest)?;
+        moved += 1;
+    }
+
+    // Merge state into whatever is already there, then drop the originals
+    let source_index = IndexStorage::new(&source.index_file)?;
+    let mut target_index = IndexStorage::new(&target.index_file)?;
+    target_index.index_mut().merge(source_index.index());
+    target_index.save_packages()?;
+
+    let source_journal = JournalStorage::new(&source.journal_file)?;
+    let mut target_journal = JournalStorage::new(&target.journal_file)?;
+    target_journal.merge(source_journal.operations()?)?;
+    target_journal.save_operations()?;
+
+    let _ = fs::remove_file(&source.index_file);
+    let _ = fs::remove_file(&source.journal_file);
+    let _ = fs::remove_dir(&source.entries_dir);
+
+    let mut config = ConfigStorage::new(&source.config_file)?;
+    config.get_mut_config().stash_dir = Some(destination.clone());
+    config.save_config()?;
+
+    println!(
+        "Moved {} entries from {} to {}",
+        moved,
+        source.data_dir.display(),
+        destination.display()
+    );
+
+    Ok(())
+}
+
+fn entry_dirs(entries_dir: &Path) -> Result<Vec<PathBuf>> {
+    if !entries_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(entries_dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Rename, falling back to copy and delete across filesystems
+fn move_path(src: &Path, dest: &Path) -> Result<()> {
+    if fs::rename(src, dest).is_ok() {
+        return Ok(());
+    }
+
+    if let Err(err) = copy_tree(src, dest) {
+        let _ = fs::remove_dir_all(dest);
+        return Err(err.context(format!("Failed to move {}; it was left in place", src.display())));
+    }
+
+    fs::remove_dir_all(src).with_context(|| format!("Moved {} but could not remove it", src.display()))
+}
+
+fn copy_tree(src: &Path, dest: &Path) -> Result<()> {
+    for entry in walker::walk(src) {
+        let entry = entry?;
+        let target = entry.under(dest);
+
+        if entry.metadata.is_dir() {
+            fs::create_dir_all(&target)?;
+        } else if entry.metadata.file_type().is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(fs::read_link(&entry.path)?, &target)?;
+        } else {
+            sparse::copy_file(&entry.path, &target, &entry.metadata, sparse::DEFAULT_BUFFER)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use anyhow::Result;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::paths::AppDirs;
+
+/// Show the names an entry has had, reconstructed from the journal
+pub fn run(identifier: &str) -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let entry = entry_manager.load_entry_by_identifier(identifier)?;
+    let renames = entry_manager.renames(&entry.uuid)?;
+
+    println!("Name history of {}:", entry.uuid);
+
+    if renames.is_empty() {
+        println!("[{}] '{}' (never renamed)", entry.created.format("%Y-%m-%d %H:%M:%S"), entry.name);
+        return Ok(());
+    }
+
+    // The name before the first journaled rename is the one it was pushed with
+    println!("[{}] '{}' (pushed)", entry.created.format("%Y-%m-%d %H:%M:%S"), renames[0].1);
+    for (timestamp, _, new_name) in &renames {
+        println!("[{}] '{}'", timestamp.format("%Y-%m-%d %H:%M:%S"), new_name);
+    }
+
+    Ok(())
+}
@@ -1,34 +1,115 @@
-use anyhow::{Result, anyhow};
-use crate::services::entry_manager::EntryManager;
-use crate::services::storage::{IndexStorage, JournalStorage};
+use anyhow::Result;
+use std::collections::BTreeMap;
+use crate::errors::StashError;
+use crate::models::entry::Entry;
+use crate::models::item::{Item, ItemKind};
+use crate::services::entry_manager::{select_items, EntryManager, ManifestChecks};
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::display::{escape_path, format_size, SizeStyle};
 use crate::utils::paths::AppDirs;
 
-pub fn run(identifier: &Option<String>, force: &bool) -> Result<()> {
+/// Copy an entry's files into the current directory and leave it stashed,
+/// or with `list_only` just print its items as a tree. A non-empty `only`
+/// narrows either to the items whose stashed path matches one of its globs.
+pub fn run(
+    identifier: &Option<String>,
+    force: &bool,
+    list_only: &bool,
+    only: &[glob::Pattern],
+    sizes: &SizeStyle,
+) -> Result<()> {
     let cwd = std::env::current_dir()?;
     let dirs = AppDirs::new();
+    let config = ConfigStorage::existing(&dirs.config_file)?;
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
-    let entry_manager = EntryManager::new(
+    let mut entry_manager = EntryManager::new(
         &dirs.entries_dir,
         &mut index_storage,
         &mut journal_storage,
     )?;
+    entry_manager.set_manifest_checks(ManifestChecks::from_flags(config.verify_integrity, false));
 
     let entry = if let Some(ident) = identifier {
         entry_manager.load_entry_by_identifier(ident)?
     } else {
         let meta = entry_manager.most_recent_entry()
-            .ok_or_else(|| anyhow!("No stashed entries found"))?;
+            .ok_or_else(|| StashError::NothingMatched("No stashed entries found".into()))?;
         entry_manager.load_entry(&meta.uuid)?
     };
 
-    entry_manager.peek_entry(&entry.uuid, &cwd, *force)?;
+    // Everything shown comes from the manifest, which is never compressed,
+    // so the entry's data isn't touched
+    if *list_only {
+        print_tree(&select_items(&entry, only)?, *sizes);
+        return Ok(());
+    }
+
+    let peeked = entry_manager.peek_entry(&entry.uuid, &cwd, *force, config.preserve_mtime, config.verify_integrity, only)?;
 
     println!(
         "Peeked {} file(s) from '{}'",
-        entry.items.len(),
-        entry.name
+        peeked.items.len(),
+        entry.display_name()
     );
 
     Ok(())
 }
+
+/// One path component of the tree, and the item stashed there if any
+#[derive(Default)]
+struct Node<'a> {
+    item: Option<&'a Item>,
+    children: BTreeMap<String, Node<'a>>,
+}
+
+fn print_tree(entry: &Entry, sizes: SizeStyle) {
+    let mut root = Node::default();
+    for item in &entry.items {
+        let node = item
+            .stashed_path
+            .components()
+            .map(|part| escape_path(part.as_os_str().as_ref()))
+            .fold(&mut root, |node, part| node.children.entry(part).or_default());
+        node.item = Some(item);
+    }
+
+    println!(
+        "'{}' ({} item{}, {})",
+        entry.display_name(),
+        entry.items.len(),
+        if entry.items.len() == 1 { "" } else { "s" },
+        format_size(entry.total_size_bytes, sizes)
+    );
+
+    let mut lines = Vec::new();
+    tree_lines(&root, "", sizes, &mut lines);
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+/// Draw `node`'s children in name order, each line prefixed by the guides
+/// of the levels above it
+fn tree_lines(node: &Node, prefix: &str, sizes: SizeStyle, lines: &mut Vec<String>) {
+    let count = node.children.len();
+    for (i, (name, child)) in node.children.iter().enumerate() {
+        let last = i + 1 == count;
+        lines.push(format!("{}{}{}", prefix, if last { "└── " } else { "├── " }, label(name, child, sizes)));
+        tree_lines(child, &format!("{}{}", prefix, if last { "    " } else { "│   " }), sizes, lines);
+    }
+}
+
+/// A directory's contents aren't in the manifest, so a directory item is
+/// shown whole, with its total size
+fn label(name: &str, node: &Node, sizes: SizeStyle) -> String {
+    let Some(item) = node.item else {
+        return format!("{}/", name);
+    };
+
+    match (&item.kind, &item.link_target) {
+        (ItemKind::Symlink, Some(target)) => format!("{} -> {}", name, escape_path(target)),
+        (ItemKind::Directory, _) => format!("{}/ ({})", name, format_size(item.size_bytes, sizes)),
+        _ => format!("{} ({})", name, format_size(item.size_bytes, sizes)),
+    }
+}
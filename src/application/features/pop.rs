@@ -1,20 +1,54 @@
-use anyhow::{Result, anyhow};
+use anyhow::Result;
+use console::style;
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
-use crate::services::entry_manager::{EntryManager, PopOptions};
+use crate::errors::StashError;
+use crate::application::cli::arguments::OutputFormat;
+use crate::models::Config;
+use crate::models::config::SortOrder;
+use crate::models::entry::Entry;
+use crate::services::entry_manager::{EntryManager, ManifestChecks, PopOptions, PushOptions};
 use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::git;
+use crate::utils::display::{self, escape_path};
 use crate::utils::paths::AppDirs;
 use crate::services::storage::ConfigStorage;
 
-pub fn run(
-    identifier: &Option<String>,
-    copy: &bool,
-    force: &bool,
-    restore: &bool,
-) -> Result<()> {
+/// How a pop should be carried out
+pub struct PopFlags {
+    pub copy: bool,
+    pub force: bool,
+    pub restore: bool,
+    pub missing_ok: bool,
+    /// Check the destination has room before restoring anything
+    pub verify_dest: bool,
+    pub git_aware: bool,
+    /// Shell command to run once everything is restored
+    pub post_command: Option<String>,
+    pub no_reflink: bool,
+    /// Skip the size and time summary
+    pub quiet: bool,
+    /// Restore only the items matching one of these globs
+    pub only: Vec<glob::Pattern>,
+    /// Refuse a manifest that fails validation instead of warning
+    pub strict: bool,
+    /// Stash whatever a forced pop would overwrite before restoring
+    pub backup: bool,
+    /// Restore relative to the working directory of this entry instead of
+    /// the current directory
+    pub resolve_to: Option<String>,
+    /// Pop this version of the named entry instead of the latest
+    pub version: Option<u32>,
+    /// Overrides `push_workers`
+    pub jobs: Option<usize>,
+}
+
+pub fn run(identifier: &Option<String>, flags: &PopFlags, format: &OutputFormat) -> Result<()> {
+    let PopFlags { copy, force, restore, missing_ok, git_aware, .. } = flags;
     let cwd = std::env::current_dir()?;
     let dirs = AppDirs::new();
 
-    let _config = ConfigStorage::new(&dirs.config_file);
+    let config = ConfigStorage::new(&dirs.config_file)?;
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
     let mut entry_manager = EntryManager::new(
@@ -22,40 +56,113 @@ pub fn run(
         &mut index_storage,
         &mut journal_storage
     )?;
+    entry_manager.set_reflink(!flags.no_reflink);
+    entry_manager.set_verify_dest(flags.verify_dest);
+    entry_manager.set_copy_buffer(config.get_config().copy_buffer());
+    entry_manager.set_workers(flags.jobs.unwrap_or(config.get_config().push_workers));
+    entry_manager.set_manifest_checks(ManifestChecks::from_flags(config.get_config().verify_integrity, flags.strict));
 
     // Determine which entry to pop
-    let uuid = if let Some(ident) = identifier {
-        // Try to parse as UUID first
-        if let Ok(parsed_uuid) = Uuid::parse_str(ident) {
-            // Verify it exists
-            entry_manager.load_entry(&parsed_uuid)?;
-            parsed_uuid
-        } else {
-            // Try to find by name or partial UUID
-            let entry = entry_manager.load_entry_by_identifier(ident)?;
-            entry.uuid
-        }
+    let uuid = if let (Some(name), Some(version)) = (identifier, flags.version) {
+        entry_manager.find_version(name, version)?.uuid
+    } else if let Some(ident) = identifier {
+        // Resolve by UUID or name
+        entry_manager.load_entry_by_identifier(ident)?.uuid
+    } else if flags.version.is_some() {
+        return Err(StashError::Usage("--entry-version needs the name of the entry to pop".into()).into());
     } else {
         // No identifier → pop most recent
         let recent = entry_manager.most_recent_entry()
-            .ok_or_else(|| anyhow!("No stashed entries found"))?;
+            .ok_or_else(|| StashError::NothingMatched("No stashed entries found".into()))?;
 
         recent.uuid
     };
 
-    // Execute the pop operation
-    let entry = if *restore {
-        // --restore flag: restore to original working directory
-        entry_manager.restore_entry(&uuid, *force)?
+    // Where relative paths are restored to
+    let destination = if *restore {
+        entry_manager.load_entry(&uuid)?.working_directory
+    } else if let Some(base) = &flags.resolve_to {
+        entry_manager.load_entry_by_identifier(base)?.working_directory
     } else {
-        // Default: restore to current directory
-        let options = PopOptions {
-            destination: &cwd,
-            copy,
-            force,
-        };
-        entry_manager.pop_entry(&uuid, options)?
+        cwd
+    };
+
+    if *git_aware {
+        let entry = entry_manager.load_entry(&uuid)?;
+        let targets: Vec<_> = entry.items.iter().map(|item| destination.join(&item.original_path)).collect();
+        warn_git_targets(&destination, &targets);
+    }
+
+    let preserve_mtime = config.get_config().preserve_mtime;
+    let verify = config.get_config().verify_integrity;
+
+    let backup = if flags.backup {
+        back_up_overwritten(&mut entry_manager, &uuid, &destination, &flags.only, config.get_config())?
+    } else {
+        None
+    };
+
+    let post_command = flags
+        .post_command
+        .clone()
+        .or_else(|| config.get_config().default_post_pop_command.clone());
+
+    // Restore to the current directory, the one --resolve-to gives, or with
+    // --restore the original working directory
+    let options = PopOptions {
+        destination: &destination,
+        copy: if *restore { &false } else { copy },
+        force,
+        missing_ok,
+        preserve_mtime: &preserve_mtime,
+        verify: &verify,
+        only: &flags.only,
+        post_command: &post_command,
     };
+    let popped = entry_manager.pop_entry(&uuid, options);
+
+    // The pop changed nothing, so put back what the backup moved away
+    let (entry, skipped) = match (popped, &backup) {
+        (Ok(popped), _) => popped,
+        (Err(err), Some(backup)) => {
+            entry_manager.set_verify_dest(false);
+            if let Err(undo) = entry_manager.restore_entry(&backup.uuid, true, false, preserve_mtime, verify, &[]) {
+                return Err(err.context(format!(
+                    "The files it would have overwritten are stashed as '{}' ({}); putting them back failed: {:#}",
+                    backup.display_name(),
+                    backup.short_id(),
+                    undo
+                )));
+            }
+            return Err(err);
+        }
+        (Err(err), None) => return Err(err),
+    };
+
+    entry_manager.prune_journal_if_over(config.get_config().journal_prune_limits())?;
+
+    if *format == OutputFormat::Porcelain {
+        println!("{}", entry.uuid);
+        if let Some(backup) = &backup {
+            println!("backup\t{}", backup.uuid);
+        }
+    } else {
+        report_pop(&entry, &skipped, &backup, &destination, &entry_manager, flags);
+    }
+
+    entry_manager.run_post_command(&entry, &options, *format == OutputFormat::Porcelain)
+}
+
+/// Say what a pop restored, what it skipped and what it backed up
+fn report_pop(
+    entry: &Entry,
+    skipped: &[PathBuf],
+    backup: &Option<Entry>,
+    destination: &Path,
+    entry_manager: &EntryManager,
+    flags: &PopFlags,
+) {
+    let PopFlags { copy, restore, .. } = flags;
 
     // Success message
     let action = if *copy {
@@ -66,28 +173,139 @@ pub fn run(
         "Restored"
     };
 
-    let destination = if *restore {
-        format!("to {}", entry.working_directory.display())
+    let destination = if *restore || flags.resolve_to.is_some() {
+        format!("to {}", escape_path(destination))
     } else {
         "to current directory".to_string()
     };
 
+    let restored: Vec<_> = entry
+        .items
+        .iter()
+        .filter(|item| !skipped.contains(&item.original_path))
+        .collect();
+
     println!(
         "{} {} file(s) from '{}' {}",
         action,
-        entry.items.len(),
-        entry.name,
+        restored.len(),
+        entry.display_name(),
         destination
     );
 
     // Show what was restored (up to 10 files)
-    if entry.items.len() <= 10 {
-        for item in &entry.items {
-            println!("- {}", item.original_path.display());
+    if restored.len() <= 10 {
+        for item in &restored {
+            println!("- {}", escape_path(&item.original_path));
         }
     } else {
-        println!("  ({} files total)", entry.items.len());
+        println!("  ({} files total)", restored.len());
+    }
+
+    if let Some(backup) = backup {
+        println!(
+            "Backed up {} overwritten file(s) as '{}' ({}); pop it to undo",
+            backup.items.len(),
+            backup.display_name(),
+            backup.short_id()
+        );
+    }
+
+    if let Some(transfer) = entry_manager.last_transfer().filter(|_| !flags.quiet) {
+        println!("{}", display::transfer_summary("Transferred", transfer.bytes, transfer.elapsed));
+    }
+
+    if entry_manager.reflinked_files() > 0 {
+        println!(
+            "Cloned {} file(s) with reflinks; they take no extra space until changed",
+            entry_manager.reflinked_files()
+        );
     }
 
-    Ok(())
+    if !skipped.is_empty() {
+        println!(
+            "Skipped {} file(s) whose directory no longer exists (kept in stash):",
+            skipped.len()
+        );
+        for path in skipped {
+            println!("- {}", escape_path(path));
+        }
+    }
+
+    if let Some(message) = &entry.restore_message {
+        println!("\n{}\n", style(format!("⚠  Remember: {}", message)).yellow().bold());
+    }
+}
+
+/// Warn about restore targets git ignores or that hold uncommitted changes.
+/// Does nothing outside a repository.
+fn warn_git_targets(root: &Path, targets: &[PathBuf]) {
+    if !git::is_repository(root) {
+        return;
+    }
+
+    for path in git::ignored_paths(root, targets) {
+        eprintln!("Warning: {} is ignored by git", escape_path(&path));
+    }
+
+    for path in git::modified_paths(root, targets) {
+        eprintln!(
+            "Warning: {} has uncommitted changes and would be overwritten",
+            escape_path(&path)
+        );
+    }
+}
+
+/// Move whatever popping `uuid` into `destination` would overwrite into a
+/// new entry, recorded relative to `destination` so popping it there undoes
+/// the overwrite. Nothing is stashed if nothing would be overwritten.
+fn back_up_overwritten(
+    entry_manager: &mut EntryManager,
+    uuid: &Uuid,
+    destination: &Path,
+    only: &[glob::Pattern],
+    config: &Config,
+) -> Result<Option<Entry>> {
+    let paths = entry_manager.overwritten_paths(uuid, destination, only)?;
+    if paths.is_empty() {
+        return Ok(None);
+    }
+
+    let name = Some(format!(
+        "backup-of-{}-{}",
+        entry_manager.load_entry(uuid)?.display_name(),
+        chrono::Local::now().format("%Y-%m-%d-%H%M%S")
+    ));
+    let recorded: Vec<PathBuf> = paths
+        .iter()
+        .map(|path| path.strip_prefix(destination).unwrap_or(path).to_path_buf())
+        .collect();
+
+    let options = PushOptions {
+        name: &name,
+        copy: &false,
+        hardlink: &false,
+        hardlink_fallback: &false,
+        hash: &config.compute_hashes_on_push,
+        hash_algorithm: &config.hash_algorithm,
+        hash_max_size: &config.hash_max_size(),
+        verify: &config.verify_integrity,
+        workers: &1,
+        compression: &None,
+        strict: &false,
+        sort_items: &SortOrder::Unsorted,
+        ignore_hidden: &false,
+        min_age: &None,
+        max_age: &None,
+        force: &false,
+        restore_message: &None,
+        metadata: &serde_json::Value::Null,
+        recorded_paths: Some(&recorded),
+        versioned: &false,
+        max_versions: &None,
+        pre_command: &None,
+        verbose: &false,
+    };
+
+    Ok(Some(entry_manager.create_entry(&paths, options, destination)?))
 }
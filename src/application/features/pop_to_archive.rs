@@ -0,0 +1,215 @@
+use anyhow::{anyhow, Context, Result};
+use bzip2::write::BzEncoder;
+use chrono::{Datelike, Local, Timelike};
+use flate2::write::GzEncoder;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use crate::errors::StashError;
+use crate::services::entry_manager::{EntryManager, ManifestChecks};
+use crate::services::filesystem::file_compression::ArchiveFormat;
+use crate::services::filesystem::walker;
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::display::{format_size, SizeStyle};
+use crate::utils::paths::AppDirs;
+
+/// Write an entry's items into a new archive instead of restoring them,
+/// leaving the entry stashed as a peek would. Items keep the paths they
+/// would be restored to, relative to the archive's root.
+pub fn run(identifier: &Option<String>, archive: &Path, format: &ArchiveFormat, force: &bool) -> Result<()> {
+    let dirs = AppDirs::new();
+    let config = ConfigStorage::existing(&dirs.config_file)?;
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let mut entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+    entry_manager.set_manifest_checks(ManifestChecks::from_flags(config.verify_integrity, false));
+
+    let entry = if let Some(ident) = identifier {
+        entry_manager.load_entry_by_identifier(ident)?
+    } else {
+        let meta = entry_manager.most_recent_entry()
+            .ok_or_else(|| StashError::NothingMatched("No stashed entries found".into()))?;
+        entry_manager.load_entry(&meta.uuid)?
+    };
+
+    if !*force && fs::symlink_metadata(archive).is_ok() {
+        return Err(StashError::Conflict(archive.to_path_buf()).into());
+    }
+
+    // Built under another name and renamed into place, so a failure never
+    // leaves a truncated archive behind
+    let partial = partial_path(archive);
+    let (data_dir, unpacked) = entry_manager.unpack_data(&entry.uuid)?;
+
+    let written = File::create(&partial)
+        .with_context(|| format!("Failed to create {}", partial.display()))
+        .and_then(|file| {
+            let mut writer = ArchiveWriter::new(file, *format)?;
+            for item in &entry.items {
+                for walked in walker::walk(&data_dir.join(&item.stashed_path)) {
+                    let walked = walked?;
+                    writer.add(&walked.path, &walked.under(&item.stashed_path), &walked.metadata)?;
+                }
+            }
+            writer.finish()
+        })
+        .and_then(|_| fs::rename(&partial, archive).map_err(Into::into));
+
+    if unpacked {
+        let _ = fs::remove_dir_all(&data_dir);
+    }
+    if let Err(err) = written {
+        let _ = fs::remove_file(&partial);
+        return Err(err.context(format!("Failed to write {}; the entry is unchanged", archive.display())));
+    }
+
+    println!(
+        "Wrote {} item(s) from '{}' to {} ({}); the entry is still stashed",
+        entry.items.len(),
+        entry.display_name(),
+        archive.display(),
+        format_size(fs::metadata(archive)?.len(), SizeStyle::Human)
+    );
+
+    Ok(())
+}
+
+fn partial_path(archive: &Path) -> PathBuf {
+    let mut name = archive.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    archive.with_file_name(name)
+}
+
+/// The tar stream, compressed as the archive's name asks
+enum TarStream {
+    Plain(File),
+    Gz(GzEncoder<File>),
+    Bz2(BzEncoder<File>),
+}
+
+impl Write for TarStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            TarStream::Plain(file) => file.write(buf),
+            TarStream::Gz(encoder) => encoder.write(buf),
+            TarStream::Bz2(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            TarStream::Plain(file) => file.flush(),
+            TarStream::Gz(encoder) => encoder.flush(),
+            TarStream::Bz2(encoder) => encoder.flush(),
+        }
+    }
+}
+
+impl TarStream {
+    /// Write the compressor's trailer and flush the file
+    fn finish(self) -> io::Result<()> {
+        let mut file = match self {
+            TarStream::Plain(file) => file,
+            TarStream::Gz(encoder) => encoder.finish()?,
+            TarStream::Bz2(encoder) => encoder.finish()?,
+        };
+        file.flush()
+    }
+}
+
+enum ArchiveWriter {
+    Tar(tar::Builder<TarStream>),
+    Zip(zip::ZipWriter<File>),
+}
+
+impl ArchiveWriter {
+    fn new(file: File, format: ArchiveFormat) -> Result<Self> {
+        let stream = match format {
+            ArchiveFormat::Zip => return Ok(ArchiveWriter::Zip(zip::ZipWriter::new(file))),
+            ArchiveFormat::Tar => TarStream::Plain(file),
+            ArchiveFormat::TarGz => TarStream::Gz(GzEncoder::new(file, flate2::Compression::default())),
+            ArchiveFormat::TarBz2 => TarStream::Bz2(BzEncoder::new(file, bzip2::Compression::default())),
+            other => return Err(anyhow!("Can't write {:?} archives", other)),
+        };
+
+        let mut builder = tar::Builder::new(stream);
+        // Links are archived as links, and holes stay holes
+        builder.follow_symlinks(false);
+        builder.sparse(true);
+        Ok(ArchiveWriter::Tar(builder))
+    }
+
+    /// Add one stashed file, directory or symlink under `name`. Named pipes,
+    /// sockets and device files are never stashed, so they are skipped.
+    fn add(&mut self, path: &Path, name: &Path, metadata: &Metadata) -> Result<()> {
+        let file_type = metadata.file_type();
+        if !file_type.is_file() && !file_type.is_dir() && !file_type.is_symlink() {
+            return Ok(());
+        }
+
+        match self {
+            ArchiveWriter::Tar(builder) => builder
+                .append_path_with_name(path, name)
+                .with_context(|| format!("Failed to archive {}", path.display())),
+            ArchiveWriter::Zip(writer) => {
+                let name = name.to_string_lossy().replace('\\', "/");
+                let options = zip_options(metadata);
+
+                if file_type.is_dir() {
+                    writer.add_directory(name, options)?;
+                } else if file_type.is_symlink() {
+                    let target = fs::read_link(path)?;
+                    writer.add_symlink(name, target.to_string_lossy(), options)?;
+                } else {
+                    writer.start_file(name, options)?;
+                    io::copy(&mut File::open(path)?, writer)
+                        .with_context(|| format!("Failed to archive {}", path.display()))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            ArchiveWriter::Tar(builder) => builder.into_inner()?.finish()?,
+            ArchiveWriter::Zip(writer) => writer.finish()?.flush()?,
+        }
+        Ok(())
+    }
+}
+
+/// Deflate, with the file's permissions and (local) modification time. Zip
+/// times can't go before 1980; older files get the format's default.
+fn zip_options(metadata: &Metadata) -> zip::write::SimpleFileOptions {
+    let mut options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .large_file(metadata.len() >= u32::MAX as u64);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        options = options.unix_permissions(metadata.permissions().mode());
+    }
+
+    if let Ok(modified) = metadata.modified() {
+        let local: chrono::DateTime<Local> = modified.into();
+        let time = zip::DateTime::from_date_and_time(
+            local.year().clamp(0, u16::MAX as i32) as u16,
+            local.month() as u8,
+            local.day() as u8,
+            local.hour() as u8,
+            local.minute() as u8,
+            local.second().min(59) as u8,
+        );
+        if let Ok(time) = time {
+            options = options.last_modified_time(time);
+        }
+    }
+
+    options
+}
@@ -0,0 +1,25 @@
+use anyhow::Result;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::paths::AppDirs;
+
+pub fn run() -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let mut entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let removed = entry_manager.prune_journal()?;
+
+    if removed == 0 {
+        println!("Journal has no operations for deleted entries.");
+    } else {
+        println!("Pruned {} journal operation(s) for deleted entries.", removed);
+    }
+
+    Ok(())
+}
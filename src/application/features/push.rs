@@ -1,22 +1,131 @@
-use std::path::PathBuf;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
+use crate::application::cli::arguments::{ItemSort, OutputFormat};
+use crate::errors::StashError;
+use crate::models::entry::Entry;
+use crate::models::config::{HashAlgorithm, SortOrder};
 use crate::services::entry_manager;
+use crate::models::config::CompressionLevel as ConfigLevel;
 use crate::services::entry_manager::EntryManager;
+use crate::services::filesystem::file_compression::CompressionLevel;
 use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::display;
 use crate::utils::paths::AppDirs;
 
+/// How a push should be carried out
+pub struct PushFlags {
+    pub copy: bool,
+    pub hardlink: bool,
+    /// `hardlink`, copying what can't be linked
+    pub hardlink_or_copy: bool,
+    pub no_hash: bool,
+    pub compress: bool,
+    pub strict: bool,
+    pub ignore_hidden: bool,
+    /// Overrides `ignore_hidden_by_default`
+    pub include_hidden: bool,
+    pub min_age: Option<chrono::Duration>,
+    pub max_age: Option<chrono::Duration>,
+    pub no_reflink: bool,
+    /// Overrides `sort_items_by`
+    pub sort_items_by: Option<ItemSort>,
+    /// Skip the size and time summary
+    pub quiet: bool,
+    pub force: bool,
+    pub restore_message: Option<String>,
+    /// KEY=VALUE pairs added to the entry's metadata
+    pub meta: Vec<(String, String)>,
+    /// JSON object to start the metadata from
+    pub meta_file: Option<PathBuf>,
+    /// `Some(None)` writes checksums to the default location
+    pub checksum_file: Option<Option<PathBuf>>,
+    /// Shell command to run first; the push is abandoned if it fails
+    pub pre_command: Option<String>,
+    /// Report the pre-command and its exit code
+    pub verbose: bool,
+    /// Push as the latest of up to this many versions of the name
+    pub version_tag: Option<usize>,
+    /// Overrides `push_workers`
+    pub jobs: Option<usize>,
+}
+
 pub fn run(
-    items: &Vec<PathBuf>,
+    items: &[PathBuf],
     name: &Option<String>,
-    copy: &bool,
+    flags: &PushFlags,
+    format: &OutputFormat,
 ) -> Result<()> {
     let cwd = std::env::current_dir()?;
     let dirs = AppDirs::new();
 
-    // Ensure config exists
-    ConfigStorage::new(&dirs.config_file)?;
+    let config_storage = ConfigStorage::new(&dirs.config_file)?;
+    let config = config_storage.get_config();
+
+    let hash = config.compute_hashes_on_push && !flags.no_hash;
+    if config.verify_integrity && !hash {
+        eprintln!(
+            "Warning: integrity verification requires hashes; \
+             run 'stash --rehash ENTRY' to compute them later"
+        );
+    }
+
+    let hardlink = flags.hardlink || flags.hardlink_or_copy;
+    if hardlink && flags.compress {
+        return Err(StashError::Usage(
+            "--hardlink can't be used with --compress: packing the files into an archive copies them".into(),
+        )
+        .into());
+    }
+
+    // --compress forces compression even if the configured level is None.
+    // Configured compression is skipped for hard-linked pushes, which exist
+    // to avoid copying.
+    let compress = flags.compress || (config.compress_entries && !hardlink);
+    let compression = match (&config.compression_level, compress) {
+        (_, false) => None,
+        (ConfigLevel::None, true) if flags.compress => Some(CompressionLevel::Medium),
+        (ConfigLevel::None, true) => None,
+        (ConfigLevel::Fast, true) => Some(CompressionLevel::Fast),
+        (ConfigLevel::Balanced, true) => Some(CompressionLevel::Medium),
+        (ConfigLevel::Maximum, true) => Some(CompressionLevel::Maximum),
+    };
+
+    if flags.checksum_file.is_some() {
+        if !hash {
+            return Err(StashError::Usage(
+                "--checksum-file needs file hashes; drop --no-hash or enable compute_hashes_on_push".into(),
+            )
+            .into());
+        }
+        if compression.is_some() {
+            return Err(StashError::Usage(
+                "--checksum-file can't be used with compression: the stashed files are packed into an archive".into(),
+            )
+            .into());
+        }
+    }
+
+    let ignore_hidden = flags.ignore_hidden || (config.ignore_hidden_by_default && !flags.include_hidden);
+
+    let sort_items = match flags.sort_items_by {
+        None => config.sort_items_by,
+        Some(ItemSort::Unsorted) => SortOrder::Unsorted,
+        Some(ItemSort::Name) => SortOrder::Name,
+        Some(ItemSort::Size) => SortOrder::Size,
+        Some(ItemSort::Mtime) => SortOrder::Mtime,
+    };
+
+    // Read before the pre-command runs, so a bad file stops the push early
+    let metadata = entry_metadata(flags)?;
+
+    let pre_command = flags
+        .pre_command
+        .clone()
+        .or_else(|| config.default_pre_push_command.clone());
 
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
@@ -26,20 +135,156 @@ pub fn run(
         &mut index_storage,
         &mut journal_storage,
     )?;
-
-    let default_name = items[0]
-        .file_name()
-        .expect("item must have filename")
-        .to_string_lossy()
-        .to_string();
+    entry_manager.set_reflink(!flags.no_reflink);
+    entry_manager.set_copy_buffer(config.copy_buffer());
+    let workers = flags.jobs.unwrap_or(config.push_workers);
+    entry_manager.set_workers(workers);
 
     let options = entry_manager::PushOptions {
-        name: name.as_ref().unwrap_or(&default_name),
-        copy,
+        name,
+        copy: &flags.copy,
+        hardlink: &hardlink,
+        hardlink_fallback: &flags.hardlink_or_copy,
+        hash: &hash,
+        hash_algorithm: &config.hash_algorithm,
+        hash_max_size: &config.hash_max_size(),
+        verify: &config.verify_integrity,
+        workers: &workers,
+        compression: &compression,
+        strict: &flags.strict,
+        sort_items: &sort_items,
+        ignore_hidden: &ignore_hidden,
+        min_age: &flags.min_age,
+        max_age: &flags.max_age,
+        force: &flags.force,
+        restore_message: &flags.restore_message,
+        metadata: &metadata,
+        recorded_paths: None,
+        versioned: &flags.version_tag.is_some(),
+        max_versions: &flags.version_tag,
+        pre_command: &pre_command,
+        verbose: &flags.verbose,
     };
 
-    entry_manager.create_entry(items, options, &cwd)?;
+    let entry = entry_manager.create_entry(items, options, &cwd)?;
+
+    if let Some(path) = &flags.checksum_file {
+        let path = checksum_path(path.as_deref(), &dirs.data_dir, &entry);
+        write_checksums(&entry_manager, &entry, &path)?;
+        eprintln!("Wrote checksums to {}", path.display());
+    }
+
+    if *format == OutputFormat::Porcelain {
+        println!("{}", entry.uuid);
+        return Ok(());
+    }
+
+    let skipped = entry.items.iter().filter(|item| item.hash_skipped).count();
+    if skipped > 0 && !flags.quiet {
+        eprintln!(
+            "Note: {} file(s) larger than hash_max_size_mb were not hashed; \
+             run 'stash --rehash {}' to hash them",
+            skipped,
+            entry.short_id()
+        );
+    }
+
+    if let Some(transfer) = entry_manager.last_transfer().filter(|_| !flags.quiet) {
+        let verb = if flags.copy { "Copied" } else { "Stashed" };
+        println!("{}", display::transfer_summary(verb, transfer.bytes, transfer.elapsed));
+    }
+
+    if entry.hardlinked {
+        println!(
+            "Hard-linked into '{}'; the originals and the stashed copy share their data until it is popped",
+            entry.display_name()
+        );
+    } else if entry_manager.reflinked_files() > 0 {
+        println!(
+            "Cloned {} file(s) with reflinks; they take no extra space until changed",
+            entry_manager.reflinked_files()
+        );
+    }
 
     Ok(())
 }
 
+/// Metadata from `--meta-file`, with `--meta` pairs set on top as strings.
+/// Null when neither was given.
+fn entry_metadata(flags: &PushFlags) -> Result<serde_json::Value> {
+    let mut metadata = match &flags.meta_file {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let value: serde_json::Value = serde_json::from_str(&contents)
+                .with_context(|| format!("{} is not valid JSON", path.display()))?;
+            if !value.is_object() {
+                return Err(StashError::Usage(format!("{} must contain a JSON object", path.display())).into());
+            }
+            value
+        }
+        None if flags.meta.is_empty() => return Ok(serde_json::Value::Null),
+        None => serde_json::Value::Object(Default::default()),
+    };
+
+    if let Some(object) = metadata.as_object_mut() {
+        for (key, value) in &flags.meta {
+            object.insert(key.clone(), serde_json::Value::String(value.clone()));
+        }
+    }
+
+    Ok(metadata)
+}
+
+/// Where to write checksums: PATH itself, or `<name>.sha256sums` inside it
+/// if it is a directory, or inside the stash directory if no PATH was given
+fn checksum_path(path: Option<&Path>, data_dir: &Path, entry: &Entry) -> PathBuf {
+    let extension = match entry.items.iter().find_map(|item| item.hash_algorithm()) {
+        Some(HashAlgorithm::Blake3) => "b3sums",
+        _ => "sha256sums",
+    };
+    let file_name = format!("{}.{}", entry.display_name().replace(['/', '\\'], "_"), extension);
+
+    match path {
+        Some(path) if path.is_dir() => path.join(file_name),
+        Some(path) => path.to_path_buf(),
+        None => data_dir.join(file_name),
+    }
+}
+
+/// Write `<hash>  <path>` lines for every hashed file, pointing at the stashed
+/// data so `sha256sum -c` (or `b3sum -c`) can audit the stash without stash-rs
+fn write_checksums(entry_manager: &EntryManager, entry: &Entry, path: &Path) -> Result<()> {
+    let mut out = String::new();
+    let mut unhashed = 0;
+
+    for item in &entry.items {
+        let Some(hash) = &item.hash else {
+            unhashed += 1;
+            continue;
+        };
+        let digest = hash.split_once(':').map_or(hash.as_str(), |(_, digest)| digest);
+        let file = entry_manager.item_data_path(&entry.uuid, item).to_string_lossy().to_string();
+
+        // Same escaping as sha256sum: a leading backslash marks an escaped name
+        if file.contains(['\\', '\n', '\r']) {
+            let escaped = file.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r");
+            writeln!(out, "\\{}  {}", digest, escaped)?;
+        } else {
+            writeln!(out, "{}  {}", digest, file)?;
+        }
+    }
+
+    if unhashed > 0 {
+        eprintln!(
+            "Note: {} item(s) have no hash (directories, symlinks and files over hash_max_size_mb) \
+             and are not in the checksum file",
+            unhashed
+        );
+    }
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, out).with_context(|| format!("Failed to write checksums to {}", path.display()))
+}
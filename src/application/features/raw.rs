@@ -0,0 +1,36 @@
+use anyhow::Result;
+use crate::application::cli::arguments::RawTarget;
+use crate::models::Config;
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::paths::AppDirs;
+
+/// Print internal state exactly as parsed, without modifying anything on disk
+pub fn run(target: &RawTarget) -> Result<()> {
+    let dirs = AppDirs::new();
+
+    match target {
+        RawTarget::Index => {
+            let index_storage = IndexStorage::new(&dirs.index_file)?;
+            println!("# {}", dirs.index_file.display());
+            println!("{:#?}", index_storage.index());
+        }
+        RawTarget::Journal => {
+            let journal_storage = JournalStorage::new(&dirs.journal_file)?;
+            println!("# {}", dirs.journal_file.display());
+            println!("{:#?}", journal_storage.operations()?);
+        }
+        RawTarget::Config => {
+            // ConfigStorage writes a default file when none exists, so skip it
+            if dirs.config_file.exists() {
+                let config_storage = ConfigStorage::new(&dirs.config_file)?;
+                println!("# {}", dirs.config_file.display());
+                println!("{:#?}", config_storage.get_config());
+            } else {
+                println!("# {} (missing, showing defaults)", dirs.config_file.display());
+                println!("{:#?}", Config::default());
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use anyhow::Result;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::paths::AppDirs;
+
+pub fn run(identifier: &str) -> Result<()> {
+    let dirs = AppDirs::new();
+    let config = ConfigStorage::new(&dirs.config_file)?;
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let entry = entry_manager.load_entry_by_identifier(identifier)?;
+    let hashed = entry_manager.rehash_entry(&entry.uuid, config.get_config().hash_algorithm)?;
+
+    if hashed == 0 {
+        println!("All files in '{}' already have hashes.", entry.display_name());
+    } else {
+        println!("Computed {} hash(es) for '{}'", hashed, entry.display_name());
+    }
+
+    Ok(())
+}
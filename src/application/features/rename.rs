@@ -1,4 +1,6 @@
 use anyhow::Result;
+use uuid::Uuid;
+use crate::errors::StashError;
 use crate::services::entry_manager::EntryManager;
 use crate::services::storage::{IndexStorage, JournalStorage};
 use crate::utils::paths::AppDirs;
@@ -20,3 +22,28 @@ pub fn run(old: &str, new: &str) -> Result<()> {
 
     Ok(())
 }
+
+/// Rename the entry with this exact UUID, so names that look like UUIDs or
+/// contain colons need no escaping
+pub fn run_by_uuid(uuid: &Uuid, new: &str) -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let mut entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let old = entry_manager
+        .list_entries()
+        .iter()
+        .find(|meta| &meta.uuid == uuid)
+        .map(|meta| meta.display_name())
+        .ok_or_else(|| StashError::NotFound(uuid.to_string()))?;
+    entry_manager.rename_entry(uuid, new.to_string())?;
+
+    println!("Renamed '{}' → '{}'", old, new);
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use anyhow::Result;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::paths::AppDirs;
+
+/// Undo an entry's most recent rename
+pub fn run(identifier: &str) -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let mut entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let entry = entry_manager.load_entry_by_identifier(identifier)?;
+    let (from, to) = entry_manager.revert_name(&entry.uuid)?;
+
+    println!("Renamed '{}' → '{}'", from, to);
+
+    Ok(())
+}
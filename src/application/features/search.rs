@@ -1,11 +1,13 @@
 use anyhow::Result;
+use crate::application::cli::arguments::OutputFormat;
 use crate::services::entry_manager::EntryManager;
-use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
 use crate::utils::paths::AppDirs;
-use crate::utils::display::{humanize_duration, humanize_size};
+use crate::utils::display::{format_size, format_time, porcelain_entry, SizeStyle, TimeStyle};
 
-pub fn run(pattern: &str) -> Result<()> {
+pub fn run(pattern: &str, time: &TimeStyle, sizes: &SizeStyle, format: &OutputFormat) -> Result<()> {
     let dirs = AppDirs::new();
+    let date_format = ConfigStorage::existing(&dirs.config_file)?.date_format;
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
     let entry_manager = EntryManager::new(
@@ -21,6 +23,13 @@ pub fn run(pattern: &str) -> Result<()> {
         .filter(|e| e.name.to_lowercase().contains(&pattern_lower))
         .collect();
 
+    if *format == OutputFormat::Porcelain {
+        for meta in matches {
+            println!("{}", porcelain_entry(meta));
+        }
+        return Ok(());
+    }
+
     if matches.is_empty() {
         println!("No entries match '{}'.", pattern);
         return Ok(());
@@ -29,10 +38,10 @@ pub fn run(pattern: &str) -> Result<()> {
     println!("Found {} match{}:", matches.len(), if matches.len() == 1 { "" } else { "es" });
 
     for meta in matches {
-        let age = humanize_duration(meta.created);
-        let size = humanize_size(meta.total_size_bytes);
+        let age = format_time(meta.created, *time, &date_format);
+        let size = format_size(meta.total_size_bytes, *sizes);
 
-        println!("  • {} ({} files, {}, {})", meta.name, meta.item_count, size, age);
+        println!("  • {} ({} files, {}, {})", meta.display_name(), meta.item_count, size, age);
     }
 
     Ok(())
@@ -0,0 +1,65 @@
+use anyhow::Result;
+use crate::application::cli::arguments::OutputFormat;
+use crate::models::EntryMetadata;
+use crate::services::storage::{ConfigStorage, IndexStorage, JournalStorage};
+use crate::utils::display::{format_size, format_time, porcelain_field, SizeStyle, TimeStyle};
+use crate::utils::paths::AppDirs;
+
+/// Entries within this fraction of `warn_size_mb` count as near it
+const NEAR_WARN_SIZE: f64 = 0.9;
+
+/// A short overview of the stash, meant for a shell startup file
+pub fn run(time: &TimeStyle, sizes: &SizeStyle, format: &OutputFormat) -> Result<()> {
+    let dirs = AppDirs::new();
+    let config = ConfigStorage::existing(&dirs.config_file)?;
+    let index_storage = IndexStorage::new(&dirs.index_file)?;
+    let journal_storage = JournalStorage::new(&dirs.journal_file)?;
+
+    let entries = index_storage.list_all();
+    let total: u64 = entries.iter().map(|meta| meta.total_size_bytes).sum();
+    let oldest = entries.iter().min_by_key(|meta| meta.created);
+    let newest = entries.iter().max_by_key(|meta| meta.created);
+
+    let warn_bytes = config.warn_size_mb.saturating_mul(1024 * 1024);
+    let near_limit: Vec<&EntryMetadata> = entries
+        .iter()
+        .filter(|meta| warn_bytes > 0 && meta.total_size_bytes as f64 >= warn_bytes as f64 * NEAR_WARN_SIZE)
+        .collect();
+
+    let undoable = journal_storage.count_matching(|op| op.is_undoable())?;
+
+    if *format == OutputFormat::Porcelain {
+        println!("entries={}", entries.len());
+        println!("size_bytes={}", total);
+        println!("oldest={}", oldest.map(|meta| meta.created.to_rfc3339()).unwrap_or_default());
+        println!("last_push={}", newest.map(|meta| porcelain_field(&meta.name)).unwrap_or_default());
+        println!("last_push_at={}", newest.map(|meta| meta.created.to_rfc3339()).unwrap_or_default());
+        println!("near_warn_size={}", near_limit.len());
+        println!("undoable={}", undoable);
+        return Ok(());
+    }
+
+    let (Some(oldest), Some(newest)) = (oldest, newest) else {
+        println!("Stash is empty.");
+        return Ok(());
+    };
+
+    println!(
+        "{} entr{}, {}",
+        entries.len(),
+        if entries.len() == 1 { "y" } else { "ies" },
+        format_size(total, *sizes)
+    );
+    println!("Oldest: '{}', {}", oldest.display_name(), format_time(oldest.created, *time, &config.date_format));
+    println!("Last push: '{}', {}", newest.display_name(), format_time(newest.created, *time, &config.date_format));
+    if !near_limit.is_empty() {
+        println!(
+            "Near the {} size warning: {}",
+            format_size(warn_bytes, *sizes),
+            near_limit.iter().map(|meta| format!("'{}'", meta.display_name())).collect::<Vec<_>>().join(", ")
+        );
+    }
+    println!("Undoable operations: {}", undoable);
+
+    Ok(())
+}
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::paths::AppDirs;
+
+/// Exchange the names of two entries
+pub fn run(first: &str, second: &str) -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let mut entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let a = entry_manager.load_entry_by_identifier(first)?;
+    let b = entry_manager.load_entry_by_identifier(second)?;
+    entry_manager.swap_names(&a.uuid, &b.uuid)?;
+
+    println!("Swapped names: '{}' ↔ '{}'", a.display_name(), b.display_name());
+
+    Ok(())
+}
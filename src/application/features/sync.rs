@@ -0,0 +1,170 @@
+use anyhow::{Context, Result, anyhow};
+use std::fs;
+use std::path::Path;
+use crate::application::cli::arguments::SyncDirection;
+use crate::models::Index;
+use crate::models::index::MergeConflict;
+use crate::services::remote_sync::{self, remote_path};
+use crate::services::storage::{self, IndexStorage, JournalStorage};
+use crate::utils::lock::StashLock;
+use crate::utils::paths::AppDirs;
+
+pub fn run(remote: &str, direction: &SyncDirection, dry_run: &bool) -> Result<()> {
+    let dirs = AppDirs::new();
+    let _lock = StashLock::acquire(&dirs.lock_file)?;
+
+    let staging = std::env::temp_dir().join(format!("stash-sync-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&staging)?;
+
+    let result = match direction {
+        SyncDirection::Push => push(&dirs, remote, &staging, *dry_run),
+        SyncDirection::Pull => pull(&dirs, remote, &staging, *dry_run),
+    };
+
+    let _ = fs::remove_dir_all(&staging);
+
+    result
+}
+
+fn push(dirs: &AppDirs, remote: &str, staging: &Path, dry_run: bool) -> Result<()> {
+    let index_storage = IndexStorage::new(&dirs.index_file)?;
+    let journal_storage = JournalStorage::new(&dirs.journal_file)?;
+
+    // Reconcile against whatever is already on the remote
+    let mut merged = fetch_index(remote, staging, "remote-index.json")?.unwrap_or_default();
+    let conflicts = merged.merge(index_storage.index());
+
+    let changes = remote_sync::merge_dirs(
+        dirs.entries_dir.as_os_str(),
+        remote_path(remote, "entries").as_ref(),
+        dry_run,
+    )?;
+
+    report(&changes, &conflicts, dry_run);
+
+    if dry_run {
+        println!("Would upload index with {} entries to {}", merged.len(), remote);
+        return Ok(());
+    }
+
+    let staged_journal = staging.join("journal.log");
+    remote_sync::fetch_file(&remote_path(remote, "journal.log"), &staged_journal)?;
+    let mut journal = JournalStorage::new(&staged_journal)?;
+    journal.merge(journal_storage.operations()?)?;
+    journal.save_operations()?;
+    remote_sync::upload_file(&staged_journal, &remote_path(remote, "journal.log"))?;
+
+    let staged_index = staging.join("index.json");
+    fs::write(&staged_index, storage::to_json(&merged)?)?;
+    remote_sync::upload_file(&staged_index, &remote_path(remote, "index.json"))?;
+
+    // Read the index back to make sure the remote holds what we sent
+    let uploaded = fetch_index(remote, staging, "uploaded-index.json")?
+        .ok_or_else(|| anyhow!("Remote index missing after upload to {}", remote))?;
+    let missing = merged
+        .entries
+        .iter()
+        .filter(|m| uploaded.get_metadata(&m.uuid).is_none())
+        .count();
+    if missing > 0 {
+        return Err(anyhow!(
+            "Remote index at {} is missing {} entries after upload",
+            remote,
+            missing
+        ));
+    }
+
+    println!("Pushed stash to {} ({} entries)", remote, merged.len());
+
+    Ok(())
+}
+
+fn pull(dirs: &AppDirs, remote: &str, staging: &Path, dry_run: bool) -> Result<()> {
+    let remote_index = fetch_index(remote, staging, "remote-index.json")?
+        .ok_or_else(|| anyhow!("No stash index found at {}", remote))?;
+
+    fs::create_dir_all(&dirs.entries_dir)?;
+    let changes = remote_sync::merge_dirs(
+        remote_path(remote, "entries").as_ref(),
+        dirs.entries_dir.as_os_str(),
+        dry_run,
+    )?;
+
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut merged = index_storage.index().clone();
+    let conflicts = merged.merge(&remote_index);
+
+    report(&changes, &conflicts, dry_run);
+
+    if dry_run {
+        println!("Would update local index to {} entries", merged.len());
+        return Ok(());
+    }
+
+    // Entries listed in the index must have arrived with their manifests
+    for meta in &merged.entries {
+        let manifest = dirs.entries_dir.join(meta.uuid.to_string()).join("manifest.json");
+        if !manifest.exists() {
+            eprintln!("Warning: entry '{}' ({}) has no data locally", meta.display_name(), meta.uuid);
+        }
+    }
+
+    *index_storage.index_mut() = merged;
+    index_storage.save_packages()?;
+
+    let staged_journal = staging.join("journal.log");
+    if remote_sync::fetch_file(&remote_path(remote, "journal.log"), &staged_journal)? {
+        let remote_journal = JournalStorage::new(&staged_journal)?;
+        let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+        journal_storage.merge(remote_journal.operations()?)?;
+    }
+
+    println!(
+        "Pulled stash from {} ({} entries)",
+        remote,
+        index_storage.entry_count()
+    );
+
+    Ok(())
+}
+
+/// Fetch and strictly parse the remote index; `None` if the remote has none
+fn fetch_index(remote: &str, staging: &Path, file_name: &str) -> Result<Option<Index>> {
+    let path = staging.join(file_name);
+
+    if !remote_sync::fetch_file(&remote_path(remote, "index.json"), &path)? {
+        return Ok(None);
+    }
+
+    let json = fs::read_to_string(&path)?;
+    let index = serde_json::from_str(&json)
+        .with_context(|| format!("Remote index at {} is not valid", remote))?;
+
+    Ok(Some(index))
+}
+
+fn report(changes: &[String], conflicts: &[MergeConflict], dry_run: bool) {
+    if changes.is_empty() {
+        println!("Entries are up to date.");
+    } else {
+        let verb = if dry_run { "Would transfer" } else { "Transferred" };
+        println!("{} {} file(s):", verb, changes.len());
+        for change in changes {
+            println!("  {}", change);
+        }
+    }
+
+    if !conflicts.is_empty() {
+        println!("Resolved {} conflict(s) by keeping newer metadata:", conflicts.len());
+        for conflict in conflicts {
+            println!(
+                "  {}: kept '{}' ({}), discarded '{}' ({})",
+                &conflict.uuid.to_string()[..6],
+                conflict.kept.name,
+                conflict.kept.last_updated().format("%Y-%m-%d %H:%M:%S"),
+                conflict.discarded.name,
+                conflict.discarded.last_updated().format("%Y-%m-%d %H:%M:%S"),
+            );
+        }
+    }
+}
@@ -1,11 +1,52 @@
-use anyhow::{Result, anyhow};
-use std::path::PathBuf;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex};
+use uuid::Uuid;
 use crate::services::entry_manager::EntryManager;
 use crate::services::storage::{IndexStorage, JournalStorage};
 use crate::utils::paths::AppDirs;
-use crate::services::filesystem::tape_archives;
+use crate::errors::StashError;
 
-pub fn run(output_path: &PathBuf) -> Result<()> {
+/// Progress of an export, kept next to the archive until it finishes so an
+/// interrupted export can pick up where it stopped
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ExportRecord {
+    entries: Vec<ExportedEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedEntry {
+    uuid: Uuid,
+    /// Directory the entry was written to inside the archive
+    dir: String,
+    /// Archive length once this entry was complete
+    end: u64,
+}
+
+/// The end-of-archive marker `tar::Builder::finish` writes: two zero blocks
+const TRAILER_LEN: u64 = 1024;
+
+/// An entry to export, with the directory it gets inside the archive
+struct Job {
+    uuid: Uuid,
+    name: String,
+    dir: String,
+    source: PathBuf,
+}
+
+/// How far the writer has got, so workers stay a bounded distance ahead
+struct Progress {
+    appended: usize,
+    stopped: bool,
+}
+
+pub fn run(output_path: &PathBuf, resume: &bool, jobs: &usize) -> Result<()> {
     let dirs = AppDirs::new();
     let mut index_storage = IndexStorage::new(&dirs.index_file)?;
     let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
@@ -18,50 +59,221 @@ pub fn run(output_path: &PathBuf) -> Result<()> {
     let entries = entry_manager.list_entries();
 
     if entries.is_empty() {
-        return Err(anyhow!("No entries to export"));
+        return Err(StashError::NothingMatched("No entries to export".into()).into());
     }
 
-    // Create a temporary directory for collecting all entries
-    let temp_dir = std::env::temp_dir().join(format!("stash-export-{}", uuid::Uuid::new_v4()));
-    std::fs::create_dir_all(&temp_dir)?;
+    let record_path = record_path(output_path);
+    let (mut record, file) = if *resume {
+        reopen(output_path, &record_path)?
+    } else {
+        (ExportRecord::default(), File::create(output_path)?)
+    };
 
-    println!("Exporting {} entries to {}...", entries.len(), output_path.display());
+    let done: HashSet<Uuid> = record.entries.iter().map(|e| e.uuid).collect();
+    let mut used_dirs: HashSet<String> = record.entries.iter().map(|e| e.dir.clone()).collect();
 
-    // Copy all entries into temp directory
-    for meta in entries {
+    // Sorted by UUID so exporting an unchanged stash twice gives the same bytes
+    let mut pending: Vec<_> = entries.iter().filter(|meta| !done.contains(&meta.uuid)).collect();
+    pending.sort_by_key(|meta| meta.uuid);
+
+    let mut queue = Vec::with_capacity(pending.len());
+    for meta in &pending {
         let entry = entry_manager.load_entry(&meta.uuid)?;
 
-        let entry_dir = dirs.entries_dir.join(meta.uuid.to_string());
-        let dest_dir = temp_dir.join(&entry.name);
+        // Names may repeat or contain separators; keep one directory per entry
+        let mut dir = entry.display_name().replace(['/', '\\'], "_");
+        if used_dirs.contains(&dir) {
+            dir = format!("{}-{}", dir, entry.short_id());
+        }
+        used_dirs.insert(dir.clone());
 
-        // Copy the entire entry directory (including manifest and data)
-        copy_dir_all(&entry_dir, &dest_dir)?;
+        queue.push(Job {
+            uuid: meta.uuid,
+            name: entry.display_name(),
+            dir,
+            source: dirs.entries_dir.join(meta.uuid.to_string()),
+        });
+    }
 
-        println!("  • {}", entry.name);
+    if *resume {
+        println!(
+            "Resuming export to {}: {} entries already exported, {} to go...",
+            output_path.display(),
+            done.len(),
+            pending.len()
+        );
+    } else {
+        println!("Exporting {} entries to {}...", entries.len(), output_path.display());
     }
 
-    // Create tar archive from temp directory
-    tape_archives::create_tar(&temp_dir, output_path)?;
+    let mut archive = tar::Builder::new(file);
+
+    // Configure builder to work cross-platform; see create_tar for the
+    // header format
+    #[cfg(unix)]
+    archive.mode(tar::HeaderMode::Deterministic);
+    archive.sparse(true);
+
+    // Workers read entries into archive fragments while this thread appends
+    // finished ones in order, recording each once it is complete
+    let write_result = export_parallel(&queue, output_path, (*jobs).max(1), |job, fragment| {
+        let mut data = File::open(fragment)?;
+        let len = data.metadata()?.len().saturating_sub(TRAILER_LEN);
+        std::io::copy(&mut data.by_ref().take(len), archive.get_mut())?;
+
+        let end = archive.get_mut().stream_position()?;
+        record.entries.push(ExportedEntry { uuid: job.uuid, dir: job.dir.clone(), end });
+        fs::write(&record_path, serde_json::to_string_pretty(&record)?)?;
+
+        println!("  • {}", job.name);
+        Ok(())
+    });
+    write_result?;
 
-    // Cleanup temp directory
-    std::fs::remove_dir_all(&temp_dir)?;
+    archive.finish()?;
 
-    println!("Exported {} entries to {}", entries.len(), output_path.display());
+    // A finished archive has nothing left to resume
+    let _ = fs::remove_file(&record_path);
+
+    println!("Exported {} entries to {}", record.entries.len(), output_path.display());
 
     Ok(())
 }
 
-fn copy_dir_all(src: &std::path::Path, dst: &std::path::Path) -> Result<()> {
-    std::fs::create_dir_all(dst)?;
-    for entry in std::fs::read_dir(src)? {
-        let entry = entry?;
-        let ty = entry.file_type()?;
-        if ty.is_dir() {
-            copy_dir_all(&entry.path(), &dst.join(entry.file_name()))?;
-        } else {
-            std::fs::copy(entry.path(), dst.join(entry.file_name()))?;
+/// Archive every job into a fragment next to `output` on up to `jobs`
+/// threads, handing the fragments to `append` in queue order. Workers stay
+/// at most `2 * jobs` entries ahead of `append`, which bounds the disk the
+/// fragments take.
+fn export_parallel(
+    queue: &[Job],
+    output: &Path,
+    jobs: usize,
+    mut append: impl FnMut(&Job, &Path) -> Result<()>,
+) -> Result<()> {
+    let window = jobs * 2;
+    let next = AtomicUsize::new(0);
+    let progress = Mutex::new(Progress { appended: 0, stopped: false });
+    let advanced = Condvar::new();
+    let (senders, receivers): (Vec<_>, Vec<_>) = queue.iter().map(|_| mpsc::sync_channel(1)).unzip();
+
+    let result = std::thread::scope(|scope| {
+        for _ in 0..jobs.min(queue.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = queue.get(i) else {
+                    break;
+                };
+
+                let mut state = progress.lock().unwrap_or_else(|e| e.into_inner());
+                while !state.stopped && i >= state.appended + window {
+                    state = advanced.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+                if state.stopped {
+                    break;
+                }
+                drop(state);
+
+                let fragment = fragment_path(output, &job.uuid);
+                let _ = senders[i].send(write_fragment(job, &fragment).map(|_| fragment));
+            });
+        }
+
+        let mut result = Ok(());
+        for (job, receiver) in queue.iter().zip(&receivers) {
+            let written = receiver
+                .recv()
+                .expect("every queued entry is either written or abandoned")
+                .and_then(|fragment| {
+                    let appended = append(job, &fragment);
+                    let _ = fs::remove_file(&fragment);
+                    appended
+                })
+                .with_context(|| {
+                    format!(
+                        "Failed to export '{}'; fix the problem and run again with --continue to resume",
+                        job.name
+                    )
+                });
+
+            let mut state = progress.lock().unwrap_or_else(|e| e.into_inner());
+            state.appended += 1;
+            if written.is_err() {
+                state.stopped = true;
+            }
+            advanced.notify_all();
+            drop(state);
+
+            if written.is_err() {
+                result = written;
+                break;
+            }
+        }
+        result
+    });
+
+    // Fragments finished after a failure are never appended
+    for receiver in &receivers {
+        if let Ok(Ok(fragment)) = receiver.try_recv() {
+            let _ = fs::remove_file(fragment);
         }
     }
-    Ok(())
+
+    result
 }
 
+/// Archive one entry's directory, manifest and data, into its own file
+fn write_fragment(job: &Job, fragment: &Path) -> Result<()> {
+    let mut archive = tar::Builder::new(File::create(fragment)?);
+
+    // The same header settings as the archive the fragment ends up in
+    #[cfg(unix)]
+    archive.mode(tar::HeaderMode::Deterministic);
+    archive.sparse(true);
+
+    let written = archive.append_dir_all(&job.dir, &job.source).and_then(|_| archive.finish());
+    if written.is_err() {
+        let _ = fs::remove_file(fragment);
+    }
+    Ok(written?)
+}
+
+/// Where an entry is archived before being appended to `output`
+fn fragment_path(output: &Path, uuid: &Uuid) -> PathBuf {
+    let name = output.file_name().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!(".{}.{}.part", name, uuid))
+}
+
+/// Where the progress of an export to `output` is kept
+fn record_path(output: &Path) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(".export.json");
+    output.with_file_name(name)
+}
+
+/// Open an interrupted export for appending, cutting off anything written
+/// after its last complete entry
+fn reopen(output: &Path, record_path: &Path) -> Result<(ExportRecord, File)> {
+    let contents = fs::read_to_string(record_path).map_err(|_| {
+        StashError::Usage(format!(
+            "No interrupted export to continue at {} (finished exports can't be extended)",
+            output.display()
+        ))
+    })?;
+    let record: ExportRecord = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to read export progress from {}", record_path.display()))?;
+
+    let end = record.entries.last().map_or(0, |e| e.end);
+    let mut file = File::options().write(true).open(output)?;
+    if file.metadata()?.len() < end {
+        return Err(StashError::Integrity(format!(
+            "{} is shorter than its recorded progress; start the export again without --continue",
+            output.display()
+        ))
+        .into());
+    }
+
+    file.set_len(end)?;
+    file.seek(SeekFrom::Start(end))?;
+
+    Ok((record, file))
+}
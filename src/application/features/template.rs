@@ -0,0 +1,78 @@
+use anyhow::Result;
+use std::path::PathBuf;
+use crate::errors::StashError;
+use crate::services::entry_manager::EntryManager;
+use crate::services::storage::{IndexStorage, JournalStorage};
+use crate::utils::display::escape_path;
+use crate::utils::normalize;
+use crate::utils::paths::AppDirs;
+
+/// Mark an entry as a template (`--mark-template`), or unmark it
+pub fn mark(identifier: &str, is_template: &bool) -> Result<()> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let mut entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let entry = entry_manager.load_entry_by_identifier(identifier)?;
+    entry_manager.set_template(&entry.uuid, *is_template)?;
+
+    if *is_template {
+        println!(
+            "Marked '{}' as a template; push its {} item(s) again with --template {}",
+            entry.display_name(),
+            entry.items.len(),
+            entry.display_name()
+        );
+    } else {
+        println!("'{}' is no longer a template", entry.display_name());
+    }
+
+    Ok(())
+}
+
+/// The paths a push from the template `template` stashes: the template's
+/// items, taken from the current directory. Items that aren't there are
+/// skipped with a warning.
+pub fn items(template: &str) -> Result<Vec<PathBuf>> {
+    let dirs = AppDirs::new();
+    let mut index_storage = IndexStorage::new(&dirs.index_file)?;
+    let mut journal_storage = JournalStorage::new(&dirs.journal_file)?;
+    let entry_manager = EntryManager::new(
+        &dirs.entries_dir,
+        &mut index_storage,
+        &mut journal_storage,
+    )?;
+
+    let meta = entry_manager
+        .list_entries()
+        .iter()
+        .find(|meta| meta.is_template && (normalize::same_text(&meta.name, template) || meta.uuid.to_string() == template))
+        .ok_or_else(|| {
+            StashError::NotFound(format!("{} (no template by that name; mark one with --mark-template)", template))
+        })?;
+    let entry = entry_manager.load_entry(&meta.uuid)?;
+
+    let (present, missing): (Vec<PathBuf>, Vec<PathBuf>) = entry
+        .item_paths()
+        .into_iter()
+        .partition(|path| std::fs::symlink_metadata(path).is_ok());
+
+    for path in &missing {
+        eprintln!("Skipping {}: not in the current directory", escape_path(path));
+    }
+    if present.is_empty() {
+        return Err(StashError::NothingMatched(format!(
+            "None of the {} item(s) of template '{}' exist here",
+            missing.len(),
+            entry.display_name()
+        ))
+        .into());
+    }
+
+    Ok(present)
+}
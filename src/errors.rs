@@ -0,0 +1,88 @@
+use std::fmt;
+use std::path::PathBuf;
+use crate::utils::display::{format_size, SizeStyle};
+
+/// Failures that scripts may want to branch on. Each variant maps to a
+/// distinct process exit code; any other error exits with `1`.
+#[derive(Debug)]
+pub enum StashError {
+    /// Malformed or ambiguous command line (exit code 2)
+    Usage(String),
+    /// No entry matches the given identifier (exit code 3)
+    NotFound(String),
+    /// A restore destination already exists (exit code 4)
+    Conflict(PathBuf),
+    /// Items would land on the same path of a case-insensitive filesystem
+    /// (exit code 4)
+    CaseCollision { root: PathBuf, pairs: Vec<(PathBuf, PathBuf)> },
+    /// Stashed data does not match its recorded hashes (exit code 5)
+    Integrity(String),
+    /// There was nothing to operate on (exit code 6)
+    NothingMatched(String),
+    /// A dry run found changes it would make (exit code 1)
+    PendingChanges(String),
+    /// Not enough free space to restore or stash (exit code 7)
+    InsufficientSpace { path: PathBuf, needed: u64, available: u64 },
+    /// A command run on the user's behalf failed (exits with its code)
+    CommandFailed { command: String, code: i32 },
+}
+
+impl StashError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StashError::Usage(_) => 2,
+            StashError::NotFound(_) => 3,
+            StashError::Conflict(_) | StashError::CaseCollision { .. } => 4,
+            StashError::Integrity(_) => 5,
+            StashError::NothingMatched(_) => 6,
+            StashError::PendingChanges(_) => 1,
+            StashError::InsufficientSpace { .. } => 7,
+            StashError::CommandFailed { code, .. } => *code,
+        }
+    }
+}
+
+impl fmt::Display for StashError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StashError::Usage(message) => write!(f, "{}", message),
+            StashError::NotFound(identifier) => write!(f, "Entry not found: {}", identifier),
+            StashError::Conflict(path) => write!(
+                f,
+                "Destination {:?} already exists. Use --force to overwrite.",
+                path
+            ),
+            StashError::CaseCollision { root, pairs } => {
+                write!(f, "{:?} ignores case, so these items would overwrite each other:", root)?;
+                for (first, second) in pairs {
+                    write!(f, "\n  {:?} and {:?}", first, second)?;
+                }
+                Ok(())
+            }
+            StashError::Integrity(message) => write!(f, "{}", message),
+            StashError::NothingMatched(message) => write!(f, "{}", message),
+            StashError::PendingChanges(message) => write!(f, "{}", message),
+            StashError::InsufficientSpace { path, needed, available } => write!(
+                f,
+                "Not enough space on {:?}: need {} but only {} is available ({} short)",
+                path,
+                format_size(*needed, SizeStyle::Human),
+                format_size(*available, SizeStyle::Human),
+                format_size(needed.saturating_sub(*available), SizeStyle::Human)
+            ),
+            StashError::CommandFailed { command, code } => {
+                write!(f, "Command `{}` failed with exit code {}", command, code)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StashError {}
+
+/// Resolve the exit code for an error, looking through any added context
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<StashError>())
+        .map(StashError::exit_code)
+        .unwrap_or(1)
+}
@@ -1,3 +1,4 @@
+mod errors;
 mod models;
 mod application;
 mod services;
@@ -13,6 +14,8 @@ fn main() {
     let cli = Cli::parse();
 
     if let Err(err) = cli.run() {
+        let code = errors::exit_code(&err);
+
         #[cfg(debug_assertions)]
         {
             eprintln!("{:?}", style(err).red());
@@ -32,7 +35,7 @@ fn main() {
             );
         }
 
-        std::process::exit(1);
+        std::process::exit(code);
     }
 }
 
@@ -1,4 +1,10 @@
+use serde::de::DeserializeOwned;
+use serde::de::value::{Error as ValueError, StrDeserializer};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Overrides `stash_dir`; read before the rest of the config is loaded
+pub const STASH_DIR_ENV_VAR: &str = "STASH_DIR";
 
 /// Policy for handling name conflicts in the stash
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,27 +32,95 @@ pub enum CompressionLevel {
     Maximum,
 }
 
+/// Order of the items in a new entry's manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// By stashed path only
+    #[default]
+    Unsorted,
+    /// By original path
+    Name,
+    /// Largest first
+    Size,
+    /// Most recently modified first
+    Mtime,
+}
+
+/// Algorithm used for new file hashes. Stored hashes carry their
+/// algorithm as a prefix, so changing this never invalidates old entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    /// Much faster on large files
+    Blake3,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     // Defaults section
     pub clean_days: u64,  // Renamed from clean_after_days
     pub warn_size_mb: u64,
     pub ambiguity_mode: AmbiguityMode,
 
+    // Storage section
+    /// Where the global stash lives instead of `~/.stash`. Move an existing
+    /// stash there with `stash --move-to-stash-dir`.
+    pub stash_dir: Option<PathBuf>,
+    /// Write the index and manifests without indentation. They are read back
+    /// either way.
+    pub compact_json: bool,
+
     // Behavior section
     pub preserve_mtime: bool,
     pub verify_integrity: bool,
     pub follow_symlinks: bool,
+    pub compute_hashes_on_push: bool,
+    pub hash_algorithm: HashAlgorithm,
+    /// Files larger than this are pushed without a hash; 0 hashes everything
+    pub hash_max_size_mb: u64,
+    pub push_workers: usize, // threads used to copy and move files; --jobs overrides
+    /// Buffer for copies the kernel can't do by itself (hashing copies,
+    /// sparse files), in KB; 64 to 65536
+    pub copy_buffer_kb: usize,
+    /// Push as if --ignore-hidden were given; --include-hidden overrides it
+    pub ignore_hidden_by_default: bool,
+    /// Item order in new manifests unless --sort-items-by is given
+    pub sort_items_by: SortOrder,
+    /// Shell command run before every push unless --pre-command is given
+    pub default_pre_push_command: Option<String>,
+    /// Shell command run after every pop unless --post-command is given
+    pub default_post_pop_command: Option<String>,
 
     // Display section
     pub date_format: String,
     pub show_sizes: bool,
 
+    // Maintenance section
+    pub journal_prune_threshold: usize, // 0 disables automatic pruning
+    /// Also prune once the journal file grows past this many KB (0 disables)
+    pub journal_prune_size_kb: u64,
+    /// Also prune once an operation of a deleted entry is this many days old
+    /// (0 disables)
+    pub journal_prune_age_days: u64,
+
     // Future features
     pub compress_entries: bool,
     pub compression_level: CompressionLevel,
 }
 
+/// When pops, dumps and cleans prune the journal: once any limit is passed.
+/// A limit of 0 is never passed.
+#[derive(Debug, Clone, Copy)]
+pub struct JournalPruneLimits {
+    pub operations: usize,
+    pub bytes: u64,
+    pub age_days: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AmbiguityMode {
     Ask,
@@ -60,14 +134,128 @@ impl Default for Config {
             clean_days: 30,
             warn_size_mb: 100,
             ambiguity_mode: AmbiguityMode::Ask,
+            stash_dir: None,
+            compact_json: false,
             preserve_mtime: true,
             verify_integrity: true,
             follow_symlinks: false,
+            compute_hashes_on_push: true,
+            hash_algorithm: HashAlgorithm::Sha256,
+            hash_max_size_mb: 0,
+            push_workers: 4,
+            copy_buffer_kb: 1024,
+            ignore_hidden_by_default: false,
+            sort_items_by: SortOrder::Unsorted,
+            default_pre_push_command: None,
+            default_post_pop_command: None,
             date_format: "%Y-%m-%d %H:%M".to_string(),
             show_sizes: true,
+            journal_prune_threshold: 1000,
+            journal_prune_size_kb: 1024,
+            journal_prune_age_days: 90,
             compress_entries: false,
             compression_level: CompressionLevel::Balanced,
         }
     }
 }
 
+impl Config {
+    /// `hash_max_size_mb` in bytes, or `None` when every file is hashed
+    pub fn hash_max_size(&self) -> Option<u64> {
+        (self.hash_max_size_mb > 0).then(|| self.hash_max_size_mb.saturating_mul(1024 * 1024))
+    }
+
+    /// `copy_buffer_kb` in bytes, between 64 KB (the blocks sparse copies
+    /// look for holes in, so never less is used) and 64 MB, since every
+    /// concurrent copy has a buffer of its own
+    pub fn copy_buffer(&self) -> usize {
+        self.copy_buffer_kb.clamp(64, 64 * 1024) * 1024
+    }
+
+    /// The `journal_prune_*` settings, with the size in bytes
+    pub fn journal_prune_limits(&self) -> JournalPruneLimits {
+        JournalPruneLimits {
+            operations: self.journal_prune_threshold,
+            bytes: self.journal_prune_size_kb.saturating_mul(1024),
+            age_days: self.journal_prune_age_days,
+        }
+    }
+
+    /// Override fields from `STASH_*` environment variables, which take
+    /// precedence over the config file. Malformed values are reported and
+    /// ignored.
+    pub fn apply_env(&mut self) {
+        if let Some(dir) = std::env::var_os(STASH_DIR_ENV_VAR).filter(|d| !d.is_empty()) {
+            self.stash_dir = Some(PathBuf::from(dir));
+        }
+
+        env_override("STASH_CLEAN_DAYS", &mut self.clean_days, parse_number);
+        env_override("STASH_WARN_SIZE_MB", &mut self.warn_size_mb, parse_number);
+        env_override("STASH_COMPRESS", &mut self.compress_entries, parse_bool);
+        env_override("STASH_COMPRESSION_LEVEL", &mut self.compression_level, parse_variant);
+        env_override("STASH_COMPUTE_HASHES", &mut self.compute_hashes_on_push, parse_bool);
+        env_override("STASH_HASH_ALGORITHM", &mut self.hash_algorithm, parse_variant);
+        env_override("STASH_HASH_MAX_SIZE_MB", &mut self.hash_max_size_mb, parse_number);
+        env_override("STASH_VERIFY_INTEGRITY", &mut self.verify_integrity, parse_bool);
+        env_override("STASH_PRESERVE_MTIME", &mut self.preserve_mtime, parse_bool);
+        env_override("STASH_DATE_FORMAT", &mut self.date_format, parse_text);
+        env_override("STASH_SORT_ITEMS_BY", &mut self.sort_items_by, parse_variant);
+        env_override("STASH_PUSH_WORKERS", &mut self.push_workers, parse_number);
+        env_override("STASH_COPY_BUFFER_KB", &mut self.copy_buffer_kb, parse_number);
+        env_override("STASH_COMPACT_JSON", &mut self.compact_json, parse_bool);
+        env_override("STASH_JOURNAL_PRUNE_THRESHOLD", &mut self.journal_prune_threshold, parse_number);
+        env_override("STASH_JOURNAL_PRUNE_SIZE_KB", &mut self.journal_prune_size_kb, parse_number);
+        env_override("STASH_JOURNAL_PRUNE_AGE_DAYS", &mut self.journal_prune_age_days, parse_number);
+    }
+}
+
+fn env_override<T>(name: &str, field: &mut T, parse: fn(&str) -> Result<T, String>) {
+    let Ok(value) = std::env::var(name) else {
+        return;
+    };
+
+    match parse(value.trim()) {
+        Ok(parsed) => *field = parsed,
+        Err(problem) => eprintln!("Warning: ignoring {}={:?}: {}", name, value, problem),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| "expected a non-negative whole number".to_string())
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err("expected true or false".to_string()),
+    }
+}
+
+fn parse_text(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err("expected a non-empty value".to_string());
+    }
+    Ok(value.to_string())
+}
+
+/// Enum values are spelled as in the config file
+fn parse_variant<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    T::deserialize(StrDeserializer::<ValueError>::new(value)).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copy_buffer_is_clamped() {
+        let buffer = |kb| Config { copy_buffer_kb: kb, ..Config::default() }.copy_buffer();
+
+        assert_eq!(buffer(0), 64 * 1024);
+        assert_eq!(buffer(4), 64 * 1024);
+        assert_eq!(buffer(1024), 1024 * 1024);
+        assert_eq!(buffer(64 * 1024), 64 * 1024 * 1024);
+        assert_eq!(buffer(1024 * 1024), 64 * 1024 * 1024);
+    }
+}
@@ -3,7 +3,9 @@ use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
-use crate::models::item::Item;
+use crate::models::item::{Item, ItemKind};
+use crate::models::validation::{ValidationWarning, WarningSeverity};
+use crate::utils::normalize;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Entry {
@@ -11,10 +13,24 @@ pub struct Entry {
     pub name: String,
     pub created: DateTime<Utc>,
     pub updated: DateTime<Utc>,
+    #[serde(with = "crate::utils::serde_path")]
     pub working_directory: PathBuf,
     pub items: Vec<Item>,
     pub total_size_bytes: u64,
+    /// On-disk size of the data archive, once the entry has been compressed
+    #[serde(default)]
+    pub compressed_size_bytes: Option<u64>,
     pub was_destructive: bool,
+    /// Reminder shown when the entry is popped
+    #[serde(default)]
+    pub restore_message: Option<String>,
+    /// Files were hard-linked into the stash, so the originals and the
+    /// stashed copies are the same data until the entry is popped
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hardlinked: bool,
+    /// Free-form notes attached with `--meta`/`--meta-file`; null when unset
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub metadata: serde_json::Value,
 }
 
 impl Entry {
@@ -33,7 +49,11 @@ impl Entry {
             working_directory,
             items,
             total_size_bytes,
+            compressed_size_bytes: None,
             was_destructive,
+            restore_message: None,
+            hardlinked: false,
+            metadata: serde_json::Value::Null,
         }
     }
 
@@ -53,20 +73,97 @@ impl Entry {
         self.uuid.to_string()[..6].to_string()
     }
 
+    /// Name for display and export; unnamed entries show their short UUID
+    pub fn display_name(&self) -> String {
+        if self.name.trim().is_empty() {
+            self.short_id()
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// Whether the metadata has `key` set to `value`. Strings compare as-is;
+    /// other values are compared with `value` parsed as JSON, so `count=3`
+    /// matches `3`
+    pub fn metadata_matches(&self, key: &str, value: &str) -> bool {
+        match self.metadata.get(key) {
+            Some(serde_json::Value::String(s)) => s == value,
+            Some(other) => serde_json::from_str::<serde_json::Value>(value).is_ok_and(|v| v == *other),
+            None => false,
+        }
+    }
+
+    /// Check the manifest against itself: problems that would make a restore
+    /// or an integrity check unreliable, plus a few things worth knowing
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        let mut warnings = Vec::new();
+
+        if self.items.is_empty() {
+            warnings.push(ValidationWarning::new(WarningSeverity::Critical, "the entry has no items"));
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for item in &self.items {
+            if !seen.insert(&item.stashed_path) {
+                warnings.push(ValidationWarning::new(
+                    WarningSeverity::Critical,
+                    format!("more than one item is stored at {:?}", item.stashed_path),
+                ));
+            }
+
+            if item.kind != ItemKind::File {
+                continue;
+            }
+            if item.size_bytes == 0 {
+                warnings.push(ValidationWarning::new(
+                    WarningSeverity::Info,
+                    format!("{:?} is empty", item.original_path),
+                ));
+            }
+            if item.hash.is_none() && !item.hash_skipped {
+                warnings.push(ValidationWarning::new(
+                    WarningSeverity::Warning,
+                    format!("{:?} has no hash, so it can't be verified", item.original_path),
+                ));
+            }
+        }
+
+        if !self.working_directory.exists() {
+            warnings.push(ValidationWarning::new(
+                WarningSeverity::Info,
+                format!("the working directory {:?} no longer exists", self.working_directory),
+            ));
+        }
+
+        warnings
+    }
+
     pub fn contains_path(&self, path: &str) -> bool {
+        let path = normalize::nfc(path);
         self.items.iter().any(|item| {
-            item.original_path.to_string_lossy().contains(path)
+            normalize::nfc(&item.original_path.to_string_lossy()).contains(&path)
         })
     }
 
+    /// The item stashed from `original_path`, ignoring Unicode normalization
     pub fn get_item(&self, original_path: &Path) -> Option<&Item> {
-        self.items.iter().find(|item| item.original_path == original_path)
+        self.items.iter().find(|item| normalize::same_path(&item.original_path, original_path))
+    }
+
+    /// Original paths of every item, as cached in the index
+    pub fn item_paths(&self) -> Vec<PathBuf> {
+        self.items.iter().map(|item| item.original_path.clone()).collect()
     }
 
     pub fn file_count(&self) -> usize {
         self.items.len()
     }
 
+    /// Size of the compressed data archive, or `None` if stored uncompressed
+    pub fn total_compressed_size_bytes(&self) -> Option<u64> {
+        self.compressed_size_bytes
+    }
+
     pub fn recalculate_size(&mut self) {
         self.total_size_bytes = self.items.iter().map(|i| i.size_bytes).sum();
         self.touch();
@@ -1,14 +1,65 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use uuid::Uuid;
+use crate::utils::normalize;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EntryMetadata {
     pub uuid: Uuid,
     pub name: String,
     pub created: DateTime<Utc>,
+    #[serde(default)]
+    pub updated: Option<DateTime<Utc>>,
     pub total_size_bytes: u64,
+    #[serde(default)]
+    pub compressed_size_bytes: Option<u64>,
     pub item_count: usize,
+    /// Deleting the entry's data failed part-way; reported by `--health`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub removal_failed: bool,
+    /// Original paths of the entry's items, copied from the manifest so path
+    /// queries only open the manifests that can match. Missing in indexes
+    /// written before the cache existed; `stash --init --force` rebuilds it.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::utils::serde_path::option_vec")]
+    pub item_paths: Option<Vec<PathBuf>>,
+    /// Marked with `--mark-template`: `--template` pushes its items again
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_template: bool,
+    /// Set on entries pushed with `--version-tag`: the entry is this version
+    /// of its name, and the name alone resolves to the highest one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+}
+
+impl EntryMetadata {
+    /// Name for display; unnamed entries show their short UUID
+    pub fn display_name(&self) -> String {
+        if self.name.trim().is_empty() {
+            self.uuid.to_string()[..6].to_string()
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// The cached item paths, unless they are missing or plainly out of
+    /// date, in which case the manifest has to be read instead
+    pub fn cached_paths(&self) -> Option<&[PathBuf]> {
+        self.item_paths.as_deref().filter(|paths| paths.len() == self.item_count)
+    }
+
+    /// When the metadata last changed, falling back to creation time
+    pub fn last_updated(&self) -> DateTime<Utc> {
+        self.updated.unwrap_or(self.created)
+    }
+}
+
+/// A UUID present in both indexes with differing metadata
+#[derive(Debug, Clone)]
+pub struct MergeConflict {
+    pub uuid: Uuid,
+    pub kept: EntryMetadata,
+    pub discarded: EntryMetadata,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,19 +94,33 @@ impl Index {
         }
     }
 
-    pub fn add_entry(&mut self, uuid: Uuid, name: String, size: u64, item_count: usize) {
+    pub fn add_entry(&mut self, uuid: Uuid, name: String, size: u64, item_paths: Vec<PathBuf>) {
         let metadata = EntryMetadata {
             uuid,
             name,
             created: Utc::now(),
+            updated: Some(Utc::now()),
             total_size_bytes: size,
-            item_count,
+            compressed_size_bytes: None,
+            item_count: item_paths.len(),
+            removal_failed: false,
+            item_paths: Some(item_paths),
+            is_template: false,
+            version: None,
         };
         self.total_size_bytes += size;
         self.entries.push(metadata);
         self.touch();
     }
 
+    /// Insert existing metadata (e.g. from an import), keeping stack order
+    pub fn insert_entry(&mut self, metadata: EntryMetadata) {
+        self.total_size_bytes += metadata.total_size_bytes;
+        self.entries.push(metadata);
+        self.entries.sort_by_key(|e| e.created);
+        self.touch();
+    }
+
     pub fn remove_entry(&mut self, uuid: &Uuid) -> Option<EntryMetadata> {
         let pos = self.entries.iter().position(|e| &e.uuid == uuid)?;
         let entry = self.entries.remove(pos);
@@ -68,8 +133,42 @@ impl Index {
         self.entries.iter().find(|e| &e.uuid == uuid)
     }
 
+    /// The entry with this name; of several versions, the latest
     pub fn find_by_name(&self, name: &str) -> Option<&EntryMetadata> {
-        self.entries.iter().find(|e| e.name == name)
+        let mut named = self.entries.iter().filter(|e| normalize::same_text(&e.name, name));
+        let first = named.next()?;
+        Some(named.fold(first, |latest, e| if e.version > latest.version { e } else { latest }))
+    }
+
+    /// Every entry with this name, lowest version first; entries without a
+    /// version come first, in stack order
+    pub fn versions_of(&self, name: &str) -> Vec<&EntryMetadata> {
+        let mut versions: Vec<_> = self.entries.iter().filter(|e| normalize::same_text(&e.name, name)).collect();
+        versions.sort_by_key(|e| e.version);
+        versions
+    }
+
+    /// A specific version of a name
+    pub fn find_version(&self, name: &str, version: u32) -> Option<&EntryMetadata> {
+        self.versions_of(name).into_iter().find(|e| e.version == Some(version))
+    }
+
+    /// Number the entry as the next version of its name. Earlier entries
+    /// with the name that have no version yet are numbered first, oldest
+    /// first, so the name's history stays in order.
+    pub fn assign_version(&mut self, uuid: &Uuid) -> Option<u32> {
+        let name = self.get_metadata(uuid)?.name.clone();
+        let mut next = self.versions_of(&name).iter().filter_map(|e| e.version).max().unwrap_or(0);
+
+        for entry in self.entries.iter_mut().filter(|e| normalize::same_text(&e.name, &name)) {
+            if entry.version.is_none() || &entry.uuid == uuid {
+                next += 1;
+                entry.version = Some(next);
+            }
+        }
+
+        self.touch();
+        self.get_metadata(uuid)?.version
     }
 
     pub fn find_by_identifier(&self, identifier: &str) -> Option<&EntryMetadata> {
@@ -84,31 +183,80 @@ impl Index {
     }
 
     pub fn search(&self, pattern: &str) -> Vec<&EntryMetadata> {
-        let pattern_lower = pattern.to_lowercase();
+        let pattern_lower = normalize::nfc(pattern).to_lowercase();
         self.entries
             .iter()
             .filter(|e| {
-                e.name.to_lowercase().contains(&pattern_lower)
+                normalize::nfc(&e.name).to_lowercase().contains(&pattern_lower)
                     || e.uuid.to_string().starts_with(&pattern_lower)
             })
             .collect()
     }
 
-    pub fn remove_older_than_days(&mut self, days: i64) -> Vec<Uuid> {
-        let cutoff = Utc::now() - chrono::Duration::days(days);
-        let (old, keep): (Vec<_>, Vec<_>) = self
+    /// Entries created before `until` and, if given, at or after `since`:
+    /// the ones `clean` removes
+    pub fn created_between(&self, since: Option<DateTime<Utc>>, until: DateTime<Utc>) -> Vec<&EntryMetadata> {
+        self.entries
+            .iter()
+            .filter(|e| e.created < until && since.is_none_or(|since| e.created >= since))
+            .collect()
+    }
+
+    /// Entries beyond the `n` most recently created, newest first. With
+    /// `among`, only those entries are ranked; the rest are never returned.
+    pub fn all_except_latest(&self, n: usize, among: Option<&[Uuid]>) -> Vec<&EntryMetadata> {
+        let mut entries: Vec<_> = self
             .entries
-            .drain(..)
-            .partition(|e| e.created < cutoff);
+            .iter()
+            .filter(|e| among.is_none_or(|among| among.contains(&e.uuid)))
+            .collect();
+        entries.sort_by_key(|e| std::cmp::Reverse(e.created));
+        entries.split_off(n.min(entries.len()))
+    }
 
-        self.entries = keep;
+    pub fn remove_entries(&mut self, uuids: &[Uuid]) {
+        let before = self.entries.len();
+        self.entries.retain(|e| !uuids.contains(&e.uuid));
         self.total_size_bytes = self.entries.iter().map(|e| e.total_size_bytes).sum();
 
-        if !old.is_empty() {
+        if self.entries.len() != before {
             self.touch();
         }
+    }
+
+    /// Union another index into this one, keyed by UUID.
+    /// When both sides hold different metadata for the same entry, the more
+    /// recently updated copy wins and the conflict is reported.
+    pub fn merge(&mut self, other: &Index) -> Vec<MergeConflict> {
+        let mut conflicts = Vec::new();
+
+        for incoming in &other.entries {
+            match self.entries.iter_mut().find(|e| e.uuid == incoming.uuid) {
+                None => self.entries.push(incoming.clone()),
+                Some(existing) if existing == incoming => {}
+                Some(existing) => {
+                    let (kept, discarded) = if incoming.last_updated() > existing.last_updated() {
+                        let discarded = std::mem::replace(existing, incoming.clone());
+                        (incoming.clone(), discarded)
+                    } else {
+                        (existing.clone(), incoming.clone())
+                    };
+
+                    conflicts.push(MergeConflict {
+                        uuid: incoming.uuid,
+                        kept,
+                        discarded,
+                    });
+                }
+            }
+        }
+
+        // Keep stack order: the most recent entry stays last
+        self.entries.sort_by_key(|e| e.created);
+        self.total_size_bytes = self.entries.iter().map(|e| e.total_size_bytes).sum();
+        self.touch();
 
-        old.into_iter().map(|e| e.uuid).collect()
+        conflicts
     }
 
     pub fn most_recent(&self) -> Option<&EntryMetadata> {
@@ -127,3 +275,18 @@ impl Index {
         self.updated = Utc::now();
     }
 }
+
+/// Entries created before this are older than `days`
+pub fn clean_cutoff(days: i64) -> DateTime<Utc> {
+    Utc::now() - chrono::Duration::days(days)
+}
+
+/// Where a clean window ends: at `until`, now when only `since` is given,
+/// and otherwise `days` ago
+pub fn clean_window_end(days: i64, since: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>) -> DateTime<Utc> {
+    match (since, until) {
+        (_, Some(until)) => until,
+        (Some(_), None) => Utc::now(),
+        (None, None) => clean_cutoff(days),
+    }
+}
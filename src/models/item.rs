@@ -2,7 +2,9 @@ use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
+use crate::models::config::HashAlgorithm;
+use crate::utils::hashing;
 
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
@@ -16,13 +18,32 @@ pub enum ItemKind {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Item {
+    #[serde(with = "crate::utils::serde_path")]
     pub original_path: PathBuf,
+    #[serde(with = "crate::utils::serde_path")]
     pub stashed_path: PathBuf,
     pub kind: ItemKind,
     pub size_bytes: u64,
     pub permissions: u32,
     pub modified: DateTime<Utc>,
     pub hash: Option<String>,
+    #[serde(default)]
+    pub hash_verified_at: Option<DateTime<Utc>>,
+    /// The file was over `hash_max_size_mb` when pushed, so `hash` was left
+    /// empty on purpose rather than because hashing was turned off
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hash_skipped: bool,
+    /// Where a symlink item points; its size is the length of this path
+    #[serde(default, with = "crate::utils::serde_path::option")]
+    pub link_target: Option<PathBuf>,
+    /// Named pipes, sockets and device files found inside a directory item.
+    /// Their contents can't be stashed, so they are never copied or restored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "crate::utils::serde_path::vec")]
+    pub special_files: Vec<PathBuf>,
+    /// Hidden files and directories inside a directory item that
+    /// `--ignore-hidden` left where they were
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "crate::utils::serde_path::vec")]
+    pub left_in_place: Vec<PathBuf>,
 }
 
 impl Item {
@@ -43,9 +64,19 @@ impl Item {
             permissions,
             modified,
             hash,
+            hash_verified_at: None,
+            hash_skipped: false,
+            link_target: None,
+            special_files: Vec::new(),
+            left_in_place: Vec::new(),
         }
     }
 
+    /// Algorithm the stored hash was made with, if there is one
+    pub fn hash_algorithm(&self) -> Option<HashAlgorithm> {
+        self.hash.as_deref().map(hashing::algorithm_of)
+    }
+
     pub fn matches_pattern(&self, pattern: &str) -> bool {
         self.original_path
             .to_string_lossy()
@@ -76,12 +107,21 @@ impl Item {
         #[cfg(windows)]
         let permissions = 0;
 
-        let size_bytes = metadata.len();
+        let link_target = if kind == ItemKind::Symlink {
+            Some(fs::read_link(&original_path)?)
+        } else {
+            None
+        };
+
+        let size_bytes = match &link_target {
+            Some(target) => target.as_os_str().len() as u64,
+            None => metadata.len(),
+        };
 
         let modified = metadata.modified()?.into();
 
         let hash = if calculate_hash && kind == ItemKind::File {
-            Some(calculate_file_hash(&original_path)?)
+            Some(hashing::calculate_hash(&original_path, HashAlgorithm::default())?)
         } else {
             None
         };
@@ -94,24 +134,11 @@ impl Item {
             permissions,
             modified,
             hash,
+            hash_verified_at: None,
+            hash_skipped: false,
+            link_target,
+            special_files: Vec::new(),
+            left_in_place: Vec::new(),
         })
     }
 }
-
-fn calculate_file_hash(path: &Path) -> std::io::Result<String> {
-    use sha2::{Sha256, Digest};
-    use std::fs::File;
-    use std::io::Read;
-
-    let mut file = File::open(path)?;
-    let mut hasher = Sha256::new();
-    let mut buffer = [0u8; 8192];
-
-    loop {
-        let n = file.read(&mut buffer)?;
-        if n == 0 { break; }
-        hasher.update(&buffer[..n]);
-    }
-
-    Ok(format!("sha256:{:x}", hasher.finalize()))
-}
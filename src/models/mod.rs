@@ -3,6 +3,7 @@ pub mod item;
 pub mod operation;
 pub mod index;
 pub mod config;
+pub mod validation;
 
 pub use index::Index;
 pub use index::EntryMetadata;
@@ -10,3 +11,4 @@ pub use index::EntryMetadata;
 pub use operation::OperationKind;
 pub use operation::Operation;
 pub use config::Config;
+pub use validation::{ValidationWarning, WarningSeverity};
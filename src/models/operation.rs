@@ -5,14 +5,60 @@ use uuid::Uuid;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum OperationKind {
-    Push { entry_id: Uuid, file_count: usize },
-    Copy { entry_id: Uuid, file_count: usize },
-    Pop { entry_id: Uuid, destination: PathBuf },
-    Peek { entry_id: Uuid, destination: PathBuf },
+    Push {
+        entry_id: Uuid,
+        file_count: usize,
+        /// Entry name and bytes stashed, for `--history --diff`; missing
+        /// from journals written before they were recorded
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size_bytes: Option<u64>,
+    },
+    Copy {
+        entry_id: Uuid,
+        file_count: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size_bytes: Option<u64>,
+    },
+    Pop {
+        entry_id: Uuid,
+        #[serde(with = "crate::utils::serde_path")]
+        destination: PathBuf,
+        /// Items left in the entry after a partial pop (`--only`,
+        /// `--missing-ok`); missing when the whole entry was popped
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kept: Option<usize>,
+    },
+    Peek {
+        entry_id: Uuid,
+        #[serde(with = "crate::utils::serde_path")]
+        destination: PathBuf,
+    },
     Drop { entry_id: Uuid, deleted: bool },
     Dump { entry_count: usize, deleted: bool },
     Rename { entry_id: Uuid, old_name: String, new_name: String },
-    Clean { removed_count: usize, days: i64 },
+    Clean {
+        removed_count: usize,
+        days: i64,
+        /// The window given with --since/--until; missing for a plain
+        /// older-than-`days` clean
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        since: Option<DateTime<Utc>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        until: Option<DateTime<Utc>>,
+        /// With --keep-latest, the number of entries kept instead of a window
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        keep_latest: Option<usize>,
+    },
+    Import { entry_id: Uuid, name: String },
+    Edit {
+        entry_id: Uuid,
+        #[serde(with = "crate::utils::serde_path")]
+        path: PathBuf,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,13 +79,21 @@ impl Operation {
 
     pub fn describe(&self) -> String {
         match &self.kind {
-            OperationKind::Push { entry_id, file_count } => {
+            OperationKind::Push { entry_id, file_count, .. } => {
                 format!("Pushed {} file(s) to entry {}", file_count, short_uuid(entry_id))
             }
-            OperationKind::Copy { entry_id, file_count } => {
+            OperationKind::Copy { entry_id, file_count, .. } => {
                 format!("Copied {} file(s) to entry {}", file_count, short_uuid(entry_id))
             }
-            OperationKind::Pop { entry_id, destination } => {
+            OperationKind::Pop { entry_id, destination, kept: Some(kept) } => {
+                format!(
+                    "Popped part of entry {} to {} ({} item(s) left)",
+                    short_uuid(entry_id),
+                    destination.display(),
+                    kept
+                )
+            }
+            OperationKind::Pop { entry_id, destination, kept: None } => {
                 format!("Popped entry {} to {}", short_uuid(entry_id), destination.display())
             }
             OperationKind::Peek { entry_id, destination } => {
@@ -62,23 +116,31 @@ impl Operation {
             OperationKind::Rename { entry_id, old_name, new_name } => {
                 format!("Renamed entry {} from '{}' to '{}'", short_uuid(entry_id), old_name, new_name)
             }
-            OperationKind::Clean { removed_count, days } => {
-                format!("Cleaned {} entries older than {} days", removed_count, days)
+            OperationKind::Clean { removed_count, days, since, until, keep_latest } => {
+                format!("Cleaned {} entries {}", removed_count, clean_window(*days, *since, *until, *keep_latest))
+            }
+            OperationKind::Import { entry_id, name } => {
+                format!("Imported entry {} as '{}'", short_uuid(entry_id), name)
+            }
+            OperationKind::Edit { entry_id, path } => {
+                format!("Edited {} in entry {}", path.display(), short_uuid(entry_id))
             }
         }
     }
 
+    /// Undoing a `Push` restores the moved originals; undoing a `Copy`
+    /// only drops the entry, since the originals were never removed.
     pub fn is_undoable(&self) -> bool {
         matches!(
             self.kind,
             OperationKind::Push { .. }
+                | OperationKind::Copy { .. }
                 | OperationKind::Pop { .. }
                 | OperationKind::Drop { deleted: false, .. }
                 | OperationKind::Rename { .. }
         )
     }
 
-
     pub fn involves_entry(&self, entry_id: &Uuid) -> bool {
         self.entry_id() == Some(*entry_id)
     }
@@ -90,7 +152,9 @@ impl Operation {
             | OperationKind::Pop { entry_id, .. }
             | OperationKind::Peek { entry_id, .. }
             | OperationKind::Drop { entry_id, .. }
-            | OperationKind::Rename { entry_id, .. } => Some(*entry_id),
+            | OperationKind::Rename { entry_id, .. }
+            | OperationKind::Import { entry_id, .. }
+            | OperationKind::Edit { entry_id, .. } => Some(*entry_id),
             _ => None,
         }
     }
@@ -99,3 +163,25 @@ impl Operation {
 fn short_uuid(uuid: &Uuid) -> String {
     uuid.to_string()[..6].to_string()
 }
+
+/// The entries a clean covers, e.g. "older than 30 days", "created between
+/// 2024-01-01 00:00 and 2024-03-01 00:00" or "beyond the latest 5"
+pub fn clean_window(
+    days: i64,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    keep_latest: Option<usize>,
+) -> String {
+    let date = |time: DateTime<Utc>| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string();
+
+    if let Some(n) = keep_latest {
+        return format!("beyond the latest {}", n);
+    }
+
+    match (since, until) {
+        (None, None) => format!("older than {} days", days),
+        (Some(since), None) => format!("created since {}", date(since)),
+        (None, Some(until)) => format!("created before {}", date(until)),
+        (Some(since), Some(until)) => format!("created between {} and {}", date(since), date(until)),
+    }
+}
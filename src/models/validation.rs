@@ -0,0 +1,42 @@
+use std::fmt;
+
+/// How serious a manifest problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WarningSeverity {
+    /// Worth knowing, but nothing is wrong with the entry
+    Info,
+    /// Something a check or restore can't rely on
+    Warning,
+    /// The manifest contradicts itself; restoring it may lose data
+    Critical,
+}
+
+impl fmt::Display for WarningSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            WarningSeverity::Info => "info",
+            WarningSeverity::Warning => "warning",
+            WarningSeverity::Critical => "critical",
+        };
+        f.write_str(label)
+    }
+}
+
+/// One problem `Entry::validate` found in a manifest
+#[derive(Debug, Clone)]
+pub struct ValidationWarning {
+    pub severity: WarningSeverity,
+    pub message: String,
+}
+
+impl ValidationWarning {
+    pub fn new(severity: WarningSeverity, message: impl Into<String>) -> Self {
+        Self { severity, message: message.into() }
+    }
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
@@ -1,32 +1,174 @@
 use anyhow::{Result, Context, anyhow};
 use chrono::{Utc, DateTime};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
-use sha2::{Sha256, Digest};
-use std::io::Read;
-use crate::models::{Operation, OperationKind};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crate::errors::StashError;
+use crate::models::{EntryMetadata, Operation, OperationKind, ValidationWarning, WarningSeverity};
 use crate::models::entry::Entry;
 use crate::models::item::{Item, ItemKind};
+use crate::models::index;
 use crate::services::storage::index_storage::IndexStorage;
 use crate::services::storage::journal_storage::JournalStorage;
-use crate::services::filesystem::permission_handler;
+use crate::services::storage;
+use crate::services::filesystem::file_compression::{self, CompressionLevel};
+use crate::services::filesystem::{permission_handler, reflink, sparse, walker};
+use crate::services::filesystem::walker::WalkEntry;
+use crate::models::config::{HashAlgorithm, JournalPruneLimits, SortOrder};
+use crate::utils::{display, hashing, normalize, paths, shell};
 
 pub struct EntryManager<'a> {
     entries_root: &'a PathBuf,
     index_storage: &'a mut IndexStorage,
     journal_storage: &'a mut JournalStorage,
+    /// Clone files with reflinks where the filesystem supports it
+    reflink: bool,
+    /// Files cloned with reflinks so far
+    reflinked: AtomicUsize,
+    /// Buffer size for copies that go through user space
+    copy_buffer: usize,
+    /// Threads copying the files of one tree at a time
+    workers: usize,
+    /// Data moved by the last push or pop
+    last_transfer: Option<Transfer>,
+    /// What `load_entry` does with problems `Entry::validate` finds
+    manifest_checks: ManifestChecks,
+    /// Entries whose manifest problems have already been reported
+    reported: Mutex<HashSet<Uuid>>,
+    /// Check the destination has room before a pop restores anything
+    verify_dest: bool,
+}
+
+/// What `load_entry` does with the problems `Entry::validate` finds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestChecks {
+    /// Don't validate manifests
+    Off,
+    /// Report warnings and critical problems on stderr, once per entry
+    Warn,
+    /// Refuse to load a manifest with warnings or critical problems (`--strict`)
+    Strict,
+}
+
+impl ManifestChecks {
+    /// Checks for `verify_integrity`, made fatal by `--strict`
+    pub fn from_flags(verify_integrity: bool, strict: bool) -> Self {
+        match (verify_integrity, strict) {
+            (_, true) => ManifestChecks::Strict,
+            (true, false) => ManifestChecks::Warn,
+            (false, false) => ManifestChecks::Off,
+        }
+    }
+}
+
+/// Bytes a push or pop moved and how long the filesystem work took
+#[derive(Debug, Clone, Copy)]
+pub struct Transfer {
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// Free space a push leaves on the stash's filesystem, at least; larger
+/// pushes keep 5% of their size free on top
+const PUSH_SPACE_MARGIN: u64 = 16 * 1024 * 1024;
+
+/// What `clean_old_entries` did
+#[derive(Default)]
+pub struct CleanOutcome {
+    pub removed: Vec<Uuid>,
+    /// Entries left in the index (flagged) because their data couldn't be removed
+    pub failed: Vec<(Uuid, anyhow::Error)>,
 }
 
 pub struct PushOptions<'a> {
-    pub name: &'a String,
+    /// Without a name the entry is named after its first item
+    pub name: &'a Option<String>,
     pub copy: &'a bool,
+    /// Hard-link files into the stash instead of copying them, leaving the
+    /// originals in place
+    pub hardlink: &'a bool,
+    /// With `hardlink`, copy items on another filesystem instead of failing
+    pub hardlink_fallback: &'a bool,
+    pub hash: &'a bool,
+    /// Maximum threads used to copy items (copy mode only)
+    pub workers: &'a usize,
+    /// Algorithm for new hashes when `hash` is set
+    pub hash_algorithm: &'a HashAlgorithm,
+    /// Files over this many bytes are left unhashed
+    pub hash_max_size: &'a Option<u64>,
+    /// Check items moved across filesystems against their hashes, not just
+    /// their sizes (`verify_integrity`)
+    pub verify: &'a bool,
+    /// Pack `data/` into a single archive after stashing
+    pub compression: &'a Option<CompressionLevel>,
+    /// Fail on special files instead of skipping them with a warning
+    pub strict: &'a bool,
+    /// Order of the items in the manifest
+    pub sort_items: &'a SortOrder,
+    /// Leave dotfiles and dot-directories out, at the top level and inside
+    /// directories
+    pub ignore_hidden: &'a bool,
+    /// Skip items modified more recently than this
+    pub min_age: &'a Option<chrono::Duration>,
+    /// Skip items modified longer ago than this
+    pub max_age: &'a Option<chrono::Duration>,
+    /// Skip the free-space check, for filesystems that misreport it
+    pub force: &'a bool,
+    /// Reminder shown when the entry is popped
+    pub restore_message: &'a Option<String>,
+    /// Stored on the entry as-is
+    pub metadata: &'a serde_json::Value,
+    /// Paths to record for the items instead of the ones they are read
+    /// from, for content that has no real location of its own
+    pub recorded_paths: Option<&'a [PathBuf]>,
+    /// Number the entry as the next version of its name, instead of leaving
+    /// entries that share a name apart only by UUID
+    pub versioned: &'a bool,
+    /// With `versioned`, delete the oldest versions beyond this many
+    pub max_versions: &'a Option<usize>,
+    /// Shell command run in the working directory before anything is
+    /// stashed; if it fails nothing is
+    pub pre_command: &'a Option<String>,
+    /// Log the pre-command and its exit code
+    pub verbose: &'a bool,
 }
 
+#[derive(Clone, Copy)]
 pub struct PopOptions<'a> {
     pub destination: &'a PathBuf,
     pub copy: &'a bool,
     pub force: &'a bool,
+    /// Skip items whose destination directory no longer exists instead of
+    /// recreating it; skipped items stay in the stash
+    pub missing_ok: &'a bool,
+    /// Set each item's recorded modification time (`preserve_mtime`)
+    pub preserve_mtime: &'a bool,
+    /// Check restored files against their recorded hashes before any of
+    /// them is put in place (`verify_integrity`)
+    pub verify: &'a bool,
+    /// Restore only items whose stashed path matches one of these; the
+    /// rest stay in the stash. Empty restores everything.
+    pub only: &'a [glob::Pattern],
+    /// Shell command for `run_post_command` to run once the pop is done
+    pub post_command: &'a Option<String>,
+}
+
+/// Outcome of re-hashing a single stashed item
+pub enum HashStatus {
+    /// Stored hash matches the stash data
+    Ok,
+    /// Stored hash differs from the stash data
+    Mismatch { expected: String, actual: String },
+    /// The item's data file is missing from the stash
+    Missing,
+    /// No hash was stored for the item
+    Unhashed,
+    /// The file was too large to hash when it was pushed
+    TooLarge,
 }
 
 impl<'a> EntryManager<'a> {
@@ -40,12 +182,55 @@ impl<'a> EntryManager<'a> {
             entries_root,
             index_storage,
             journal_storage,
+            reflink: true,
+            reflinked: AtomicUsize::new(0),
+            copy_buffer: sparse::DEFAULT_BUFFER,
+            workers: 1,
+            last_transfer: None,
+            manifest_checks: ManifestChecks::Off,
+            reported: Mutex::new(HashSet::new()),
+            verify_dest: false,
         })
     }
 
+    /// Always copy bytes, even where files could be cloned (`--no-reflink`)
+    pub fn set_reflink(&mut self, enabled: bool) {
+        self.reflink = enabled;
+    }
+
+    /// Buffer size for copies the kernel can't do by itself (`copy_buffer_kb`)
+    pub fn set_copy_buffer(&mut self, bytes: usize) {
+        self.copy_buffer = bytes;
+    }
+
+    /// Refuse pops whose destination lacks the room they need (`--verify-dest`)
+    pub fn set_verify_dest(&mut self, enabled: bool) {
+        self.verify_dest = enabled;
+    }
+
+    /// Threads used to copy the files of a tree (`--jobs`, `push_workers`)
+    pub fn set_workers(&mut self, workers: usize) {
+        self.workers = workers.max(1);
+    }
+
+    /// Validate manifests as they are loaded
+    pub fn set_manifest_checks(&mut self, checks: ManifestChecks) {
+        self.manifest_checks = checks;
+    }
+
+    /// How many files have been cloned with reflinks instead of copied
+    pub fn reflinked_files(&self) -> usize {
+        self.reflinked.load(Ordering::Relaxed)
+    }
+
+    /// Size and duration of the last push or pop
+    pub fn last_transfer(&self) -> Option<Transfer> {
+        self.last_transfer
+    }
+
     pub fn create_entry(
         &mut self,
-        paths: &Vec<PathBuf>,
+        paths: &[PathBuf],
         options: PushOptions,
         working_directory: &Path,
     ) -> Result<Entry> {
@@ -53,404 +238,1883 @@ impl<'a> EntryManager<'a> {
             return Err(anyhow!("No paths provided"));
         }
 
-        let mut items = Vec::new();
-        let mut total_size = 0u64;
-
-        for path in paths {
-            let metadata = fs::symlink_metadata(path)
-                .with_context(|| format!("Failed to read {:?}", path))?;
+        if let Some(command) = options.pre_command {
+            // Its output goes to stderr, where it can't mix with porcelain output
+            shell::run(command, working_directory, true, *options.verbose)
+                .context("Pre-push command failed; nothing was stashed")?;
+        }
 
-            let kind = if metadata.is_dir() {
-                ItemKind::Directory
-            } else if metadata.file_type().is_symlink() {
-                ItemKind::Symlink
-            } else {
-                ItemKind::File
-            };
+        // Paths like `.` have no file name; such entries display as their short UUID.
+        // Odd bytes and control characters are escaped so the name stays one line.
+        let name = options.name.clone().unwrap_or_else(|| {
+            paths[0]
+                .file_name()
+                .map(|name| display::escape_path(Path::new(name)))
+                .unwrap_or_default()
+        });
+
+        let selected: Vec<PathBuf>;
+        let paths = if options.recorded_paths.is_none() {
+            selected = self.select_paths(paths, &options)?;
+            &selected[..]
+        } else {
+            paths
+        };
 
-            // Calculate actual size including directory contents
-            let size = self.calculate_size(path)?;
-            total_size += size;
+        // Hard links only work within one filesystem
+        let linked = if *options.hardlink {
+            self.plan_links(paths, *options.hardlink_fallback)?
+        } else {
+            vec![false; paths.len()]
+        };
+        let keep_originals = *options.copy || *options.hardlink;
+
+        // Independent items are stashed in parallel, and the files inside a
+        // copied tree share what is left of the pool
+        let workers = (*options.workers).max(1);
+        let file_workers = (workers / workers.min(paths.len())).max(1);
+        let this = &*self;
+        let hash = options.hash.then_some(*options.hash_algorithm);
+
+        // Each path is walked once. Copies replay that walk and hash files
+        // as they stream through, so their data is read only once.
+        let sources: Vec<_> = paths.iter().zip(&linked).collect();
+        let staged = parallel_map(&sources, workers, |(path, link)| {
+            let copied = keep_originals && !**link;
+            let survey = survey(path, *options.ignore_hidden, copied || *options.ignore_hidden)?;
+            let too_large = hash.is_some()
+                && survey.metadata.is_file()
+                && options.hash_max_size.is_some_and(|max| survey.size > max);
+            Ok(Staged {
+                path: path.to_path_buf(),
+                survey,
+                link: **link,
+                hash_on_copy: hash.filter(|_| copied && !too_large),
+                hash_skipped: too_large,
+            })
+        })?;
+
+        // Before hashing, which alone could take a long time for a push that can't fit
+        if !*options.force {
+            self.check_push_space(&staged, *options.copy)?;
+        }
 
-            // Preserve original modified time
-            let modified = metadata.modified()
-                .ok()
-                .and_then(|t| DateTime::from_timestamp(
-                    t.duration_since(std::time::UNIX_EPOCH).ok()?.as_secs() as i64, 0
-                ))
-                .unwrap_or_else(Utc::now);
+        let mut items = parallel_map(&staged, workers, |staged| {
+            let hash = hash.filter(|_| staged.hash_on_copy.is_none() && !staged.hash_skipped);
+            this.describe_item(staged, hash, *options.strict)
+        })?;
 
-            // Calculate hash for files
-            let hash = if metadata.is_file() {
-                Some(self.calculate_hash(path)?)
-            } else {
-                None
-            };
+        if let Some(recorded) = options.recorded_paths {
+            for (item, path) in items.iter_mut().zip(recorded) {
+                item.original_path = path.clone();
+                item.stashed_path = paths::portable_path(path);
+            }
+        }
+        let total_size = items.iter().map(|i| i.size_bytes).sum();
+
+        // Distinct paths can still share a stashed location (e.g. `/a` and `root/a`)
+        let mut seen = HashSet::new();
+        if let Some(clash) = items.iter().find(|item| !seen.insert(&item.stashed_path)) {
+            return Err(StashError::Usage(format!(
+                "{:?} would be stored in the same place as another item; push it separately",
+                clash.original_path
+            ))
+            .into());
+        }
 
-            items.push(Item {
-                original_path: path.clone(),
-                stashed_path: path.clone(),
-                kind,
-                size_bytes: size,
-                permissions: permission_handler::get_permissions(path)?,
-                modified,
-                hash,
-            });
+        // A stash on a filesystem that ignores case can't keep `Readme.md`
+        // and `README.md` apart
+        if normalize::is_case_insensitive(self.entries_root) {
+            let pairs = normalize::case_collisions(items.iter().map(|item| item.stashed_path.as_path()));
+            if !pairs.is_empty() {
+                return Err(StashError::CaseCollision { root: self.entries_root.clone(), pairs }.into());
+            }
+            if !normalize::is_case_insensitive(working_directory) {
+                eprintln!(
+                    "Warning: the stash at {:?} ignores case; files inside pushed directories whose \
+                     names differ only in case will overwrite each other",
+                    self.entries_root
+                );
+            }
         }
 
-        let entry = Entry::new(
-            options.name.clone(),
+        let mut entry = Entry::new(
+            name,
             items,
             working_directory.to_path_buf(),
-            !options.copy,
+            !keep_originals,
         );
+        entry.restore_message = options.restore_message.clone();
+        entry.metadata = options.metadata.clone();
+        entry.hardlinked = linked.contains(&true);
 
         let entry_dir = self.entry_dir(&entry.uuid);
         let data_dir = entry_dir.join("data");
         fs::create_dir_all(&data_dir)?;
 
-        // Move/copy files to stash
-        for item in &entry.items {
-            let src = &item.original_path;
-            let dest = data_dir.join(&item.stashed_path);
-
-            if let Some(parent) = dest.parent() {
-                fs::create_dir_all(parent)?;
+        // Move/copy files to stash, putting back anything already moved if
+        // an item fails so the push is all or nothing
+        let sources: Vec<_> = staged.iter().zip(&entry.items).collect();
+        let started = Instant::now();
+        let hashes = match parallel_map(&sources, workers, |(staged, item)| {
+            this.stash_item(staged, item, &data_dir, keep_originals, *options.verify, file_workers)
+        }) {
+            Ok(hashes) => hashes,
+            Err(err) => {
+                if keep_originals || self.unstash_items(&entry.items, &data_dir) {
+                    let _ = fs::remove_dir_all(&entry_dir);
+                } else {
+                    eprintln!("Warning: files that could not be returned remain in {:?}", data_dir);
+                }
+                return Err(err);
             }
+        };
+        self.last_transfer = Some(Transfer { bytes: total_size, elapsed: started.elapsed() });
 
-            if *options.copy {
-                // Copy mode: leave originals in place
-                self.copy_recursively(src, &dest)?;
-            } else {
-                // Move mode: relocate to stash
-                self.move_recursively(src, &dest)?;
+        for (item, hash) in entry.items.iter_mut().zip(hashes) {
+            if hash.is_some() {
+                item.hash = hash;
             }
-
-            // Preserve timestamps
-            self.preserve_timestamps(src, &dest)?;
         }
 
+        sort_items(&mut entry.items, *options.sort_items);
         self.write_manifest(&entry)?;
 
         self.index_storage.add_entry(
             entry.uuid,
             entry.name.clone(),
             total_size,
-            entry.items.len(),
+            entry.item_paths(),
         )?;
 
-        // Log the operation (don't log copy operations for undo purposes)
-        if !*options.copy {
-            let kind = OperationKind::Push {
+        if let Some(level) = options.compression {
+            // stashed_path stays relative to the archive root, which mirrors data/
+            file_compression::compress(&data_dir, &data_dir, *level)?;
+            fs::remove_dir_all(&data_dir)?;
+            entry.compressed_size_bytes = self.record_compressed_size(&entry.uuid)?;
+        }
+
+        // Copies are logged separately: undoing one only drops the entry
+        let kind = if *options.copy {
+            OperationKind::Copy {
                 entry_id: entry.uuid,
                 file_count: entry.items.len(),
-            };
-            self.journal_storage.append(Operation::new(kind))?;
+                name: Some(entry.name.clone()),
+                size_bytes: Some(entry.total_size_bytes),
+            }
+        } else {
+            OperationKind::Push {
+                entry_id: entry.uuid,
+                file_count: entry.items.len(),
+                name: Some(entry.name.clone()),
+                size_bytes: Some(entry.total_size_bytes),
+            }
+        };
+        self.journal_storage.append(Operation::new(kind))?;
+
+        if *options.versioned {
+            self.record_version(&entry, *options.max_versions)?;
         }
 
         Ok(entry)
     }
 
-    pub fn pop_entry(
-        &mut self,
-        uuid: &Uuid,
-        options: PopOptions,
-    ) -> Result<Entry> {
-        let entry = self.load_entry(uuid)?;
-        let data_dir = self.entry_dir(uuid).join("data");
+    /// Number a new entry as the latest version of its name, then delete the
+    /// oldest versions beyond `max_versions`
+    fn record_version(&mut self, entry: &Entry, max_versions: Option<usize>) -> Result<()> {
+        self.index_storage.assign_version(&entry.uuid)?;
 
-        for item in &entry.items {
-            let src = data_dir.join(&item.stashed_path);
-            let dest = options.destination.join(&item.stashed_path);
+        let Some(max) = max_versions else {
+            return Ok(());
+        };
+        let versions = self.index_storage.versions_of(&entry.name);
+        let excess: Vec<(Uuid, Option<u32>)> = versions
+            .iter()
+            .take(versions.len().saturating_sub(max.max(1)))
+            .map(|meta| (meta.uuid, meta.version))
+            .collect();
+
+        for (uuid, version) in excess {
+            self.delete_entry(&uuid)?;
+            eprintln!(
+                "Deleted version {} of '{}'; keeping the latest {}",
+                version.unwrap_or_default(),
+                entry.display_name(),
+                max
+            );
+        }
 
-            // Check for existing files
-            if dest.exists() && !options.force {
-                return Err(anyhow!(
-                    "Destination {:?} already exists. Use --force to overwrite.",
-                    dest
-                ));
-            }
+        Ok(())
+    }
 
-            // Ensure parent directories exist
-            if let Some(parent) = dest.parent() {
-                fs::create_dir_all(parent)?;
-            }
+    /// Drop the paths the hidden and age filters leave out, reporting what
+    /// was skipped. A directory's age is that of the newest thing in it.
+    fn select_paths(&self, paths: &[PathBuf], options: &PushOptions) -> Result<Vec<PathBuf>> {
+        let now = Utc::now();
+        let mut selected = Vec::new();
+        let mut too_new = 0;
+        let mut too_old = 0;
 
-            // Copy or move the item
-            if *options.copy {
-                self.copy_recursively(&src, &dest)?;
-            } else {
-                self.move_recursively(&src, &dest)?;
+        for path in paths {
+            if *options.ignore_hidden && path.file_name().is_some_and(walker::is_hidden) {
+                eprintln!("Skipping hidden {:?}", path);
+                continue;
             }
 
-            // Restore permissions
-            permission_handler::set_permissions(&dest, item.permissions)?;
+            if options.min_age.is_some() || options.max_age.is_some() {
+                let age = now - newest_mtime(path, *options.ignore_hidden)?;
+                if options.min_age.is_some_and(|min| age < min) {
+                    too_new += 1;
+                    continue;
+                }
+                if options.max_age.is_some_and(|max| age > max) {
+                    too_old += 1;
+                    continue;
+                }
+            }
 
-            // Restore timestamps
-            self.restore_timestamps(&dest, item.modified)?;
+            selected.push(path.clone());
         }
 
-        // Remove entry from stash if not copying
-        if !*options.copy {
-            self.delete_entry_internal(uuid)?;
+        if too_new > 0 {
+            eprintln!("Skipped {} item(s) modified more recently than --min-age", too_new);
+        }
+        if too_old > 0 {
+            eprintln!("Skipped {} item(s) modified longer ago than --max-age", too_old);
         }
 
-        self.journal_storage.append(Operation::new(
-            OperationKind::Pop {
-                entry_id: *uuid,
-                destination: options.destination.clone(),
-            }
-        ))?;
+        if selected.is_empty() {
+            let hint = if too_new + too_old > 0 {
+                "loosen --min-age/--max-age"
+            } else {
+                "use --include-hidden"
+            };
+            return Err(StashError::NothingMatched(format!(
+                "Every path given was filtered out; nothing to stash ({})",
+                hint
+            ))
+            .into());
+        }
 
-        Ok(entry)
+        Ok(selected)
     }
 
-    /// Peek: copy files out without removing from stash
-    pub fn peek_entry(
-        &self,
-        uuid: &Uuid,
-        destination: &Path,
-        force: bool,
-    ) -> Result<Entry> {
-        let entry = self.load_entry(uuid)?;
-        let data_dir = self.entry_dir(uuid).join("data");
-
-        for item in &entry.items {
-            let src = data_dir.join(&item.stashed_path);
-            let dest = destination.join(&item.stashed_path);
+    /// Read metadata, size, and (optionally) hash for a path about to be stashed
+    fn describe_item(&self, staged: &Staged, hash: Option<HashAlgorithm>, strict: bool) -> Result<Item> {
+        let path = staged.path.as_path();
+        let metadata = &staged.survey.metadata;
 
-            if dest.exists() && !force {
+        for (special, kind) in &staged.survey.special_files {
+            if strict {
                 return Err(anyhow!(
-                    "Destination {:?} already exists. Use --force to overwrite.",
-                    dest
+                    "{:?} is a {} and can't be stashed (push without --strict to skip it)",
+                    special,
+                    kind
                 ));
             }
-
-            if let Some(parent) = dest.parent() {
-                fs::create_dir_all(parent)?;
-            }
-
-            self.copy_recursively(&src, &dest)?;
-            permission_handler::set_permissions(&dest, item.permissions)?;
-            self.restore_timestamps(&dest, item.modified)?;
+            eprintln!("Warning: skipping {} {:?}", kind, special);
         }
 
-        // Note: peek doesn't modify the stash or journal
-        Ok(entry)
-    }
-
-    /// Restore to original working directory
-    pub fn restore_entry(
-        &mut self,
-        uuid: &Uuid,
-        force: bool,
-    ) -> Result<Entry> {
-        let entry = self.load_entry(uuid)?;
-        let original_dir = entry.working_directory.clone();
+        let kind = if metadata.is_dir() {
+            ItemKind::Directory
+        } else if metadata.file_type().is_symlink() {
+            ItemKind::Symlink
+        } else {
+            ItemKind::File
+        };
 
-        self.pop_entry(uuid, PopOptions {
-            destination: &original_dir,
-            copy: &false,
-            force: &force,
+        // Links are stored by target; following them could fail or mislead
+        let link_target = if kind == ItemKind::Symlink {
+            Some(fs::read_link(path)?)
+        } else {
+            None
+        };
+
+        // Calculate actual size including directory contents
+        let size = match &link_target {
+            Some(target) => target.as_os_str().len() as u64,
+            None => staged.survey.size,
+        };
+
+        // A link's own mode bits are meaningless, and its target may not exist
+        let permissions = match &link_target {
+            Some(_) => 0o777,
+            None => permission_handler::get_permissions(path)?,
+        };
+
+        // Preserve original modified time
+        let modified = metadata.modified()
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+
+        // Calculate hash for files
+        let hash = match hash {
+            Some(algorithm) if metadata.is_file() => Some(hashing::calculate_hash(path, algorithm)?),
+            _ => None,
+        };
+
+        Ok(Item {
+            original_path: path.to_path_buf(),
+            stashed_path: paths::portable_path(path),
+            kind,
+            size_bytes: size,
+            permissions,
+            modified,
+            hash,
+            hash_verified_at: None,
+            hash_skipped: staged.hash_skipped,
+            link_target,
+            special_files: staged.survey.special_files.iter().map(|(path, _)| path.clone()).collect(),
+            left_in_place: staged.survey.left_in_place.clone(),
         })
     }
 
-    pub fn rename_entry(&mut self, uuid: &Uuid, new_name: String) -> Result<()> {
-        let entry = self.load_entry(uuid)?;
-        let old_name = entry.name.clone();
-
-        self.write_manifest(&entry)?;
-        self.index_storage.update_entry_name(uuid, new_name.clone())?;
+    /// Which paths can be hard-linked into the stash. Those on another
+    /// filesystem are refused, or copied instead when `fallback` is set.
+    fn plan_links(&self, paths: &[PathBuf], fallback: bool) -> Result<Vec<bool>> {
+        let probe = self
+            .entries_root
+            .ancestors()
+            .find(|p| p.exists())
+            .unwrap_or(self.entries_root);
 
-        self.journal_storage.append(Operation::new(
-            OperationKind::Rename {
-                entry_id: *uuid,
-                old_name,
-                new_name,
+        let mut linked = Vec::with_capacity(paths.len());
+        for path in paths {
+            if same_filesystem(path, probe) {
+                linked.push(true);
+            } else if fallback {
+                eprintln!("Copying {:?}: it is on a different filesystem than the stash", path);
+                linked.push(false);
+            } else {
+                return Err(StashError::Usage(format!(
+                    "{:?} can't be hard-linked: it is on a different filesystem than the stash ({}); \
+                     use --hardlink-or-copy or --copy",
+                    path,
+                    mount_point(probe).display()
+                ))
+                .into());
             }
-        ))?;
+        }
 
-        Ok(())
+        Ok(linked)
     }
 
-    pub fn delete_entry(&mut self, uuid: &Uuid) -> Result<()> {
-        self.delete_entry_internal(uuid)?;
+    /// Move, copy or hard-link a single item into the entry's data directory
+    /// Returns the item's hash when it was computed during the copy.
+    fn stash_item(
+        &self,
+        staged: &Staged,
+        item: &Item,
+        data_dir: &Path,
+        copy: bool,
+        verify: bool,
+        workers: usize,
+    ) -> Result<Option<String>> {
+        let src = staged.path.as_path();
+        let dest = data_dir.join(&item.stashed_path);
+        let verify_hashes = item.hash_algorithm().filter(|_| verify);
+        let mut hash = None;
+
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
 
-        self.journal_storage.append(Operation::new(
-            OperationKind::Drop {
-                entry_id: *uuid,
-                deleted: true,
+        if staged.link {
+            // The originals stay where they are and share their data with the stash
+            self.link_tree(src, &dest, !item.left_in_place.is_empty())?;
+        } else if !item.left_in_place.is_empty() {
+            // Hidden entries stay behind, so the directory can't simply be
+            // renamed: copy the rest, then delete only what was copied
+            self.copy_walked(&staged.survey.entries, &dest, workers)?;
+            if !copy {
+                self.verify_copy(src, &dest, verify_hashes, true)?;
+                remove_visible(src)?;
             }
-        ))?;
-
-        Ok(())
-    }
+        } else if copy {
+            // Copy mode: leave originals in place
+            match staged.hash_on_copy {
+                Some(algorithm) if staged.survey.metadata.is_file() => {
+                    hash = Some(self.copy_file_hashing(src, &dest, &staged.survey.metadata, algorithm)?);
+                }
+                _ => self.copy_walked(&staged.survey.entries, &dest, workers)?,
+            }
+        } else {
+            // Move mode: relocate to stash
+            self.move_recursively(src, &dest, verify_hashes)?;
+        }
 
-    fn delete_entry_internal(&mut self, uuid: &Uuid) -> Result<()> {
-        let entry_dir = self.entry_dir(uuid);
-        fs::remove_dir_all(&entry_dir)
-            .with_context(|| format!("Failed to remove {:?}", entry_dir))?;
-        self.index_storage.remove_entry(uuid)?;
-        Ok(())
+        // Preserve timestamps
+        self.preserve_timestamps(src, &dest)?;
+        Ok(hash)
     }
 
-    pub fn clean_old_entries(&mut self, days: i64) -> Result<Vec<Uuid>> {
-        let removed = self.index_storage.remove_older_than_days(days)?;
+    /// Return items moved into `data_dir` to their original location after
+    /// a failed push. Returns false if anything had to be left behind.
+    fn unstash_items(&self, items: &[Item], data_dir: &Path) -> bool {
+        let mut complete = true;
 
-        for uuid in &removed {
-            let dir = self.entry_dir(uuid);
-            let _ = fs::remove_dir_all(dir);
-        }
+        for item in items {
+            let stashed = data_dir.join(&item.stashed_path);
+            if fs::symlink_metadata(&stashed).is_err() || exists_locally(&item.original_path) {
+                continue;
+            }
 
-        self.journal_storage.append(Operation::new(
-            OperationKind::Clean {
-                removed_count: removed.len(),
-                days,
+            if let Err(err) = self.move_recursively(&stashed, &item.original_path, None) {
+                eprintln!(
+                    "Warning: could not return {:?} to {:?}: {:#}",
+                    stashed, item.original_path, err
+                );
+                complete = false;
             }
-        ))?;
+        }
 
-        Ok(removed)
+        complete
     }
 
-    pub fn load_entry(&self, uuid: &Uuid) -> Result<Entry> {
-        let manifest = self.entry_dir(uuid).join("manifest.json");
-        let json = fs::read_to_string(&manifest)
-            .with_context(|| format!("Failed to read {:?}", manifest))?;
-        Ok(serde_json::from_str(&json)?)
-    }
+    pub fn pop_entry(
+        &mut self,
+        uuid: &Uuid,
+        options: PopOptions,
+    ) -> Result<(Entry, Vec<PathBuf>)> {
+        let full = self.load_entry(uuid)?;
+        let entry = select_items(&full, options.only)?;
 
-    pub fn load_entry_by_identifier(&self, ident: &str) -> Result<Entry> {
-        let meta = self.index_storage
-            .find_by_identifier(ident)
-            .ok_or_else(|| anyhow!("Entry not found: {}", ident))?;
-        self.load_entry(&meta.uuid)
-    }
+        if self.verify_dest {
+            self.ensure_space(&entry, options.destination, *options.copy)?;
+        }
 
-    pub fn list_entries(&self) -> &[crate::models::index::EntryMetadata] {
-        self.index_storage.list_all()
-    }
+        let (data_dir, unpacked) = self.unpack_data(uuid)?;
 
-    pub fn most_recent_entry(&self) -> Option<&crate::models::index::EntryMetadata> {
-        self.index_storage.most_recent()
-    }
+        let started = Instant::now();
+        let result = self.restore_items(&entry, &data_dir, &options);
+        let elapsed = started.elapsed();
 
-    pub fn find_entries_containing_path(
-        &self,
-        path: &Path,
-    ) -> Result<Vec<Uuid>> {
-        let mut matches = Vec::new();
-        for meta in self.index_storage.list_all() {
-            let entry = self.load_entry(&meta.uuid)?;
-            if entry.get_item(path).is_some() {
-                matches.push(meta.uuid);
+        // A compressed entry that stays in the stash keeps only its archive
+        if unpacked && (*options.copy || result.is_err()) {
+            let _ = fs::remove_dir_all(&data_dir);
+        }
+        let skipped = result?;
+
+        let bytes = entry
+            .items
+            .iter()
+            .filter(|item| !skipped.contains(&item.original_path))
+            .map(|item| item.size_bytes)
+            .sum();
+        self.last_transfer = Some(Transfer { bytes, elapsed });
+
+        // Remove entry from stash if not copying; keep whatever was skipped
+        // or not selected. Restoring the last items left removes the entry
+        // like any full pop.
+        let mut partial = None;
+        if !*options.copy {
+            let kept: Vec<PathBuf> = full
+                .items
+                .iter()
+                .filter(|item| entry.get_item(&item.original_path).is_none())
+                .map(|item| item.original_path.clone())
+                .chain(skipped.iter().cloned())
+                .collect();
+
+            if kept.is_empty() {
+                self.delete_entry_internal(uuid)?;
+            } else {
+                self.retain_items(&full, &kept)?;
+                partial = Some(kept.len());
             }
         }
-        Ok(matches)
-    }
 
-    fn write_manifest(&self, entry: &Entry) -> Result<()> {
-        let path = self.entry_dir(&entry.uuid).join("manifest.json");
-        let json = serde_json::to_string_pretty(entry)?;
-        fs::write(path, json)?;
-        Ok(())
-    }
+        self.journal_storage.append(Operation::new(
+            OperationKind::Pop {
+                entry_id: *uuid,
+                destination: options.destination.clone(),
+                kept: partial,
+            }
+        ))?;
 
-    fn entry_dir(&self, uuid: &Uuid) -> PathBuf {
-        self.entries_root.join(uuid.to_string())
+        Ok((entry, skipped))
     }
 
-    /// Calculate total size including directory contents
-    fn calculate_size(&self, path: &Path) -> Result<u64> {
-        let metadata = fs::symlink_metadata(path)?;
-
-        if metadata.is_file() {
-            Ok(metadata.len())
-        } else if metadata.is_dir() {
-            let mut total = 0u64;
-            for entry in fs::read_dir(path)? {
-                let entry = entry?;
-                total += self.calculate_size(&entry.path())?;
-            }
-            Ok(total)
+    /// Run `options.post_command`, if any, in the directory `entry` was
+    /// pushed from, as if it were run there by hand; an entry whose directory
+    /// is gone runs it where the files were restored. Meant to be called once
+    /// the pop has been reported. `quiet` sends its output to stderr.
+    pub fn run_post_command(&self, entry: &Entry, options: &PopOptions, quiet: bool) -> Result<()> {
+        let Some(command) = options.post_command else {
+            return Ok(());
+        };
+        let dir = if entry.working_directory.is_dir() {
+            &entry.working_directory
         } else {
-            Ok(0) // Symlinks
-        }
+            options.destination
+        };
+        shell::run(command, dir, quiet, !quiet)
     }
 
-    /// Calculate SHA256 hash of a file
-    fn calculate_hash(&self, path: &Path) -> Result<String> {
-        let mut file = fs::File::open(path)?;
-        let mut hasher = Sha256::new();
-        let mut buffer = [0u8; 8192];
+    /// Bytes restoring `entry` to `destination` would write, and the bytes
+    /// available there. Moves within one filesystem are renames and need no
+    /// space; copies, compressed entries and cross-device moves need it all.
+    pub fn check_space(&self, entry: &Entry, destination: &Path, copy: bool) -> Result<(u64, u64)> {
+        // The destination may not exist yet; measure the closest ancestor that does
+        let probe = destination
+            .ancestors()
+            .find(|p| p.exists())
+            .unwrap_or(destination);
 
-        loop {
-            let bytes_read = file.read(&mut buffer)?;
-            if bytes_read == 0 {
-                break;
-            }
-            hasher.update(&buffer[..bytes_read]);
-        }
+        let available = fs2::available_space(probe)
+            .with_context(|| format!("Failed to read free space for {:?}", probe))?;
 
-        Ok(format!("sha256:{:x}", hasher.finalize()))
-    }
+        let entry_dir = self.entry_dir(&entry.uuid);
+        let writes_data = copy
+            || entry.compressed_size_bytes.is_some()
+            || !same_filesystem(&entry_dir, probe);
 
-    /// Copy files/directories recursively
-    fn copy_recursively(&self, src: &Path, dest: &Path) -> Result<()> {
-        let metadata = fs::symlink_metadata(src)?;
+        let needed = if writes_data { entry.total_size_bytes } else { 0 };
 
-        if metadata.is_dir() {
-            fs::create_dir_all(dest)?;
-            for entry in fs::read_dir(src)? {
-                let entry = entry?;
-                let src_path = entry.path();
-                let dest_path = dest.join(entry.file_name());
-                self.copy_recursively(&src_path, &dest_path)?;
-            }
-        } else if metadata.file_type().is_symlink() {
-            #[cfg(unix)]
-            {
-                let target = fs::read_link(src)?;
-                std::os::unix::fs::symlink(target, dest)?;
-            }
-            #[cfg(windows)]
-            {
-                fs::copy(src, dest)?;
+        Ok((needed, available))
+    }
+
+    /// What at `destination` a forced pop of `uuid` would overwrite: whatever
+    /// sits where an item goes, the files a directory item would land on
+    /// inside an existing directory, and differently encoded twins. Paths
+    /// that only get created are left out.
+    pub fn overwritten_paths(&self, uuid: &Uuid, destination: &Path, only: &[glob::Pattern]) -> Result<Vec<PathBuf>> {
+        let entry = select_items(&self.load_entry(uuid)?, only)?;
+        let (data_dir, unpacked) = self.unpack_data(uuid)?;
+
+        let mut twins = normalize::Twins::under(destination);
+        let mut overwritten = Vec::new();
+        let result = entry.items.iter().try_for_each(|item| {
+            let dest = destination.join(&item.original_path);
+            match fs::symlink_metadata(&dest) {
+                Ok(metadata) if metadata.is_dir() && item.kind == ItemKind::Directory => {
+                    overwritten.extend(merge_targets(&data_dir.join(&item.stashed_path), &dest)?);
+                }
+                Ok(_) => overwritten.push(dest),
+                Err(_) => overwritten.extend(twins.find(&dest)),
             }
-        } else {
-            fs::copy(src, dest)?;
+            Ok::<_, anyhow::Error>(())
+        });
+
+        if unpacked {
+            let _ = fs::remove_dir_all(&data_dir);
         }
+        result?;
 
-        Ok(())
+        overwritten.sort();
+        overwritten.dedup();
+        Ok(overwritten)
     }
 
-    /// Move files/directories recursively
-    fn move_recursively(&self, src: &Path, dest: &Path) -> Result<()> {
-        // Try simple rename first (works if on same filesystem)
-        if fs::rename(src, dest).is_ok() {
+    /// Fail before anything is written if the stash's filesystem can't hold
+    /// the data a push copies: everything in copy mode, and items on other
+    /// filesystems in move mode. A margin is kept free on top.
+    fn check_push_space(&self, staged: &[Staged], copy: bool) -> Result<()> {
+        let probe = self
+            .entries_root
+            .ancestors()
+            .find(|p| p.exists())
+            .unwrap_or(self.entries_root);
+
+        let needed: u64 = staged
+            .iter()
+            .filter(|staged| copy || !same_filesystem(&staged.path, probe))
+            .map(|staged| staged.survey.size)
+            .sum();
+        if needed == 0 {
             return Ok(());
         }
 
-        // Fall back to copy + delete for cross-filesystem moves
-        self.copy_recursively(src, dest)?;
+        let available = fs2::available_space(probe)
+            .with_context(|| format!("Failed to read free space for {:?}", probe))?;
+        let needed = needed + (needed / 20).max(PUSH_SPACE_MARGIN);
 
-        if src.is_dir() {
-            fs::remove_dir_all(src)?;
-        } else {
-            fs::remove_file(src)?;
+        if needed > available {
+            return Err(StashError::InsufficientSpace {
+                path: mount_point(probe),
+                needed,
+                available,
+            }
+            .into());
         }
 
         Ok(())
     }
 
-    /// Preserve timestamps from source to destination
-    fn preserve_timestamps(&self, src: &Path, dest: &Path) -> Result<()> {
-        if let Ok(metadata) = fs::metadata(src) {
-            if let (Ok(accessed), Ok(modified)) = (metadata.accessed(), metadata.modified()) {
-                let _ = filetime::set_file_times(
-                    dest,
-                    filetime::FileTime::from_system_time(accessed),
-                    filetime::FileTime::from_system_time(modified),
-                );
+    /// Fail with `InsufficientSpace`, naming the destination's mount point,
+    /// if restoring `entry` to `destination` wouldn't fit
+    fn ensure_space(&self, entry: &Entry, destination: &Path, copy: bool) -> Result<()> {
+        let (needed, available) = self.check_space(entry, destination, copy)?;
+        if needed > available {
+            let probe = destination.ancestors().find(|p| p.exists()).unwrap_or(destination);
+            return Err(StashError::InsufficientSpace {
+                path: mount_point(probe),
+                needed,
+                available,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Shrink a partially popped entry down to the items still in the stash
+    fn retain_items(&mut self, entry: &Entry, kept: &[PathBuf]) -> Result<()> {
+        let mut remaining = entry.clone();
+        remaining.items.retain(|item| kept.contains(&item.original_path));
+        remaining.recalculate_size();
+
+        // A compressed entry's archive still holds everything; drop the
+        // unpacked copy of the items that were restored
+        if remaining.compressed_size_bytes.is_some() {
+            remaining.compressed_size_bytes = None;
+            fs::remove_file(self.entry_dir(&entry.uuid).join("data.tar.gz"))?;
+            self.index_storage.set_compressed_size(&entry.uuid, None)?;
+        }
+
+        self.write_manifest(&remaining)?;
+        self.index_storage.set_item_paths(vec![(entry.uuid, remaining.item_paths())])?;
+        self.index_storage.update_entry_metadata(
+            &entry.uuid,
+            None,
+            remaining.total_size_bytes as i64 - entry.total_size_bytes as i64,
+            remaining.items.len() as isize - entry.items.len() as isize,
+        )
+    }
+
+    /// Restore each item, returning the original paths of items skipped
+    /// because of `missing_ok`.
+    ///
+    /// Every item is checked and its data staged under a temporary name next
+    /// to its destination (and, with `verify`, checked against its recorded
+    /// hash) before any of them is put in place, so a failure leaves both the
+    /// stash and the destination as they were.
+    fn restore_items(&self, entry: &Entry, data_dir: &Path, options: &PopOptions) -> Result<Vec<PathBuf>> {
+        // Fail before touching anything rather than leave a partial restore
+        let missing: Vec<String> = entry
+            .items
+            .iter()
+            .filter(|item| item.link_target.is_none())
+            .filter(|item| fs::symlink_metadata(data_dir.join(&item.stashed_path)).is_err())
+            .map(|item| format!("  {}", item.original_path.display()))
+            .collect();
+
+        if !missing.is_empty() {
+            return Err(StashError::Integrity(format!(
+                "Entry '{}' is missing stashed data for {} item(s); nothing was restored:\n{}\n\
+                 Run 'stash --info {} --hash-check' for details.",
+                entry.display_name(),
+                missing.len(),
+                missing.join("\n"),
+                entry.short_id()
+            ))
+            .into());
+        }
+
+        let renames = self.case_renames(entry, options)?;
+        let mut twins = normalize::Twins::under(options.destination);
+        let mut skipped = Vec::new();
+        let mut pending = Vec::new();
+
+        for item in &entry.items {
+            let src = data_dir.join(&item.stashed_path);
+            let original = renames.get(&item.original_path).unwrap_or(&item.original_path);
+            let dest = options.destination.join(original);
+
+            if *options.missing_ok && dest.parent().is_some_and(|parent| !parent.exists()) {
+                skipped.push(item.original_path.clone());
+                continue;
+            }
+
+            // Check for existing files (including dangling links). A directory
+            // whose hidden entries were left behind is expected to still be
+            // there; the stashed part is merged back into it.
+            let dest_exists = fs::symlink_metadata(&dest).is_ok();
+            let merge = !item.left_in_place.is_empty() && dest.is_dir();
+            if !*options.force {
+                if merge {
+                    if let Some(target) = merge_targets(&src, &dest)?.into_iter().next() {
+                        return Err(StashError::Conflict(target).into());
+                    }
+                } else if dest_exists {
+                    return Err(StashError::Conflict(dest).into());
+                } else if let Some(twin) = twins.find(&dest) {
+                    // A filesystem that normalizes names may already hold
+                    // this file under differently encoded bytes. With force
+                    // the rename into place lands on that same file, so the
+                    // twin itself is never removed.
+                    return Err(StashError::Conflict(twin).into());
+                }
+            }
+
+            pending.push(PendingRestore { item, src, dest, staged: None, moved: false });
+        }
+
+        // Everything is staged and verified before the destination changes.
+        // Whatever a forced restore replaces is set aside rather than
+        // deleted, so a failure while putting items in place is undone too.
+        let mut created = Vec::new();
+        let mut placed = Vec::new();
+        let result = self
+            .stage_restores(&mut pending, &mut created, options)
+            .and_then(|_| pending.iter().try_for_each(|restore| restore.place(&mut placed)));
+
+        if let Err(err) = result {
+            for placement in placed.iter().rev() {
+                placement.undo();
+            }
+            for restore in pending.iter().rev() {
+                restore.unstage();
+            }
+            for dir in created.iter().rev() {
+                let _ = fs::remove_dir_all(dir);
+            }
+            return Err(err.context("Nothing was restored; the stash and the destination are unchanged"));
+        }
+
+        for aside in placed.iter().filter_map(|placement| placement.displaced.as_ref()) {
+            if let Err(err) = remove_path(aside) {
+                eprintln!("Warning: could not delete replaced {:?}: {}", aside, err);
+            }
+        }
+
+        for restore in &pending {
+            let PendingRestore { item, src, dest, .. } = restore;
+
+            // A staged directory merged into an existing one is left holding
+            // only the directories it shared with it
+            if let Some(staged) = restore.staged.as_ref().filter(|staged| exists_locally(staged)) {
+                remove_path(staged)?;
+            }
+
+            // Data copied out for a move leaves the stash only now that
+            // every item is in place
+            if !restore.moved && !*options.copy && exists_locally(src) {
+                remove_path(src)?;
+            }
+
+            if item.link_target.is_some() {
+                if *options.preserve_mtime {
+                    let mtime = filetime::FileTime::from_unix_time(
+                        item.modified.timestamp(),
+                        item.modified.timestamp_subsec_nanos(),
+                    );
+                    let _ = filetime::set_symlink_file_times(dest, mtime, mtime);
+                }
+                continue;
+            }
+
+            // Every restore mode ends here, so all of them get the recorded
+            // permissions and (unless disabled) modification time back
+            permission_handler::set_permissions(dest, item.permissions)?;
+
+            if *options.preserve_mtime {
+                self.restore_timestamps(dest, item.modified)?;
+            }
+        }
+
+        Ok(skipped)
+    }
+
+    /// Put each item's data under a temporary name next to its destination,
+    /// recording in `created` the directories made for it. Links are
+    /// recreated from their recorded target, which also works for links
+    /// whose target is gone. Moves rename the data out of the stash where
+    /// they can; copies, cross-device moves and files other hard links share
+    /// are copied.
+    fn stage_restores(&self, pending: &mut [PendingRestore], created: &mut Vec<PathBuf>, options: &PopOptions) -> Result<()> {
+        for restore in pending.iter_mut() {
+            if let Some(parent) = restore.dest.parent() {
+                if let Some(top) = parent.ancestors().take_while(|p| !p.exists()).last() {
+                    created.push(top.to_path_buf());
+                }
+                fs::create_dir_all(parent)?;
+            }
+
+            let staged = temp_sibling(&restore.dest);
+            restore.staged = Some(staged.clone());
+
+            if let Some(target) = &restore.item.link_target {
+                create_symlink(target, &staged)?;
+                if !exists_locally(&staged) {
+                    restore.staged = None;
+                }
+                continue;
+            }
+
+            // Renaming a file other links still point at would hand the user
+            // an inode the stash shares; copy it out instead
+            let rename = !*options.copy && !has_shared_links(&restore.src)?;
+            if rename && fs::rename(&restore.src, &staged).is_ok() {
+                restore.moved = true;
+            } else {
+                self.copy_recursively(&restore.src, &staged)?;
+                if rename {
+                    // Across filesystems; the stored hashes are checked below
+                    let hashes = restore.item.hash_algorithm().filter(|_| !*options.verify);
+                    self.verify_copy(&restore.src, &staged, hashes, false)?;
+                }
+            }
+
+            if *options.verify {
+                self.verify_restored(restore.item, &staged)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Compare a staged file with the hash recorded when it was pushed
+    fn verify_restored(&self, item: &Item, staged: &Path) -> Result<()> {
+        let Some(expected) = &item.hash else {
+            return Ok(());
+        };
+
+        let actual = hashing::calculate_hash(staged, hashing::algorithm_of(expected))?;
+        if &actual != expected {
+            return Err(StashError::Integrity(format!(
+                "Stashed data for {:?} does not match its recorded hash (expected {}, got {})",
+                item.original_path, expected, actual
+            ))
+            .into());
+        }
+
+        Ok(())
+    }
+
+    /// Items that differ only in case would land on the same file when the
+    /// destination ignores case. Without force that's an error listing the
+    /// pairs; with force the later item of each pair gets a new name.
+    fn case_renames(&self, entry: &Entry, options: &PopOptions) -> Result<HashMap<PathBuf, PathBuf>> {
+        let pairs = normalize::case_collisions(entry.items.iter().map(|item| item.original_path.as_path()));
+        if pairs.is_empty() || !normalize::is_case_insensitive(options.destination) {
+            return Ok(HashMap::new());
+        }
+
+        if !*options.force {
+            return Err(StashError::CaseCollision { root: options.destination.clone(), pairs }.into());
+        }
+
+        let mut taken: HashSet<String> = entry
+            .items
+            .iter()
+            .map(|item| normalize::fold(&item.original_path.to_string_lossy()))
+            .collect();
+        let mut renames = HashMap::new();
+
+        for (_, path) in pairs {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let extension = path.extension().map(|ext| format!(".{}", ext.to_string_lossy()));
+            let renamed = (2..)
+                .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension.as_deref().unwrap_or(""))))
+                .find(|candidate| {
+                    !taken.contains(&normalize::fold(&candidate.to_string_lossy()))
+                        && fs::symlink_metadata(options.destination.join(candidate)).is_err()
+                })
+                .expect("an unused name");
+
+            eprintln!("Warning: restoring {:?} as {:?} to avoid a case collision", path, renamed);
+            taken.insert(normalize::fold(&renamed.to_string_lossy()));
+            renames.insert(path, renamed);
+        }
+
+        Ok(renames)
+    }
+
+    /// Peek: copy files out without removing from stash
+    pub fn peek_entry(
+        &self,
+        uuid: &Uuid,
+        destination: &Path,
+        force: bool,
+        preserve_mtime: bool,
+        verify: bool,
+        only: &[glob::Pattern],
+    ) -> Result<Entry> {
+        let entry = select_items(&self.load_entry(uuid)?, only)?;
+        if !force {
+            self.ensure_space(&entry, destination, true)?;
+        }
+        let (data_dir, unpacked) = self.unpack_data(uuid)?;
+
+        let options = PopOptions {
+            destination: &destination.to_path_buf(),
+            copy: &true,
+            force: &force,
+            missing_ok: &false,
+            preserve_mtime: &preserve_mtime,
+            verify: &verify,
+            only,
+            post_command: &None,
+        };
+        let result = self.restore_items(&entry, &data_dir, &options);
+
+        if unpacked {
+            let _ = fs::remove_dir_all(&data_dir);
+        }
+        result?;
+
+        // Note: peek doesn't modify the stash or journal
+        Ok(entry)
+    }
+
+    /// Restore to original working directory
+    pub fn restore_entry(
+        &mut self,
+        uuid: &Uuid,
+        force: bool,
+        missing_ok: bool,
+        preserve_mtime: bool,
+        verify: bool,
+        only: &[glob::Pattern],
+    ) -> Result<(Entry, Vec<PathBuf>)> {
+        let entry = self.load_entry(uuid)?;
+        let original_dir = entry.working_directory.clone();
+
+        self.pop_entry(uuid, PopOptions {
+            destination: &original_dir,
+            copy: &false,
+            force: &force,
+            missing_ok: &missing_ok,
+            preserve_mtime: &preserve_mtime,
+            verify: &verify,
+            only,
+            post_command: &None,
+        })
+    }
+
+    pub fn rename_entry(&mut self, uuid: &Uuid, new_name: String) -> Result<()> {
+        if new_name.trim().is_empty() {
+            return Err(StashError::Usage("Entry names cannot be empty".into()).into());
+        }
+
+        if let Some(other) = self.index_storage.find_by_name(&new_name) {
+            if other.uuid != *uuid {
+                return Err(StashError::Usage(format!(
+                    "An entry named '{}' already exists ({})",
+                    new_name,
+                    &other.uuid.to_string()[..6]
+                ))
+                .into());
+            }
+        }
+
+        let mut entry = self.load_entry(uuid)?;
+        let old_name = std::mem::replace(&mut entry.name, new_name.clone());
+        entry.touch();
+
+        self.write_manifest(&entry)?;
+        self.index_storage.update_entry_name(uuid, new_name.clone())?;
+
+        self.journal_storage.append(Operation::new(
+            OperationKind::Rename {
+                entry_id: *uuid,
+                old_name,
+                new_name,
+            }
+        ))?;
+
+        Ok(())
+    }
+
+    /// Exchange the names of two entries. The index changes in one save and
+    /// each entry's rename is journaled on its own.
+    pub fn swap_names(&mut self, first: &Uuid, second: &Uuid) -> Result<()> {
+        if first == second {
+            return Err(StashError::Usage("--swap needs two different entries".into()).into());
+        }
+
+        let mut a = self.load_entry(first)?;
+        let mut b = self.load_entry(second)?;
+        std::mem::swap(&mut a.name, &mut b.name);
+        a.touch();
+        b.touch();
+
+        self.write_manifest(&a)?;
+        self.write_manifest(&b)?;
+        self.index_storage.swap_names(first, second)?;
+
+        for entry in [&a, &b] {
+            let other = if entry.uuid == a.uuid { &b } else { &a };
+            self.journal_storage.append(Operation::new(OperationKind::Rename {
+                entry_id: entry.uuid,
+                old_name: other.name.clone(),
+                new_name: entry.name.clone(),
+            }))?;
+        }
+
+        Ok(())
+    }
+
+    /// Every journaled rename of an entry as `(when, old_name, new_name)`,
+    /// oldest first
+    pub fn renames(&self, uuid: &Uuid) -> Result<Vec<(DateTime<Utc>, String, String)>> {
+        Ok(self
+            .journal_storage
+            .for_entry(uuid)?
+            .into_iter()
+            .filter_map(|op| match op.kind {
+                OperationKind::Rename { old_name, new_name, .. } => Some((op.timestamp, old_name, new_name)),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Give an entry back the name it had before its last rename. The revert
+    /// is itself journaled, so reverting twice swaps the names back.
+    /// Returns the names it changed from and to.
+    pub fn revert_name(&mut self, uuid: &Uuid) -> Result<(String, String)> {
+        let (_, previous, _) = self.renames(uuid)?.pop().ok_or_else(|| {
+            StashError::NothingMatched("This entry has never been renamed".into())
+        })?;
+
+        let current = self.load_entry(uuid)?.name;
+        self.rename_entry(uuid, previous.clone())?;
+
+        Ok((current, previous))
+    }
+
+    pub fn delete_entry(&mut self, uuid: &Uuid) -> Result<()> {
+        self.delete_entry_internal(uuid)?;
+
+        self.journal_storage.append(Operation::new(
+            OperationKind::Drop {
+                entry_id: *uuid,
+                deleted: true,
+            }
+        ))?;
+
+        Ok(())
+    }
+
+    /// Delete an entry's data, then its index record. If the data can't be
+    /// removed the record stays, flagged so `--health` reports it.
+    fn delete_entry_internal(&mut self, uuid: &Uuid) -> Result<()> {
+        if let Err(err) = self.remove_entry_dir(uuid) {
+            self.index_storage.flag_removal_failed(uuid)?;
+            return Err(err);
+        }
+
+        self.index_storage
+            .remove_entry(uuid)
+            .context("Removed the entry's data but could not update the index")?;
+        Ok(())
+    }
+
+    /// Remove entries older than `days`, or created before `until` when it is
+    /// given, and not before `since`. Entries whose data can't be removed are
+    /// kept in the index, flagged, and returned with the error.
+    pub fn clean_old_entries(
+        &mut self,
+        days: i64,
+        since: Option<DateTime<Utc>>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<CleanOutcome> {
+        let targets: Vec<Uuid> = self
+            .index_storage
+            .created_between(since, index::clean_window_end(days, since, until))
+            .iter()
+            .map(|meta| meta.uuid)
+            .collect();
+
+        let outcome = self.remove_cleaned(targets)?;
+        self.journal_storage.append(Operation::new(
+            OperationKind::Clean {
+                removed_count: outcome.removed.len(),
+                days,
+                since,
+                until,
+                keep_latest: None,
+            }
+        ))?;
+
+        Ok(outcome)
+    }
+
+    /// Remove every entry but the `n` most recently created, or with
+    /// `groups` all but the `n` newest of each group; entries in no group
+    /// are kept. Failures are handled as in `clean_old_entries`.
+    pub fn clean_all_except_latest(&mut self, n: usize, groups: Option<&[Vec<Uuid>]>) -> Result<CleanOutcome> {
+        let targets: Vec<Uuid> = self
+            .all_except_latest(n, groups)
+            .iter()
+            .map(|meta| meta.uuid)
+            .collect();
+
+        let mut outcome = self.remove_cleaned_data(targets);
+        if groups.is_none() && outcome.failed.is_empty() {
+            outcome.removed = self.index_storage.remove_all_except_latest(n);
+            self.index_storage
+                .save_packages()
+                .context("Removed old entries' data but could not update the index")?;
+        } else {
+            self.drop_cleaned(&outcome)?;
+        }
+
+        self.journal_storage.append(Operation::new(
+            OperationKind::Clean {
+                removed_count: outcome.removed.len(),
+                // Only --clean DAYS uses it
+                days: 0,
+                since: None,
+                until: None,
+                keep_latest: Some(n),
+            }
+        ))?;
+
+        Ok(outcome)
+    }
+
+    fn remove_cleaned(&mut self, targets: Vec<Uuid>) -> Result<CleanOutcome> {
+        let outcome = self.remove_cleaned_data(targets);
+        self.drop_cleaned(&outcome)?;
+        Ok(outcome)
+    }
+
+    /// Delete the data of each of `targets`, leaving the index alone
+    fn remove_cleaned_data(&self, targets: Vec<Uuid>) -> CleanOutcome {
+        let mut outcome = CleanOutcome::default();
+        for uuid in targets {
+            match self.remove_entry_dir(&uuid) {
+                Ok(()) => outcome.removed.push(uuid),
+                Err(err) => outcome.failed.push((uuid, err)),
+            }
+        }
+        outcome
+    }
+
+    /// Drop the entries whose data is gone from the index, and flag those
+    /// whose data could not be removed
+    fn drop_cleaned(&mut self, outcome: &CleanOutcome) -> Result<()> {
+        for (uuid, _) in &outcome.failed {
+            self.index_storage.flag_removal_failed(uuid)?;
+        }
+
+        self.index_storage
+            .remove_entries(&outcome.removed)
+            .context("Removed old entries' data but could not update the index")
+    }
+
+    /// Delete an entry directory. It is first renamed aside, which either
+    /// fully happens or not at all; if deleting the renamed copy then fails
+    /// part-way, whatever is left is put back under the entry's name.
+    fn remove_entry_dir(&self, uuid: &Uuid) -> Result<()> {
+        let entry_dir = self.entry_dir(uuid);
+        if !exists_locally(&entry_dir) {
+            return Ok(());
+        }
+
+        let removed = temp_sibling(&entry_dir);
+        fs::rename(&entry_dir, &removed)
+            .with_context(|| format!("Failed to remove {:?}", entry_dir))?;
+
+        if let Err(err) = fs::remove_dir_all(&removed) {
+            let _ = fs::rename(&removed, &entry_dir);
+            return Err(anyhow!(err).context(format!(
+                "Failed to remove {:?}; some of its data may already be gone",
+                entry_dir
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Add an exported entry directory (manifest.json + data/) to the stash.
+    /// Returns `None` if an entry with the same UUID already exists.
+    pub fn import_entry(&mut self, source: &Path) -> Result<Option<Entry>> {
+        let manifest = source.join("manifest.json");
+        let json = fs::read_to_string(&manifest)
+            .with_context(|| format!("Failed to read {:?}", manifest))?;
+        let entry: Entry = serde_json::from_str(&json)
+            .with_context(|| format!("Invalid manifest {:?}", manifest))?;
+
+        if self.index_storage.contains(&entry.uuid) {
+            return Ok(None);
+        }
+
+        self.copy_recursively(source, &self.entry_dir(&entry.uuid))?;
+
+        self.index_storage.insert_entry(EntryMetadata {
+            uuid: entry.uuid,
+            name: entry.name.clone(),
+            created: entry.created,
+            updated: Some(Utc::now()),
+            total_size_bytes: entry.total_size_bytes,
+            compressed_size_bytes: entry.compressed_size_bytes,
+            item_count: entry.items.len(),
+            removal_failed: false,
+            item_paths: Some(entry.item_paths()),
+            is_template: false,
+            version: None,
+        })?;
+
+        self.journal_storage.append(Operation::new(
+            OperationKind::Import {
+                entry_id: entry.uuid,
+                name: entry.name.clone(),
+            }
+        ))?;
+
+        Ok(Some(entry))
+    }
+
+    /// Recompute stored hashes against the stash data.
+    /// Items that verify are stamped with `hash_verified_at`.
+    pub fn check_hashes(&self, uuid: &Uuid) -> Result<(Entry, Vec<HashStatus>)> {
+        let mut entry = self.load_entry(uuid)?;
+        let (data_dir, unpacked) = self.unpack_data(uuid)?;
+        let mut statuses = Vec::with_capacity(entry.items.len());
+
+        for item in entry.items.iter_mut() {
+            let Some(expected) = &item.hash else {
+                statuses.push(if item.hash_skipped { HashStatus::TooLarge } else { HashStatus::Unhashed });
+                continue;
+            };
+
+            let data_path = data_dir.join(&item.stashed_path);
+            if !data_path.is_file() {
+                statuses.push(HashStatus::Missing);
+                continue;
+            }
+
+            let actual = hashing::calculate_hash(&data_path, hashing::algorithm_of(expected))?;
+            if &actual == expected {
+                item.hash_verified_at = Some(Utc::now());
+                statuses.push(HashStatus::Ok);
+            } else {
+                statuses.push(HashStatus::Mismatch {
+                    expected: expected.clone(),
+                    actual,
+                });
+            }
+        }
+
+        if unpacked {
+            fs::remove_dir_all(&data_dir)?;
+        }
+
+        self.write_manifest(&entry)?;
+
+        Ok((entry, statuses))
+    }
+
+    /// Compute hashes for file items that were pushed without one, including
+    /// those over `hash_max_size_mb`. Returns the number of items hashed.
+    pub fn rehash_entry(&self, uuid: &Uuid, algorithm: HashAlgorithm) -> Result<usize> {
+        let mut entry = self.load_entry(uuid)?;
+        let (data_dir, unpacked) = self.unpack_data(uuid)?;
+        let mut hashed = 0;
+
+        for item in entry.items.iter_mut() {
+            if item.kind != ItemKind::File || item.hash.is_some() {
+                continue;
+            }
+
+            let data_path = data_dir.join(&item.stashed_path);
+            item.hash = Some(hashing::calculate_hash(&data_path, algorithm)
+                .with_context(|| format!("Failed to hash {:?}", data_path))?);
+            item.hash_verified_at = Some(Utc::now());
+            item.hash_skipped = false;
+            hashed += 1;
+        }
+
+        if unpacked {
+            fs::remove_dir_all(&data_dir)?;
+        }
+
+        if hashed > 0 {
+            entry.touch();
+            self.write_manifest(&entry)?;
+        }
+
+        Ok(hashed)
+    }
+
+    /// Find a file item by its original path, or by a unique path suffix
+    pub fn find_item<'e>(&self, entry: &'e Entry, path: &Path) -> Result<&'e Item> {
+        if let Some(item) = entry.get_item(path) {
+            return Ok(item);
+        }
+
+        let matches: Vec<&Item> = entry
+            .items
+            .iter()
+            .filter(|item| normalize::path_ends_with(&item.original_path, path))
+            .collect();
+
+        match matches.as_slice() {
+            [item] => Ok(item),
+            [] => Err(StashError::NotFound(format!(
+                "{} in entry '{}'",
+                path.display(),
+                entry.display_name()
+            ))
+            .into()),
+            _ => Err(StashError::Usage(format!(
+                "'{}' matches {} items in entry '{}'; use the full path",
+                path.display(),
+                matches.len(),
+                entry.display_name()
+            ))
+            .into()),
+        }
+    }
+
+    /// Bytes an entry's directory occupies on disk, counted in blocks like
+    /// `du`. Compressed entries and sparse files take less than their size.
+    pub fn disk_usage(&self, uuid: &Uuid) -> Result<u64> {
+        let mut total = 0;
+        for found in walker::walk(&self.entry_dir(uuid)) {
+            let found = found?;
+            if !found.duplicate {
+                total += sparse::disk_usage(&found.metadata);
+            }
+        }
+        Ok(total)
+    }
+
+    /// Location of an item's data inside the stash
+    pub fn item_data_path(&self, uuid: &Uuid, item: &Item) -> PathBuf {
+        self.entry_dir(uuid).join("data").join(&item.stashed_path)
+    }
+
+    /// Refresh an item's size, hash and modified time after its stashed
+    /// data was changed in place, and journal the edit
+    pub fn update_item(&mut self, uuid: &Uuid, original_path: &Path) -> Result<()> {
+        let mut entry = self.load_entry(uuid)?;
+        let data_path = self.entry_dir(uuid).join("data");
+
+        let item = entry
+            .items
+            .iter_mut()
+            .find(|item| normalize::same_path(&item.original_path, original_path))
+            .ok_or_else(|| StashError::NotFound(original_path.display().to_string()))?;
+
+        let data_path = data_path.join(&item.stashed_path);
+        let old_size = item.size_bytes;
+
+        item.size_bytes = fs::metadata(&data_path)?.len();
+        item.modified = Utc::now();
+        if let Some(hash) = &item.hash {
+            item.hash = Some(hashing::calculate_hash(&data_path, hashing::algorithm_of(hash))?);
+            item.hash_verified_at = Some(Utc::now());
+        }
+
+        let size_delta = item.size_bytes as i64 - old_size as i64;
+
+        entry.recalculate_size();
+        self.write_manifest(&entry)?;
+        self.index_storage.update_entry_metadata(uuid, None, size_delta, 0)?;
+
+        self.journal_storage.append(Operation::new(
+            OperationKind::Edit {
+                entry_id: *uuid,
+                path: original_path.to_path_buf(),
+            }
+        ))?;
+
+        Ok(())
+    }
+
+    /// Drop journal operations that reference entries no longer in the index
+    pub fn prune_journal(&mut self) -> Result<usize> {
+        let existing: Vec<Uuid> = self.index_storage
+            .list_all()
+            .iter()
+            .map(|m| m.uuid)
+            .collect();
+
+        self.journal_storage.compact(&existing)
+    }
+
+    /// Prune the journal once it passes any of `limits`: too many operations,
+    /// too many bytes, or an operation of a deleted entry older than the age
+    pub fn prune_journal_if_over(&mut self, limits: JournalPruneLimits) -> Result<usize> {
+        if !self.journal_over(limits)? {
+            return Ok(0);
+        }
+
+        self.prune_journal()
+    }
+
+    fn journal_over(&self, limits: JournalPruneLimits) -> Result<bool> {
+        if limits.operations > 0 && self.journal_storage.len()? > limits.operations {
+            return Ok(true);
+        }
+        if limits.bytes > 0 && self.journal_storage.size()? > limits.bytes {
+            return Ok(true);
+        }
+        if limits.age_days == 0 {
+            return Ok(false);
+        }
+
+        // Only operations pruning would remove count, so a journal of live
+        // entries doesn't trigger a prune on every call
+        let cutoff = Utc::now() - chrono::Duration::days(limits.age_days.min(i32::MAX as u64) as i64);
+        let existing: HashSet<Uuid> = self.index_storage.list_all().iter().map(|m| m.uuid).collect();
+        let stale = self.journal_storage.count_matching(|op| {
+            op.timestamp < cutoff && op.entry_id().is_some_and(|id| !existing.contains(&id))
+        })?;
+
+        Ok(stale > 0)
+    }
+
+    pub fn load_entry(&self, uuid: &Uuid) -> Result<Entry> {
+        let manifest = self.entry_dir(uuid).join("manifest.json");
+        let json = fs::read_to_string(&manifest)
+            .with_context(|| format!("Failed to read {:?}", manifest))?;
+        let entry: Entry = serde_json::from_str(&json)?;
+        self.check_manifest(&entry)?;
+        Ok(entry)
+    }
+
+    /// Report or reject what `Entry::validate` finds, per `manifest_checks`.
+    /// Informational findings are left to `--info` and `--health`.
+    fn check_manifest(&self, entry: &Entry) -> Result<()> {
+        if self.manifest_checks == ManifestChecks::Off {
+            return Ok(());
+        }
+
+        let problems: Vec<ValidationWarning> = entry
+            .validate()
+            .into_iter()
+            .filter(|warning| warning.severity > WarningSeverity::Info)
+            .collect();
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        if self.manifest_checks == ManifestChecks::Strict {
+            let details: Vec<String> = problems.iter().map(|problem| format!("  {}", problem)).collect();
+            return Err(StashError::Integrity(format!(
+                "The manifest of '{}' failed validation:\n{}",
+                entry.display_name(),
+                details.join("\n")
+            ))
+            .into());
+        }
+
+        if self.reported.lock().unwrap().insert(entry.uuid) {
+            for problem in &problems {
+                let qualifier = if problem.severity == WarningSeverity::Critical { " (critical)" } else { "" };
+                eprintln!("Warning: entry '{}'{}: {}", entry.display_name(), qualifier, problem.message);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mark or unmark an entry as a template for `--template`
+    pub fn set_template(&mut self, uuid: &Uuid, is_template: bool) -> Result<()> {
+        self.index_storage.set_template(uuid, is_template)
+    }
+
+    pub fn load_entry_by_identifier(&self, ident: &str) -> Result<Entry> {
+        let meta = self.index_storage
+            .find_by_identifier(ident)
+            .ok_or_else(|| StashError::NotFound(ident.to_string()))?;
+        self.load_entry(&meta.uuid)
+    }
+
+    /// A specific version of a name. Names without that version report the
+    /// versions they do have.
+    pub fn find_version(&self, name: &str, version: u32) -> Result<&crate::models::index::EntryMetadata> {
+        if let Some(meta) = self.index_storage.find_version(name, version) {
+            return Ok(meta);
+        }
+
+        let named = self.index_storage.versions_of(name);
+        if named.is_empty() {
+            return Err(StashError::NotFound(name.to_string()).into());
+        }
+        let known: Vec<String> = named.iter().filter_map(|meta| meta.version.map(|v| v.to_string())).collect();
+        let hint = if known.is_empty() { "it has no versions".to_string() } else { format!("versions: {}", known.join(", ")) };
+        Err(StashError::NotFound(format!("version {} of '{}' ({})", version, name, hint)).into())
+    }
+
+    pub fn list_entries(&self) -> &[crate::models::index::EntryMetadata] {
+        self.index_storage.list_all()
+    }
+
+    pub fn most_recent_entry(&self) -> Option<&crate::models::index::EntryMetadata> {
+        self.index_storage.most_recent()
+    }
+
+    /// The entries `clean_old_entries` would remove, without removing them
+    pub fn created_between(&self, since: Option<DateTime<Utc>>, until: DateTime<Utc>) -> Vec<EntryMetadata> {
+        self.index_storage.created_between(since, until).into_iter().cloned().collect()
+    }
+
+    /// The entries `clean_all_except_latest` would remove, without removing them
+    pub fn all_except_latest(&self, n: usize, groups: Option<&[Vec<Uuid>]>) -> Vec<EntryMetadata> {
+        match groups {
+            None => self.index_storage.all_except_latest(n, None).into_iter().cloned().collect(),
+            Some(groups) => groups
+                .iter()
+                .flat_map(|group| self.index_storage.all_except_latest(n, Some(group)))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Entries holding an item stashed from `path`. Only the manifests the
+    /// index's path cache can't rule out are read.
+    pub fn find_entries_containing_path(
+        &self,
+        path: &Path,
+    ) -> Result<Vec<Uuid>> {
+        let mut matches = Vec::new();
+        // Only a usable cache can rule an entry out
+        let candidates = self.index_storage.list_all().iter().filter(|meta| {
+            meta.cached_paths()
+                .is_none_or(|paths| paths.iter().any(|cached| normalize::same_path(cached, path)))
+        });
+
+        for meta in candidates {
+            let entry = self.load_entry(&meta.uuid)?;
+            if entry.get_item(path).is_some() {
+                matches.push(meta.uuid);
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Refill every entry's cached item paths from its manifest. Returns the
+    /// number of entries whose cache was missing or out of date; manifests
+    /// that can't be read are reported and skipped.
+    pub fn rebuild_path_cache(&mut self) -> Result<usize> {
+        let mut updates = Vec::new();
+        for meta in self.index_storage.list_all() {
+            match self.load_entry(&meta.uuid) {
+                Ok(entry) => updates.push((meta.uuid, entry.item_paths())),
+                Err(err) => eprintln!("Warning: skipping entry {}: {:#}", meta.uuid, err),
+            }
+        }
+
+        self.index_storage.set_item_paths(updates)
+    }
+
+    /// Measure the entry's data archives (`.tar.gz`/`.tar.zst`) and record
+    /// their size in the manifest and index. Returns `None` if the data is
+    /// stored as raw files.
+    pub fn record_compressed_size(&mut self, uuid: &Uuid) -> Result<Option<u64>> {
+        let mut entry = self.load_entry(uuid)?;
+        let size = self.archived_data_size(uuid)?;
+
+        entry.compressed_size_bytes = size;
+        self.write_manifest(&entry)?;
+        self.index_storage.set_compressed_size(uuid, size)?;
+
+        Ok(size)
+    }
+
+    /// Return the entry's data directory, unpacking a compressed entry's
+    /// archive into it first. The flag is true if the caller should remove
+    /// the unpacked directory once done reading.
+    pub fn unpack_data(&self, uuid: &Uuid) -> Result<(PathBuf, bool)> {
+        let data_dir = self.entry_dir(uuid).join("data");
+        let archive = self.entry_dir(uuid).join("data.tar.gz");
+
+        if data_dir.is_dir() || !archive.is_file() {
+            return Ok((data_dir, false));
+        }
+
+        file_compression::decompress(&archive, &data_dir)
+            .with_context(|| format!("Failed to unpack {:?}", archive))?;
+
+        Ok((data_dir, true))
+    }
+
+    fn archived_data_size(&self, uuid: &Uuid) -> Result<Option<u64>> {
+        let entry_dir = self.entry_dir(uuid);
+
+        if entry_dir.join("data").is_dir() {
+            return Ok(None);
+        }
+
+        let mut total = None;
+        for file in fs::read_dir(&entry_dir)? {
+            let file = file?;
+            let name = file.file_name().to_string_lossy().to_lowercase();
+            if name.ends_with(".tar.gz") || name.ends_with(".tar.zst") {
+                *total.get_or_insert(0) += file.metadata()?.len();
+            }
+        }
+
+        Ok(total)
+    }
+
+    fn write_manifest(&self, entry: &Entry) -> Result<()> {
+        let path = self.entry_dir(&entry.uuid).join("manifest.json");
+        let json = storage::to_json(entry)?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    fn entry_dir(&self, uuid: &Uuid) -> PathBuf {
+        self.entries_root.join(uuid.to_string())
+    }
+
+    /// Copy files/directories recursively, keeping permissions and
+    /// modification times
+    fn copy_recursively(&self, src: &Path, dest: &Path) -> Result<()> {
+        self.copy_tree(src, dest, false)
+    }
+
+    /// `copy_recursively`, optionally leaving out hidden entries
+    fn copy_tree(&self, src: &Path, dest: &Path, skip_hidden: bool) -> Result<()> {
+        let entries = walker::walk(src).skip_hidden(skip_hidden).collect::<Result<Vec<_>, _>>()?;
+        self.copy_walked(&entries, dest, self.workers)
+    }
+
+    /// `copy_tree` over the entries of an earlier walk. Directories and
+    /// symlinks are made first, in walk order, so every file has somewhere
+    /// to go; the files are then copied on up to `workers` threads. Once a
+    /// file fails no more are started, and the ones already copied are
+    /// deleted again before the error is returned.
+    fn copy_walked(&self, entries: &[WalkEntry], dest: &Path, workers: usize) -> Result<()> {
+        let mut directories = Vec::new();
+        let mut files = Vec::new();
+
+        for entry in entries {
+            if entry.metadata.is_file() {
+                files.push(entry);
+            } else {
+                self.copy_entry(entry, dest, &mut directories)?;
+            }
+        }
+
+        let copied = Mutex::new(Vec::new());
+        let result = parallel_map(&files, workers, |entry| {
+            let dest_path = entry.under(dest);
+            self.copy_walked_file(entry, &dest_path)?;
+            copied.lock().unwrap_or_else(|e| e.into_inner()).push(dest_path);
+            Ok(())
+        });
+
+        if let Err(err) = result {
+            let copied = copied.into_inner().unwrap_or_else(|e| e.into_inner());
+            for path in &copied {
+                let _ = fs::remove_file(path);
+            }
+            if copied.is_empty() {
+                return Err(err);
+            }
+            return Err(err.context(format!(
+                "Copying into {:?} failed; removed the {} file(s) already copied",
+                dest,
+                copied.len()
+            )));
+        }
+
+        finish_directories(directories)
+    }
+
+    /// Copy one walked entry to its place under `dest`. Writing into a
+    /// directory bumps its mtime and may need permissions it shouldn't end
+    /// up with, so directories are collected and finished by
+    /// `finish_directories` once everything below them has been copied.
+    fn copy_entry(&self, entry: &WalkEntry, dest: &Path, directories: &mut Vec<DirectoryToFinish>) -> Result<()> {
+        let src_path = &entry.path;
+        let dest_path = entry.under(dest);
+        let file_type = entry.metadata.file_type();
+        let mtime = filetime::FileTime::from_last_modification_time(&entry.metadata);
+
+        if file_type.is_dir() {
+            // The walk yields parents first, so only the top needs its parents made
+            match fs::create_dir(&dest_path) {
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => fs::create_dir_all(&dest_path)?,
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && dest_path.is_dir() => {}
+                other => other?,
+            }
+            directories.push((dest_path, entry.metadata.permissions(), mtime));
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            {
+                let target = fs::read_link(src_path)?;
+                std::os::unix::fs::symlink(target, &dest_path)?;
+            }
+            #[cfg(windows)]
+            {
+                fs::copy(src_path, &dest_path)?;
+            }
+            let _ = filetime::set_symlink_file_times(&dest_path, mtime, mtime);
+        } else if special_kind(&file_type).is_some() {
+            // Reading a pipe or device would block or never end; these were
+            // reported when the item was described
+        } else {
+            self.copy_walked_file(entry, &dest_path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy a walked regular file, keeping its modification time
+    fn copy_walked_file(&self, entry: &WalkEntry, dest_path: &Path) -> Result<()> {
+        self.copy_file(&entry.path, dest_path, &entry.metadata)?;
+        let mtime = filetime::FileTime::from_last_modification_time(&entry.metadata);
+        let _ = filetime::set_file_mtime(dest_path, mtime);
+        Ok(())
+    }
+
+    /// Mirror `src` at `dest` with a hard link for every file. Directories
+    /// and symlinks are recreated, since they can't be hard-linked.
+    fn link_tree(&self, src: &Path, dest: &Path, skip_hidden: bool) -> Result<()> {
+        let mut directories = Vec::new();
+
+        for entry in walker::walk(src).skip_hidden(skip_hidden) {
+            let entry = entry?;
+            let dest_path = entry.under(dest);
+            let file_type = entry.metadata.file_type();
+
+            if file_type.is_dir() {
+                fs::create_dir_all(&dest_path)?;
+                directories.push((dest_path, entry.metadata.permissions()));
+            } else if file_type.is_symlink() {
+                create_symlink(&fs::read_link(&entry.path)?, &dest_path)?;
+            } else if special_kind(&file_type).is_none() {
+                fs::hard_link(&entry.path, &dest_path)
+                    .with_context(|| format!("Failed to hard-link {:?}", entry.path))?;
+            }
+        }
+
+        for (path, permissions) in directories.into_iter().rev() {
+            fs::set_permissions(&path, permissions)?;
+        }
+
+        Ok(())
+    }
+
+    /// `copy_file`, also returning the file's hash. A clone shares the
+    /// original's data, so only then is the file read separately.
+    fn copy_file_hashing(&self, src: &Path, dest: &Path, metadata: &fs::Metadata, algorithm: HashAlgorithm) -> Result<String> {
+        if self.reflink && reflink::clone_file(src, dest).is_ok() {
+            self.reflinked.fetch_add(1, Ordering::Relaxed);
+            return Ok(hashing::calculate_hash(src, algorithm)?);
+        }
+
+        let mut hasher = hashing::StreamHasher::new(algorithm);
+        sparse::copy_file_with(src, dest, metadata, self.copy_buffer, |data| hasher.update(data))?;
+        Ok(hasher.finish())
+    }
+
+    /// Clone a file where the filesystem allows it, otherwise copy it
+    fn copy_file(&self, src: &Path, dest: &Path, metadata: &fs::Metadata) -> Result<()> {
+        if self.reflink && reflink::clone_file(src, dest).is_ok() {
+            self.reflinked.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        sparse::copy_file(src, dest, metadata, self.copy_buffer)?;
+        Ok(())
+    }
+
+    /// Move files/directories recursively
+    fn move_recursively(&self, src: &Path, dest: &Path, verify_hashes: Option<HashAlgorithm>) -> Result<()> {
+        // Try simple rename first (works if on same filesystem)
+        if fs::rename(src, dest).is_ok() {
+            return Ok(());
+        }
+
+        // Fall back to copy + delete for cross-filesystem moves. The copy is
+        // built under a temporary name and only renamed into place once it
+        // checks out, so a failed copy never leaves a partial item behind.
+        let staged = temp_sibling(dest);
+        let copied = self
+            .copy_recursively(src, &staged)
+            .and_then(|_| self.verify_copy(src, &staged, verify_hashes, false))
+            .and_then(|_| fs::rename(&staged, dest).map_err(Into::into));
+
+        if let Err(err) = copied {
+            let _ = remove_path(&staged);
+            return Err(err.context(format!(
+                "Failed to move {:?} to {:?}; the original is untouched",
+                src, dest
+            )));
+        }
+
+        // Pipes, sockets and devices aren't copied (see `copy_entry`), so
+        // deleting the whole source would destroy them; only what was
+        // copied is removed and they stay where they were
+        let special = walker::walk(src)
+            .flatten()
+            .filter(|entry| special_kind(&entry.metadata.file_type()).is_some())
+            .count();
+        if special > 0 {
+            remove_copied(src)?;
+            eprintln!(
+                "Warning: left {} special file(s) behind in {:?}; they can't be moved",
+                special, src
+            );
+            return Ok(());
+        }
+
+        // Set the source aside before deleting it: the rename either fully
+        // happens or not at all, so a failure here can still be undone
+        let removed = temp_sibling(src);
+        if let Err(err) = fs::rename(src, &removed) {
+            let _ = remove_path(dest);
+            return Err(anyhow!(err).context(format!(
+                "Failed to remove {:?} after copying it; the original is untouched",
+                src
+            )));
+        }
+
+        if let Err(err) = remove_path(&removed) {
+            eprintln!(
+                "Warning: moved {:?} but could not delete leftover {:?}: {}",
+                src, removed, err
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Check a copy against its source: file sizes always, contents too
+    /// when a hash algorithm is given
+    fn verify_copy(&self, src: &Path, copy: &Path, hashes: Option<HashAlgorithm>, skip_hidden: bool) -> Result<()> {
+        for entry in walker::walk(src).skip_hidden(skip_hidden) {
+            let entry = entry?;
+            if !entry.metadata.is_file() {
+                continue;
+            }
+
+            let src = &entry.path;
+            let copy = entry.under(copy);
+            let copy_len = fs::symlink_metadata(&copy)?.len();
+
+            if entry.metadata.len() != copy_len {
+                return Err(anyhow!(
+                    "Copy of {:?} is incomplete: {} of {} bytes",
+                    src,
+                    copy_len,
+                    entry.metadata.len()
+                ));
+            }
+            let mismatch = match hashes {
+                Some(algorithm) => hashing::calculate_hash(src, algorithm)? != hashing::calculate_hash(&copy, algorithm)?,
+                None => false,
+            };
+            if mismatch {
+                return Err(StashError::Integrity(format!(
+                    "Copy of {:?} does not match the original",
+                    src
+                ))
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Preserve timestamps from source to destination
+    fn preserve_timestamps(&self, src: &Path, dest: &Path) -> Result<()> {
+        if let Ok(metadata) = fs::metadata(src) {
+            if let (Ok(accessed), Ok(modified)) = (metadata.accessed(), metadata.modified()) {
+                let _ = filetime::set_file_times(
+                    dest,
+                    filetime::FileTime::from_system_time(accessed),
+                    filetime::FileTime::from_system_time(modified),
+                );
             }
         }
         Ok(())
@@ -458,8 +2122,882 @@ impl<'a> EntryManager<'a> {
 
     /// Restore specific timestamp to a file
     fn restore_timestamps(&self, path: &Path, modified: DateTime<chrono::Utc>) -> Result<()> {
-        let mtime = filetime::FileTime::from_unix_time(modified.timestamp(), 0);
+        let mtime = filetime::FileTime::from_unix_time(modified.timestamp(), modified.timestamp_subsec_nanos());
         let _ = filetime::set_file_mtime(path, mtime);
         Ok(())
     }
 }
+
+/// A copied directory whose permissions and mtime are set once its
+/// contents are in place
+type DirectoryToFinish = (PathBuf, fs::Permissions, filetime::FileTime);
+
+fn finish_directories(directories: Vec<DirectoryToFinish>) -> Result<()> {
+    for (path, permissions, mtime) in directories.into_iter().rev() {
+        fs::set_permissions(&path, permissions)?;
+        let _ = filetime::set_file_mtime(&path, mtime);
+    }
+
+    Ok(())
+}
+
+/// An item checked and ready to be restored
+struct PendingRestore<'e> {
+    item: &'e Item,
+    src: PathBuf,
+    dest: PathBuf,
+    /// Temporary path next to `dest` holding the item's data
+    staged: Option<PathBuf>,
+    /// `staged` was renamed out of the stash rather than copied
+    moved: bool,
+}
+
+impl PendingRestore<'_> {
+    /// Move the staged item to its destination, recording each rename
+    fn place(&self, placed: &mut Vec<Placement>) -> Result<()> {
+        match &self.staged {
+            Some(staged) => place_path(staged, &self.dest, placed),
+            None => Ok(()),
+        }
+    }
+
+    /// Put a staged item back: rename it into the stash, or drop the copy
+    fn unstage(&self) {
+        let Some(staged) = &self.staged else {
+            return;
+        };
+        let undone = if self.moved {
+            fs::rename(staged, &self.src)
+        } else if exists_locally(staged) {
+            remove_path(staged)
+        } else {
+            Ok(())
+        };
+        if let Err(err) = undone {
+            eprintln!("Warning: could not put back {:?}: {}", staged, err);
+        }
+    }
+}
+
+/// A staged path renamed into place, and what it replaced there
+struct Placement {
+    staged: PathBuf,
+    dest: PathBuf,
+    /// Where whatever was at `dest` was set aside
+    displaced: Option<PathBuf>,
+}
+
+impl Placement {
+    fn undo(&self) {
+        let undone = fs::rename(&self.dest, &self.staged).and_then(|_| match &self.displaced {
+            Some(aside) => fs::rename(aside, &self.dest),
+            None => Ok(()),
+        });
+        if let Err(err) = undone {
+            eprintln!("Warning: could not undo restoring {:?}: {}", self.dest, err);
+        }
+    }
+}
+
+/// Rename `staged` to `dest`. A staged directory is merged into a directory
+/// already at `dest` entry by entry, as a copy into it would be; anything
+/// else at `dest` is set aside.
+fn place_path(staged: &Path, dest: &Path, placed: &mut Vec<Placement>) -> Result<()> {
+    let existing = fs::symlink_metadata(dest).ok();
+    if existing.as_ref().is_some_and(|m| m.is_dir()) && fs::symlink_metadata(staged)?.is_dir() {
+        for child in fs::read_dir(staged)? {
+            let child = child?;
+            place_path(&child.path(), &dest.join(child.file_name()), placed)?;
+        }
+        return Ok(());
+    }
+
+    let displaced = match existing {
+        Some(_) => {
+            let aside = temp_sibling(dest);
+            fs::rename(dest, &aside).with_context(|| format!("Failed to set aside {:?}", dest))?;
+            Some(aside)
+        }
+        None => None,
+    };
+
+    if let Err(err) = fs::rename(staged, dest) {
+        if let Some(aside) = &displaced {
+            let _ = fs::rename(aside, dest);
+        }
+        return Err(anyhow!(err).context(format!("Failed to move {:?} into place", dest)));
+    }
+
+    placed.push(Placement { staged: staged.to_path_buf(), dest: dest.to_path_buf(), displaced });
+    Ok(())
+}
+
+/// A path about to be pushed and what is known about it before anything moves
+struct Staged {
+    path: PathBuf,
+    survey: Survey,
+    /// Hard-link the item into the stash instead of copying or moving it
+    link: bool,
+    /// Hash the item while copying it rather than up front
+    hash_on_copy: Option<HashAlgorithm>,
+    /// Too large to hash under `hash_max_size_mb`
+    hash_skipped: bool,
+}
+
+/// What a single walk of a pushed path turned up
+struct Survey {
+    /// The path's own metadata
+    metadata: fs::Metadata,
+    /// Bytes in regular files, counting hard-linked files once
+    size: u64,
+    special_files: Vec<(PathBuf, &'static str)>,
+    /// Hidden entries the walk left out (only when skipping hidden files)
+    left_in_place: Vec<PathBuf>,
+    /// Everything walked, the path itself first; empty unless requested,
+    /// since a moved item is renamed and never needs it
+    entries: Vec<WalkEntry>,
+}
+
+/// Walk `path` once, recording the entries too if `keep_entries` is set
+fn survey(path: &Path, skip_hidden: bool, keep_entries: bool) -> Result<Survey> {
+    let metadata = fs::symlink_metadata(path)
+        .with_context(|| format!("Failed to read {:?}", path))?;
+
+    if let Some(kind) = special_kind(&metadata.file_type()) {
+        return Err(anyhow!("{:?} is a {} and can't be stashed", path, kind));
+    }
+
+    let mut walk = walker::walk(path).skip_hidden(skip_hidden && metadata.is_dir());
+    let mut size = 0;
+    let mut special_files = Vec::new();
+    let mut entries = Vec::new();
+
+    for entry in walk.by_ref() {
+        let entry = entry?;
+        if entry.metadata.is_file() && !entry.duplicate {
+            size += entry.metadata.len();
+        }
+        if let Some(kind) = special_kind(&entry.metadata.file_type()) {
+            special_files.push((entry.path.clone(), kind));
+        }
+        if keep_entries {
+            entries.push(entry);
+        }
+    }
+
+    Ok(Survey {
+        metadata,
+        size,
+        special_files,
+        left_in_place: walk.skipped_hidden().to_vec(),
+        entries,
+    })
+}
+
+/// Apply `f` to every input on up to `workers` threads, keeping input order.
+/// Once a call fails no new inputs are started, and the first failure (in
+/// input order) is returned; callers undo whatever did complete.
+fn parallel_map<T, R, F>(inputs: &[T], workers: usize, f: F) -> Result<Vec<R>>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R> + Sync,
+{
+    if workers <= 1 || inputs.len() <= 1 {
+        return inputs.iter().map(&f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let slots: Vec<Mutex<Option<Result<R>>>> = inputs.iter().map(|_| Mutex::new(None)).collect();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers.min(inputs.len()) {
+            scope.spawn(|| loop {
+                if failed.load(Ordering::Relaxed) {
+                    break;
+                }
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(i) else {
+                    break;
+                };
+                let result = f(input);
+                if result.is_err() {
+                    failed.store(true, Ordering::Relaxed);
+                }
+                *slots[i].lock().unwrap_or_else(|e| e.into_inner()) = Some(result);
+            });
+        }
+    });
+
+    let mut results = Vec::with_capacity(inputs.len());
+    for slot in slots {
+        match slot.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            Some(Ok(value)) => results.push(value),
+            Some(Err(err)) => return Err(err),
+            // Never started because another input failed
+            None => {}
+        }
+    }
+
+    Ok(results)
+}
+
+/// Describe a named pipe, socket or device file; `None` for anything that
+/// can be stashed
+#[cfg(unix)]
+fn special_kind(file_type: &fs::FileType) -> Option<&'static str> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if file_type.is_fifo() {
+        Some("named pipe")
+    } else if file_type.is_socket() {
+        Some("socket")
+    } else if file_type.is_block_device() || file_type.is_char_device() {
+        Some("device file")
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+fn special_kind(_file_type: &fs::FileType) -> Option<&'static str> {
+    None
+}
+
+/// `entry` narrowed to the items whose stashed path matches one of `only`,
+/// or all of it if `only` is empty. `*` stops at `/`; `**` crosses it.
+pub fn select_items(entry: &Entry, only: &[glob::Pattern]) -> Result<Entry> {
+    if only.is_empty() {
+        return Ok(entry.clone());
+    }
+
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..Default::default()
+    };
+
+    let mut selected = entry.clone();
+    selected
+        .items
+        .retain(|item| only.iter().any(|pattern| pattern.matches_path_with(&item.stashed_path, options)));
+
+    if selected.items.is_empty() {
+        let patterns: Vec<_> = only.iter().map(|p| format!("'{}'", p)).collect();
+        return Err(StashError::NothingMatched(format!(
+            "{} matches none of the {} item(s) in entry '{}'; see 'stash --info {}'",
+            patterns.join(", "),
+            entry.items.len(),
+            entry.display_name(),
+            entry.short_id()
+        ))
+        .into());
+    }
+
+    selected.recalculate_size();
+    Ok(selected)
+}
+
+/// Put items in the manifest order asked for. Items start out sorted by
+/// stashed path, which is the whole order when none is asked for and breaks
+/// ties otherwise, so pushing the same tree twice gives the same manifest.
+fn sort_items(items: &mut [Item], order: SortOrder) {
+    items.sort_by(|a, b| a.stashed_path.cmp(&b.stashed_path));
+    match order {
+        SortOrder::Unsorted => {}
+        SortOrder::Name => items.sort_by(|a, b| a.original_path.cmp(&b.original_path)),
+        SortOrder::Size => items.sort_by_key(|item| std::cmp::Reverse(item.size_bytes)),
+        SortOrder::Mtime => items.sort_by_key(|item| std::cmp::Reverse(item.modified)),
+    }
+}
+
+/// Delete everything below `dir` that isn't hidden, keeping the
+/// directories that still hold hidden entries
+fn remove_visible(dir: &Path) -> Result<()> {
+    let mut directories = Vec::new();
+
+    for entry in walker::walk(dir).skip_hidden(true) {
+        let entry = entry?;
+        if entry.metadata.is_dir() {
+            directories.push(entry.path);
+        } else {
+            fs::remove_file(&entry.path)?;
+        }
+    }
+
+    // Deepest first; a directory that isn't empty holds hidden entries
+    for directory in directories.iter().rev() {
+        let _ = fs::remove_dir(directory);
+    }
+
+    Ok(())
+}
+
+/// Whether anything, including a broken symlink, exists at `path`
+fn exists_locally(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+/// Files a merge of `src` into `dest` would overwrite
+fn merge_targets(src: &Path, dest: &Path) -> Result<Vec<PathBuf>> {
+    let mut targets = Vec::new();
+    for entry in walker::walk(src) {
+        let entry = entry?;
+        if !entry.metadata.is_dir() {
+            let target = entry.under(dest);
+            if exists_locally(&target) {
+                targets.push(target);
+            }
+        }
+    }
+    Ok(targets)
+}
+
+/// A hidden, unique path next to `path`, on the same filesystem
+fn temp_sibling(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.stash-{}", name, Uuid::new_v4()))
+}
+
+/// Remove a file, link or directory tree
+fn remove_path(path: &Path) -> std::io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Whether any file at or below `path` has other hard links to it
+#[cfg(unix)]
+fn has_shared_links(path: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    for entry in walker::walk(path) {
+        let metadata = entry?.metadata;
+        if metadata.is_file() && metadata.nlink() > 1 {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(not(unix))]
+fn has_shared_links(_path: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+/// Delete what `copy_recursively` copied out of `src`: its files and
+/// symlinks, and the directories left empty. Special files and the
+/// directories holding them stay.
+fn remove_copied(src: &Path) -> Result<()> {
+    let entries = walker::walk(src).collect::<Result<Vec<_>, _>>()?;
+
+    for entry in entries.iter().rev() {
+        let file_type = entry.metadata.file_type();
+        if file_type.is_dir() {
+            // Fails, as it should, while a special file is still inside
+            let _ = fs::remove_dir(&entry.path);
+        } else if special_kind(&file_type).is_none() {
+            fs::remove_file(&entry.path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The latest modification time at or below `path`, leaving out hidden
+/// entries when they won't be stashed
+fn newest_mtime(path: &Path, skip_hidden: bool) -> Result<DateTime<Utc>> {
+    let mut newest = None;
+    for entry in walker::walk(path).skip_hidden(skip_hidden) {
+        let modified: DateTime<Utc> = entry?.metadata.modified()?.into();
+        newest = newest.max(Some(modified));
+    }
+    newest.ok_or_else(|| anyhow!("{:?} could not be read", path))
+}
+
+/// Whether two existing paths live on the same filesystem
+#[cfg(unix)]
+fn same_filesystem(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_a: &Path, _b: &Path) -> bool {
+    false
+}
+
+/// The mount point of the filesystem holding the existing path `path`
+#[cfg(unix)]
+fn mount_point(path: &Path) -> PathBuf {
+    use std::os::unix::fs::MetadataExt;
+
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let Ok(device) = fs::metadata(&path).map(|m| m.dev()) else {
+        return path;
+    };
+
+    path.ancestors()
+        .take_while(|dir| fs::metadata(dir).is_ok_and(|m| m.dev() == device))
+        .last()
+        .map_or(path.clone(), Path::to_path_buf)
+}
+
+#[cfg(not(unix))]
+fn mount_point(path: &Path) -> PathBuf {
+    path.ancestors().last().unwrap_or(path).to_path_buf()
+}
+
+/// Create a symbolic link at `dest` pointing to `target`
+#[cfg(unix)]
+fn create_symlink(target: &Path, dest: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, dest)
+        .with_context(|| format!("Failed to create symlink {:?}", dest))
+}
+
+/// Create a symbolic link at `dest` pointing to `target`. Creating links
+/// needs extra privileges on Windows, so failure only warns.
+#[cfg(windows)]
+fn create_symlink(target: &Path, dest: &Path) -> Result<()> {
+    let result = if dest.parent().map(|p| p.join(target)).is_some_and(|t| t.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, dest)
+    } else {
+        std::os::windows::fs::symlink_file(target, dest)
+    };
+
+    if let Err(e) = result {
+        eprintln!(
+            "Warning: could not recreate symlink {} -> {}: {}",
+            dest.display(),
+            target.display(),
+            e
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+#[path = "../../tests/common/read_only.rs"]
+mod read_only;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::os::unix::fs::MetadataExt;
+    #[cfg(unix)]
+    use super::read_only::ReadOnly;
+
+    /// A directory under the temp dir and one on `/dev/shm` (usually tmpfs),
+    /// if the machine has it on a different filesystem
+    #[cfg(unix)]
+    fn two_filesystems() -> Option<(tempfile::TempDir, tempfile::TempDir)> {
+        let disk = tempfile::tempdir().unwrap();
+        let memory = tempfile::tempdir_in("/dev/shm").ok()?;
+        if fs::metadata(disk.path()).unwrap().dev() == fs::metadata(memory.path()).unwrap().dev() {
+            return None;
+        }
+        Some((disk, memory))
+    }
+
+    /// `parent/item/{a.txt, sub/b.txt}`
+    fn tree_in(parent: &Path) -> PathBuf {
+        let item = parent.join("item");
+        fs::create_dir_all(item.join("sub")).unwrap();
+        fs::write(item.join("a.txt"), "alpha").unwrap();
+        fs::write(item.join("sub").join("b.txt"), "beta").unwrap();
+        item
+    }
+
+    fn assert_tree_intact(item: &Path) {
+        assert_eq!(fs::read_to_string(item.join("a.txt")).unwrap(), "alpha");
+        assert_eq!(fs::read_to_string(item.join("sub").join("b.txt")).unwrap(), "beta");
+    }
+
+    #[cfg(unix)]
+    fn names_in(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Run `f` with an entry manager over a scratch stash
+    fn with_manager<T>(f: impl FnOnce(&mut EntryManager) -> T) -> T {
+        let stash = tempfile::tempdir().unwrap();
+        let entries = stash.path().join("entries");
+        let mut index = IndexStorage::new(&stash.path().join("index.json")).unwrap();
+        let mut journal = JournalStorage::new(&stash.path().join("journal.log")).unwrap();
+        let mut manager = EntryManager::new(&entries, &mut index, &mut journal).unwrap();
+        f(&mut manager)
+    }
+
+    /// Options for a hashed push of `paths` as they are, copied or moved
+    fn push_options<'a>(name: &'a Option<String>, copy: &'a bool) -> PushOptions<'a> {
+        PushOptions {
+            name,
+            copy,
+            hardlink: &false,
+            hardlink_fallback: &false,
+            hash: &true,
+            workers: &1,
+            hash_algorithm: &HashAlgorithm::Sha256,
+            hash_max_size: &None,
+            verify: &true,
+            compression: &None,
+            strict: &false,
+            sort_items: &SortOrder::Unsorted,
+            ignore_hidden: &false,
+            min_age: &None,
+            max_age: &None,
+            force: &true,
+            restore_message: &None,
+            metadata: &serde_json::Value::Null,
+            recorded_paths: None,
+            versioned: &false,
+            max_versions: &None,
+            pre_command: &None,
+            verbose: &false,
+        }
+    }
+
+    /// `count` files of up to a few hundred bytes spread over 10 directories
+    fn small_files(root: &Path, count: usize) {
+        for i in 0..count {
+            let dir = root.join(format!("dir{}", i % 10));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(format!("file{}.txt", i)), "x".repeat(i % 300)).unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn cross_filesystem_moves_copy_then_delete() {
+        let Some((disk, memory)) = two_filesystems() else {
+            return;
+        };
+        let src = tree_in(memory.path());
+        let dest = disk.path().join("item");
+
+        with_manager(|manager| manager.move_recursively(&src, &dest, Some(HashAlgorithm::Sha256))).unwrap();
+
+        assert_tree_intact(&dest);
+        assert!(!src.exists());
+        assert!(names_in(memory.path()).is_empty());
+        assert_eq!(names_in(disk.path()), ["item"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_failed_copy_leaves_nothing_behind() {
+        let Some((disk, memory)) = two_filesystems() else {
+            return;
+        };
+        let src = tree_in(memory.path());
+        let data = disk.path().join("data");
+        fs::create_dir(&data).unwrap();
+        let Some(_read_only) = ReadOnly::new(&[&data]) else {
+            return;
+        };
+
+        let err = with_manager(|manager| manager.move_recursively(&src, &data.join("item"), None)).unwrap_err();
+
+        assert!(format!("{:#}", err).contains("the original is untouched"), "{:#}", err);
+        assert_tree_intact(&src);
+        assert!(names_in(&data).is_empty());
+    }
+
+    #[test]
+    fn copies_that_do_not_match_fail_verification() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = tree_in(&dir.path().join("src"));
+        let copy = dir.path().join("copy");
+        with_manager(|manager| manager.copy_recursively(&src, &copy)).unwrap();
+
+        fs::write(copy.join("a.txt"), "ALPHA").unwrap();
+        let err = with_manager(|manager| manager.verify_copy(&src, &copy, Some(HashAlgorithm::Sha256), false)).unwrap_err();
+        assert!(matches!(err.downcast_ref::<StashError>(), Some(StashError::Integrity(_))), "{:#}", err);
+        // Sizes alone can't tell
+        with_manager(|manager| manager.verify_copy(&src, &copy, None, false)).unwrap();
+
+        fs::write(copy.join("a.txt"), "alp").unwrap();
+        let err = with_manager(|manager| manager.verify_copy(&src, &copy, None, false)).unwrap_err();
+        assert!(err.to_string().contains("incomplete: 3 of 5 bytes"), "{:#}", err);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_failed_rename_into_place_removes_the_copy() {
+        let Some((disk, memory)) = two_filesystems() else {
+            return;
+        };
+        let src = tree_in(memory.path());
+        // A non-empty directory can't be replaced by a rename
+        let dest = disk.path().join("item");
+        fs::create_dir(&dest).unwrap();
+        fs::write(dest.join("keep.txt"), "keep").unwrap();
+
+        let err = with_manager(|manager| manager.move_recursively(&src, &dest, None)).unwrap_err();
+
+        assert!(format!("{:#}", err).contains("the original is untouched"), "{:#}", err);
+        assert_tree_intact(&src);
+        assert_eq!(names_in(disk.path()), ["item"]);
+        assert_eq!(names_in(&dest), ["keep.txt"]);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn a_source_that_cannot_be_removed_undoes_the_move() {
+        let Some((disk, memory)) = two_filesystems() else {
+            return;
+        };
+        let parent = disk.path().join("parent");
+        let src = tree_in(&parent);
+        let dest = memory.path().join("item");
+        let Some(_read_only) = ReadOnly::new(&[&parent]) else {
+            return;
+        };
+
+        let err = with_manager(|manager| manager.move_recursively(&src, &dest, None)).unwrap_err();
+
+        assert!(format!("{:#}", err).contains("Failed to remove"), "{:#}", err);
+        assert!(format!("{:#}", err).contains("the original is untouched"), "{:#}", err);
+        assert_tree_intact(&src);
+        assert_eq!(names_in(&parent), ["item"]);
+        assert!(names_in(memory.path()).is_empty());
+    }
+
+    #[test]
+    fn parallel_map_keeps_input_order() {
+        let inputs: Vec<usize> = (0..100).collect();
+
+        let doubled = parallel_map(&inputs, 8, |n| Ok(n * 2)).unwrap();
+
+        assert_eq!(doubled, inputs.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parallel_map_stops_starting_work_after_a_failure() {
+        let inputs: Vec<usize> = (0..10_000).collect();
+        let started = AtomicUsize::new(0);
+
+        let result = parallel_map(&inputs, 4, |&n| {
+            started.fetch_add(1, Ordering::Relaxed);
+            if n == 10 {
+                anyhow::bail!("input {} failed", n);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            Ok(n)
+        });
+
+        assert_eq!(result.unwrap_err().to_string(), "input 10 failed");
+        // Only the inputs already claimed when the failure was seen ran
+        assert!(started.load(Ordering::Relaxed) < 100, "{} inputs started", started.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn parallel_map_reports_the_first_failure_in_input_order() {
+        let inputs: Vec<usize> = (0..4).collect();
+
+        let result = parallel_map(&inputs, 1, |&n| -> Result<usize> {
+            anyhow::bail!("input {} failed", n)
+        });
+
+        assert_eq!(result.unwrap_err().to_string(), "input 0 failed");
+    }
+
+    #[test]
+    fn copied_and_moved_items_are_described_alike() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("original");
+        let copied_tree = original.join("tree");
+        small_files(&copied_tree, 50);
+        fs::write(original.join("single.txt"), "single").unwrap();
+        let moved = dir.path().join("moved");
+        with_manager(|manager| manager.copy_recursively(&original, &moved)).unwrap();
+
+        let name = Some("entry".to_string());
+        let push = |root: &Path, copy: &bool| {
+            let paths = [root.join("tree"), root.join("single.txt")];
+            with_manager(|manager| manager.create_entry(&paths, push_options(&name, copy), root)).unwrap()
+        };
+        let copied = push(&original, &true);
+        let moved = push(&moved, &false);
+
+        assert_eq!(copied.total_size_bytes, moved.total_size_bytes);
+        for (copied, moved) in copied.items.iter().zip(&moved.items) {
+            assert_eq!(copied.stashed_path.file_name(), moved.stashed_path.file_name());
+            assert_eq!(copied.kind, moved.kind);
+            assert_eq!(copied.size_bytes, moved.size_bytes);
+            assert_eq!(copied.permissions, moved.permissions);
+            assert_eq!(copied.modified, moved.modified);
+            assert_eq!(copied.hash, moved.hash);
+        }
+
+        let tree = copied.items.iter().find(|item| item.kind == ItemKind::Directory).unwrap();
+        let size: u64 = walker::walk(&copied_tree).flatten().filter(|e| e.metadata.is_file()).map(|e| e.metadata.len()).sum();
+        assert_eq!(tree.size_bytes, size);
+        let single = copied.items.iter().find(|item| item.kind == ItemKind::File).unwrap();
+        assert_eq!(
+            single.hash.as_deref(),
+            Some(hashing::calculate_hash(&original.join("single.txt"), HashAlgorithm::Sha256).unwrap().as_str())
+        );
+    }
+
+    #[test]
+    fn the_journal_is_pruned_past_its_size_or_age_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let limits = |bytes, age_days| JournalPruneLimits { operations: 0, bytes, age_days };
+
+        with_manager(|manager| {
+            let name = Some("gone".to_string());
+            let gone = manager.create_entry(&[tree_in(dir.path())], push_options(&name, &true), dir.path()).unwrap();
+            manager.delete_entry(&gone.uuid).unwrap();
+            let size = manager.journal_storage.size().unwrap();
+
+            assert_eq!(manager.prune_journal_if_over(limits(0, 0)).unwrap(), 0);
+            assert_eq!(manager.prune_journal_if_over(limits(size, 0)).unwrap(), 0);
+            // The deleted entry's operations are all from just now
+            assert_eq!(manager.prune_journal_if_over(limits(0, 1)).unwrap(), 0);
+
+            assert!(manager.prune_journal_if_over(limits(size - 1, 0)).unwrap() > 0);
+            assert!(manager.journal_storage.for_entry(&gone.uuid).unwrap().is_empty());
+        });
+
+        with_manager(|manager| {
+            let gone = Uuid::new_v4();
+            let mut old = Operation::new(OperationKind::Drop { entry_id: gone, deleted: true });
+            old.timestamp = Utc::now() - chrono::Duration::days(2);
+            manager.journal_storage.append(old).unwrap();
+            manager.journal_storage.append(Operation::new(OperationKind::Dump { entry_count: 0, deleted: false })).unwrap();
+
+            assert_eq!(manager.prune_journal_if_over(limits(0, 3)).unwrap(), 0);
+            assert_eq!(manager.prune_journal_if_over(limits(0, 1)).unwrap(), 1);
+            // What is left is a global operation, so the age limit is not passed again
+            assert_eq!(manager.prune_journal_if_over(limits(0, 1)).unwrap(), 0);
+            assert_eq!(manager.journal_storage.len().unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn parallel_pushes_store_the_same_entry_as_serial_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let roots: Vec<PathBuf> = (0..12)
+            .map(|i| {
+                let root = dir.path().join(format!("tree{}", i));
+                small_files(&root, 20 + i);
+                root
+            })
+            .collect();
+        let name = Some("trees".to_string());
+
+        // The entry's items, and every file under its data directory
+        let push = |workers: usize| {
+            with_manager(|manager| {
+                let options = PushOptions { workers: &workers, ..push_options(&name, &true) };
+                let entry = manager.create_entry(&roots, options, dir.path()).unwrap();
+                let mut files: Vec<_> = walker::walk(&manager.entry_dir(&entry.uuid).join("data"))
+                    .flatten()
+                    .filter(|e| e.metadata.is_file())
+                    .map(|e| (e.relative, fs::read(&e.path).unwrap()))
+                    .collect();
+                files.sort();
+                (serde_json::to_value(&entry.items).unwrap(), files)
+            })
+        };
+        let (serial_items, serial_files) = push(1);
+        let (parallel_items, parallel_files) = push(8);
+
+        assert_eq!(serial_files.len(), (0..12).map(|i| 20 + i).sum::<usize>());
+        assert_eq!(serial_files, parallel_files);
+        assert_eq!(serial_items, parallel_items);
+    }
+
+    #[test]
+    fn only_move_pushes_are_destructive() {
+        let dir = tempfile::tempdir().unwrap();
+        let name = Some("tree".to_string());
+
+        with_manager(|manager| {
+            let item = tree_in(dir.path());
+            let copied = manager.create_entry(std::slice::from_ref(&item), push_options(&name, &true), dir.path()).unwrap();
+            assert!(!copied.was_destructive);
+            assert_tree_intact(&item);
+
+            let moved = manager.create_entry(std::slice::from_ref(&item), push_options(&name, &false), dir.path()).unwrap();
+            assert!(moved.was_destructive);
+            assert!(!item.exists());
+
+            // As stored in the manifests
+            assert!(!manager.load_entry(&copied.uuid).unwrap().was_destructive);
+            assert!(manager.load_entry(&moved.uuid).unwrap().was_destructive);
+        });
+    }
+
+    /// `cargo test --release -- --ignored single_walk_push`
+    #[test]
+    #[ignore = "benchmark; writes 20,000 small files"]
+    fn single_walk_push_beats_separate_size_hash_and_copy_passes() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+        small_files(&tree, 20_000);
+
+        // The push as it was: one walk to size the tree, one to hash every
+        // file, and a third inside the copy
+        let started = Instant::now();
+        let size: u64 = walker::walk(&tree).flatten().map(|e| e.metadata.len()).sum();
+        for entry in walker::walk(&tree).flatten().filter(|e| e.metadata.is_file()) {
+            hashing::calculate_hash(&entry.path, HashAlgorithm::Sha256).unwrap();
+        }
+        with_manager(|manager| manager.copy_recursively(&tree, &dir.path().join("three-pass"))).unwrap();
+        let separate = started.elapsed();
+
+        let name = Some("tree".to_string());
+        let started = Instant::now();
+        let entry = with_manager(|manager| {
+            manager.create_entry(std::slice::from_ref(&tree), push_options(&name, &true), dir.path())
+        })
+        .unwrap();
+        let single = started.elapsed();
+
+        eprintln!("separate passes: {:?}, single walk: {:?}", separate, single);
+        assert!(entry.total_size_bytes <= size);
+        assert!(single < separate, "single walk {:?}, separate passes {:?}", single, separate);
+    }
+
+    #[test]
+    fn path_queries_survive_a_stale_cache_until_it_is_rebuilt() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("notes.txt");
+        fs::write(&file, "notes").unwrap();
+        let name = Some("notes".to_string());
+
+        with_manager(|manager| {
+            let entry = manager.create_entry(std::slice::from_ref(&file), push_options(&name, &true), dir.path()).unwrap();
+            assert_eq!(manager.find_entries_containing_path(&file).unwrap(), [entry.uuid]);
+
+            // A cache with the wrong number of paths is ignored
+            manager.index_storage.set_item_paths(vec![(entry.uuid, Vec::new())]).unwrap();
+            assert_eq!(manager.find_entries_containing_path(&file).unwrap(), [entry.uuid]);
+
+            // One that looks complete but names other paths rules the entry
+            // out until it is rebuilt from the manifest
+            manager.index_storage.set_item_paths(vec![(entry.uuid, vec![dir.path().join("other.txt")])]).unwrap();
+            assert!(manager.find_entries_containing_path(&file).unwrap().is_empty());
+
+            assert_eq!(manager.rebuild_path_cache().unwrap(), 1);
+            assert_eq!(manager.find_entries_containing_path(&file).unwrap(), [entry.uuid]);
+            assert_eq!(manager.rebuild_path_cache().unwrap(), 0);
+        });
+    }
+}
@@ -9,7 +9,7 @@ use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression as GzCompression;
 use tar::Archive;
-use crate::services::filesystem::tape_archives::{create_tar, unpack_tar};
+use crate::services::filesystem::tape_archives::{create_tar, unpack_archive, unpack_tar};
 
 /// Compression level - algorithm is chosen automatically based on level
 #[derive(Debug, Clone, Copy)]
@@ -46,24 +46,91 @@ impl CompressionLevel {
     }
 }
 
+/// Archive formats `decompress` can recognize
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarBz2,
+    TarXz,
+    TarZst,
+    Zip,
+    /// A single gzip-compressed file
+    Gz,
+    /// A single bzip2-compressed file
+    Bz2,
+}
+
+impl ArchiveFormat {
+    fn name(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarBz2 => "tar.bz2",
+            ArchiveFormat::TarXz => "tar.xz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::Zip => "zip",
+            ArchiveFormat::Gz => "gz",
+            ArchiveFormat::Bz2 => "bz2",
+        }
+    }
+}
+
+/// Identify an archive by its magic bytes. The content decides the
+/// compression; the file name only tells a compressed tarball from a single
+/// compressed file (`.gz` vs `.tar.gz`) and names plain tars too old to
+/// carry the `ustar` magic. Files without a telling name, such as downloads
+/// and stdin, are taken to be tarballs.
+pub fn detect_archive_format(path: &Path) -> Result<ArchiveFormat> {
+    let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+    let single = |suffix: &str| name.ends_with(suffix) && !name.trim_end_matches(suffix).ends_with(".tar");
+
+    let kind = infer::get_from_path(path)?;
+    let format = match kind.map(|kind| kind.extension()) {
+        Some("gz") if single(".gz") => ArchiveFormat::Gz,
+        Some("gz") => ArchiveFormat::TarGz,
+        Some("bz2") if single(".bz2") => ArchiveFormat::Bz2,
+        Some("bz2") => ArchiveFormat::TarBz2,
+        Some("xz") => ArchiveFormat::TarXz,
+        Some("zst") => ArchiveFormat::TarZst,
+        Some("zip") => ArchiveFormat::Zip,
+        Some("tar") => ArchiveFormat::Tar,
+        _ if name.ends_with(".tar") || !name.contains('.') => ArchiveFormat::Tar,
+        _ => return Err(anyhow!("Unrecognized archive format: {}", path.display())),
+    };
+
+    Ok(format)
+}
+
+/// The format a new archive should have, going by its file name: `.tar`,
+/// `.tar.gz` or `.tgz`, `.tar.bz2` or `.tbz2`, or `.zip`
+pub fn format_for_name(path: &Path) -> Option<ArchiveFormat> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+
+    [
+        (".tar.gz", ArchiveFormat::TarGz),
+        (".tgz", ArchiveFormat::TarGz),
+        (".tar.bz2", ArchiveFormat::TarBz2),
+        (".tbz2", ArchiveFormat::TarBz2),
+        (".tar", ArchiveFormat::Tar),
+        (".zip", ArchiveFormat::Zip),
+    ]
+    .into_iter()
+    .find(|(suffix, _)| name.ends_with(suffix))
+    .map(|(_, format)| format)
+}
+
 /// Decompress a file into the output folder and return the root path extracted
 pub fn decompress(input: &Path, output: &Path) -> Result<PathBuf> {
     std::fs::create_dir_all(output)?;
 
-    let name = input.file_name().unwrap().to_string_lossy().to_lowercase();
-
-    if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
-        decompress_tar_wrapped(input, output, GzDecoder::new)
-    } else if name.ends_with(".tar.bz2") || name.ends_with(".tbz") || name.ends_with(".tbz2") {
-        decompress_tar_wrapped(input, output, BzDecoder::new)
-    } else if name.ends_with(".tar") {
-        unpack_tar(input, output)
-    } else {
-        match input.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase().as_str() {
-            "gz" => decompress_single(input, output, GzDecoder::new),
-            "bz2" => decompress_single(input, output, BzDecoder::new),
-            ext => Err(anyhow!("Unsupported archive format: {}", ext)),
-        }
+    match detect_archive_format(input)? {
+        ArchiveFormat::Tar => unpack_tar(input, output),
+        ArchiveFormat::TarGz => decompress_tar_wrapped(input, output, GzDecoder::new),
+        ArchiveFormat::TarBz2 => decompress_tar_wrapped(input, output, BzDecoder::new),
+        ArchiveFormat::Gz => decompress_single(input, output, GzDecoder::new),
+        ArchiveFormat::Bz2 => decompress_single(input, output, BzDecoder::new),
+        format => Err(anyhow!("{} archives are not supported yet", format.name())),
     }
 }
 
@@ -87,28 +154,7 @@ where
     F: FnOnce(File) -> R,
 {
     let file = File::open(input)?;
-    let mut archive = Archive::new(wrapper(file));
-
-    // Configure archive to work cross-platform
-    archive.set_preserve_permissions(cfg!(unix));
-    archive.set_preserve_mtime(true);
-    archive.set_unpack_xattrs(false);
-
-    let mut paths = Vec::new();
-
-    for entry in archive.entries()? {
-        let mut entry = entry?;
-        let path = output.join(entry.path()?);
-
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        entry.unpack(&path)?;
-        paths.push(path);
-    }
-
-    Ok(common_root(&paths, output))
+    unpack_archive(Archive::new(wrapper(file)), output)
 }
 
 fn decompress_single<R, F>(input: &Path, output: &Path, wrapper: F) -> Result<PathBuf>
@@ -146,24 +192,3 @@ where
     std::fs::remove_file(&temp_tar)?;
     Ok(())
 }
-
-fn common_root(paths: &[PathBuf], output: &Path) -> PathBuf {
-    if paths.is_empty() {
-        return output.to_path_buf();
-    }
-
-    let first = paths[0].strip_prefix(output).unwrap();
-    let mut components: Vec<_> = first.components().collect();
-
-    for path in &paths[1..] {
-        let path_comps: Vec<_> = path.strip_prefix(output).unwrap().components().collect();
-        components.truncate(
-            components.iter()
-                .zip(&path_comps)
-                .take_while(|(a, b)| a == b)
-                .count()
-        );
-    }
-
-    output.join(components.iter().fold(PathBuf::new(), |acc, c| acc.join(c)))
-}
@@ -1,3 +1,6 @@
 pub mod file_compression;
 pub mod permission_handler;
 pub mod tape_archives;
+pub mod walker;
+pub mod sparse;
+pub mod reflink;
@@ -0,0 +1,12 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Clone `src` to `dest` so both share the same blocks until either is
+/// changed (FICLONE on btrfs and XFS, clonefile on APFS). Fails when the
+/// filesystem can't, or the two are on different devices; nothing is left
+/// behind at `dest` then, so the caller can fall back to a real copy.
+pub fn clone_file(src: &Path, dest: &Path) -> io::Result<()> {
+    reflink_copy::reflink(src, dest)?;
+    fs::set_permissions(dest, fs::metadata(src)?.permissions())
+}
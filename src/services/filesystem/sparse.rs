@@ -0,0 +1,209 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Holes are only punched for runs of zeros at least this long
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Copy buffer used unless `copy_buffer_kb` says otherwise
+pub const DEFAULT_BUFFER: usize = 1024 * 1024;
+
+/// Bytes the file actually occupies on disk
+#[cfg(unix)]
+pub fn disk_usage(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks is always in 512-byte units, whatever the filesystem block size
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+pub fn disk_usage(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+/// Whether a regular file has holes, i.e. uses less disk than its length
+pub fn is_sparse(metadata: &Metadata) -> bool {
+    metadata.is_file() && disk_usage(metadata) < metadata.len()
+}
+
+/// Copy a file like `fs::copy`, but keep sparse files sparse: zero blocks
+/// are skipped over instead of written, so holes aren't filled in.
+///
+/// `metadata` is the source's, usually from the walk that found it, so it
+/// isn't looked up again. Dense files go through `fs::copy`, which leaves the
+/// copying to the kernel where it can (`copy_file_range` or `sendfile` on
+/// Linux, `fcopyfile` on macOS); sparse files are read through a buffer of
+/// `buffer_size` bytes.
+pub fn copy_file(src: &Path, dest: &Path, metadata: &Metadata, buffer_size: usize) -> io::Result<u64> {
+    if !is_sparse(metadata) {
+        return fs::copy(src, dest);
+    }
+
+    copy_buffered(src, dest, metadata, buffer_size, |_| {})
+}
+
+/// `copy_file`, also handing every block of data to `on_data` on its way
+/// through (holes as zeros), so the file is read only once when its contents
+/// are needed as well. Never clones or offloads the copy to the kernel.
+pub fn copy_file_with(
+    src: &Path,
+    dest: &Path,
+    metadata: &Metadata,
+    buffer_size: usize,
+    on_data: impl FnMut(&[u8]),
+) -> io::Result<u64> {
+    copy_buffered(src, dest, metadata, buffer_size, on_data)
+}
+
+fn copy_buffered(
+    src: &Path,
+    dest: &Path,
+    metadata: &Metadata,
+    buffer_size: usize,
+    mut on_data: impl FnMut(&[u8]),
+) -> io::Result<u64> {
+    let sparse = is_sparse(metadata);
+
+    let mut input = File::open(src)?;
+    let mut output = File::create(dest)?;
+    let mut buffer = vec![0u8; buffer_size.max(BLOCK_SIZE)];
+    let mut copied = 0u64;
+
+    loop {
+        let read = read_block(&mut input, &mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        on_data(&buffer[..read]);
+
+        // Holes are looked for block by block, whatever the buffer size
+        for block in buffer[..read].chunks(BLOCK_SIZE) {
+            if sparse && block.iter().all(|&b| b == 0) {
+                output.seek(SeekFrom::Current(block.len() as i64))?;
+            } else {
+                output.write_all(block)?;
+            }
+        }
+        copied += read as u64;
+    }
+
+    // A trailing hole was only seeked over; set_len makes it part of the file
+    output.set_len(copied)?;
+    fs::set_permissions(dest, metadata.permissions())?;
+
+    Ok(copied)
+}
+
+/// Fill `buffer` as far as the file allows
+fn read_block(input: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match input.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    /// A dense file of `len` bytes of varied data
+    fn dense_file(dir: &Path, name: &str, len: usize) -> std::path::PathBuf {
+        let path = dir.join(name);
+        let data: Vec<u8> = (0..len).map(|i| (i * 7 % 253) as u8 + 1).collect();
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    /// Copying as it was done before: `io::copy` through its 8 KB buffer
+    fn naive_copy(src: &Path, dest: &Path) -> io::Result<u64> {
+        io::copy(&mut File::open(src)?, &mut File::create(dest)?)
+    }
+
+    #[test]
+    fn copies_dense_files_whatever_the_buffer() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dense_file(dir.path(), "src", 3 * BLOCK_SIZE + 17);
+        let metadata = fs::metadata(&src).unwrap();
+
+        for buffer in [1, 4 * 1024, BLOCK_SIZE, DEFAULT_BUFFER] {
+            let dest = dir.path().join(format!("dest-{}", buffer));
+            assert_eq!(copy_file(&src, &dest, &metadata, buffer).unwrap(), metadata.len());
+            assert_eq!(fs::read(&dest).unwrap(), fs::read(&src).unwrap());
+
+            let dest = dir.path().join(format!("dest-with-{}", buffer));
+            let mut seen = 0;
+            copy_file_with(&src, &dest, &metadata, buffer, |data| seen += data.len()).unwrap();
+            assert_eq!(seen as u64, metadata.len());
+            assert_eq!(fs::read(&dest).unwrap(), fs::read(&src).unwrap());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_holes_and_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("sparse");
+        let file = File::create(&src).unwrap();
+        file.set_len(64 * 1024 * 1024).unwrap();
+        drop(file);
+        let mut file = fs::OpenOptions::new().write(true).open(&src).unwrap();
+        file.seek(SeekFrom::Start(BLOCK_SIZE as u64 * 3)).unwrap();
+        file.write_all(b"data").unwrap();
+        drop(file);
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o640)).unwrap();
+        let metadata = fs::metadata(&src).unwrap();
+        if !is_sparse(&metadata) {
+            return; // the filesystem filled the holes in
+        }
+
+        let dest = dir.path().join("copy");
+        copy_file(&src, &dest, &metadata, DEFAULT_BUFFER).unwrap();
+
+        let copied = fs::metadata(&dest).unwrap();
+        assert_eq!(copied.len(), metadata.len());
+        assert!(disk_usage(&copied) < copied.len() / 2, "holes were filled in");
+        assert_eq!(copied.permissions().mode() & 0o777, 0o640);
+        assert_eq!(fs::read(&dest).unwrap(), fs::read(&src).unwrap());
+    }
+
+    /// `cargo test --release -- --ignored copy_throughput`
+    #[test]
+    #[ignore = "benchmark; writes about 750 MB"]
+    fn copy_throughput_beats_small_buffered_copies() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dense_file(dir.path(), "large", 256 * 1024 * 1024);
+        let metadata = fs::metadata(&src).unwrap();
+        // Warm the page cache so both sides read from memory
+        naive_copy(&src, &dir.path().join("warm")).unwrap();
+
+        let time = |copy: &dyn Fn(&Path) -> io::Result<u64>, name: &str| {
+            let dest = dir.path().join(name);
+            let started = Instant::now();
+            copy(&dest).unwrap();
+            let elapsed = started.elapsed();
+            fs::remove_file(&dest).unwrap();
+            elapsed
+        };
+
+        let before = time(&|dest| naive_copy(&src, dest), "naive");
+        let kernel = time(&|dest| copy_file(&src, dest, &metadata, DEFAULT_BUFFER), "kernel");
+        let buffered = time(&|dest| copy_file_with(&src, dest, &metadata, DEFAULT_BUFFER, |_| {}), "buffered");
+
+        let rate = |elapsed: std::time::Duration| 256.0 / elapsed.as_secs_f64();
+        eprintln!(
+            "256 MB: 8 KB io::copy {:.0} MB/s, copy_file {:.0} MB/s, buffered {:.0} MB/s",
+            rate(before),
+            rate(kernel),
+            rate(buffered)
+        );
+        assert!(kernel <= before, "copy_file is slower than io::copy");
+        assert!(buffered <= before * 2, "buffered copies fell far behind io::copy");
+    }
+}
@@ -1,13 +1,19 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
 use tar::Archive;
 
-/// Unpack a TAR archive into the output folder
+/// Unpack a TAR archive into the output folder. ustar, GNU (including
+/// sparse files) and PAX archives are all accepted.
 pub fn unpack_tar(input: &Path, output: &Path) -> Result<PathBuf> {
-    let file = File::open(input)?;
-    let mut archive = Archive::new(file);
+    unpack_archive(Archive::new(File::open(input)?), output)
+}
 
+/// Unpack every member of `archive` inside `output`. Archives can come from
+/// anywhere (`--import URL`), so a member that would land outside `output`,
+/// through `..` or a link, is an error rather than being written.
+pub fn unpack_archive<R: Read>(mut archive: Archive<R>, output: &Path) -> Result<PathBuf> {
     // Configure archive to work cross-platform
     archive.set_preserve_permissions(cfg!(unix));
     archive.set_preserve_mtime(true);
@@ -17,14 +23,15 @@ pub fn unpack_tar(input: &Path, output: &Path) -> Result<PathBuf> {
 
     for entry in archive.entries()? {
         let mut entry = entry?;
-        let path = output.join(entry.path()?);
+        let name = entry.path()?.into_owned();
 
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
+        if !entry.unpack_in(output)? {
+            bail!("Refusing to unpack '{}': it would be written outside {}", name.display(), output.display());
         }
 
-        entry.unpack(&path)?;
-        paths.push(path);
+        // unpack_in drops leading `/` and `.` components the same way
+        let relative: PathBuf = name.components().filter(|c| matches!(c, Component::Normal(_))).collect();
+        paths.push(output.join(relative));
     }
 
     Ok(common_root(&paths, output))
@@ -39,6 +46,10 @@ pub fn create_tar(source: &Path, output: &Path) -> Result<()> {
     #[cfg(unix)]
     archive.mode(tar::HeaderMode::Deterministic);
 
+    // Headers are GNU, whose long-name records and base-256 sizes lift
+    // ustar's 255-byte path and 8 GB file limits; holes stay holes
+    archive.sparse(true);
+
     if source.is_dir() {
         archive.append_dir_all(".", source)?;
     } else {
@@ -72,3 +83,112 @@ fn common_root(paths: &[PathBuf], output: &Path) -> PathBuf {
 
     output.join(components.iter().fold(PathBuf::new(), |acc, c| acc.join(c)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom, Write};
+    use std::os::unix::fs::MetadataExt;
+
+    /// A tar holding one file whose name is written into the header as-is,
+    /// bypassing the checks `Header::set_path` makes
+    fn archive_with_member(name: &str) -> Vec<u8> {
+        let mut header = tar::Header::new_gnu();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_cksum();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append(&header, &b"owned"[..]).unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn refuses_members_that_climb_out_of_the_output() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output = scratch.path().join("out");
+        std::fs::create_dir(&output).unwrap();
+
+        let tar = archive_with_member("../../escaped.txt");
+        let result = unpack_archive(Archive::new(&tar[..]), &output);
+
+        assert!(result.is_err());
+        assert!(!scratch.path().join("escaped.txt").exists());
+        assert!(!output.join("escaped.txt").exists());
+    }
+
+    #[test]
+    fn keeps_absolute_members_inside_the_output() {
+        let scratch = tempfile::tempdir().unwrap();
+        let output = scratch.path().join("out");
+        std::fs::create_dir(&output).unwrap();
+
+        let tar = archive_with_member("/etc/stash-test.txt");
+        let root = unpack_archive(Archive::new(&tar[..]), &output).unwrap();
+
+        assert_eq!(std::fs::read(output.join("etc/stash-test.txt")).unwrap(), b"owned");
+        assert!(root.starts_with(&output));
+    }
+
+    #[test]
+    fn round_trips_a_sparse_file_over_8_gb() {
+        let scratch = tempfile::tempdir().unwrap();
+        let source = scratch.path().join("source");
+        std::fs::create_dir(&source).unwrap();
+
+        // 9 GiB, almost all of it a hole, with data at both ends
+        let size = 9 * 1024 * 1024 * 1024;
+        let mut image = File::create(source.join("disk.img")).unwrap();
+        image.write_all(b"start").unwrap();
+        image.seek(SeekFrom::Start(size - 3)).unwrap();
+        image.write_all(b"end").unwrap();
+        drop(image);
+
+        let tar = scratch.path().join("out.tar");
+        create_tar(&source, &tar).unwrap();
+        assert!(std::fs::metadata(&tar).unwrap().len() < 1024 * 1024, "the hole was written out");
+
+        let output = scratch.path().join("out");
+        std::fs::create_dir(&output).unwrap();
+        unpack_tar(&tar, &output).unwrap();
+
+        let restored = output.join("disk.img");
+        let metadata = std::fs::metadata(&restored).unwrap();
+        assert_eq!(metadata.len(), size);
+        assert!(metadata.blocks() * 512 < 1024 * 1024, "the hole was filled in");
+
+        let mut restored = File::open(&restored).unwrap();
+        let mut start = [0; 5];
+        restored.read_exact(&mut start).unwrap();
+        assert_eq!(&start, b"start");
+        let mut end = [0; 3];
+        restored.seek(SeekFrom::Start(size - 3)).unwrap();
+        restored.read_exact(&mut end).unwrap();
+        assert_eq!(&end, b"end");
+    }
+
+    #[test]
+    fn round_trips_a_300_character_path() {
+        let scratch = tempfile::tempdir().unwrap();
+        let source = scratch.path().join("source");
+
+        // Components stay under the 255-byte file name limit
+        let relative: PathBuf = ["a".repeat(100), "b".repeat(100), format!("{}.txt", "c".repeat(96))]
+            .iter()
+            .collect();
+        assert_eq!(relative.as_os_str().len(), 302);
+        std::fs::create_dir_all(source.join(relative.parent().unwrap())).unwrap();
+        std::fs::write(source.join(&relative), "deep").unwrap();
+
+        let tar = scratch.path().join("out.tar");
+        create_tar(&source, &tar).unwrap();
+
+        let output = scratch.path().join("out");
+        std::fs::create_dir(&output).unwrap();
+        unpack_tar(&tar, &output).unwrap();
+
+        assert_eq!(std::fs::read_to_string(output.join(&relative)).unwrap(), "deep");
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::{Result, anyhow};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+/// Trees nested deeper than this are almost certainly a loop or a mistake
+pub const MAX_DEPTH: usize = 512;
+
+/// One path found by [`walk`]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    /// Path below the walk's root; empty for the root itself
+    pub relative: PathBuf,
+    /// Metadata of the path itself; symlinks are never followed
+    pub metadata: Metadata,
+    /// A hard link to a file already yielded earlier in this walk
+    pub duplicate: bool,
+}
+
+impl WalkEntry {
+    /// Where this entry lands when the walked tree is mirrored at `dest`
+    pub fn under(&self, dest: &Path) -> PathBuf {
+        if self.relative.as_os_str().is_empty() {
+            dest.to_path_buf()
+        } else {
+            dest.join(&self.relative)
+        }
+    }
+}
+
+/// Walk `root` and everything below it, parents before children and siblings
+/// in name order, without following symlinks. Directories reached twice
+/// (bind mounts) are skipped, repeated hard links are flagged, and the depth
+/// is capped at [`MAX_DEPTH`].
+pub fn walk(root: &Path) -> Walker {
+    Walker {
+        root: root.to_path_buf(),
+        stack: vec![(root.to_path_buf(), PathBuf::new(), 0)],
+        seen: HashSet::new(),
+        skip_hidden: false,
+        hidden: Vec::new(),
+    }
+}
+
+pub struct Walker {
+    root: PathBuf,
+    stack: Vec<(PathBuf, PathBuf, usize)>,
+    seen: HashSet<(u64, u64)>,
+    skip_hidden: bool,
+    hidden: Vec<PathBuf>,
+}
+
+impl Walker {
+    /// Leave out files and directories below the root whose name starts
+    /// with a dot, along with everything inside them
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.skip_hidden = skip;
+        self
+    }
+
+    /// The hidden entries left out so far by `skip_hidden`, in walk order
+    pub fn skipped_hidden(&self) -> &[PathBuf] {
+        &self.hidden
+    }
+
+    fn visit(&mut self, path: PathBuf, relative: PathBuf, depth: usize) -> Result<Option<WalkEntry>> {
+        let metadata = fs::symlink_metadata(&path)?;
+
+        // Directories are tracked to break cycles, files to spot hard links
+        let first_visit = match file_id(&metadata) {
+            Some(id) if metadata.is_dir() || metadata.is_file() => self.seen.insert(id),
+            _ => true,
+        };
+
+        if metadata.is_dir() {
+            if !first_visit {
+                return Ok(None);
+            }
+            if depth >= MAX_DEPTH {
+                return Err(anyhow!(
+                    "{} has directories nested more than {} levels deep; refusing to go further",
+                    self.root.display(),
+                    MAX_DEPTH
+                ));
+            }
+
+            // read_dir order is up to the filesystem; sorting by name makes
+            // every walk, and so every manifest and archive, reproducible
+            let mut names = fs::read_dir(&path)?
+                .map(|entry| Ok(entry?.file_name()))
+                .collect::<Result<Vec<_>>>()?;
+            names.sort();
+
+            let mut children = Vec::new();
+            for name in names {
+                if self.skip_hidden && is_hidden(&name) {
+                    self.hidden.push(path.join(&name));
+                    continue;
+                }
+                children.push((path.join(&name), relative.join(&name), depth + 1));
+            }
+
+            // Reversed so children come off the stack in directory order
+            self.stack.extend(children.into_iter().rev());
+        }
+
+        Ok(Some(WalkEntry {
+            path,
+            relative,
+            duplicate: metadata.is_file() && !first_visit,
+            metadata,
+        }))
+    }
+}
+
+impl Iterator for Walker {
+    type Item = Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path, relative, depth)) = self.stack.pop() {
+            match self.visit(path, relative, depth) {
+                Ok(Some(entry)) => return Some(Ok(entry)),
+                Ok(None) => continue,
+                Err(err) => {
+                    self.stack.clear();
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Dotfiles and dot-directories
+pub fn is_hidden(name: &OsStr) -> bool {
+    name.as_encoded_bytes().starts_with(b".")
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
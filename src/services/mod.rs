@@ -1,3 +1,5 @@
 pub mod filesystem;
 pub mod storage;
 pub mod entry_manager;
+pub mod remote_sync;
+pub mod download;
@@ -0,0 +1,87 @@
+use anyhow::{Context, Result, bail};
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// rsync exit code for a partial transfer, which is what a missing source file produces
+const RSYNC_PARTIAL_TRANSFER: i32 = 23;
+
+/// Join a file name onto an rsync location (`user@host:path` or a local path)
+pub fn remote_path(remote: &str, name: &str) -> String {
+    format!("{}/{}", remote.trim_end_matches('/'), name)
+}
+
+/// Copy a single file from `source` to the local `dest`.
+/// Returns false if the source does not exist.
+pub fn fetch_file(source: &str, dest: &Path) -> Result<bool> {
+    let output = rsync(&[OsStr::new("-a"), OsStr::new(source), dest.as_os_str()])?;
+
+    if output.status.success() {
+        Ok(true)
+    } else if output.status.code() == Some(RSYNC_PARTIAL_TRANSFER) {
+        Ok(false)
+    } else {
+        bail!("Failed to fetch {}: {}", source, stderr(&output))
+    }
+}
+
+/// Copy the local file `source` to the rsync location `dest`
+pub fn upload_file(source: &Path, dest: &str) -> Result<()> {
+    let output = rsync(&[OsStr::new("-a"), source.as_os_str(), OsStr::new(dest)])?;
+
+    if !output.status.success() {
+        bail!("Failed to upload {}: {}", source.display(), stderr(&output));
+    }
+
+    Ok(())
+}
+
+/// Merge the contents of directory `source` into `dest` without deleting
+/// anything, keeping whichever copy of a file is newer.
+/// Returns one itemized line per transferred file.
+pub fn merge_dirs(source: &OsStr, dest: &OsStr, dry_run: bool) -> Result<Vec<String>> {
+    let source = with_trailing_slash(source);
+    let dest = with_trailing_slash(dest);
+
+    let mut args = vec![
+        OsStr::new("-a"),
+        OsStr::new("--update"),
+        OsStr::new("--itemize-changes"),
+    ];
+    if dry_run {
+        args.push(OsStr::new("--dry-run"));
+    }
+    args.push(&source);
+    args.push(&dest);
+
+    let output = rsync(&args)?;
+
+    if !output.status.success() {
+        bail!("Failed to transfer entries: {}", stderr(&output));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+fn rsync(args: &[&OsStr]) -> Result<Output> {
+    Command::new("rsync")
+        .args(args)
+        .output()
+        .context("Failed to run rsync (is it installed and on PATH?)")
+}
+
+fn with_trailing_slash(path: &OsStr) -> OsString {
+    let mut path = path.to_os_string();
+    if !path.to_string_lossy().ends_with('/') {
+        path.push("/");
+    }
+    path
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).trim().to_string()
+}
@@ -8,16 +8,18 @@ use toml;
 use crate::models::Config;
 
 pub struct ConfigStorage {
+    /// What the config file holds; this is what gets saved
     config: Config,
+    /// `config` with environment overrides applied; this is what gets used
+    effective: Config,
     config_file: PathBuf,
 }
 
 impl ConfigStorage {
     pub fn new(config_file: &Path) -> Result<Self> {
-
-
         let mut storage = Self {
             config: Config::default(),
+            effective: Config::default(),
             config_file: config_file.to_path_buf(),
         };
 
@@ -25,21 +27,60 @@ impl ConfigStorage {
         Ok(storage)
     }
 
+    /// The defaults overridden by `STASH_*` environment variables, for
+    /// running without a config file at all (CI, containers)
+    pub fn from_env() -> Result<Config> {
+        let mut config = Config::default();
+        config.apply_env();
+        Ok(config)
+    }
+
+    /// The config to use for read-only commands, which shouldn't create a
+    /// config file as a side effect
+    pub fn existing(config_file: &Path) -> Result<Config> {
+        if !config_file.exists() {
+            return Self::from_env();
+        }
+
+        Ok(Self::new(config_file)?.get_config().clone())
+    }
+
     /// Loads configuration from config.toml, or creates default if it doesn't exist.
+    /// If it can't be created (e.g. a read-only home), the defaults and the
+    /// environment are used without one.
     pub fn load_config(&mut self) -> Result<()> {
         if !self.config_file.exists() {
-            return self.save_config();
+            if let Err(err) = self.save_config() {
+                eprintln!("Warning: {:#}; using defaults and STASH_* variables", err);
+                self.config = Config::default();
+                self.effective = Self::from_env()?;
+            }
+            return Ok(());
         }
 
         let toml_str = fs::read_to_string(&self.config_file)
             .map_err(|e| io::Error::other(format!("Failed to load config: {}", e)))?;
 
         self.config = toml::from_str(&toml_str).unwrap_or_default();
+        self.refresh_effective();
         Ok(())
     }
 
-    /// Saves the current configuration to config.toml.
-    pub fn save_config(&self) -> Result<()> {
+    fn refresh_effective(&mut self) {
+        self.effective = self.config.clone();
+        self.effective.apply_env();
+    }
+
+    /// Saves the current configuration to config.toml. Environment
+    /// overrides are never written.
+    pub fn save_config(&mut self) -> Result<()> {
+        self.refresh_effective();
+
+        if let Some(parent) = self.config_file.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| io::Error::other(format!("Failed to create config directory: {}", e)))?;
+        }
+
         let toml = toml::to_string_pretty(&self.config)
             .map_err(|e| io::Error::other(format!("Failed to serialize config: {}", e)))?;
 
@@ -49,10 +90,12 @@ impl ConfigStorage {
         Ok(())
     }
 
+    /// The configuration in effect: the file, overridden by the environment
     pub fn get_config(&self) -> &Config {
-        &self.config
+        &self.effective
     }
 
+    /// The file's configuration, to change and then `save_config`
     pub fn get_mut_config(&mut self) -> &mut Config {
         &mut self.config
     }
@@ -83,10 +126,18 @@ impl ConfigStorage {
         let parsed_value = self.convert_value(value)?;
         current.insert(final_key[0].to_string(), parsed_value);
 
-        self.config = root
+        let config: Config = root
             .try_into()
             .map_err(|e| format!("Failed to update config: {}", e))?;
 
+        // Deserializing drops keys the config doesn't have; a key that
+        // doesn't survive the round trip was never a config key
+        let saved = toml::Value::try_from(&config).map_err(|e| format!("Failed to serialize config: {}", e))?;
+        if keys.iter().try_fold(&saved, |value, key| value.get(*key)).is_none() {
+            return Err(format!("No config key '{}'", key_path));
+        }
+        self.config = config;
+
         self.save_config()
             .map_err(|e| format!("Failed to save config: {}", e))
     }
@@ -177,9 +228,12 @@ impl ConfigStorage {
     }
 
     fn convert_value(&self, value: &str) -> Result<toml::Value, String> {
-        // Try TOML literal first
-        if let Ok(parsed) = value.parse::<toml::Value>() {
-            return Ok(parsed);
+        // Try TOML literal first; a bare value isn't a document, so it's
+        // parsed as the value of a key
+        if let Ok(toml::Value::Table(mut table)) = format!("value = {}", value).parse::<toml::Value>() {
+            if let Some(parsed) = table.remove("value") {
+                return Ok(parsed);
+            }
         }
 
         // Fallback to string
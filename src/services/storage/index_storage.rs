@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 use std::{fs};
 use anyhow::{Result, anyhow};
+use chrono::{DateTime, Utc};
 use uuid::Uuid;
 use crate::models::{Index, EntryMetadata};
 
@@ -41,7 +42,7 @@ impl IndexStorage {
             fs::create_dir_all(parent)
                 .map_err(|e| anyhow!("Failed to create index directory: {}", e))?;
         }
-        let json = serde_json::to_string_pretty(&self.stash)
+        let json = super::to_json(&self.stash)
             .map_err(|e| anyhow!("Failed to serialize index: {}", e))?;
         fs::write(&self.stash_file, json)
             .map_err(|e| anyhow!("Failed to write index file: {}", e))?;
@@ -64,8 +65,46 @@ impl IndexStorage {
     }
 
     /// Add a new entry to the index and save
-    pub fn add_entry(&mut self, uuid: Uuid, name: String, size: u64, item_count: usize) -> Result<()> {
-        self.stash.add_entry(uuid, name, size, item_count);
+    pub fn add_entry(&mut self, uuid: Uuid, name: String, size: u64, item_paths: Vec<PathBuf>) -> Result<()> {
+        self.stash.add_entry(uuid, name, size, item_paths);
+        self.save_packages()
+    }
+
+    /// Replace entries' cached item paths, saving once if any changed.
+    /// Returns how many changed; unknown UUIDs are ignored.
+    pub fn set_item_paths(&mut self, updates: Vec<(Uuid, Vec<PathBuf>)>) -> Result<usize> {
+        let mut changed = 0;
+        for (uuid, item_paths) in updates {
+            let Some(entry) = self.stash.entries.iter_mut().find(|e| e.uuid == uuid) else {
+                continue;
+            };
+            if entry.item_paths.as_ref() != Some(&item_paths) {
+                entry.item_paths = Some(item_paths);
+                changed += 1;
+            }
+        }
+
+        if changed > 0 {
+            self.save_packages()?;
+        }
+        Ok(changed)
+    }
+
+    /// Mark or unmark an entry as a template and save
+    pub fn set_template(&mut self, uuid: &Uuid, is_template: bool) -> Result<()> {
+        let entry = self.stash.entries.iter_mut().find(|e| &e.uuid == uuid)
+            .ok_or_else(|| anyhow!("Entry with UUID {} not found", uuid))?;
+
+        entry.is_template = is_template;
+        entry.updated = Some(chrono::Utc::now());
+
+        self.stash.touch();
+        self.save_packages()
+    }
+
+    /// Insert existing entry metadata and save
+    pub fn insert_entry(&mut self, metadata: EntryMetadata) -> Result<()> {
+        self.stash.insert_entry(metadata);
         self.save_packages()
     }
 
@@ -88,6 +127,24 @@ impl IndexStorage {
         self.stash.find_by_name(name)
     }
 
+    /// Every entry sharing a name, lowest version first
+    pub fn versions_of(&self, name: &str) -> Vec<&EntryMetadata> {
+        self.stash.versions_of(name)
+    }
+
+    /// Find a specific version of a name
+    pub fn find_version(&self, name: &str, version: u32) -> Option<&EntryMetadata> {
+        self.stash.find_version(name, version)
+    }
+
+    /// Number an entry as the next version of its name and save
+    pub fn assign_version(&mut self, uuid: &Uuid) -> Result<u32> {
+        let version = self.stash.assign_version(uuid)
+            .ok_or_else(|| anyhow!("Entry with UUID {} not found", uuid))?;
+        self.save_packages()?;
+        Ok(version)
+    }
+
     /// Find entry by identifier (UUID or name)
     pub fn find_by_identifier(&self, identifier: &str) -> Option<&EntryMetadata> {
         self.stash.find_by_identifier(identifier)
@@ -98,13 +155,48 @@ impl IndexStorage {
         self.stash.search(pattern)
     }
 
-    /// Remove entries older than specified days and save
-    pub fn remove_older_than_days(&mut self, days: i64) -> Result<Vec<Uuid>> {
-        let removed = self.stash.remove_older_than_days(days);
-        if !removed.is_empty() {
+    /// Entries created in `[since, until)`, without removing them
+    pub fn created_between(&self, since: Option<DateTime<Utc>>, until: DateTime<Utc>) -> Vec<&EntryMetadata> {
+        self.stash.created_between(since, until)
+    }
+
+    /// Entries beyond the `n` most recent (of `among`, if given), without
+    /// removing them
+    pub fn all_except_latest(&self, n: usize, among: Option<&[Uuid]>) -> Vec<&EntryMetadata> {
+        self.stash.all_except_latest(n, among)
+    }
+
+    /// Drop every entry but the `n` most recently created from the index and
+    /// return their UUIDs. Only the loaded index changes; `save_packages`
+    /// writes it. Delete the entries' data first, so that an entry whose data
+    /// can't be removed is never left out of the index.
+    pub fn remove_all_except_latest(&mut self, n: usize) -> Vec<Uuid> {
+        let removed: Vec<Uuid> = self
+            .stash
+            .all_except_latest(n, None)
+            .iter()
+            .map(|meta| meta.uuid)
+            .collect();
+        self.stash.remove_entries(&removed);
+        removed
+    }
+
+    /// Remove several entries by UUID and save once
+    pub fn remove_entries(&mut self, uuids: &[Uuid]) -> Result<()> {
+        if uuids.is_empty() {
+            return Ok(());
+        }
+        self.stash.remove_entries(uuids);
+        self.save_packages()
+    }
+
+    /// Mark an entry whose data could not be fully deleted and save
+    pub fn flag_removal_failed(&mut self, uuid: &Uuid) -> Result<()> {
+        if let Some(meta) = self.stash.entries.iter_mut().find(|e| &e.uuid == uuid) {
+            meta.removal_failed = true;
             self.save_packages()?;
         }
-        Ok(removed)
+        Ok(())
     }
 
     /// Get the most recently created entry
@@ -127,9 +219,14 @@ impl IndexStorage {
         self.stash.is_empty()
     }
 
-    /// Get the total size of all entries in bytes
+    /// Get the total on-disk size of all entries in bytes,
+    /// counting compressed entries at their compressed size
     pub fn total_size(&self) -> u64 {
-        self.stash.total_size_bytes
+        self.stash
+            .entries
+            .iter()
+            .map(|e| e.compressed_size_bytes.unwrap_or(e.total_size_bytes))
+            .sum()
     }
 
     /// Get the path to the index file
@@ -180,6 +277,7 @@ impl IndexStorage {
     pub fn update_entry_name(&mut self, uuid: &Uuid, name: String) -> Result<()> {
         if let Some(entry) = self.stash.entries.iter_mut().find(|e| &e.uuid == uuid) {
             entry.name = name;
+            entry.updated = Some(chrono::Utc::now());
             self.stash.touch();
             self.save_packages()?;
             Ok(())
@@ -188,6 +286,38 @@ impl IndexStorage {
         }
     }
 
+    /// Exchange two entries' names with a single save, so the index never
+    /// holds both under one name
+    pub fn swap_names(&mut self, uuid1: &Uuid, uuid2: &Uuid) -> Result<()> {
+        let position = |uuid: &Uuid| {
+            self.stash.entries.iter().position(|e| &e.uuid == uuid)
+                .ok_or_else(|| anyhow!("Entry with UUID {} not found", uuid))
+        };
+        let (first, second) = (position(uuid1)?, position(uuid2)?);
+
+        let name = std::mem::take(&mut self.stash.entries[first].name);
+        self.stash.entries[first].name = std::mem::replace(&mut self.stash.entries[second].name, name);
+
+        let now = chrono::Utc::now();
+        self.stash.entries[first].updated = Some(now);
+        self.stash.entries[second].updated = Some(now);
+
+        self.stash.touch();
+        self.save_packages()
+    }
+
+    /// Record (or clear) an entry's compressed size and save
+    pub fn set_compressed_size(&mut self, uuid: &Uuid, size: Option<u64>) -> Result<()> {
+        let entry = self.stash.entries.iter_mut().find(|e| &e.uuid == uuid)
+            .ok_or_else(|| anyhow!("Entry with UUID {} not found", uuid))?;
+
+        entry.compressed_size_bytes = size;
+        entry.updated = Some(chrono::Utc::now());
+
+        self.stash.touch();
+        self.save_packages()
+    }
+
     /// Update an existing entry's metadata and save
     pub fn update_entry_metadata(
         &mut self,
@@ -219,6 +349,8 @@ impl IndexStorage {
                 entry.item_count = entry.item_count.saturating_sub((-item_count_delta) as usize);
             }
 
+            entry.updated = Some(chrono::Utc::now());
+
             self.stash.touch();
             self.save_packages()?;
             Ok(())
@@ -227,3 +359,35 @@ impl IndexStorage {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_all_except_latest_drops_the_oldest_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut storage = IndexStorage::new(&dir.path().join("index.json")).unwrap();
+        let uuids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        for (days, uuid) in uuids.iter().enumerate() {
+            storage.add_entry(*uuid, format!("entry-{}", days), 1, Vec::new()).unwrap();
+            let meta = storage.index_mut().entries.iter_mut().find(|e| e.uuid == *uuid).unwrap();
+            meta.created = Utc::now() - chrono::Duration::days(days as i64);
+        }
+        storage.save_packages().unwrap();
+
+        let mut removed = storage.remove_all_except_latest(2);
+
+        removed.sort();
+        let mut oldest = uuids[2..].to_vec();
+        oldest.sort();
+        assert_eq!(removed, oldest);
+        assert_eq!(storage.entry_count(), 2);
+        assert!(storage.contains(&uuids[0]) && storage.contains(&uuids[1]));
+
+        // Nothing is written until the caller saves
+        storage.reload().unwrap();
+        assert_eq!(storage.entry_count(), 4);
+        assert!(storage.remove_all_except_latest(4).is_empty());
+    }
+}
@@ -1,48 +1,93 @@
-use std::{fs, path::{Path, PathBuf}};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
-use crate::models::Operation;
+use crate::models::{Operation, OperationKind};
 
+/// Bytes read from the end of the journal at first by `recent`; doubled until
+/// enough lines are in view
+const TAIL_WINDOW: u64 = 64 * 1024;
+
+/// The journal is one JSON operation per line, oldest first. Journals written
+/// as a single JSON array by older versions are still read, and rewritten as
+/// lines the next time anything is recorded.
+///
+/// Nothing is parsed up front: appends go straight to the end of the file,
+/// `recent`, `last`, `len` and `count_matching` only read what they need,
+/// and the rest load every operation the first time they are called.
 pub struct JournalStorage {
-    journal: Vec<Operation>,
+    journal: OnceLock<Vec<Operation>>,
     log_file: PathBuf,
 }
 
 impl JournalStorage {
     pub fn new(log_file: &Path) -> Result<Self> {
-        let mut storage = Self {
-            journal: Vec::new(),
+        Ok(Self {
+            journal: OnceLock::new(),
             log_file: log_file.to_path_buf(),
-        };
+        })
+    }
+
+    /// Every operation, read from the journal file on first use
+    fn loaded(&self) -> Result<&Vec<Operation>> {
+        if let Some(journal) = self.journal.get() {
+            return Ok(journal);
+        }
+
+        let journal = self.load_operations()?;
+        Ok(self.journal.get_or_init(|| journal))
+    }
 
-        storage.load_operations()?;
-        Ok(storage)
+    fn loaded_mut(&mut self) -> Result<&mut Vec<Operation>> {
+        self.loaded()?;
+        Ok(self.journal.get_mut().expect("journal loaded above"))
     }
 
-    /// Load all journal from the journal.json file.
-    fn load_operations(&mut self) -> Result<()> {
+    /// Load all journal from the journal.log file.
+    fn load_operations(&self) -> Result<Vec<Operation>> {
         if !self.log_file.exists() {
-            self.journal.clear();
-            return Ok(());
+            return Ok(Vec::new());
         }
 
         let json = fs::read_to_string(&self.log_file)
             .with_context(|| format!("Failed to read journal file {:?}", self.log_file))?;
 
-        self.journal = serde_json::from_str(&json)
-            .with_context(|| "Failed to deserialize journal")?;
+        if json.trim_start().starts_with('[') {
+            return serde_json::from_str(&json).with_context(|| "Failed to deserialize journal");
+        }
 
-        Ok(())
+        json.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| parse_line(line).with_context(|| format!("Failed to deserialize journal line {}", i + 1)))
+            .collect()
+    }
+
+    /// Whether the file still holds the single-array form of older versions
+    fn is_legacy(&self) -> Result<bool> {
+        let mut start = [0u8; 64];
+        let read = match File::open(&self.log_file) {
+            Ok(mut file) => file.read(&mut start)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err).with_context(|| format!("Failed to read journal file {:?}", self.log_file)),
+        };
+
+        Ok(start[..read].iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'['))
     }
 
-    /// Save all journal to the journal.json file.
+    /// Save all journal to the journal.log file.
     pub fn save_operations(&self) -> Result<()> {
-        let json = serde_json::to_string_pretty(&self.journal)
-            .context("Failed to serialize journal")?;
+        let mut lines = String::new();
+        for op in self.loaded()? {
+            lines.push_str(&serde_json::to_string(op).context("Failed to serialize journal")?);
+            lines.push('\n');
+        }
 
-        fs::write(&self.log_file, json)
+        fs::write(&self.log_file, lines)
             .with_context(|| format!("Failed to write journal file {:?}", self.log_file))?;
 
         Ok(())
@@ -50,18 +95,43 @@ impl JournalStorage {
 
     /// Append an operation to the journal
     pub fn append(&mut self, operation: Operation) -> Result<()> {
-        self.journal.push(operation);
-        self.save_operations()
+        if self.journal.get().is_none() && self.is_legacy()? {
+            self.loaded()?;
+        }
+
+        // A loaded journal is kept current; a legacy one is converted by the rewrite
+        if let Some(journal) = self.journal.get_mut() {
+            journal.push(operation);
+            return self.save_operations();
+        }
+
+        let mut line = serde_json::to_string(&operation).context("Failed to serialize journal")?;
+        line.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.log_file)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .with_context(|| format!("Failed to write journal file {:?}", self.log_file))
     }
 
     /// Get the most recent operation
     pub fn last(&self) -> Result<Option<Operation>> {
-        Ok(self.journal.last().cloned())
+        Ok(self.recent(1)?.pop())
+    }
+
+    /// Get the most recent operation whose kind matches the predicate
+    pub fn last_matching<F>(&self, predicate: F) -> Result<Option<&Operation>>
+    where
+        F: Fn(&OperationKind) -> bool,
+    {
+        Ok(self.loaded()?.iter().rev().find(|op| predicate(&op.kind)))
     }
 
     /// Get journal since a specific time
     pub fn since(&self, since: DateTime<Utc>) -> Result<Vec<Operation>> {
-        Ok(self.journal
+        Ok(self.loaded()?
             .iter()
             .filter(|op| op.timestamp > since)
             .cloned()
@@ -70,35 +140,299 @@ impl JournalStorage {
 
     /// Get journal for a specific entry
     pub fn for_entry(&self, entry_id: &Uuid) -> Result<Vec<Operation>> {
-        Ok(self.journal
+        Ok(self.loaded()?
             .iter()
             .filter(|op| op.involves_entry(entry_id))
             .cloned()
             .collect())
     }
 
-    /// Get recent N journal
+    /// Get recent N journal, reading only the end of the file
     pub fn recent(&self, n: usize) -> Result<Vec<Operation>> {
-        let start = self.journal.len().saturating_sub(n);
-        Ok(self.journal[start..].to_vec())
+        if self.journal.get().is_some() || self.is_legacy()? {
+            let journal = self.loaded()?;
+            let start = journal.len().saturating_sub(n);
+            return Ok(journal[start..].to_vec());
+        }
+
+        self.tail_lines(n)?
+            .iter()
+            .map(|line| parse_line(line).context("Failed to deserialize journal"))
+            .collect()
+    }
+
+    /// The last `n` non-empty lines of the journal file
+    fn tail_lines(&self, n: usize) -> Result<Vec<String>> {
+        let mut file = match File::open(&self.log_file) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err).with_context(|| format!("Failed to read journal file {:?}", self.log_file)),
+        };
+        let size = file.metadata()?.len();
+
+        let mut window = TAIL_WINDOW;
+        loop {
+            let start = size.saturating_sub(window);
+            let mut tail = Vec::with_capacity((size - start) as usize);
+            file.seek(SeekFrom::Start(start))?;
+            file.read_to_end(&mut tail)?;
+
+            let text = String::from_utf8_lossy(&tail);
+            let mut lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+            // Unless the window reaches the start, its first line may be cut off
+            let whole = if start == 0 { lines.len() } else { lines.len().saturating_sub(1) };
+
+            if whole >= n || start == 0 {
+                let keep = lines.len().saturating_sub(n.min(whole));
+                return Ok(lines.split_off(keep).into_iter().map(str::to_string).collect());
+            }
+            window = window.saturating_mul(2);
+        }
     }
 
     /// Clear the journal (use with caution!)
     pub fn clear(&mut self) -> Result<()> {
-        self.journal.clear();
+        self.loaded_mut()?.clear();
         self.save_operations()
     }
 
     /// Compact the journal (remove journal for deleted entries)
-    pub fn compact(&mut self, existing_entry_ids: &[Uuid]) -> Result<()> {
-        self.journal.retain(|op| {
+    /// Returns the number of operations removed
+    pub fn compact(&mut self, existing_entry_ids: &[Uuid]) -> Result<usize> {
+        let journal = self.loaded_mut()?;
+        let before = journal.len();
+
+        journal.retain(|op| {
             match op.entry_id() {
                 Some(id) => existing_entry_ids.contains(&id),
                 None => true, // Keep journal without entry_id (e.g. global ops)
             }
         });
 
-        self.save_operations()
+        let removed = before - journal.len();
+        if removed > 0 {
+            self.save_operations()?;
+        }
+
+        Ok(removed)
     }
+
+    /// Merge operations recorded elsewhere, skipping ones already present
+    /// Returns the number of operations added
+    pub fn merge(&mut self, operations: &[Operation]) -> Result<usize> {
+        let journal = self.loaded_mut()?;
+        let before = journal.len();
+
+        for op in operations {
+            if !journal.iter().any(|existing| existing.id == op.id) {
+                journal.push(op.clone());
+            }
+        }
+
+        let added = journal.len() - before;
+        if added > 0 {
+            journal.sort_by_key(|op| op.timestamp);
+            self.save_operations()?;
+        }
+
+        Ok(added)
+    }
+
+    /// Get all recorded operations
+    pub fn operations(&self) -> Result<&[Operation]> {
+        Ok(self.loaded()?)
+    }
+
+    /// Count the operations `predicate` accepts. Line-oriented journals are
+    /// read a line at a time and never held in memory.
+    pub fn count_matching<F>(&self, predicate: F) -> Result<usize>
+    where
+        F: Fn(&Operation) -> bool,
+    {
+        if self.journal.get().is_some() || self.is_legacy()? {
+            return Ok(self.loaded()?.iter().filter(|op| predicate(op)).count());
+        }
+
+        let file = match File::open(&self.log_file) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err).with_context(|| format!("Failed to read journal file {:?}", self.log_file)),
+        };
+
+        let mut count = 0;
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.with_context(|| format!("Failed to read journal file {:?}", self.log_file))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let operation = parse_line(&line).with_context(|| format!("Failed to deserialize journal line {}", i + 1))?;
+            if predicate(&operation) {
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Size of the journal file in bytes, 0 before anything is recorded
+    pub fn size(&self) -> Result<u64> {
+        match fs::metadata(&self.log_file) {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(err) => Err(err).with_context(|| format!("Failed to read journal file {:?}", self.log_file)),
+        }
+    }
+
+    /// Get the number of recorded operations. Line-oriented journals are
+    /// counted without being parsed.
+    pub fn len(&self) -> Result<usize> {
+        if self.journal.get().is_some() || self.is_legacy()? {
+            return Ok(self.loaded()?.len());
+        }
+
+        match fs::read(&self.log_file) {
+            Ok(bytes) => Ok(bytes.split(|b| *b == b'\n').filter(|line| !line.trim_ascii().is_empty()).count()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(err) => Err(err).with_context(|| format!("Failed to read journal file {:?}", self.log_file)),
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Result<Operation> {
+    Ok(serde_json::from_str(line)?)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn push(file_count: usize) -> Operation {
+        Operation::new(OperationKind::Push {
+            entry_id: Uuid::new_v4(),
+            file_count,
+            name: Some(format!("entry-{}", file_count)),
+            size_bytes: Some(file_count as u64),
+        })
+    }
+
+    /// A line-oriented journal of `n` pushes, the i-th with `file_count` i
+    fn journal_of(dir: &Path, n: usize) -> PathBuf {
+        let path = dir.join("journal.log");
+        let mut lines = String::new();
+        for i in 0..n {
+            lines.push_str(&serde_json::to_string(&push(i)).unwrap());
+            lines.push('\n');
+        }
+        fs::write(&path, lines).unwrap();
+        path
+    }
+
+    fn file_counts(operations: &[Operation]) -> Vec<usize> {
+        operations
+            .iter()
+            .map(|op| match op.kind {
+                OperationKind::Push { file_count, .. } => file_count,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recent_reads_only_the_tail_of_a_large_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = journal_of(dir.path(), 50_000);
+
+        let journal = JournalStorage::new(&path).unwrap();
+        let started = Instant::now();
+        let recent = journal.recent(20).unwrap();
+        let tail_time = started.elapsed();
+
+        assert_eq!(file_counts(&recent), (49_980..50_000).collect::<Vec<_>>());
+        assert!(journal.journal.get().is_none(), "recent parsed the whole journal");
+
+        let started = Instant::now();
+        assert_eq!(JournalStorage::new(&path).unwrap().operations().unwrap().len(), 50_000);
+        let full_time = started.elapsed();
+
+        assert!(
+            tail_time * 10 < full_time,
+            "recent(20) took {:?}, loading everything {:?}",
+            tail_time,
+            full_time
+        );
+    }
+
+    #[test]
+    fn appends_do_not_load_the_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = journal_of(dir.path(), 100);
+
+        let mut journal = JournalStorage::new(&path).unwrap();
+        journal.append(push(100)).unwrap();
+
+        assert!(journal.journal.get().is_none());
+        assert_eq!(journal.len().unwrap(), 101);
+        assert_eq!(file_counts(&journal.recent(2).unwrap()), [99, 100]);
+    }
+
+    #[test]
+    fn count_matching_streams_the_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = journal_of(dir.path(), 1_000);
+
+        let journal = JournalStorage::new(&path).unwrap();
+        let even = journal
+            .count_matching(|op| matches!(op.kind, OperationKind::Push { file_count, .. } if file_count % 2 == 0))
+            .unwrap();
+
+        assert_eq!(even, 500);
+        assert!(journal.journal.get().is_none(), "count_matching parsed the whole journal into memory");
+    }
+
+    #[test]
+    fn legacy_array_journals_are_read_and_rewritten_as_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.log");
+        fs::write(&path, serde_json::to_string_pretty(&vec![push(0), push(1)]).unwrap()).unwrap();
+
+        let mut journal = JournalStorage::new(&path).unwrap();
+        assert_eq!(file_counts(&journal.recent(1).unwrap()), [1]);
+        journal.append(push(2)).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+        assert_eq!(file_counts(JournalStorage::new(&path).unwrap().operations().unwrap()), [0, 1, 2]);
+    }
+
+    #[test]
+    fn compact_drops_a_deleted_entrys_operations_and_keeps_global_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.log");
+        let deleted = Uuid::new_v4();
+        let kept = push(1);
+        let kept_id = kept.entry_id().unwrap();
+
+        let mut journal = JournalStorage::new(&path).unwrap();
+        journal
+            .append(Operation::new(OperationKind::Push { entry_id: deleted, file_count: 2, name: None, size_bytes: None }))
+            .unwrap();
+        journal
+            .append(Operation::new(OperationKind::Rename {
+                entry_id: deleted,
+                old_name: "old".to_string(),
+                new_name: "new".to_string(),
+            }))
+            .unwrap();
+        journal.append(kept).unwrap();
+        journal.append(Operation::new(OperationKind::Drop { entry_id: deleted, deleted: true })).unwrap();
+        journal.append(Operation::new(OperationKind::Dump { entry_count: 1, deleted: false })).unwrap();
+
+        assert_eq!(journal.compact(&[kept_id]).unwrap(), 3);
+
+        let journal = JournalStorage::new(&path).unwrap();
+        assert!(journal.for_entry(&deleted).unwrap().is_empty());
+        assert_eq!(journal.for_entry(&kept_id).unwrap().len(), 1);
+        assert_eq!(journal.count_matching(|op| matches!(op.kind, OperationKind::Dump { .. })).unwrap(), 1);
+        assert_eq!(journal.len().unwrap(), 2);
+    }
+}
@@ -5,3 +5,97 @@ pub mod journal_storage;
 pub use journal_storage::JournalStorage;
 pub use index_storage::IndexStorage;
 pub use config_storage::ConfigStorage;
+
+use serde::Serialize;
+use std::sync::OnceLock;
+use crate::utils::paths::AppDirs;
+
+/// Serialize the index or a manifest: indented, or on one line
+/// when `compact_json` is set. serde_json reads both, so stashes written
+/// either way stay readable.
+pub fn to_json<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    if compact_json() {
+        serde_json::to_string(value)
+    } else {
+        serde_json::to_string_pretty(value)
+    }
+}
+
+/// `compact_json` from the config, read once per run. A missing or
+/// unreadable config falls back to the default.
+fn compact_json() -> bool {
+    static COMPACT: OnceLock<bool> = OnceLock::new();
+
+    *COMPACT.get_or_init(|| {
+        ConfigStorage::existing(&AppDirs::global().config_file)
+            .map(|config| config.compact_json)
+            .unwrap_or_default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::entry::Entry;
+    use crate::models::item::{Item, ItemKind};
+    use chrono::Utc;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    fn synthetic_manifest(items: usize) -> Entry {
+        let items = (0..items)
+            .map(|i| {
+                let path = PathBuf::from(format!("src/module{}/file{}.rs", i / 100, i));
+                Item::new(path.clone(), path, ItemKind::File, i as u64, 0o100644, Utc::now(), Some(format!("sha256:{:064x}", i)))
+            })
+            .collect();
+        Entry::new("synthetic".into(), items, PathBuf::from("/work"), true)
+    }
+
+    /// How long parsing `json` as a manifest takes, at best over a few runs
+    fn parse_time(json: &str) -> Duration {
+        (0..5)
+            .map(|_| {
+                let started = Instant::now();
+                let entry: Entry = serde_json::from_str(json).unwrap();
+                assert_eq!(entry.items.len(), 10_000);
+                started.elapsed()
+            })
+            .min()
+            .unwrap()
+    }
+
+    #[test]
+    fn compact_manifests_are_smaller_and_read_the_same() {
+        let entry = synthetic_manifest(10_000);
+        let pretty = serde_json::to_string_pretty(&entry).unwrap();
+        let compact = serde_json::to_string(&entry).unwrap();
+
+        assert!(compact.len() * 10 < pretty.len() * 9, "compact {} vs pretty {} bytes", compact.len(), pretty.len());
+
+        let from_pretty: Entry = serde_json::from_str(&pretty).unwrap();
+        let from_compact: Entry = serde_json::from_str(&compact).unwrap();
+        assert_eq!(serde_json::to_string(&from_pretty).unwrap(), compact);
+        assert_eq!(serde_json::to_string(&from_compact).unwrap(), compact);
+    }
+
+    /// `cargo test --release -- --ignored compact_manifests_parse`
+    #[test]
+    #[ignore = "benchmark"]
+    fn compact_manifests_parse_faster() {
+        let entry = synthetic_manifest(10_000);
+        let pretty = serde_json::to_string_pretty(&entry).unwrap();
+        let compact = serde_json::to_string(&entry).unwrap();
+
+        let pretty_time = parse_time(&pretty);
+        let compact_time = parse_time(&compact);
+
+        eprintln!(
+            "10k items: pretty {} bytes in {:?}, compact {} bytes in {:?}",
+            pretty.len(),
+            pretty_time,
+            compact.len(),
+            compact_time
+        );
+        assert!(compact_time < pretty_time);
+    }
+}
@@ -1,17 +1,69 @@
-pub fn format_bytes(bytes: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-    let mut size = bytes as f64;
-    let mut unit_idx = 0;
+use std::path::Path;
+use crate::models::EntryMetadata;
+
+/// How `--list`, `--search` and `--info` show sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SizeStyle {
+    /// Powers of 1024: `512 B`, `12.3 MB`
+    #[default]
+    Human,
+    /// The exact byte count (`--bytes`)
+    Bytes,
+    /// Powers of 1000 with SI units: `512 B`, `12.9 kB` (`--si`)
+    Si,
+}
 
-    while size >= 1024.0 && unit_idx < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_idx += 1;
+pub fn format_size(bytes: u64, style: SizeStyle) -> String {
+    let (base, units) = match style {
+        SizeStyle::Bytes => return bytes.to_string(),
+        SizeStyle::Human => (1024.0, ["B", "KB", "MB", "GB", "TB"]),
+        SizeStyle::Si => (1000.0, ["B", "kB", "MB", "GB", "TB"]),
+    };
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= base && unit < units.len() - 1 {
+        size /= base;
+        unit += 1;
     }
 
-    if unit_idx == 0 {
-        format!("{} {}", size, UNITS[unit_idx])
+    if unit == 0 {
+        format!("{} {}", bytes, units[0])
     } else {
-        format!("{:.2} {}", size, UNITS[unit_idx])
+        format!("{:.1} {}", size, units[unit])
+    }
+}
+
+/// `<verb> <size> in <seconds>s (<throughput>/s)` for the end of a push or pop
+pub fn transfer_summary(verb: &str, bytes: u64, elapsed: std::time::Duration) -> String {
+    let seconds = elapsed.as_secs_f64();
+    let summary = format!("{} {} in {:.2}s", verb, format_size(bytes, SizeStyle::Human), seconds);
+
+    // Renames finish in microseconds; a rate would be meaningless
+    if seconds < 0.01 {
+        return summary;
+    }
+    format!("{} ({}/s)", summary, format_size((bytes as f64 / seconds) as u64, SizeStyle::Human))
+}
+
+/// How `--list`, `--search` and `--info` show when something happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeStyle {
+    /// `3 hours ago`
+    Relative,
+    /// A local timestamp in the configured `date_format`
+    Absolute,
+    /// `2024-05-01 14:30 (3 hours ago)`
+    Both,
+}
+
+pub fn format_time(time: chrono::DateTime<chrono::Utc>, style: TimeStyle, date_format: &str) -> String {
+    let absolute = || time.with_timezone(&chrono::Local).format(date_format).to_string();
+
+    match style {
+        TimeStyle::Relative => humanize_duration(time),
+        TimeStyle::Absolute => absolute(),
+        TimeStyle::Both => format!("{} ({})", absolute(), humanize_duration(time)),
     }
 }
 
@@ -33,18 +85,54 @@ pub fn humanize_duration(created: chrono::DateTime<chrono::Utc>) -> String {
     }
 }
 
-pub fn humanize_size(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = KB * 1024;
-    const GB: u64 = MB * 1024;
+/// Render a path for terminal output. Control characters (including
+/// newlines) are written as escapes and bytes that aren't valid UTF-8 as
+/// `\xNN`, so hostile file names can't break or spoof the output.
+pub fn escape_path(path: &Path) -> String {
+    let mut escaped = String::new();
 
-    if bytes >= GB {
-        format!("{:.1}GB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.1}MB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.0}KB", bytes as f64 / KB as f64)
-    } else {
-        format!("{}B", bytes)
+    for chunk in path.as_os_str().as_encoded_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\n' => escaped.push_str("\\n"),
+                '\t' => escaped.push_str("\\t"),
+                '\r' => escaped.push_str("\\r"),
+                c if c.is_control() => escaped.push_str(&c.escape_unicode().to_string()),
+                c => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
     }
+
+    escaped
+}
+
+/// Escape a value for a tab-separated porcelain field.
+/// Backslashes, tabs, and line breaks are written C-style (`\\`, `\t`, `\n`, `\r`).
+pub fn porcelain_field(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format an entry as a porcelain line: `uuid\tname\tcreated\tsize\titems`
+pub fn porcelain_entry(meta: &EntryMetadata) -> String {
+    format!(
+        "{}\t{}\t{}\t{}\t{}",
+        meta.uuid,
+        porcelain_field(&meta.name),
+        meta.created.to_rfc3339(),
+        meta.total_size_bytes,
+        meta.item_count
+    )
 }
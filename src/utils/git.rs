@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variable naming the repository to read instead of the CWD
+pub const GIT_DIR_ENV_VAR: &str = "STASH_GIT_DIR";
+
+/// The most recent commit of a repository
+pub struct Commit {
+    pub hash: String,
+    pub time: DateTime<Utc>,
+}
+
+/// Directory whose repository `--since-last-git-commit` looks at
+pub fn repository_dir() -> PathBuf {
+    std::env::var_os(GIT_DIR_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::current_dir().unwrap_or_default())
+}
+
+/// Last commit of the repository containing `dir`, or `None` if git is not
+/// installed, `dir` is not in a repository, or it has no commits yet
+pub fn last_commit(dir: &Path) -> Option<Commit> {
+    let line = git(dir, &["log", "-1", "--format=%h %ci"], &[])?;
+    let (hash, time) = line.trim().split_once(' ')?;
+    let time = DateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S %z").ok()?;
+
+    Some(Commit {
+        hash: hash.to_string(),
+        time: time.with_timezone(&Utc),
+    })
+}
+
+/// Whether `dir` is inside a git work tree
+pub fn is_repository(dir: &Path) -> bool {
+    git(dir, &["rev-parse", "--is-inside-work-tree"], &[])
+        .is_some_and(|out| out.trim() == "true")
+}
+
+/// Those of `paths` that git ignores
+pub fn ignored_paths(dir: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+    // check-ignore exits 1 when nothing is ignored, so read stdout regardless
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["check-ignore", "--"])
+        .args(paths)
+        .output();
+
+    match output {
+        Ok(output) => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| dir.join(line))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Tracked files at or below `paths` with uncommitted changes
+pub fn modified_paths(dir: &Path, paths: &[PathBuf]) -> Vec<PathBuf> {
+    let Some(root) = git(dir, &["rev-parse", "--show-toplevel"], &[]) else {
+        return Vec::new();
+    };
+    let root = PathBuf::from(root.trim());
+
+    // Paths are printed relative to the top of the work tree
+    let Some(out) = git(dir, &["status", "--porcelain", "-z", "--untracked-files=no", "--"], paths) else {
+        return Vec::new();
+    };
+
+    let mut modified = Vec::new();
+    let mut records = out.split('\0').filter(|record| record.len() > 3);
+    while let Some(record) = records.next() {
+        modified.push(root.join(&record[3..]));
+
+        // Renames and copies are followed by their original path
+        if record.starts_with(['R', 'C']) {
+            records.next();
+        }
+    }
+
+    modified
+}
+
+/// Run git in `dir`, returning stdout if it succeeded
+fn git(dir: &Path, args: &[&str], paths: &[PathBuf]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .args(paths)
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout).ok()
+}
@@ -0,0 +1,175 @@
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use crate::models::config::HashAlgorithm;
+
+/// Files at least this large are memory-mapped rather than read
+pub const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// Read size for files hashed through a buffer
+const BUFFER_SIZE: usize = 1024 * 1024;
+
+/// Hash a file's contents, prefixed with the algorithm name
+/// (`sha256:...` or `blake3:...`). Large files are memory-mapped; anything
+/// that can't be mapped is read through a buffer instead.
+pub fn calculate_hash(path: &Path, algorithm: HashAlgorithm) -> std::io::Result<String> {
+    let file = File::open(path)?;
+
+    if file.metadata()?.len() >= MMAP_THRESHOLD {
+        // SAFETY: the map is only read while hashing. If another process
+        // truncates the file meanwhile the read faults, which is the same
+        // risk every mmap-based tool takes; a concurrent write only makes
+        // the hash wrong, as it would with buffered reads.
+        if let Ok(map) = unsafe { Mmap::map(&file) } {
+            let mut hasher = StreamHasher::new(algorithm);
+            hasher.update(&map);
+            return Ok(hasher.finish());
+        }
+    }
+
+    hash_reader(file, algorithm)
+}
+
+/// Hash everything `reader` yields, in the same prefixed form as
+/// [`calculate_hash`]
+pub fn hash_reader(mut reader: impl Read, algorithm: HashAlgorithm) -> std::io::Result<String> {
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+    let mut hasher = StreamHasher::new(algorithm);
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Hashes data fed to it piece by piece, for hashing a file while it is
+/// being copied rather than reading it a second time
+pub enum StreamHasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl StreamHasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => StreamHasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => StreamHasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            StreamHasher::Sha256(hasher) => hasher.update(data),
+            StreamHasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// The hash in the same prefixed form as [`calculate_hash`]
+    pub fn finish(self) -> String {
+        match self {
+            StreamHasher::Sha256(hasher) => format!("sha256:{:x}", hasher.finalize()),
+            StreamHasher::Blake3(hasher) => format!("blake3:{}", hasher.finalize().to_hex()),
+        }
+    }
+}
+
+/// The algorithm a stored hash was made with. Hashes without a known
+/// prefix predate the setting and are SHA-256.
+pub fn algorithm_of(hash: &str) -> HashAlgorithm {
+    if hash.starts_with("blake3:") {
+        HashAlgorithm::Blake3
+    } else {
+        HashAlgorithm::Sha256
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Instant;
+
+    /// Hashing as it was done before: 8 KB reads through a stack buffer
+    fn hash_in_small_reads(path: &Path, algorithm: HashAlgorithm) -> String {
+        let mut file = File::open(path).unwrap();
+        let mut buffer = [0u8; 8192];
+        let mut hasher = StreamHasher::new(algorithm);
+        loop {
+            let bytes_read = file.read(&mut buffer).unwrap();
+            if bytes_read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..bytes_read]);
+        }
+        hasher.finish()
+    }
+
+    /// A file of `len` bytes of varied data
+    fn synthetic_file(len: u64) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let chunk: Vec<u8> = (0..BUFFER_SIZE).map(|i| (i * 31 % 251) as u8).collect();
+        let mut left = len;
+        while left > 0 {
+            let n = left.min(chunk.len() as u64) as usize;
+            file.write_all(&chunk[..n]).unwrap();
+            left -= n as u64;
+        }
+        file.flush().unwrap();
+        file
+    }
+
+    #[test]
+    fn mapped_and_buffered_hashes_agree() {
+        for len in [0, 5, BUFFER_SIZE as u64 + 7, MMAP_THRESHOLD + 3] {
+            let file = synthetic_file(len);
+            for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+                let expected = hash_in_small_reads(file.path(), algorithm);
+                assert_eq!(calculate_hash(file.path(), algorithm).unwrap(), expected, "{} bytes", len);
+                assert_eq!(hash_reader(File::open(file.path()).unwrap(), algorithm).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn hashes_carry_their_algorithm() {
+        let file = synthetic_file(10);
+        let sha = calculate_hash(file.path(), HashAlgorithm::Sha256).unwrap();
+        let blake = calculate_hash(file.path(), HashAlgorithm::Blake3).unwrap();
+
+        assert_eq!(algorithm_of(&sha), HashAlgorithm::Sha256);
+        assert_eq!(algorithm_of(&blake), HashAlgorithm::Blake3);
+        assert_eq!(algorithm_of(&sha["sha256:".len()..]), HashAlgorithm::Sha256);
+    }
+
+    /// `cargo test --release -- --ignored hashing_a_large_file`
+    #[test]
+    #[ignore = "benchmark; writes a 512 MB file"]
+    fn hashing_a_large_file_beats_small_reads() {
+        let file = synthetic_file(512 * 1024 * 1024);
+        // Warm the page cache so both sides read from memory
+        hash_in_small_reads(file.path(), HashAlgorithm::Blake3);
+
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Blake3] {
+            let started = Instant::now();
+            let before = hash_in_small_reads(file.path(), algorithm);
+            let before_time = started.elapsed();
+
+            let started = Instant::now();
+            let after = calculate_hash(file.path(), algorithm).unwrap();
+            let after_time = started.elapsed();
+
+            eprintln!("{:?}: 8 KB reads {:?}, calculate_hash {:?}", algorithm, before_time, after_time);
+            assert_eq!(before, after);
+            assert!(after_time <= before_time, "{:?} hashing got slower", algorithm);
+        }
+    }
+}
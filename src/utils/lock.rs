@@ -0,0 +1,36 @@
+use anyhow::{Result, bail};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Exclusive lock on the stash, released when dropped
+pub struct StashLock {
+    path: PathBuf,
+}
+
+impl StashLock {
+    pub fn acquire(lock_file: &Path) -> Result<Self> {
+        if let Some(parent) = lock_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        match OpenOptions::new().write(true).create_new(true).open(lock_file) {
+            Ok(mut file) => {
+                writeln!(file, "{}", std::process::id())?;
+                Ok(Self { path: lock_file.to_path_buf() })
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => bail!(
+                "Stash is locked by another process.\n\
+                 If no other stash command is running, remove {}",
+                lock_file.display()
+            ),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for StashLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -1,2 +1,9 @@
 pub mod paths;
 pub mod display;
+pub mod lock;
+pub mod pager;
+pub mod git;
+pub mod hashing;
+pub mod serde_path;
+pub mod shell;
+pub mod normalize;
@@ -0,0 +1,214 @@
+//! Comparisons that ignore Unicode normalization. macOS volumes may report
+//! a name in decomposed form (NFD) that Linux stored composed (NFC), so the
+//! same name can arrive as different bytes. Stored paths are never
+//! rewritten; only comparisons go through NFC.
+//!
+//! Case-insensitive filesystems (the macOS and Windows defaults) go one step
+//! further and treat `README.md` and `Readme.md` as the same file.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs;
+use std::path::{Path, PathBuf};
+use unicode_normalization::UnicodeNormalization;
+
+/// `text` in composed form (NFC)
+pub fn nfc(text: &str) -> String {
+    text.nfc().collect()
+}
+
+/// Whether two strings are the same once normalized
+pub fn same_text(a: &str, b: &str) -> bool {
+    a == b || nfc(a) == nfc(b)
+}
+
+/// Whether two paths name the same thing once normalized. Paths that aren't
+/// valid UTF-8 are compared byte for byte.
+pub fn same_path(a: &Path, b: &Path) -> bool {
+    if a == b {
+        return true;
+    }
+
+    match (a.to_str(), b.to_str()) {
+        (Some(a), Some(b)) => nfc(a) == nfc(b),
+        _ => false,
+    }
+}
+
+/// `Path::ends_with`, ignoring normalization
+pub fn path_ends_with(path: &Path, suffix: &Path) -> bool {
+    if path.ends_with(suffix) {
+        return true;
+    }
+
+    match (path.to_str(), suffix.to_str()) {
+        (Some(path), Some(suffix)) => Path::new(&nfc(path)).ends_with(nfc(suffix)),
+        _ => false,
+    }
+}
+
+/// Finds the existing sibling of a path whose name is the same once
+/// normalized but differs in bytes, i.e. the file a normalizing filesystem
+/// would hand back for that path. Each directory is listed once.
+///
+/// On a filesystem that keeps names byte for byte such a sibling is a
+/// separate file, so nothing is looked for there.
+pub struct Twins {
+    enabled: bool,
+    /// Per directory, the names in it keyed by their composed form
+    listings: HashMap<PathBuf, HashMap<String, Vec<String>>>,
+}
+
+impl Twins {
+    /// Twins under `root`, looked for only if its filesystem normalizes names
+    pub fn under(root: &Path) -> Self {
+        Self::new(normalizes_names(root))
+    }
+
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, listings: HashMap::new() }
+    }
+
+    pub fn find(&mut self, path: &Path) -> Option<PathBuf> {
+        if !self.enabled {
+            return None;
+        }
+
+        let name = path.file_name()?.to_str()?;
+        let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let listing = self.listings.entry(parent.to_path_buf()).or_insert_with(|| list_by_nfc(parent));
+
+        listing
+            .get(&nfc(name))?
+            .iter()
+            .find(|other| *other != name)
+            .map(|other| path.with_file_name(other))
+    }
+}
+
+fn list_by_nfc(dir: &Path) -> HashMap<String, Vec<String>> {
+    let mut names: HashMap<String, Vec<String>> = HashMap::new();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return names;
+    };
+
+    for name in entries.flatten().filter_map(|entry| entry.file_name().into_string().ok()) {
+        names.entry(nfc(&name)).or_default().push(name);
+    }
+    names
+}
+
+/// Whether the filesystem holding `path` (or its closest existing ancestor)
+/// treats composed and decomposed spellings of a name as one file, found by
+/// creating a probe file and looking it up decomposed
+pub fn normalizes_names(path: &Path) -> bool {
+    let Some(dir) = path.ancestors().find(|p| p.is_dir()) else {
+        return false;
+    };
+
+    let id = uuid::Uuid::new_v4();
+    let probe = dir.join(format!(".stash-norm-\u{e9}-{}", id));
+    if fs::write(&probe, b"").is_err() {
+        return false;
+    }
+
+    let normalizes = fs::symlink_metadata(dir.join(format!(".stash-norm-e\u{301}-{}", id))).is_ok();
+    let _ = fs::remove_file(&probe);
+    normalizes
+}
+
+/// `text` as a case-insensitive filesystem would compare it
+pub fn fold(text: &str) -> String {
+    nfc(text).to_lowercase()
+}
+
+/// Pairs of paths that are distinct here but name the same file on a
+/// case-insensitive filesystem
+pub fn case_collisions<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Vec<(PathBuf, PathBuf)> {
+    let mut seen: HashMap<String, &Path> = HashMap::new();
+    let mut collisions = Vec::new();
+
+    for path in paths {
+        match seen.entry(fold(&path.to_string_lossy())) {
+            Entry::Occupied(first) if *first.get() != path => {
+                collisions.push((first.get().to_path_buf(), path.to_path_buf()));
+            }
+            Entry::Occupied(_) => {}
+            Entry::Vacant(slot) => {
+                slot.insert(path);
+            }
+        }
+    }
+
+    collisions
+}
+
+/// Whether the filesystem holding `path` (or its closest existing ancestor)
+/// ignores case, found by creating a probe file and looking it up in upper case
+pub fn is_case_insensitive(path: &Path) -> bool {
+    let Some(dir) = path.ancestors().find(|p| p.is_dir()) else {
+        return false;
+    };
+
+    let name = format!(".stash-case-{}", uuid::Uuid::new_v4());
+    let probe = dir.join(&name);
+    if fs::write(&probe, b"").is_err() {
+        return false;
+    }
+
+    let insensitive = fs::symlink_metadata(dir.join(name.to_uppercase())).is_ok();
+    let _ = fs::remove_file(&probe);
+    insensitive
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSED: &str = "caf\u{e9}.txt";
+    const DECOMPOSED: &str = "cafe\u{301}.txt";
+
+    #[test]
+    fn finds_a_twin_where_names_are_normalized() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(DECOMPOSED), b"theirs").unwrap();
+
+        let mut twins = Twins::new(true);
+
+        assert_eq!(twins.find(&dir.path().join(COMPOSED)), Some(dir.path().join(DECOMPOSED)));
+        assert_eq!(twins.find(&dir.path().join(DECOMPOSED)), None);
+    }
+
+    #[test]
+    fn ignores_twins_where_names_are_kept_byte_for_byte() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(DECOMPOSED), b"theirs").unwrap();
+
+        let mut twins = Twins::new(false);
+
+        assert_eq!(twins.find(&dir.path().join(COMPOSED)), None);
+    }
+
+    #[test]
+    fn lists_each_directory_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut twins = Twins::new(true);
+        assert_eq!(twins.find(&dir.path().join(COMPOSED)), None);
+
+        // Created after the listing, so not seen
+        fs::write(dir.path().join(DECOMPOSED), b"theirs").unwrap();
+
+        assert_eq!(twins.find(&dir.path().join(COMPOSED)), None);
+        assert_eq!(twins.listings.len(), 1);
+    }
+
+    #[test]
+    fn probe_agrees_with_the_filesystem() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(COMPOSED), b"").unwrap();
+        let normalizes = fs::symlink_metadata(dir.path().join(DECOMPOSED)).is_ok();
+
+        assert_eq!(normalizes_names(dir.path()), normalizes);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1, "probe file left behind");
+    }
+}
@@ -0,0 +1,35 @@
+use console::Term;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Print text, piping it through `$PAGER` (or `less`) when stdout is a
+/// terminal and the text is taller than it. Falls back to plain printing
+/// if no pager can be started.
+pub fn print_paged(text: &str) {
+    let term = Term::stdout();
+    let fits = text.lines().count() < term.size().0 as usize;
+
+    if !term.is_term() || fits || !try_pager(text) {
+        print!("{}", text);
+    }
+}
+
+fn try_pager(text: &str) -> bool {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -FRX".to_string());
+
+    let mut parts = pager.split_whitespace();
+    let Some(program) = parts.next() else {
+        return false;
+    };
+
+    let Ok(mut child) = Command::new(program).args(parts).stdin(Stdio::piped()).spawn() else {
+        return false;
+    };
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager early closes the pipe; that's fine
+        let _ = stdin.write_all(text.as_bytes());
+    }
+
+    child.wait().is_ok()
+}
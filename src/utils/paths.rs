@@ -1,6 +1,22 @@
 use anyhow::Result;
 use dirs;
-use std::{fs, path::PathBuf};
+use crate::models::Config;
+use crate::models::config::STASH_DIR_ENV_VAR;
+use std::{fs, path::{Component, Path, PathBuf, Prefix}};
+
+/// Name of the stash directory, both in the home directory and in projects
+const STASH_DIR_NAME: &str = ".stash";
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Whether the stash in use is the user's or a project's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StashScope {
+    /// `~/.stash`
+    Global,
+    /// `.stash/` inside a project directory
+    Local,
+}
 
 pub struct AppDirs {
     pub user_dir: PathBuf,
@@ -9,20 +25,57 @@ pub struct AppDirs {
     pub entries_dir: PathBuf,
     pub index_file: PathBuf,
     pub journal_file: PathBuf,
+    pub lock_file: PathBuf,
     pub config_file: PathBuf,
+    pub scope: StashScope,
 }
 
 impl AppDirs {
+    /// Use the nearest project stash above the current directory, if any,
+    /// otherwise the global one
     pub fn new() -> Self {
+        let project = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| Self::find_project_root(&cwd));
+
+        match project {
+            Some(root) => Self::for_project(&root),
+            None => Self::global(),
+        }
+    }
+
+    /// The user's stash: `stash_dir` from the config, or `~/.stash`
+    pub fn global() -> Self {
+        let user_dir = dirs::home_dir().unwrap();
+        let data_dir = global_data_dir();
+
+        Self::with_data_dir(user_dir, data_dir, StashScope::Global)
+    }
+
+    /// The user's stash at an explicit location
+    pub fn global_at(data_dir: &Path) -> Self {
+        let user_dir = dirs::home_dir().unwrap();
+
+        Self::with_data_dir(user_dir, data_dir.to_path_buf(), StashScope::Global)
+    }
+
+    /// A stash kept in `<root>/.stash`; the config stays per-user
+    pub fn for_project(root: &Path) -> Self {
         let user_dir = dirs::home_dir().unwrap();
-        let config_dir = dirs::config_dir().unwrap().join("stash");
+        let data_dir = root.join(STASH_DIR_NAME);
+
+        Self::with_data_dir(user_dir, data_dir, StashScope::Local)
+    }
+
+    fn with_data_dir(user_dir: PathBuf, data_dir: PathBuf, scope: StashScope) -> Self {
+        let config_dir = config_dir();
 
-        let data_dir = user_dir.join(".stash");
         let entries_dir = data_dir.join("entries");
 
         let index_file = data_dir.join("index.json");
         let journal_file = data_dir.join("journal.log");
-        let config_file = config_dir.join("config.toml");
+        let lock_file = data_dir.join("stash.lock");
+        let config_file = config_dir.join(CONFIG_FILE_NAME);
 
         Self {
             user_dir,
@@ -31,10 +84,26 @@ impl AppDirs {
             entries_dir,
             index_file,
             journal_file,
-            config_file
+            lock_file,
+            config_file,
+            scope,
         }
     }
 
+    /// Walk up from `start` looking for a project `.stash/` directory.
+    /// The global stash is never treated as a project stash.
+    fn find_project_root(start: &Path) -> Option<PathBuf> {
+        let global = global_data_dir();
+
+        start
+            .ancestors()
+            .find(|dir| {
+                let candidate = dir.join(STASH_DIR_NAME);
+                candidate.is_dir() && candidate != global
+            })
+            .map(Path::to_path_buf)
+    }
+
     pub fn init(&self) -> Result<()> {
         fs::create_dir_all(&self.data_dir)?;
         fs::create_dir_all(&self.config_dir)?;
@@ -43,3 +112,123 @@ impl AppDirs {
         Ok(())
     }
 }
+
+fn config_dir() -> PathBuf {
+    dirs::config_dir().unwrap().join("stash")
+}
+
+/// `$STASH_DIR`, the configured `stash_dir`, or `~/.stash`. A missing or
+/// unreadable config falls back to the default, as it does everywhere else.
+fn global_data_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os(STASH_DIR_ENV_VAR).filter(|d| !d.is_empty()) {
+        return PathBuf::from(dir);
+    }
+
+    let configured = fs::read_to_string(config_dir().join(CONFIG_FILE_NAME))
+        .ok()
+        .and_then(|toml_str| toml::from_str::<Config>(&toml_str).ok())
+        .and_then(|config| config.stash_dir);
+
+    configured.unwrap_or_else(|| dirs::home_dir().unwrap().join(STASH_DIR_NAME))
+}
+
+/// Turn any user-supplied path into a relative one that stays inside the
+/// directory it is joined to. Joining an absolute or drive-prefixed path
+/// would replace the base instead, so roots, drives and `..` are spelled
+/// out as ordinary directory names.
+pub fn portable_path(path: &Path) -> PathBuf {
+    let mut portable = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(drive) | Prefix::VerbatimDisk(drive) => {
+                    portable.push(format!("drive-{}", (drive as char).to_ascii_uppercase()));
+                }
+                Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+                    portable.push("unc");
+                    portable.push(server);
+                    portable.push(share);
+                }
+                Prefix::Verbatim(name) | Prefix::DeviceNS(name) => {
+                    portable.push("device");
+                    portable.push(name);
+                }
+            },
+            // A drive prefix already marks the path as absolute
+            Component::RootDir if portable.as_os_str().is_empty() => portable.push("root"),
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => portable.push("parent"),
+            Component::Normal(name) => portable.push(name),
+        }
+    }
+
+    // `.` stashes the directory itself
+    if portable.as_os_str().is_empty() {
+        portable.push(".");
+    }
+
+    portable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portable(path: &str) -> PathBuf {
+        portable_path(Path::new(path))
+    }
+
+    #[test]
+    fn relative_paths_are_kept() {
+        assert_eq!(portable("notes.txt"), Path::new("notes.txt"));
+        assert_eq!(portable("./src/./main.rs"), Path::new("src/main.rs"));
+        assert_eq!(portable("."), Path::new("."));
+    }
+
+    #[test]
+    fn parent_components_become_directories() {
+        assert_eq!(
+            portable("../shared/notes.txt"),
+            Path::new("parent/shared/notes.txt")
+        );
+        assert_eq!(portable("a/../../b"), Path::new("a/parent/parent/b"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn absolute_unix_paths_stay_inside_the_base() {
+        let base = Path::new("/stash/entries/1234/data");
+
+        let stashed = portable("/home/me/notes.txt");
+
+        assert_eq!(stashed, Path::new("root/home/me/notes.txt"));
+        assert!(base.join(&stashed).starts_with(base));
+        assert_ne!(stashed, portable("home/me/notes.txt"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn drive_letters_become_directories() {
+        let base = Path::new(r"C:\stash\entries\1234\data");
+
+        let stashed = portable(r"C:\Users\me\notes.txt");
+
+        assert_eq!(stashed, Path::new(r"drive-C\Users\me\notes.txt"));
+        assert!(base.join(&stashed).starts_with(base));
+        assert_eq!(portable(r"d:\notes.txt"), Path::new(r"drive-D\notes.txt"));
+        assert_eq!(portable(r"D:notes.txt"), Path::new(r"drive-D\notes.txt"));
+        assert_eq!(portable(r"\\?\C:\Users\me"), Path::new(r"drive-C\Users\me"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn unc_and_rooted_windows_paths_stay_inside_the_base() {
+        assert_eq!(
+            portable(r"\\server\share\notes.txt"),
+            Path::new(r"unc\server\share\notes.txt")
+        );
+        assert_eq!(portable(r"\Users\me"), Path::new(r"root\Users\me"));
+        assert_ne!(portable(r"C:\notes.txt"), portable(r"D:\notes.txt"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Serde helpers that keep paths byte-exact. Paths that are valid UTF-8 are
+//! written as plain strings, exactly as before; anything else is written as
+//! `{"bytes": [...]}` instead of failing or being mangled.
+//!
+//! Use with `#[serde(with = "crate::utils::serde_path")]`, or the `option`,
+//! `vec` and `option_vec` submodules for `Option<PathBuf>`, `Vec<PathBuf>`
+//! and `Option<Vec<PathBuf>>`.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr {
+    Text(String),
+    Raw { bytes: Vec<u8> },
+}
+
+impl Repr {
+    fn from_path(path: &Path) -> Self {
+        match path.to_str() {
+            Some(text) => Repr::Text(text.to_string()),
+            None => Repr::Raw { bytes: path_bytes(path) },
+        }
+    }
+
+    fn into_path(self) -> PathBuf {
+        match self {
+            Repr::Text(text) => PathBuf::from(text),
+            Repr::Raw { bytes } => PathBuf::from(os_string(bytes)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn os_string(bytes: Vec<u8>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes)
+}
+
+// Elsewhere the bytes are only ever read back by the same platform
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.as_os_str().as_encoded_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn os_string(bytes: Vec<u8>) -> OsString {
+    OsString::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    Repr::from_path(path).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    Ok(Repr::deserialize(deserializer)?.into_path())
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+        path.as_deref().map(Repr::from_path).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<PathBuf>, D::Error> {
+        Ok(Option::<Repr>::deserialize(deserializer)?.map(Repr::into_path))
+    }
+}
+
+pub mod vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(paths: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+        let reprs: Vec<_> = paths.iter().map(|p| Repr::from_path(p)).collect();
+        reprs.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+        Ok(Vec::<Repr>::deserialize(deserializer)?
+            .into_iter()
+            .map(Repr::into_path)
+            .collect())
+    }
+}
+
+pub mod option_vec {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(paths: &Option<Vec<PathBuf>>, serializer: S) -> Result<S::Ok, S::Error> {
+        let reprs: Option<Vec<_>> = paths.as_ref().map(|paths| paths.iter().map(|p| Repr::from_path(p)).collect());
+        reprs.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<PathBuf>>, D::Error> {
+        Ok(Option::<Vec<Repr>>::deserialize(deserializer)?
+            .map(|reprs| reprs.into_iter().map(Repr::into_path).collect()))
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use crate::errors::StashError;
+
+/// Run a user-supplied hook through `sh -c` in `dir`, passing its output
+/// through. A non-zero exit becomes a `CommandFailed` error carrying the
+/// command's exit code. `quiet` sends its stdout, and what `verbose` logs,
+/// to stderr so that porcelain output stays parseable. `verbose` logs the
+/// command and its exit code.
+pub fn run(command: &str, dir: &Path, quiet: bool, verbose: bool) -> Result<()> {
+    let log = |line: String| if quiet { eprintln!("{}", line) } else { println!("{}", line) };
+    if verbose {
+        log(format!("Running: {}", command));
+    }
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .output()
+        .with_context(|| format!("Failed to run `{}`", command))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if quiet {
+        eprint!("{}", stdout);
+    } else {
+        print!("{}", stdout);
+    }
+    eprint!("{}", String::from_utf8_lossy(&output.stderr));
+
+    // Killed by a signal: report it the way shells do
+    let code = output.status.code().unwrap_or_else(|| signal_exit_code(&output.status));
+    if verbose {
+        log(format!("exit code {}", code));
+    }
+
+    if !output.status.success() {
+        return Err(StashError::CommandFailed {
+            command: command.to_string(),
+            code,
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn signal_exit_code(status: &ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal().map_or(1, |signal| 128 + signal)
+}
+
+#[cfg(not(unix))]
+fn signal_exit_code(_status: &ExitStatus) -> i32 {
+    1
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::Sandbox;
+use std::fs;
+
+#[test]
+fn absolute_and_relative_paths_with_the_same_name_are_kept_apart() {
+    let sandbox = Sandbox::new();
+    let elsewhere = sandbox.root().join("elsewhere");
+    fs::create_dir_all(&elsewhere).unwrap();
+    let absolute = elsewhere.join("notes.txt");
+    fs::write(&absolute, "absolute").unwrap();
+    sandbox.write("notes.txt", "relative");
+
+    sandbox.ok([absolute.to_str().unwrap(), "notes.txt", "--name", "both"]);
+
+    let uuid = sandbox.uuid_of("both");
+    let data = sandbox.entry_dir(&uuid).join("data");
+    let stashed: Vec<String> = sandbox.manifest(&uuid)["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["stashed_path"].as_str().unwrap().to_string())
+        .collect();
+    assert!(stashed.contains(&"notes.txt".to_string()), "{:?}", stashed);
+    let nested = format!("root{}", absolute.to_str().unwrap());
+    assert!(stashed.contains(&nested), "{:?}", stashed);
+    assert_eq!(fs::read_to_string(data.join(&nested)).unwrap(), "absolute");
+    assert_eq!(
+        fs::read_to_string(data.join("notes.txt")).unwrap(),
+        "relative"
+    );
+
+    sandbox.ok(["pop", "both"]);
+
+    assert_eq!(fs::read_to_string(&absolute).unwrap(), "absolute");
+    assert_eq!(sandbox.read("notes.txt"), "relative");
+}
+
+#[test]
+fn parent_paths_stay_inside_the_entry() {
+    let sandbox = Sandbox::new();
+    fs::write(sandbox.root().join("outside.txt"), "outside").unwrap();
+
+    sandbox.ok(["../outside.txt", "--name", "up"]);
+
+    let uuid = sandbox.uuid_of("up");
+    assert_eq!(
+        sandbox.manifest(&uuid)["items"][0]["stashed_path"],
+        "parent/outside.txt"
+    );
+    assert!(sandbox
+        .entry_dir(&uuid)
+        .join("data/parent/outside.txt")
+        .exists());
+
+    sandbox.ok(["pop", "up"]);
+    assert_eq!(
+        fs::read_to_string(sandbox.root().join("outside.txt")).unwrap(),
+        "outside"
+    );
+}
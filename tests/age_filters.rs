@@ -0,0 +1,64 @@
+mod common;
+
+use common::{stderr, Sandbox};
+use filetime::FileTime;
+use std::path::Path;
+
+fn age_by_days(path: &Path, days: i64) {
+    let time = FileTime::from_unix_time(chrono::Utc::now().timestamp() - days * 86_400, 0);
+    filetime::set_file_mtime(path, time).unwrap();
+}
+
+#[test]
+fn min_age_skips_items_modified_recently() {
+    let sandbox = Sandbox::new();
+    let old = sandbox.write("old.txt", "old");
+    sandbox.write("new.txt", "new");
+    age_by_days(&old, 40);
+
+    let output = sandbox.run(["old.txt", "new.txt", "--name", "aged", "--min-age", "30d"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Skipped 1 item(s)"), "{}", stderr(&output));
+    assert!(!sandbox.exists("old.txt"));
+    assert!(sandbox.exists("new.txt"));
+}
+
+#[test]
+fn a_directory_is_as_new_as_its_newest_file() {
+    let sandbox = Sandbox::new();
+    let stale = sandbox.write("project/stale.txt", "stale");
+    sandbox.write("project/src/fresh.txt", "fresh");
+    age_by_days(&stale, 40);
+    age_by_days(&sandbox.work.join("project"), 40);
+
+    let output = sandbox.fails(["project", "--min-age", "30d"]);
+
+    assert_eq!(output.status.code(), Some(6));
+    assert!(sandbox.exists("project/src/fresh.txt"));
+}
+
+#[test]
+fn max_age_judges_a_directory_by_its_newest_file() {
+    let sandbox = Sandbox::new();
+    let stale = sandbox.write("project/stale.txt", "stale");
+    sandbox.write("project/src/fresh.txt", "fresh");
+    age_by_days(&stale, 40);
+    age_by_days(&sandbox.work.join("project"), 40);
+
+    sandbox.ok(["project", "--max-age", "1d"]);
+
+    assert!(!sandbox.exists("project"));
+}
+
+#[test]
+fn a_duration_needs_a_unit() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+
+    let output = sandbox.fails(["a.txt", "--min-age", "30"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("needs a unit"), "{}", stderr(&output));
+    assert!(sandbox.exists("a.txt"));
+}
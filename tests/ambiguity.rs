@@ -0,0 +1,116 @@
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use std::io::Write;
+use std::process::{Output, Stdio};
+
+/// `new.txt` exists in the working directory; `old.txt` doesn't, but is
+/// stashed as the entry `old.txt`
+fn mixed(sandbox: &Sandbox) {
+    sandbox.write("old.txt", "old");
+    sandbox.ok(["old.txt"]);
+    sandbox.write("new.txt", "new");
+}
+
+fn names(sandbox: &Sandbox) -> Vec<String> {
+    let mut names: Vec<_> = sandbox.entries().into_iter().map(|(_, name)| name).collect();
+    names.sort();
+    names
+}
+
+/// Run `stash new.txt old.txt` on a terminal, typing `answers`
+fn run_on_terminal(sandbox: &Sandbox, answers: &str) -> Output {
+    let command = format!("'{}' new.txt old.txt", env!("CARGO_BIN_EXE_stash-rs"));
+    let mut child = sandbox
+        .program_in("script", &sandbox.work)
+        .args(["-qec", &command, "/dev/null"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(answers.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn prefer_push_stashes_the_existing_paths() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("ambiguity_mode", "PreferPush");
+    mixed(&sandbox);
+
+    let output = sandbox.run(["new.txt", "old.txt"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("ignoring paths that don't exist: 'old.txt'"), "{}", stderr(&output));
+    assert!(!sandbox.exists("new.txt"));
+    assert!(!sandbox.exists("old.txt"));
+    assert_eq!(names(&sandbox), ["new.txt", "old.txt"]);
+}
+
+#[test]
+fn prefer_pop_restores_the_missing_paths() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("ambiguity_mode", "PreferPop");
+    mixed(&sandbox);
+
+    let output = sandbox.run(["new.txt", "old.txt"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("ignoring paths that exist: 'new.txt'"), "{}", stderr(&output));
+    assert_eq!(sandbox.read("old.txt"), "old");
+    assert_eq!(sandbox.read("new.txt"), "new");
+    assert!(names(&sandbox).is_empty());
+}
+
+#[test]
+fn ask_without_a_terminal_fails_and_changes_nothing() {
+    let sandbox = Sandbox::new();
+    mixed(&sandbox);
+
+    let output = sandbox.fails(["new.txt", "old.txt"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("Ambiguous operation"), "{}", stderr(&output));
+    assert_eq!(sandbox.read("new.txt"), "new");
+    assert_eq!(names(&sandbox), ["old.txt"]);
+}
+
+#[test]
+fn ask_on_a_terminal_can_push() {
+    let sandbox = Sandbox::new();
+    mixed(&sandbox);
+
+    let output = run_on_terminal(&sandbox, "y\n");
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Some paths exist ('new.txt') and some don't ('old.txt')"), "{}", stdout(&output));
+    assert!(!sandbox.exists("new.txt"));
+    assert_eq!(names(&sandbox), ["new.txt", "old.txt"]);
+}
+
+#[test]
+fn ask_on_a_terminal_can_pop() {
+    let sandbox = Sandbox::new();
+    mixed(&sandbox);
+
+    let output = run_on_terminal(&sandbox, "n\ny\n");
+
+    assert!(output.status.success(), "{}", stdout(&output));
+    assert_eq!(sandbox.read("old.txt"), "old");
+    assert_eq!(sandbox.read("new.txt"), "new");
+    assert!(names(&sandbox).is_empty());
+}
+
+#[test]
+fn ask_on_a_terminal_can_do_nothing() {
+    let sandbox = Sandbox::new();
+    mixed(&sandbox);
+
+    let output = run_on_terminal(&sandbox, "n\nn\n");
+
+    assert_eq!(output.status.code(), Some(2), "{}", stdout(&output));
+    assert!(stdout(&output).contains("Nothing done"), "{}", stdout(&output));
+    assert_eq!(sandbox.read("new.txt"), "new");
+    assert_eq!(names(&sandbox), ["old.txt"]);
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::Sandbox;
+use std::fs;
+
+#[test]
+fn backup_holds_the_overwritten_original() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "stashed");
+    sandbox.write("b.txt", "created");
+    sandbox.ok(["a.txt", "b.txt", "--name", "entry"]);
+    sandbox.write("a.txt", "original");
+
+    let stdout = sandbox.ok(["entry", "--force", "--backup"]);
+
+    assert!(stdout.contains("Backed up 1 overwritten file(s)"), "{}", stdout);
+    assert_eq!(sandbox.read("a.txt"), "stashed");
+
+    let (uuid, name) = sandbox.entries().into_iter().next().expect("no backup entry");
+    assert!(name.starts_with("backup-of-entry-"), "{}", name);
+    let items = sandbox.manifest(&uuid)["items"].as_array().unwrap().clone();
+    assert_eq!(items.len(), 1, "only the overwritten file is backed up");
+    assert_eq!(items[0]["original_path"], "a.txt");
+    let data = sandbox.entry_dir(&uuid).join("data").join(items[0]["stashed_path"].as_str().unwrap());
+    assert_eq!(fs::read_to_string(data).unwrap(), "original");
+}
+
+#[test]
+fn popping_the_backup_undoes_the_overwrite() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "stashed");
+    sandbox.ok(["a.txt", "--name", "entry"]);
+    sandbox.write("a.txt", "original");
+    sandbox.ok(["entry", "--force", "--backup"]);
+    let (_, backup) = sandbox.entries().into_iter().next().unwrap();
+
+    sandbox.ok([backup.as_str(), "--force"]);
+
+    assert_eq!(sandbox.read("a.txt"), "original");
+}
+
+#[test]
+fn nothing_is_backed_up_when_nothing_is_overwritten() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "stashed");
+    sandbox.ok(["a.txt", "--name", "entry"]);
+
+    sandbox.ok(["entry", "--force", "--backup"]);
+
+    assert!(sandbox.entries().is_empty());
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use common::{stderr, Sandbox};
+
+/// One entry per `(name, created)`, created at the given RFC 3339 time
+fn stash_created(sandbox: &Sandbox, entries: &[(&str, String)]) {
+    for (name, created) in entries {
+        sandbox.write(name, *name);
+        sandbox.ok([*name, "--name", name]);
+        sandbox.set_created(name, created);
+    }
+}
+
+fn days_ago(days: i64) -> String {
+    (Utc::now() - Duration::days(days)).to_rfc3339()
+}
+
+fn names(sandbox: &Sandbox) -> Vec<String> {
+    let mut names: Vec<String> = sandbox.entries().into_iter().map(|(_, name)| name).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn since_and_until_clean_only_the_window() {
+    let sandbox = Sandbox::new();
+    stash_created(
+        &sandbox,
+        &[
+            ("december", "2023-12-15T12:00:00Z".into()),
+            ("february", "2024-02-15T12:00:00Z".into()),
+            ("april", "2024-04-15T12:00:00Z".into()),
+            ("recent", days_ago(1)),
+        ],
+    );
+
+    sandbox.ok(["--clean", "--since", "2024-01-01", "--until", "2024-03-01"]);
+
+    assert_eq!(names(&sandbox), ["april", "december", "recent"]);
+}
+
+#[test]
+fn since_alone_cleans_up_to_now() {
+    let sandbox = Sandbox::new();
+    stash_created(
+        &sandbox,
+        &[("today", days_ago(0)), ("last-week", days_ago(7)), ("last-month", days_ago(40))],
+    );
+
+    sandbox.ok(["--clean", "--since", "10d"]);
+
+    assert_eq!(names(&sandbox), ["last-month"]);
+}
+
+#[test]
+fn until_alone_cleans_everything_before_it() {
+    let sandbox = Sandbox::new();
+    stash_created(
+        &sandbox,
+        &[("old", "2024-01-15T12:00:00Z".into()), ("new", days_ago(1))],
+    );
+
+    sandbox.ok(["clean", "--until", "2024-06-01"]);
+
+    assert_eq!(names(&sandbox), ["new"]);
+}
+
+#[test]
+fn days_alone_cleans_older_entries() {
+    let sandbox = Sandbox::new();
+    stash_created(&sandbox, &[("fresh", days_ago(2)), ("stale", days_ago(10))]);
+
+    sandbox.ok(["--clean", "7"]);
+
+    assert_eq!(names(&sandbox), ["fresh"]);
+}
+
+#[test]
+fn a_dry_run_lists_only_the_window() {
+    let sandbox = Sandbox::new();
+    stash_created(&sandbox, &[("inside", days_ago(3)), ("outside", days_ago(30))]);
+
+    let output = sandbox.fails(["--clean", "--since", "5d", "--dry-run", "--porcelain"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    let listed = String::from_utf8_lossy(&output.stdout);
+    assert!(listed.contains("inside") && !listed.contains("outside"), "{}", listed);
+    assert_eq!(names(&sandbox), ["inside", "outside"]);
+}
+
+#[test]
+fn a_bare_number_is_not_a_point_in_time() {
+    let sandbox = Sandbox::new();
+    stash_created(&sandbox, &[("entry", days_ago(1))]);
+
+    let output = sandbox.fails(["--clean", "--since", "2024"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("ambiguous"), "{}", stderr(&output));
+    assert_eq!(names(&sandbox), ["entry"]);
+}
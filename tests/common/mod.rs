@@ -0,0 +1,222 @@
+//! Runs the stash binary against a scratch home directory, so tests never
+//! touch the user's stash or config
+#![allow(dead_code)]
+
+use serde_json::Value;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use tempfile::TempDir;
+
+#[cfg(unix)]
+pub mod read_only;
+
+pub struct Sandbox {
+    root: TempDir,
+    pub home: PathBuf,
+    /// The directory commands run in unless told otherwise
+    pub work: PathBuf,
+}
+
+impl Sandbox {
+    /// A fresh home with an initialized global stash and an empty working
+    /// directory
+    pub fn new() -> Self {
+        let root = tempfile::tempdir().unwrap();
+        let home = root.path().join("home");
+        let work = root.path().join("work");
+        fs::create_dir_all(&home).unwrap();
+        fs::create_dir_all(&work).unwrap();
+
+        let sandbox = Self { root, home, work };
+        sandbox.ok(["--init"]);
+        sandbox
+    }
+
+    /// Scratch space next to the home and working directories
+    pub fn root(&self) -> &Path {
+        self.root.path()
+    }
+
+    pub fn stash_dir(&self) -> PathBuf {
+        self.home.join(".stash")
+    }
+
+    pub fn config_file(&self) -> PathBuf {
+        self.home.join(".config").join("stash").join("config.toml")
+    }
+
+    /// The binary, run in `dir` with the sandbox's home and no `STASH_*`
+    /// variables from the environment the tests run in
+    pub fn command_in(&self, dir: &Path) -> Command {
+        self.program_in(env!("CARGO_BIN_EXE_stash-rs"), dir)
+    }
+
+    /// `program` run like `command_in` runs the binary, for wrappers such as
+    /// `script` that run it in turn
+    pub fn program_in(&self, program: impl AsRef<OsStr>, dir: &Path) -> Command {
+        let mut command = Command::new(program);
+        command
+            .current_dir(dir)
+            .env("HOME", &self.home)
+            .env("XDG_CONFIG_HOME", self.home.join(".config"))
+            .stdin(Stdio::null());
+        for (key, _) in std::env::vars_os() {
+            if key.to_string_lossy().starts_with("STASH_") {
+                command.env_remove(key);
+            }
+        }
+        command
+    }
+
+    pub fn run_in<I, S>(&self, dir: &Path, args: I) -> Output
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.command_in(dir).args(args).output().unwrap()
+    }
+
+    pub fn run<I, S>(&self, args: I) -> Output
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.run_in(&self.work, args)
+    }
+
+    /// Run in the working directory, expecting success; returns stdout
+    pub fn ok<I, S>(&self, args: I) -> String
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args: Vec<S> = args.into_iter().collect();
+        let output = self.run(&args);
+        assert!(
+            output.status.success(),
+            "stash {:?} failed ({}):\n{}{}",
+            args.iter()
+                .map(|a| a.as_ref().to_string_lossy())
+                .collect::<Vec<_>>(),
+            output.status,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    }
+
+    /// Run in the working directory, expecting failure; returns the output
+    pub fn fails<I, S>(&self, args: I) -> Output
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args: Vec<S> = args.into_iter().collect();
+        let output = self.run(&args);
+        assert!(
+            !output.status.success(),
+            "stash {:?} should have failed:\n{}",
+            args.iter()
+                .map(|a| a.as_ref().to_string_lossy())
+                .collect::<Vec<_>>(),
+            String::from_utf8_lossy(&output.stdout)
+        );
+        output
+    }
+
+    /// Write `contents` to `path` under the working directory, creating
+    /// parent directories
+    pub fn write(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.work.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    pub fn read(&self, path: impl AsRef<Path>) -> String {
+        fs::read_to_string(self.work.join(path)).unwrap()
+    }
+
+    pub fn exists(&self, path: impl AsRef<Path>) -> bool {
+        fs::symlink_metadata(self.work.join(path)).is_ok()
+    }
+
+    pub fn index(&self) -> Value {
+        serde_json::from_str(&fs::read_to_string(self.stash_dir().join("index.json")).unwrap())
+            .unwrap()
+    }
+
+    /// Pretend the entry named `name` was created at `created` (RFC 3339),
+    /// in the index and in its manifest
+    pub fn set_created(&self, name: &str, created: &str) {
+        let mut index = self.index();
+        for entry in index["entries"].as_array_mut().unwrap() {
+            if entry["name"] == name {
+                entry["created"] = created.into();
+
+                let manifest_file = self
+                    .entry_dir(entry["uuid"].as_str().unwrap())
+                    .join("manifest.json");
+                let mut manifest: Value =
+                    serde_json::from_str(&fs::read_to_string(&manifest_file).unwrap()).unwrap();
+                manifest["created"] = created.into();
+                fs::write(
+                    manifest_file,
+                    serde_json::to_string_pretty(&manifest).unwrap(),
+                )
+                .unwrap();
+            }
+        }
+        fs::write(
+            self.stash_dir().join("index.json"),
+            serde_json::to_string_pretty(&index).unwrap(),
+        )
+        .unwrap();
+    }
+
+    pub fn entry_dir(&self, uuid: &str) -> PathBuf {
+        self.stash_dir().join("entries").join(uuid)
+    }
+
+    pub fn manifest(&self, uuid: &str) -> Value {
+        let json = fs::read_to_string(self.entry_dir(uuid).join("manifest.json")).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    /// `(uuid, name)` of every entry, as `--list --porcelain` prints them
+    pub fn entries(&self) -> Vec<(String, String)> {
+        self.ok(["--list", "--porcelain"])
+            .lines()
+            .map(|line| {
+                let mut fields = line.split('\t');
+                (
+                    fields.next().unwrap().to_string(),
+                    fields.next().unwrap().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    pub fn uuid_of(&self, name: &str) -> String {
+        self.entries()
+            .into_iter()
+            .find(|(_, entry)| entry == name)
+            .unwrap_or_else(|| panic!("no entry named '{}'", name))
+            .0
+    }
+
+    /// Set a config key through `stash config`
+    pub fn set_config(&self, key: &str, value: &str) {
+        self.ok(["config", key, value]);
+    }
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
@@ -0,0 +1,41 @@
+//! Read-only directories, for tests of what happens when stash can't change
+//! them. Shared by the integration tests and the unit tests in src/.
+
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// Keeps directories at mode 0o555 until dropped, then restores their modes
+pub struct ReadOnly(Vec<(PathBuf, u32)>);
+
+impl ReadOnly {
+    /// None when running as root, which ignores mode bits; the test should
+    /// then return early
+    pub fn new(dirs: &[&Path]) -> Option<Self> {
+        if euid() == 0 {
+            eprintln!("note: skipped, root can write to read-only directories");
+            return None;
+        }
+
+        let mut modes = Vec::new();
+        for dir in dirs {
+            let mode = fs::metadata(dir).unwrap().permissions().mode();
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o555)).unwrap();
+            modes.push((dir.to_path_buf(), mode));
+        }
+        Some(ReadOnly(modes))
+    }
+}
+
+impl Drop for ReadOnly {
+    fn drop(&mut self) {
+        for (dir, mode) in &self.0 {
+            let _ = fs::set_permissions(dir, fs::Permissions::from_mode(*mode));
+        }
+    }
+}
+
+/// The effective user id, read off a new file since files are owned by it
+fn euid() -> u32 {
+    tempfile::tempfile().unwrap().metadata().unwrap().uid()
+}
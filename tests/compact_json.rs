@@ -0,0 +1,39 @@
+mod common;
+
+use common::Sandbox;
+use std::fs;
+
+fn manifest_text(sandbox: &Sandbox, name: &str) -> String {
+    fs::read_to_string(sandbox.entry_dir(&sandbox.uuid_of(name)).join("manifest.json")).unwrap()
+}
+
+#[test]
+fn compact_json_writes_one_line_manifests_and_index() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("compact_json", "true");
+    sandbox.write("a.txt", "a");
+
+    sandbox.ok(["a.txt", "--name", "entry"]);
+
+    assert_eq!(manifest_text(&sandbox, "entry").trim_end().lines().count(), 1);
+    let index = fs::read_to_string(sandbox.stash_dir().join("index.json")).unwrap();
+    assert_eq!(index.trim_end().lines().count(), 1);
+}
+
+#[test]
+fn either_form_is_read_back() {
+    let sandbox = Sandbox::new();
+    sandbox.write("pretty.txt", "pretty");
+    sandbox.ok(["pretty.txt", "--name", "pretty"]);
+    sandbox.set_config("compact_json", "true");
+    sandbox.write("compact.txt", "compact");
+    sandbox.ok(["compact.txt", "--name", "compact"]);
+    assert!(manifest_text(&sandbox, "pretty").lines().count() > 1);
+
+    sandbox.ok(["pretty"]);
+    sandbox.set_config("compact_json", "false");
+    sandbox.ok(["compact"]);
+
+    assert_eq!(sandbox.read("pretty.txt"), "pretty");
+    assert_eq!(sandbox.read("compact.txt"), "compact");
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{stderr, Sandbox};
+use std::fs;
+
+#[test]
+fn sets_and_reads_back_a_key() {
+    let sandbox = Sandbox::new();
+
+    sandbox.ok(["config", "push_workers", "2"]);
+
+    assert_eq!(sandbox.ok(["config", "push_workers"]).trim(), "2");
+    assert!(fs::read_to_string(sandbox.config_file()).unwrap().contains("push_workers = 2"));
+}
+
+#[test]
+fn rejects_an_unknown_key() {
+    let sandbox = Sandbox::new();
+    let before = fs::read_to_string(sandbox.config_file()).ok();
+
+    let output = sandbox.fails(["config", "nosuch", "1"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("No config key 'nosuch'"), "{}", stderr(&output));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Set"));
+    assert_eq!(fs::read_to_string(sandbox.config_file()).ok(), before);
+}
+
+#[test]
+fn rejects_a_value_of_the_wrong_type() {
+    let sandbox = Sandbox::new();
+
+    let output = sandbox.fails(["config", "push_workers", "many"]);
+
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn a_command_named_like_a_file_warns() {
+    let sandbox = Sandbox::new();
+    sandbox.write("list", "a file called list");
+
+    let output = sandbox.run(["list"]);
+
+    assert!(output.status.success());
+    assert!(stderr(&output).contains("stash -- list"), "{}", stderr(&output));
+    assert!(sandbox.exists("list"));
+}
+
+#[test]
+fn double_dash_stashes_a_file_named_like_a_command() {
+    let sandbox = Sandbox::new();
+    sandbox.write("list", "a file called list");
+
+    sandbox.ok(["--", "list"]);
+
+    assert!(!sandbox.exists("list"));
+    assert_eq!(sandbox.entries()[0].1, "list");
+}
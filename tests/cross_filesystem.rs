@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{stderr, Sandbox};
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+use std::process::Command;
+
+/// A scratch directory on a different filesystem from the sandbox's stash,
+/// if the machine has one (`/dev/shm` is usually tmpfs)
+fn other_filesystem(sandbox: &Sandbox) -> Option<tempfile::TempDir> {
+    let shm = Path::new("/dev/shm");
+    let stash_dev = fs::metadata(sandbox.stash_dir()).ok()?.dev();
+    if fs::metadata(shm).ok()?.dev() == stash_dev {
+        return None;
+    }
+    tempfile::tempdir_in(shm).ok()
+}
+
+#[test]
+fn moving_across_filesystems_leaves_special_files_in_place() {
+    let sandbox = Sandbox::new();
+    let Some(elsewhere) = other_filesystem(&sandbox) else {
+        return;
+    };
+    let dir = elsewhere.path().join("project");
+    fs::create_dir_all(dir.join("run")).unwrap();
+    fs::write(dir.join("notes.txt"), "notes").unwrap();
+    let fifo = dir.join("run").join("pipe");
+    assert!(Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+
+    let output = sandbox.run_in(elsewhere.path(), ["project", "--name", "project"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::symlink_metadata(&fifo).unwrap().file_type().is_fifo());
+    assert!(!dir.join("notes.txt").exists());
+    assert!(stderr(&output).contains("left 1 special file(s) behind"), "{}", stderr(&output));
+
+    // The copied part is in the stash and comes back
+    fs::remove_dir_all(&dir).unwrap();
+    let output = sandbox.run_in(elsewhere.path(), ["project"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("notes.txt")).unwrap(), "notes");
+}
+
+#[test]
+fn moving_across_filesystems_removes_a_plain_tree() {
+    let sandbox = Sandbox::new();
+    let Some(elsewhere) = other_filesystem(&sandbox) else {
+        return;
+    };
+    let dir = elsewhere.path().join("project");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src").join("main.rs"), "fn main() {}").unwrap();
+
+    let output = sandbox.run_in(elsewhere.path(), ["project", "--name", "project"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!dir.exists());
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::{stderr, Sandbox};
+
+fn stash_named(sandbox: &Sandbox, names: &[&str]) {
+    for (i, name) in names.iter().enumerate() {
+        let file = format!("file{}", i);
+        sandbox.write(&file, *name);
+        sandbox.ok([file.as_str(), "--name", name]);
+    }
+}
+
+fn names(sandbox: &Sandbox) -> Vec<String> {
+    let mut names: Vec<String> = sandbox.entries().into_iter().map(|(_, name)| name).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn deletes_several_targets_at_once() {
+    let sandbox = Sandbox::new();
+    stash_named(&sandbox, &["alpha", "beta", "gamma"]);
+    let gamma = sandbox.uuid_of("gamma");
+
+    let stdout = sandbox.ok(["--delete", "alpha", &gamma, "--force"]);
+
+    assert!(stdout.contains("Deleting 2 entries"), "{}", stdout);
+    assert_eq!(names(&sandbox), ["beta"]);
+}
+
+#[test]
+fn deletes_every_entry_a_glob_matches() {
+    let sandbox = Sandbox::new();
+    stash_named(&sandbox, &["wip-one", "wip-two", "keep"]);
+
+    sandbox.ok(["--delete", "wip-*", "--force"]);
+
+    assert_eq!(names(&sandbox), ["keep"]);
+}
+
+#[test]
+fn a_failing_target_does_not_stop_the_others() {
+    let sandbox = Sandbox::new();
+    stash_named(&sandbox, &["alpha", "beta"]);
+
+    let output = sandbox.fails(["--delete", "nosuch", "alpha", "--force"]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("Skipping 'nosuch'"), "{}", stderr(&output));
+    assert_eq!(names(&sandbox), ["beta"]);
+}
+
+#[test]
+fn a_name_with_glob_characters_matches_literally_first() {
+    let sandbox = Sandbox::new();
+    stash_named(&sandbox, &["build[1]", "build1"]);
+
+    sandbox.ok(["--delete", "build[1]", "--force"]);
+
+    assert_eq!(names(&sandbox), ["build1"]);
+}
+
+#[test]
+fn a_glob_that_matches_nothing_fails() {
+    let sandbox = Sandbox::new();
+    stash_named(&sandbox, &["alpha"]);
+
+    let output = sandbox.fails(["--delete", "zz*", "--force"]);
+
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(names(&sandbox), ["alpha"]);
+}
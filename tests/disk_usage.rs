@@ -0,0 +1,113 @@
+mod common;
+
+use common::{stderr, Sandbox};
+
+fn entry_line(listing: &str) -> &str {
+    listing.lines().find(|line| line.starts_with("1.")).unwrap()
+}
+
+#[test]
+fn disk_usage_alone_only_adds_the_totals() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "hello");
+    sandbox.ok(["a.txt", "--quiet"]);
+
+    let listing = sandbox.ok(["--list", "--disk-usage"]);
+
+    assert!(!entry_line(&listing).contains("on disk"), "{}", listing);
+    let total = listing
+        .lines()
+        .find(|line| line.starts_with("Total:"))
+        .unwrap();
+    assert!(
+        total.contains("logical, ") && total.ends_with("on disk"),
+        "{}",
+        total
+    );
+}
+
+#[test]
+fn verbose_shows_logical_and_on_disk_size_per_entry() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "hello");
+    sandbox.ok(["a.txt", "--quiet"]);
+
+    let listing = sandbox.ok(["--list", "--verbose", "--disk-usage"]);
+
+    let line = entry_line(&listing);
+    assert!(line.contains("5 B logical, "), "{}", line);
+    assert!(line.contains(" on disk"), "{}", line);
+}
+
+#[test]
+fn verbose_needs_disk_usage() {
+    let sandbox = Sandbox::new();
+
+    let output = sandbox.fails(["--list", "--verbose"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("--disk-usage"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn summary_sums_logical_and_on_disk_sizes() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "hello");
+    sandbox.write("b.txt", "world!");
+    sandbox.ok(["a.txt", "--quiet"]);
+    sandbox.ok(["b.txt", "--quiet"]);
+
+    let porcelain = sandbox.ok(["--list", "--summary", "--disk-usage", "--porcelain"]);
+
+    let rows: Vec<_> = porcelain
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect();
+    let disk: u64 = rows
+        .iter()
+        .map(|row| row.rsplit('\t').next().unwrap().parse::<u64>().unwrap())
+        .sum();
+    let total = porcelain.lines().last().unwrap();
+    assert_eq!(
+        total,
+        format!(
+            "# total entries=2 items=2 size_bytes=11 disk_bytes={}",
+            disk
+        )
+    );
+
+    let listing = sandbox.ok(["--list", "--summary", "--disk-usage"]);
+    let total = listing
+        .lines()
+        .find(|line| line.starts_with("Total:"))
+        .unwrap();
+    assert!(
+        total.starts_with("Total: 2 entries, 2 files, 11 B logical, "),
+        "{}",
+        total
+    );
+}
+
+#[test]
+fn the_list_command_takes_the_same_flags() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "hello");
+    sandbox.ok(["a.txt", "--quiet"]);
+
+    let listing = sandbox.ok(["list", "--verbose", "--disk-usage", "--summary"]);
+
+    assert!(
+        entry_line(&listing).contains("5 B logical, "),
+        "{}",
+        listing
+    );
+    assert!(
+        listing.contains("Total: 1 entry, 1 files, 5 B logical, "),
+        "{}",
+        listing
+    );
+}
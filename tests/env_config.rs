@@ -0,0 +1,114 @@
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use std::fs;
+use std::process::Output;
+
+/// Run with `vars` set on top of the sandbox's environment
+fn run_with(sandbox: &Sandbox, vars: &[(&str, &str)], args: &[&str]) -> Output {
+    let mut command = sandbox.command_in(&sandbox.work);
+    command.envs(vars.iter().copied()).args(args);
+    command.output().unwrap()
+}
+
+/// The effective value of a config key with `vars` set
+fn effective(sandbox: &Sandbox, vars: &[(&str, &str)], key: &str) -> String {
+    let output = run_with(sandbox, vars, &["config", key]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    stdout(&output).trim().to_string()
+}
+
+#[test]
+fn environment_overrides_the_config_file() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("clean_days", "14");
+
+    assert_eq!(effective(&sandbox, &[], "clean_days"), "14");
+    assert_eq!(
+        effective(&sandbox, &[("STASH_CLEAN_DAYS", "7")], "clean_days"),
+        "7"
+    );
+    assert_eq!(
+        effective(&sandbox, &[("STASH_COMPRESS", "yes")], "compress_entries"),
+        "true"
+    );
+    assert_eq!(
+        effective(&sandbox, &[("STASH_WARN_SIZE_MB", " 5 ")], "warn_size_mb"),
+        "5"
+    );
+    assert_eq!(
+        effective(
+            &sandbox,
+            &[("STASH_HASH_ALGORITHM", "blake3")],
+            "hash_algorithm"
+        ),
+        "blake3"
+    );
+}
+
+#[test]
+fn environment_overrides_the_defaults() {
+    let sandbox = Sandbox::new();
+    fs::remove_file(sandbox.config_file()).unwrap();
+
+    assert_eq!(
+        effective(&sandbox, &[("STASH_CLEAN_DAYS", "3")], "clean_days"),
+        "3"
+    );
+}
+
+#[test]
+fn malformed_values_warn_and_fall_back() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("clean_days", "14");
+
+    let output = run_with(
+        &sandbox,
+        &[("STASH_CLEAN_DAYS", "soon")],
+        &["config", "clean_days"],
+    );
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), "14");
+    assert!(
+        stderr(&output).contains("ignoring STASH_CLEAN_DAYS"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn overrides_are_not_written_back() {
+    let sandbox = Sandbox::new();
+
+    let output = run_with(
+        &sandbox,
+        &[("STASH_CLEAN_DAYS", "7")],
+        &["config", "warn_size_mb", "5"],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let config = fs::read_to_string(sandbox.config_file()).unwrap();
+    assert!(config.contains("clean_days = 30"), "{}", config);
+    assert!(config.contains("warn_size_mb = 5"), "{}", config);
+}
+
+#[test]
+fn overrides_apply_to_pushes() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+
+    let output = run_with(
+        &sandbox,
+        &[("STASH_HASH_ALGORITHM", "blake3")],
+        &["a.txt", "--name", "entry"],
+    );
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    let uuid = sandbox.uuid_of("entry");
+    let hash = sandbox.manifest(&uuid)["items"][0]["hash"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(hash.starts_with("blake3:"), "{}", hash);
+}
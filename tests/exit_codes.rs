@@ -0,0 +1,49 @@
+mod common;
+
+use common::{stderr, Sandbox};
+
+#[test]
+fn a_pop_onto_an_existing_file_exits_4() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "stashed");
+    sandbox.ok(["notes.txt", "--name", "notes"]);
+    sandbox.write("notes.txt", "local");
+
+    let output = sandbox.fails(["pop", "notes"]);
+
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("already exists"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(sandbox.read("notes.txt"), "local");
+    assert_eq!(sandbox.entries().len(), 1);
+}
+
+#[test]
+fn an_unknown_identifier_exits_3() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "notes");
+    sandbox.ok(["notes.txt", "--name", "notes"]);
+
+    for args in [
+        &["pop", "missing"][..],
+        &["--info", "missing"],
+        &["--delete", "missing", "--force"],
+    ] {
+        let output = sandbox.fails(args);
+        assert_eq!(
+            output.status.code(),
+            Some(3),
+            "{:?}: {}",
+            args,
+            stderr(&output)
+        );
+        assert!(
+            stderr(&output).contains("Entry not found: missing"),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
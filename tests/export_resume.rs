@@ -0,0 +1,93 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{stderr, Sandbox};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+/// Stash `one`, `two` and `three`; returns their UUIDs in the order an
+/// export writes them
+fn stash_three(sandbox: &Sandbox) -> Vec<String> {
+    for name in ["one", "two", "three"] {
+        sandbox.write(name, name);
+        sandbox.ok([name]);
+    }
+    let mut uuids: Vec<_> = sandbox.entries().into_iter().map(|(uuid, _)| uuid).collect();
+    uuids.sort();
+    uuids
+}
+
+/// Top-level directories in the archive, one per exported entry
+fn exported_dirs(archive: &Path) -> BTreeSet<String> {
+    let mut archive = tar::Archive::new(File::open(archive).unwrap());
+    archive
+        .entries()
+        .unwrap()
+        .map(|member| {
+            let path = member.unwrap().path().unwrap().into_owned();
+            path.components().next().unwrap().as_os_str().to_string_lossy().into_owned()
+        })
+        .collect()
+}
+
+#[test]
+fn an_interrupted_export_resumes_where_it_stopped() {
+    let sandbox = Sandbox::new();
+    let uuids = stash_three(&sandbox);
+    let archive = sandbox.root().join("out.tar");
+    let record = sandbox.root().join("out.tar.export.json");
+
+    // A link that leads nowhere can't be archived, so the export stops at
+    // the last entry after writing the other two
+    let broken = sandbox.entry_dir(&uuids[2]).join("broken");
+    symlink("/nonexistent/target", &broken).unwrap();
+
+    let output = sandbox.fails(["--tar", archive.to_str().unwrap()]);
+    assert!(stderr(&output).contains("run again with --continue"), "{}", stderr(&output));
+    let progress: serde_json::Value = serde_json::from_str(&fs::read_to_string(&record).unwrap()).unwrap();
+    let done: Vec<_> = progress["entries"].as_array().unwrap().iter().map(|e| e["uuid"].as_str().unwrap()).collect();
+    assert_eq!(done, [&uuids[0], &uuids[1]]);
+
+    fs::remove_file(&broken).unwrap();
+    let out = sandbox.ok(["--tar", archive.to_str().unwrap(), "--continue"]);
+
+    assert!(out.contains("2 entries already exported, 1 to go"), "{}", out);
+    assert!(!record.exists());
+    assert_eq!(exported_dirs(&archive), BTreeSet::from(["one".into(), "three".into(), "two".into()]));
+
+    // Resuming gives the archive a single export would have written
+    let fresh = sandbox.root().join("fresh.tar");
+    sandbox.ok(["--tar", fresh.to_str().unwrap()]);
+    assert_eq!(fs::read(&archive).unwrap(), fs::read(&fresh).unwrap());
+}
+
+#[test]
+fn continue_needs_an_interrupted_export() {
+    let sandbox = Sandbox::new();
+    stash_three(&sandbox);
+    let archive = sandbox.root().join("out.tar");
+    sandbox.ok(["--tar", archive.to_str().unwrap()]);
+
+    let output = sandbox.fails(["--tar", archive.to_str().unwrap(), "--continue"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("No interrupted export to continue"), "{}", stderr(&output));
+}
+
+#[test]
+fn a_truncated_partial_export_is_not_resumed() {
+    let sandbox = Sandbox::new();
+    let uuids = stash_three(&sandbox);
+    let archive = sandbox.root().join("out.tar");
+    symlink("/nonexistent/target", sandbox.entry_dir(&uuids[2]).join("broken")).unwrap();
+    sandbox.fails(["--tar", archive.to_str().unwrap()]);
+
+    File::options().write(true).open(&archive).unwrap().set_len(512).unwrap();
+    let output = sandbox.fails(["--tar", archive.to_str().unwrap(), "--continue"]);
+
+    assert_eq!(output.status.code(), Some(5));
+    assert!(stderr(&output).contains("shorter than its recorded progress"), "{}", stderr(&output));
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use common::{stderr, Sandbox};
+use std::path::Path;
+use std::process::Command;
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=stash", "-c", "user.email=stash@example.com"])
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[test]
+fn warns_when_a_restored_file_is_ignored() {
+    let sandbox = Sandbox::new();
+    git(&sandbox.work, &["init", "-q"]);
+    sandbox.write(".gitignore", "*.log\n");
+    sandbox.write("debug.log", "trace");
+    sandbox.ok(["debug.log"]);
+
+    let output = sandbox.run(["debug.log", "--git-aware"]);
+
+    assert!(output.status.success());
+    assert!(stderr(&output).contains("debug.log is ignored by git"), "{}", stderr(&output));
+    assert_eq!(sandbox.read("debug.log"), "trace");
+}
+
+#[test]
+fn warns_before_overwriting_uncommitted_changes() {
+    let sandbox = Sandbox::new();
+    git(&sandbox.work, &["init", "-q"]);
+    sandbox.write("notes.txt", "committed");
+    git(&sandbox.work, &["add", "notes.txt"]);
+    git(&sandbox.work, &["commit", "-q", "-m", "notes"]);
+    sandbox.ok(["notes.txt", "--copy", "--name", "notes"]);
+    sandbox.write("notes.txt", "edited");
+
+    let output = sandbox.run(["notes", "--git-aware", "--force"]);
+
+    assert!(output.status.success());
+    assert!(stderr(&output).contains("has uncommitted changes"), "{}", stderr(&output));
+}
+
+#[test]
+fn does_nothing_outside_a_repository() {
+    let sandbox = Sandbox::new();
+    sandbox.write("debug.log", "trace");
+    sandbox.ok(["debug.log"]);
+
+    let output = sandbox.run(["debug.log", "--git-aware"]);
+
+    assert!(output.status.success());
+    assert!(!stderr(&output).contains("Warning"), "{}", stderr(&output));
+}
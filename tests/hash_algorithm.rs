@@ -0,0 +1,70 @@
+mod common;
+
+use common::{stdout, Sandbox};
+use std::fs;
+
+fn hash_of(sandbox: &Sandbox, name: &str) -> String {
+    let uuid = sandbox.uuid_of(name);
+    sandbox.manifest(&uuid)["items"][0]["hash"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn blake3_entries_round_trip() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("hash_algorithm", "blake3");
+    sandbox.write("a.txt", "contents");
+
+    sandbox.ok(["a.txt", "--name", "entry"]);
+
+    assert!(hash_of(&sandbox, "entry").starts_with("blake3:"));
+    let checked = sandbox.ok(["--info", "entry", "--hash-check"]);
+    assert!(checked.contains("OK"), "{}", checked);
+
+    sandbox.ok(["pop", "entry"]);
+    assert_eq!(sandbox.read("a.txt"), "contents");
+}
+
+#[test]
+fn each_entry_verifies_with_the_algorithm_it_was_hashed_with() {
+    let sandbox = Sandbox::new();
+    sandbox.write("old.txt", "old");
+    sandbox.ok(["old.txt", "--name", "old"]);
+    sandbox.set_config("hash_algorithm", "blake3");
+    sandbox.write("new.txt", "new");
+    sandbox.ok(["new.txt", "--name", "new"]);
+
+    assert!(hash_of(&sandbox, "old").starts_with("sha256:"));
+    assert!(hash_of(&sandbox, "new").starts_with("blake3:"));
+    for name in ["old", "new"] {
+        let checked = sandbox.ok(["--info", name, "--hash-check"]);
+        assert!(checked.contains("OK"), "{}: {}", name, checked);
+    }
+
+    sandbox.set_config("hash_algorithm", "sha256");
+    sandbox.ok(["pop", "new"]);
+    sandbox.ok(["pop", "old"]);
+    assert_eq!(sandbox.read("new.txt"), "new");
+    assert_eq!(sandbox.read("old.txt"), "old");
+}
+
+#[test]
+fn a_tampered_blake3_entry_is_caught() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("hash_algorithm", "blake3");
+    sandbox.write("a.txt", "contents");
+    sandbox.ok(["a.txt", "--name", "entry"]);
+    let uuid = sandbox.uuid_of("entry");
+    fs::write(
+        sandbox.entry_dir(&uuid).join("data").join("a.txt"),
+        "tampered",
+    )
+    .unwrap();
+
+    let output = sandbox.fails(["--info", "entry", "--hash-check"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("MISMATCH"), "{}", stdout(&output));
+}
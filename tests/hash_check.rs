@@ -0,0 +1,29 @@
+mod common;
+
+use common::{stdout, Sandbox};
+use std::fs;
+
+#[test]
+fn intact_data_checks_out() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "contents");
+    sandbox.ok(["a.txt", "--name", "entry"]);
+
+    let stdout = sandbox.ok(["--info", "entry", "--hash-check"]);
+
+    assert!(stdout.contains("OK"), "{}", stdout);
+}
+
+#[test]
+fn a_mismatch_exits_with_1() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "contents");
+    sandbox.ok(["a.txt", "--name", "entry"]);
+    let uuid = sandbox.uuid_of("entry");
+    fs::write(sandbox.entry_dir(&uuid).join("data").join("a.txt"), "tampered").unwrap();
+
+    let output = sandbox.fails(["--info", "entry", "--hash-check"]);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("MISMATCH"), "{}", stdout(&output));
+}
@@ -0,0 +1,86 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::Sandbox;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+
+fn non_utf8_name() -> &'static OsStr {
+    OsStr::from_bytes(b"bad\xff.txt")
+}
+
+#[test]
+fn hostile_names_round_trip_byte_exactly() {
+    let sandbox = Sandbox::new();
+    sandbox.write(non_utf8_name(), "invalid utf-8");
+    sandbox.write("new\nline.txt", "newline");
+
+    sandbox.ok([non_utf8_name(), OsStr::new("new\nline.txt")]);
+
+    assert!(!sandbox.exists(non_utf8_name()));
+    assert!(!sandbox.exists("new\nline.txt"));
+
+    sandbox.ok(["pop"]);
+
+    assert_eq!(sandbox.read(non_utf8_name()), "invalid utf-8");
+    assert_eq!(sandbox.read("new\nline.txt"), "newline");
+}
+
+#[test]
+fn default_names_are_escaped() {
+    let sandbox = Sandbox::new();
+    sandbox.write(non_utf8_name(), "invalid utf-8");
+
+    sandbox.ok([non_utf8_name()]);
+
+    assert_eq!(sandbox.index()["entries"][0]["name"], "bad\\xff.txt");
+    let list = sandbox.ok(["--list"]);
+    assert!(list.contains("bad\\xff.txt"), "{}", list);
+}
+
+#[test]
+fn list_and_info_show_hostile_names_on_one_line_each() {
+    let sandbox = Sandbox::new();
+    sandbox.write(non_utf8_name(), "invalid utf-8");
+    sandbox.write("new\nline.txt", "newline");
+    sandbox.ok([
+        non_utf8_name(),
+        OsStr::new("new\nline.txt"),
+        OsStr::new("--name"),
+        OsStr::new("entry"),
+    ]);
+
+    let list = sandbox.ok(["--list"]);
+    assert_eq!(
+        list.lines().filter(|line| line.contains("entry")).count(),
+        1,
+        "{}",
+        list
+    );
+
+    let info = sandbox.ok(["--info", "entry"]);
+    assert!(info.contains("bad\\xff.txt"), "{}", info);
+    assert!(info.contains("new\\nline.txt"), "{}", info);
+    assert!(!info.contains("new\nline"), "{}", info);
+}
+
+#[test]
+fn the_manifest_keeps_the_raw_bytes() {
+    let sandbox = Sandbox::new();
+    sandbox.write(non_utf8_name(), "invalid utf-8");
+    sandbox.ok([non_utf8_name(), OsStr::new("--name"), OsStr::new("entry")]);
+
+    let uuid = sandbox.uuid_of("entry");
+    let stashed = &sandbox.manifest(&uuid)["items"][0]["stashed_path"];
+    let bytes: Vec<u8> = stashed["bytes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|byte| byte.as_u64().unwrap() as u8)
+        .collect();
+    assert_eq!(bytes, b"bad\xff.txt");
+    let data = sandbox.entry_dir(&uuid).join("data").join(non_utf8_name());
+    assert_eq!(fs::read_to_string(data).unwrap(), "invalid utf-8");
+}
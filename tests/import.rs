@@ -0,0 +1,43 @@
+mod common;
+
+use common::Sandbox;
+
+/// A tar holding one file whose name is written into the header as-is
+fn archive_with_member(name: &str) -> Vec<u8> {
+    let mut header = tar::Header::new_gnu();
+    header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+    header.set_size(5);
+    header.set_mode(0o644);
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_cksum();
+
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.append(&header, &b"owned"[..]).unwrap();
+    builder.into_inner().unwrap()
+}
+
+#[test]
+fn import_refuses_an_archive_that_writes_outside_its_directory() {
+    let sandbox = Sandbox::new();
+    let evil = sandbox.write("evil.tar", archive_with_member("../../../../../../escaped.txt"));
+
+    sandbox.fails(["--import".as_ref(), evil.as_os_str()]);
+
+    assert!(!sandbox.root().join("escaped.txt").exists());
+    assert!(!std::path::Path::new("/escaped.txt").exists());
+    assert!(sandbox.entries().is_empty());
+}
+
+#[test]
+fn import_round_trips_an_export() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "hello");
+    sandbox.ok(["notes.txt", "--name", "notes"]);
+    sandbox.ok(["--tar", "export.tar"]);
+    sandbox.ok(["--delete", "notes", "--force"]);
+
+    sandbox.ok(["--import", "export.tar"]);
+    sandbox.ok(["notes"]);
+
+    assert_eq!(sandbox.read("notes.txt"), "hello");
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{stderr, Sandbox};
+use std::fs;
+
+#[test]
+fn init_defaults_to_the_global_stash() {
+    let sandbox = Sandbox::new();
+
+    let default = sandbox.ok(["--init"]);
+    let global = sandbox.ok(["--init", "--global"]);
+
+    let expected = format!(
+        "Initialized global stash at {}",
+        sandbox.stash_dir().display()
+    );
+    assert!(default.contains(&expected), "{}", default);
+    assert!(global.contains(&expected), "{}", global);
+    assert!(!global.contains("Active stash"), "{}", global);
+}
+
+#[test]
+fn local_init_creates_an_ignored_project_stash() {
+    let sandbox = Sandbox::new();
+    let project = sandbox.work.join(".stash");
+
+    let stdout = sandbox.ok(["--init", "--local"]);
+
+    assert!(
+        stdout.contains(&format!("Initialized local stash at {}", project.display())),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        fs::read_to_string(project.join(".gitignore")).unwrap(),
+        "*\n"
+    );
+    assert!(project.join("entries").is_dir());
+
+    sandbox.write("a.txt", "a");
+    sandbox.ok(["a.txt", "--name", "here"]);
+    assert_eq!(fs::read_dir(project.join("entries")).unwrap().count(), 1);
+    assert_eq!(
+        fs::read_dir(sandbox.stash_dir().join("entries"))
+            .unwrap()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn global_init_in_a_project_names_the_active_stash() {
+    let sandbox = Sandbox::new();
+    sandbox.ok(["--init", "--local"]);
+
+    let stdout = sandbox.ok(["--init", "--global"]);
+
+    assert!(stdout.contains("Initialized global stash"), "{}", stdout);
+    let active = format!(
+        "Active stash here: local stash at {}",
+        sandbox.work.join(".stash").display()
+    );
+    assert!(stdout.contains(&active), "{}", stdout);
+}
+
+#[test]
+fn global_and_local_conflict() {
+    let sandbox = Sandbox::new();
+
+    let output = sandbox.fails(["--init", "--global", "--local"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("cannot be used with"),
+        "{}",
+        stderr(&output)
+    );
+}
@@ -0,0 +1,27 @@
+mod common;
+
+use common::{stderr, Sandbox};
+
+#[test]
+fn jobs_is_accepted_when_pushing_and_popping() {
+    let sandbox = Sandbox::new();
+    for i in 0..20 {
+        sandbox.write(format!("tree/{}.txt", i), i.to_string());
+    }
+
+    sandbox.ok(["tree", "--name", "tree", "--jobs", "4"]);
+    sandbox.ok(["pop", "tree", "--jobs", "4"]);
+
+    assert_eq!(sandbox.read("tree/7.txt"), "7");
+}
+
+#[test]
+fn jobs_is_rejected_by_other_operations() {
+    let sandbox = Sandbox::new();
+
+    for args in [&["--list", "--jobs", "2"][..], &["--history", "--jobs", "2"], &["--peek", "--jobs", "2"]] {
+        let output = sandbox.run(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}: {}", args, stderr(&output));
+        assert!(stderr(&output).contains("--jobs"), "{:?}: {}", args, stderr(&output));
+    }
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+
+/// Push one entry per `(name, project)`, created a day apart in that order;
+/// an empty project leaves the entry without metadata
+fn push_all(sandbox: &Sandbox, entries: &[(&str, &str)]) {
+    for (day, (name, project)) in entries.iter().enumerate() {
+        sandbox.write(name, *name);
+        if project.is_empty() {
+            sandbox.ok([*name, "--name", name]);
+        } else {
+            sandbox.ok([*name, "--name", name, "--meta", &format!("project={}", project)]);
+        }
+        sandbox.set_created(name, &format!("2024-01-{:02}T12:00:00Z", day + 1));
+    }
+}
+
+fn names(sandbox: &Sandbox) -> Vec<String> {
+    let mut names: Vec<_> = sandbox.entries().into_iter().map(|(_, name)| name).collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn keeps_only_the_latest_entries() {
+    let sandbox = Sandbox::new();
+    push_all(&sandbox, &[("a", ""), ("b", ""), ("c", "")]);
+
+    let out = sandbox.ok(["--clean", "--keep-latest", "2"]);
+
+    assert!(out.contains("• a "), "{}", out);
+    assert_eq!(names(&sandbox), ["b", "c"]);
+}
+
+#[test]
+fn days_conflict_with_keep_latest() {
+    let sandbox = Sandbox::new();
+    push_all(&sandbox, &[("a", ""), ("b", "")]);
+
+    for args in [&["--clean", "7", "--keep-latest", "1"][..], &["clean", "7", "--keep-latest", "1"]] {
+        let output = sandbox.fails(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}: {}", args, stderr(&output));
+    }
+    assert_eq!(names(&sandbox), ["a", "b"]);
+}
+
+#[test]
+fn a_key_value_filter_ranks_only_matching_entries() {
+    let sandbox = Sandbox::new();
+    push_all(&sandbox, &[("api1", "api"), ("web1", "web"), ("api2", "api"), ("api3", "api")]);
+
+    sandbox.ok(["clean", "--keep-latest", "1", "--filter-meta", "project=api"]);
+
+    assert_eq!(names(&sandbox), ["api3", "web1"]);
+}
+
+#[test]
+fn a_bare_key_keeps_the_latest_of_each_value() {
+    let sandbox = Sandbox::new();
+    push_all(&sandbox, &[
+        ("api1", "api"),
+        ("web1", "web"),
+        ("api2", "api"),
+        ("loose", ""),
+        ("web2", "web"),
+        ("api3", "api"),
+    ]);
+
+    let preview = sandbox.run(["--clean", "--keep-latest", "1", "--filter-meta", "project", "--dry-run"]);
+    assert!(stdout(&preview).contains("Would clean 3 entries"), "{}", stdout(&preview));
+
+    sandbox.ok(["--clean", "--keep-latest", "1", "--filter-meta", "project"]);
+
+    assert_eq!(names(&sandbox), ["api3", "loose", "web2"]);
+}
+
+#[test]
+fn list_rejects_a_bare_key() {
+    let sandbox = Sandbox::new();
+
+    let output = sandbox.fails(["--list", "--filter-meta", "project"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("needs KEY=VALUE"), "{}", stderr(&output));
+}
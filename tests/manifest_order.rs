@@ -0,0 +1,47 @@
+mod common;
+
+use common::Sandbox;
+use serde_json::Value;
+
+fn item_paths(manifest: &Value) -> Vec<String> {
+    manifest["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["stashed_path"].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn write_tree(sandbox: &Sandbox) {
+    sandbox.write("tree/b.txt", "b");
+    sandbox.write("tree/a/nested.txt", "nested");
+    sandbox.write("zeta.txt", "z");
+    sandbox.write("alpha.txt", "a");
+}
+
+fn push_tree(sandbox: &Sandbox, name: &str) -> Value {
+    sandbox.ok(["zeta.txt", "tree", "alpha.txt", "--copy", "--name", name]);
+    sandbox.manifest(&sandbox.uuid_of(name))
+}
+
+#[test]
+fn pushing_the_same_tree_twice_gives_the_same_items() {
+    let sandbox = Sandbox::new();
+    write_tree(&sandbox);
+
+    let first = push_tree(&sandbox, "first");
+    let second = push_tree(&sandbox, "second");
+
+    assert_eq!(item_paths(&first), item_paths(&second));
+    assert_eq!(first["items"], second["items"]);
+}
+
+#[test]
+fn items_are_sorted_by_stashed_path_by_default() {
+    let sandbox = Sandbox::new();
+    write_tree(&sandbox);
+
+    let manifest = push_tree(&sandbox, "entry");
+
+    assert_eq!(item_paths(&manifest), ["alpha.txt", "tree", "zeta.txt"]);
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use serde_json::Value;
+use std::fs;
+
+/// An entry named `entry` holding two files; returns its UUID
+fn stashed(sandbox: &Sandbox) -> String {
+    sandbox.write("a.txt", "a");
+    sandbox.write("b.txt", "b");
+    sandbox.ok(["a.txt", "b.txt", "--name", "entry"]);
+    sandbox.uuid_of("entry")
+}
+
+fn edit_manifest(sandbox: &Sandbox, uuid: &str, edit: impl FnOnce(&mut Value)) {
+    let mut manifest = sandbox.manifest(uuid);
+    edit(&mut manifest);
+    let path = sandbox.entry_dir(uuid).join("manifest.json");
+    fs::write(path, serde_json::to_string_pretty(&manifest).unwrap()).unwrap();
+}
+
+#[test]
+fn a_consistent_manifest_loads_quietly() {
+    let sandbox = Sandbox::new();
+    stashed(&sandbox);
+
+    let output = sandbox.run(["pop", "entry", "--strict"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!stderr(&output).contains("Warning"), "{}", stderr(&output));
+}
+
+#[test]
+fn missing_hashes_warn_and_fail_with_strict() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("compute_hashes_on_push", "false");
+    stashed(&sandbox);
+
+    let output = sandbox.fails(["pop", "entry", "--strict"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(
+        stderr(&output).contains("failed validation"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(
+        stderr(&output).contains("has no hash"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!sandbox.exists("a.txt"));
+
+    let output = sandbox.run(["pop", "entry"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Warning: entry 'entry': \"a.txt\" has no hash"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(sandbox.read("a.txt"), "a");
+}
+
+#[test]
+fn duplicate_stashed_paths_are_critical() {
+    let sandbox = Sandbox::new();
+    let uuid = stashed(&sandbox);
+    edit_manifest(&sandbox, &uuid, |manifest| {
+        manifest["items"][1]["stashed_path"] = manifest["items"][0]["stashed_path"].clone();
+    });
+
+    let output = sandbox.run(["--peek", "entry", "--list-only"]);
+    assert!(
+        stderr(&output).contains("(critical): more than one item is stored at"),
+        "{}",
+        stderr(&output)
+    );
+
+    let output = sandbox.fails(["--info", "entry", "--strict"]);
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[test]
+fn entries_without_items_are_critical() {
+    let sandbox = Sandbox::new();
+    let uuid = stashed(&sandbox);
+    edit_manifest(&sandbox, &uuid, |manifest| {
+        manifest["items"] = Value::Array(Vec::new())
+    });
+
+    let info = sandbox.ok(["--info", "entry"]);
+
+    assert!(
+        info.contains("critical: the entry has no items"),
+        "{}",
+        info
+    );
+}
+
+#[test]
+fn informational_findings_only_show_in_info() {
+    let sandbox = Sandbox::new();
+    sandbox.write("empty.txt", "");
+    let project = sandbox.root().join("project");
+    fs::create_dir_all(&project).unwrap();
+    fs::write(project.join("a.txt"), "a").unwrap();
+    let output = sandbox.run_in(&project, ["a.txt", "--name", "gone"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    fs::remove_dir_all(&project).unwrap();
+    sandbox.ok(["empty.txt", "--name", "empty"]);
+
+    let info = sandbox.ok(["--info", "gone"]);
+    assert!(info.contains("no longer exists"), "{}", info);
+    let info = sandbox.ok(["--info", "empty"]);
+    assert!(info.contains("info: \"empty.txt\" is empty"), "{}", info);
+
+    let output = sandbox.run(["pop", "empty", "--strict"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("empty.txt"));
+}
+
+#[test]
+fn checks_are_off_without_verify_integrity() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("compute_hashes_on_push", "false");
+    stashed(&sandbox);
+    sandbox.set_config("verify_integrity", "false");
+
+    let output = sandbox.run(["pop", "entry"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        !stderr(&output).contains("has no hash"),
+        "{}",
+        stderr(&output)
+    );
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::{stderr, Sandbox};
+use serde_json::{json, Value};
+
+fn info_json(sandbox: &Sandbox, name: &str) -> Value {
+    serde_json::from_str(&sandbox.ok(["--info", name, "--json"])).unwrap()
+}
+
+#[test]
+fn meta_pairs_are_set_on_top_of_the_meta_file() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    let file = sandbox.write("meta.json", r#"{"ticket": "ABC-1", "env": {"os": "linux"}}"#);
+
+    sandbox.ok([
+        "a.txt",
+        "--name",
+        "entry",
+        "--meta-file",
+        file.to_str().unwrap(),
+        "--meta",
+        "ticket=XYZ-2",
+        "--meta",
+        "team=core",
+    ]);
+
+    let expected = json!({"ticket": "XYZ-2", "team": "core", "env": {"os": "linux"}});
+    assert_eq!(sandbox.manifest(&sandbox.uuid_of("entry"))["metadata"], expected);
+    assert_eq!(info_json(&sandbox, "entry")["metadata"], expected);
+}
+
+#[test]
+fn meta_pairs_alone_make_an_object_of_strings() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+
+    sandbox.ok(["a.txt", "--name", "entry", "--meta", "build=42"]);
+
+    assert_eq!(info_json(&sandbox, "entry")["metadata"], json!({"build": "42"}));
+}
+
+#[test]
+fn entries_without_metadata_have_none() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+
+    sandbox.ok(["a.txt", "--name", "entry"]);
+
+    assert!(sandbox.manifest(&sandbox.uuid_of("entry")).get("metadata").is_none());
+    assert_eq!(info_json(&sandbox, "entry")["metadata"], Value::Null);
+}
+
+#[test]
+fn a_meta_file_must_hold_an_object() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    let file = sandbox.write("meta.json", "[1, 2]");
+
+    let output = sandbox.fails(["a.txt", "--meta-file", file.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("must contain a JSON object"), "{}", stderr(&output));
+    assert!(sandbox.exists("a.txt"));
+}
+
+#[test]
+fn list_filters_on_metadata() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.write("b.txt", "b");
+    sandbox.ok(["a.txt", "--name", "api", "--meta", "project=api"]);
+    sandbox.ok(["b.txt", "--name", "web", "--meta", "project=web"]);
+
+    let listed = sandbox.ok(["--list", "--filter-meta", "project=api", "--porcelain"]);
+
+    assert_eq!(listed.lines().count(), 1);
+    assert!(listed.contains("\tapi\t"), "{}", listed);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{stderr, Sandbox};
+use std::fs;
+
+/// A stash with two entries left after a third was popped, so the journal
+/// holds more than the index
+fn populated() -> Sandbox {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.write("b.txt", "b");
+    sandbox.write("c.txt", "c");
+    sandbox.ok(["a.txt", "--name", "one"]);
+    sandbox.ok(["b.txt", "--name", "two"]);
+    sandbox.ok(["c.txt", "--name", "three"]);
+    sandbox.ok(["pop", "three"]);
+    sandbox
+}
+
+#[test]
+fn a_populated_stash_moves_to_the_new_directory() {
+    let sandbox = populated();
+    let old = sandbox.stash_dir();
+    let uuids: Vec<String> = ["one", "two"]
+        .iter()
+        .map(|name| sandbox.uuid_of(name))
+        .collect();
+    let destination = sandbox.root().join("moved");
+
+    sandbox.ok(["--move-to-stash-dir", destination.to_str().unwrap()]);
+
+    for uuid in &uuids {
+        assert!(destination
+            .join("entries")
+            .join(uuid)
+            .join("manifest.json")
+            .is_file());
+        assert!(!old.join("entries").join(uuid).exists());
+    }
+    assert!(destination.join("index.json").is_file());
+    assert!(destination.join("journal.log").is_file());
+    assert!(!old.join("index.json").exists());
+    assert!(!old.join("journal.log").exists());
+
+    let config = fs::read_to_string(sandbox.config_file()).unwrap();
+    assert!(config.contains(destination.to_str().unwrap()), "{}", config);
+    assert_eq!(sandbox.entries().len(), 2);
+
+    sandbox.ok(["pop", "one"]);
+    assert_eq!(sandbox.read("a.txt"), "a");
+    let history = sandbox.ok(["--history"]);
+    assert_eq!(history.matches("Pushed").count(), 3, "{}", history);
+    assert_eq!(history.matches("Popped").count(), 2, "{}", history);
+}
+
+#[test]
+fn a_dry_run_moves_nothing() {
+    let sandbox = populated();
+    let destination = sandbox.root().join("moved");
+
+    let stdout = sandbox.ok([
+        "--move-to-stash-dir",
+        destination.to_str().unwrap(),
+        "--dry-run",
+    ]);
+
+    assert!(stdout.contains("Would move 2 entries"), "{}", stdout);
+    assert!(!destination.exists());
+    assert!(sandbox.stash_dir().join("index.json").is_file());
+    let config = fs::read_to_string(sandbox.config_file()).unwrap();
+    assert!(
+        !config.contains(destination.to_str().unwrap()),
+        "{}",
+        config
+    );
+}
+
+#[test]
+fn an_existing_stash_needs_merge() {
+    let sandbox = populated();
+    let destination = sandbox.root().join("moved");
+    let other = Sandbox::new();
+    other.write("d.txt", "d");
+    other.ok(["d.txt", "--name", "four"]);
+    other.ok(["--move-to-stash-dir", destination.to_str().unwrap()]);
+
+    let output = sandbox.fails(["--move-to-stash-dir", destination.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("--merge"), "{}", stderr(&output));
+
+    sandbox.ok([
+        "--move-to-stash-dir",
+        destination.to_str().unwrap(),
+        "--merge",
+    ]);
+
+    let mut names: Vec<String> = sandbox
+        .entries()
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect();
+    names.sort();
+    assert_eq!(names, ["four", "one", "two"]);
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{stderr, Sandbox};
+
+fn stashed_as(name: &str) -> Sandbox {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.ok(["a.txt", "--name", name]);
+    sandbox
+}
+
+/// The quoted names in `--name-history` output, oldest first
+fn timeline(sandbox: &Sandbox, entry: &str) -> Vec<String> {
+    sandbox
+        .ok(["--name-history", entry])
+        .lines()
+        .skip(1)
+        .map(|line| line.split('\'').nth(1).unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn a_rename_chain_shows_every_name_in_order() {
+    let sandbox = stashed_as("first");
+    sandbox.ok(["--rename", "first:second"]);
+    sandbox.ok(["--rename", "second:third"]);
+
+    assert_eq!(timeline(&sandbox, "third"), ["first", "second", "third"]);
+
+    let history = sandbox.ok(["--name-history", "third"]);
+    assert!(history.contains("'first' (pushed)"), "{}", history);
+}
+
+#[test]
+fn revert_name_restores_the_previous_name() {
+    let sandbox = stashed_as("first");
+    let uuid = sandbox.uuid_of("first");
+    sandbox.ok(["--rename", "first:second"]);
+    sandbox.ok(["--rename", "second:third"]);
+
+    sandbox.ok(["--revert-name", "third"]);
+
+    assert_eq!(sandbox.entries(), [(uuid.clone(), "second".to_string())]);
+    assert_eq!(sandbox.manifest(&uuid)["name"], "second");
+    assert_eq!(
+        timeline(&sandbox, "second"),
+        ["first", "second", "third", "second"]
+    );
+}
+
+#[test]
+fn entries_never_renamed_show_only_their_name() {
+    let sandbox = stashed_as("solo");
+
+    assert_eq!(timeline(&sandbox, "solo"), ["solo"]);
+    let history = sandbox.ok(["--name-history", "solo"]);
+    assert!(history.contains("(never renamed)"), "{}", history);
+
+    let output = sandbox.fails(["--revert-name", "solo"]);
+    assert_eq!(output.status.code(), Some(6));
+    assert!(
+        stderr(&output).contains("never been renamed"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(sandbox.entries()[0].1, "solo");
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::Sandbox;
+
+const COMPOSED: &str = "caf\u{e9}.txt";
+const DECOMPOSED: &str = "cafe\u{301}.txt";
+
+/// On a filesystem that keeps names byte for byte (ext4, tmpfs) the
+/// differently encoded sibling is a separate file, not the destination
+fn keeps_byte_for_byte_names() -> bool {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join(COMPOSED), "").unwrap();
+    std::fs::symlink_metadata(dir.path().join(DECOMPOSED)).is_err()
+}
+
+#[test]
+fn a_differently_encoded_sibling_is_not_a_conflict() {
+    if !keeps_byte_for_byte_names() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    sandbox.write(COMPOSED, "stashed");
+    sandbox.ok([COMPOSED, "--name", "cafe"]);
+    sandbox.write(DECOMPOSED, "unrelated");
+
+    sandbox.ok(["cafe"]);
+
+    assert_eq!(sandbox.read(COMPOSED), "stashed");
+    assert_eq!(sandbox.read(DECOMPOSED), "unrelated");
+}
+
+#[test]
+fn force_leaves_a_differently_encoded_sibling_alone() {
+    if !keeps_byte_for_byte_names() {
+        return;
+    }
+    let sandbox = Sandbox::new();
+    sandbox.write(COMPOSED, "stashed");
+    sandbox.ok([COMPOSED, "--name", "cafe"]);
+    sandbox.write(DECOMPOSED, "unrelated");
+
+    sandbox.ok(["cafe", "--force"]);
+
+    assert_eq!(sandbox.read(COMPOSED), "stashed");
+    assert_eq!(sandbox.read(DECOMPOSED), "unrelated");
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::Sandbox;
+use serde_json::Value;
+use std::fs;
+
+/// The `Pop` operations in the journal, oldest first
+fn pops(sandbox: &Sandbox) -> Vec<Value> {
+    fs::read_to_string(sandbox.stash_dir().join("journal.log"))
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .filter_map(|op| op["kind"].get("Pop").cloned())
+        .collect()
+}
+
+fn push_two(sandbox: &Sandbox) -> String {
+    sandbox.write("a.txt", "a");
+    sandbox.write("b.txt", "bb");
+    sandbox.ok(["a.txt", "b.txt", "--name", "pair"]);
+    sandbox.uuid_of("pair")
+}
+
+#[test]
+fn a_partial_pop_keeps_the_rest_of_the_entry() {
+    let sandbox = Sandbox::new();
+    let uuid = push_two(&sandbox);
+
+    sandbox.ok(["pop", "pair", "--only", "a.txt"]);
+
+    assert_eq!(sandbox.read("a.txt"), "a");
+    assert!(!sandbox.exists("b.txt"));
+    let items = sandbox.manifest(&uuid)["items"].as_array().unwrap().clone();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["original_path"], "b.txt");
+    let entry = &sandbox.index()["entries"][0];
+    assert_eq!(entry["total_size_bytes"], 2);
+    assert_eq!(entry["item_count"], 1);
+    assert_eq!(pops(&sandbox)[0]["kept"], 1);
+}
+
+#[test]
+fn popping_the_last_remaining_item_removes_the_entry() {
+    let sandbox = Sandbox::new();
+    let uuid = push_two(&sandbox);
+    sandbox.ok(["pop", "pair", "--only", "a.txt"]);
+
+    sandbox.ok(["pop", "pair", "--only", "b.txt"]);
+
+    assert_eq!(sandbox.read("b.txt"), "bb");
+    assert!(sandbox.entries().is_empty());
+    assert!(!sandbox.entry_dir(&uuid).exists());
+    let pops = pops(&sandbox);
+    assert_eq!(pops.len(), 2);
+    assert!(pops[1].get("kept").is_none(), "{}", pops[1]);
+}
+
+#[test]
+fn selecting_every_item_is_a_normal_pop() {
+    let sandbox = Sandbox::new();
+    let uuid = push_two(&sandbox);
+
+    sandbox.ok(["pop", "pair", "--only", "*.txt"]);
+
+    assert!(sandbox.entries().is_empty());
+    assert!(!sandbox.entry_dir(&uuid).exists());
+    assert!(pops(&sandbox)[0].get("kept").is_none());
+}
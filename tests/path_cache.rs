@@ -0,0 +1,53 @@
+mod common;
+
+use common::{stderr, stdout, Sandbox};
+use std::fs;
+
+/// Replace the cached item paths of every entry in the index
+fn set_cached_paths(sandbox: &Sandbox, paths: Option<&[&str]>) {
+    let mut index = sandbox.index();
+    for entry in index["entries"].as_array_mut().unwrap() {
+        match paths {
+            Some(paths) => entry["item_paths"] = paths.iter().map(|p| serde_json::Value::from(*p)).collect(),
+            None => {
+                entry.as_object_mut().unwrap().remove("item_paths");
+            }
+        }
+    }
+    fs::write(sandbox.stash_dir().join("index.json"), serde_json::to_string_pretty(&index).unwrap()).unwrap();
+}
+
+#[test]
+fn a_stale_cache_is_reported_and_rebuilt() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "notes");
+    sandbox.ok(["notes.txt"]);
+    set_cached_paths(&sandbox, Some(&["else.txt"]));
+
+    let output = sandbox.run(["--health"]);
+    let report = format!("{}{}", stdout(&output), stderr(&output));
+    assert!(report.contains("stale item path cache"), "{}", report);
+    assert!(sandbox.ok(["--list", "--export-paths"]).contains("else.txt"));
+
+    let out = sandbox.ok(["--init", "--force"]);
+
+    assert!(out.contains("Rebuilt the item path cache for 1 entry"), "{}", out);
+    let paths = sandbox.ok(["--list", "--export-paths"]);
+    assert!(paths.contains("notes.txt"), "{}", paths);
+    assert!(!paths.contains("else.txt"), "{}", paths);
+    assert_eq!(sandbox.index()["entries"][0]["item_paths"][0], "notes.txt");
+}
+
+#[test]
+fn a_missing_cache_falls_back_to_the_manifest() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "notes");
+    sandbox.ok(["notes.txt"]);
+    set_cached_paths(&sandbox, None);
+
+    let paths = sandbox.ok(["--list", "--export-paths"]);
+    assert!(paths.contains("notes.txt"), "{}", paths);
+
+    sandbox.ok(["--init", "--force"]);
+    assert_eq!(sandbox.index()["entries"][0]["item_paths"][0], "notes.txt");
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::{stderr, Sandbox};
+use std::fs;
+
+fn push_project(sandbox: &Sandbox) {
+    sandbox.write("src/main.rs", "fn main() {}");
+    sandbox.write("src/lib.rs", "");
+    sandbox.write("notes.txt", "notes");
+    sandbox.ok(["src/main.rs", "src/lib.rs", "notes.txt", "--name", "build"]);
+}
+
+#[test]
+fn peek_copies_only_matching_items() {
+    let sandbox = Sandbox::new();
+    push_project(&sandbox);
+
+    let out = sandbox.ok(["--peek", "build", "--only", "src/*.rs"]);
+
+    assert!(out.contains("Peeked 2 file(s)"), "{}", out);
+    assert!(sandbox.exists("src/main.rs"));
+    assert!(sandbox.exists("src/lib.rs"));
+    assert!(!sandbox.exists("notes.txt"));
+    assert_eq!(sandbox.manifest(&sandbox.uuid_of("build"))["items"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn peek_subcommand_takes_only() {
+    let sandbox = Sandbox::new();
+    push_project(&sandbox);
+
+    sandbox.ok(["peek", "build", "--only", "notes.txt"]);
+
+    assert!(sandbox.exists("notes.txt"));
+    assert!(!sandbox.exists("src/main.rs"));
+}
+
+#[test]
+fn list_only_shows_only_matching_items() {
+    let sandbox = Sandbox::new();
+    push_project(&sandbox);
+
+    let tree = sandbox.ok(["--peek", "build", "--list-only", "--only", "src/*.rs"]);
+
+    assert!(tree.contains("main.rs"), "{}", tree);
+    assert!(!tree.contains("notes.txt"), "{}", tree);
+}
+
+#[test]
+fn peek_with_an_unmatched_glob_fails() {
+    let sandbox = Sandbox::new();
+    push_project(&sandbox);
+
+    let output = sandbox.fails(["--peek", "build", "--only", "*.md"]);
+
+    assert_eq!(output.status.code(), Some(6));
+    assert!(stderr(&output).contains("none of the 3 item(s)"), "{}", stderr(&output));
+}
+
+#[test]
+fn peek_with_a_missing_data_file_fails_before_writing_anything() {
+    let sandbox = Sandbox::new();
+    push_project(&sandbox);
+    let uuid = sandbox.uuid_of("build");
+    fs::remove_file(sandbox.entry_dir(&uuid).join("data").join("notes.txt")).unwrap();
+
+    let output = sandbox.fails(["--peek", "build"]);
+
+    assert_eq!(output.status.code(), Some(5));
+    assert!(stderr(&output).contains("missing stashed data for 1 item(s)"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("notes.txt"), "{}", stderr(&output));
+    // The items before the missing one weren't restored either
+    assert!(!sandbox.exists("src/main.rs"));
+    assert!(!sandbox.exists("src/lib.rs"));
+    assert!(!sandbox.exists("notes.txt"));
+    assert_eq!(fs::read_dir(sandbox.work.join("src")).unwrap().count(), 0);
+}
@@ -0,0 +1,82 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{stderr, Sandbox};
+use std::fs;
+use std::os::unix::fs::symlink;
+
+/// An entry holding a directory whose dotfile was left behind (so popping
+/// merges into it), a symlink and a file, in that order
+fn stash_mixed_entry(sandbox: &Sandbox) -> String {
+    sandbox.write("proj/a", "a");
+    sandbox.write("proj/.hidden", "hidden");
+    sandbox.write("z.txt", "z");
+    symlink("z.txt", sandbox.work.join("link")).unwrap();
+    sandbox.ok(["proj", "link", "z.txt", "--name", "mixed", "--ignore-hidden"]);
+    sandbox.uuid_of("mixed")
+}
+
+/// Overwrite the stashed copy of `z.txt` so it no longer matches its hash
+fn corrupt_last_item(sandbox: &Sandbox, uuid: &str) {
+    fs::write(sandbox.entry_dir(uuid).join("data").join("z.txt"), "!").unwrap();
+}
+
+fn assert_stash_intact(sandbox: &Sandbox, uuid: &str) {
+    let data = sandbox.entry_dir(uuid).join("data");
+    assert_eq!(fs::read_to_string(data.join("proj").join("a")).unwrap(), "a");
+    assert!(fs::symlink_metadata(data.join("link")).is_ok());
+    assert!(data.join("z.txt").exists());
+    assert_eq!(sandbox.uuid_of("mixed"), uuid);
+}
+
+#[test]
+fn a_corrupt_item_rolls_back_the_whole_pop() {
+    let sandbox = Sandbox::new();
+    let uuid = stash_mixed_entry(&sandbox);
+    corrupt_last_item(&sandbox, &uuid);
+
+    let output = sandbox.fails(["mixed"]);
+
+    assert!(stderr(&output).contains("Nothing was restored"), "{}", stderr(&output));
+    assert!(!sandbox.exists("proj/a"), "merge was applied");
+    assert!(!sandbox.exists("link"), "symlink was created");
+    assert!(!sandbox.exists("z.txt"));
+    assert_eq!(sandbox.read("proj/.hidden"), "hidden");
+    assert_eq!(fs::read_dir(&sandbox.work).unwrap().count(), 1, "staged files left behind");
+    assert_stash_intact(&sandbox, &uuid);
+}
+
+#[test]
+fn a_corrupt_item_leaves_what_force_would_replace() {
+    let sandbox = Sandbox::new();
+    let uuid = stash_mixed_entry(&sandbox);
+    corrupt_last_item(&sandbox, &uuid);
+    sandbox.write("proj/a", "mine");
+    sandbox.write("link", "not a link");
+    sandbox.write("z.txt", "mine too");
+
+    sandbox.fails(["mixed", "--force"]);
+
+    assert_eq!(sandbox.read("proj/a"), "mine");
+    assert_eq!(sandbox.read("link"), "not a link");
+    assert_eq!(sandbox.read("z.txt"), "mine too");
+    assert_stash_intact(&sandbox, &uuid);
+}
+
+#[test]
+fn merges_and_links_are_restored_once_everything_checks_out() {
+    let sandbox = Sandbox::new();
+    stash_mixed_entry(&sandbox);
+    sandbox.write("proj/a", "mine");
+
+    sandbox.ok(["mixed", "--force"]);
+
+    assert_eq!(sandbox.read("proj/a"), "a");
+    assert_eq!(sandbox.read("proj/.hidden"), "hidden");
+    assert_eq!(fs::read_link(sandbox.work.join("link")).unwrap().to_str(), Some("z.txt"));
+    assert_eq!(sandbox.read("z.txt"), "z");
+    assert_eq!(fs::read_dir(&sandbox.work).unwrap().count(), 3, "staged files left behind");
+    assert_eq!(fs::read_dir(sandbox.work.join("proj")).unwrap().count(), 2);
+    assert!(sandbox.entries().is_empty());
+}
@@ -0,0 +1,111 @@
+mod common;
+
+use common::Sandbox;
+
+fn lines(output: &str) -> Vec<Vec<&str>> {
+    output
+        .lines()
+        .map(|line| line.split('\t').collect())
+        .collect()
+}
+
+#[test]
+fn list_prints_uuid_name_created_size_and_items() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "12345");
+    sandbox.write("b.txt", "678");
+    sandbox.ok(["a.txt", "b.txt", "--name", "pair"]);
+    let uuid = sandbox.uuid_of("pair");
+    let created = sandbox.index()["entries"][0]["created"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let listed = sandbox.ok(["--list", "--porcelain"]);
+
+    let rows = lines(&listed);
+    assert_eq!(rows.len(), 1, "{}", listed);
+    let row = &rows[0];
+    assert_eq!(row.len(), 5, "{}", listed);
+    assert_eq!(row[0], uuid);
+    assert_eq!(row[1], "pair");
+    assert_eq!(
+        chrono::DateTime::parse_from_rfc3339(row[2]).unwrap(),
+        chrono::DateTime::parse_from_rfc3339(&created).unwrap()
+    );
+    assert_eq!(row[3], "8");
+    assert_eq!(row[4], "2");
+    assert!(!listed.contains('\x1b'));
+}
+
+#[test]
+fn search_prints_the_list_format() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.ok(["a.txt", "--name", "wanted"]);
+    sandbox.write("b.txt", "b");
+    sandbox.ok(["b.txt", "--name", "other"]);
+
+    let found = sandbox.ok(["--search", "want", "--porcelain"]);
+    let listed = sandbox.ok(["--list", "--porcelain"]);
+
+    assert_eq!(lines(&found).len(), 1, "{}", found);
+    assert!(
+        listed.lines().any(|line| line == found.trim_end()),
+        "{}\n{}",
+        found,
+        listed
+    );
+}
+
+#[test]
+fn names_are_escaped_to_stay_on_one_line() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.ok(["a.txt", "--name", "tab\there\nnewline\\slash"]);
+
+    let listed = sandbox.ok(["--list", "--porcelain"]);
+
+    let rows = lines(&listed);
+    assert_eq!(rows.len(), 1, "{}", listed);
+    assert_eq!(rows[0][1], "tab\\there\\nnewline\\\\slash");
+}
+
+#[test]
+fn find_prints_uuid_and_path() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.write("b.txt", "b");
+    sandbox.ok(["a.txt", "--name", "first", "--copy"]);
+    sandbox.ok(["a.txt", "b.txt", "--name", "second"]);
+    let first = sandbox.uuid_of("first");
+    let second = sandbox.uuid_of("second");
+
+    let found = sandbox.ok(["--find", "a.txt", "--porcelain"]);
+
+    let mut rows = lines(&found);
+    rows.sort();
+    let mut expected = vec![
+        vec![first.as_str(), "a.txt"],
+        vec![second.as_str(), "a.txt"],
+    ];
+    expected.sort();
+    assert_eq!(rows, expected);
+    assert_eq!(sandbox.ok(["--find", "c.txt", "--porcelain"]), "");
+}
+
+#[test]
+fn push_and_pop_print_only_the_entry_uuid() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+
+    let pushed = sandbox.ok(["a.txt", "--name", "entry", "--porcelain"]);
+
+    let uuid = sandbox.uuid_of("entry");
+    assert_eq!(pushed, format!("{}\n", uuid));
+
+    let popped = sandbox.ok(["pop", "entry", "--porcelain"]);
+
+    assert_eq!(popped, format!("{}\n", uuid));
+    assert_eq!(sandbox.read("a.txt"), "a");
+}
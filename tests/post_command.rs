@@ -0,0 +1,60 @@
+mod common;
+
+use common::Sandbox;
+use std::fs;
+
+#[test]
+fn runs_in_the_directory_the_entry_was_pushed_from() {
+    let sandbox = Sandbox::new();
+    let project = sandbox.work.join("project");
+    sandbox.write("project/notes.txt", "notes");
+    sandbox.run_in(&project, ["notes.txt", "--name", "notes"]);
+    let elsewhere = sandbox.root().join("elsewhere");
+    fs::create_dir_all(&elsewhere).unwrap();
+
+    let output = sandbox.run_in(&elsewhere, ["pop", "notes", "--post-command", "pwd > ran-in.txt"]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(elsewhere.join("notes.txt").exists());
+    let ran_in = fs::read_to_string(project.join("ran-in.txt")).unwrap();
+    assert_eq!(fs::canonicalize(ran_in.trim()).unwrap(), fs::canonicalize(&project).unwrap());
+    assert!(!elsewhere.join("ran-in.txt").exists());
+}
+
+#[test]
+fn a_failing_command_passes_its_exit_code_through() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "notes");
+    sandbox.ok(["notes.txt"]);
+
+    let output = sandbox.fails(["notes.txt", "--post-command", "exit 9"]);
+
+    assert_eq!(output.status.code(), Some(9));
+    assert!(sandbox.exists("notes.txt"));
+}
+
+#[test]
+fn runs_once_after_the_pop_is_reported() {
+    let sandbox = Sandbox::new();
+    for format in ["--porcelain", "--quiet"] {
+        sandbox.write("notes.txt", "notes");
+        sandbox.ok(["notes.txt", "--name", "notes"]);
+
+        let output = sandbox.ok(["pop", "notes", format, "--post-command", "echo ran >> hook.log"]);
+
+        assert!(sandbox.exists("notes.txt"), "{}", output);
+    }
+    assert_eq!(sandbox.read("hook.log"), "ran\nran\n");
+}
+
+#[test]
+fn restore_pops_run_it_too() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "notes");
+    sandbox.ok(["notes.txt", "--name", "notes"]);
+
+    sandbox.ok(["pop", "notes", "--restore", "--post-command", "echo ran > hook.log"]);
+
+    assert_eq!(sandbox.read("hook.log"), "ran\n");
+    assert!(sandbox.exists("notes.txt"));
+}
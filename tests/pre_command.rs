@@ -0,0 +1,58 @@
+mod common;
+
+use common::{stderr, Sandbox};
+use std::fs;
+
+#[test]
+fn a_failing_command_leaves_the_sources_and_the_index_alone() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "notes");
+    sandbox.write("src/main.rs", "fn main() {}");
+
+    let output = sandbox.fails(["notes.txt", "src", "--pre-command", "exit 9"]);
+
+    assert_eq!(output.status.code(), Some(9));
+    assert!(
+        stderr(&output).contains("nothing was stashed"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(sandbox.read("notes.txt"), "notes");
+    assert_eq!(sandbox.read("src/main.rs"), "fn main() {}");
+    assert!(sandbox.entries().is_empty());
+    let entries_dir = sandbox.stash_dir().join("entries");
+    assert!(fs::read_dir(&entries_dir).map_or(true, |mut dir| dir.next().is_none()));
+}
+
+#[test]
+fn runs_in_the_working_directory_before_stashing() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "notes");
+
+    sandbox.ok(["notes.txt", "--pre-command", "cp notes.txt seen.txt"]);
+
+    assert_eq!(sandbox.read("seen.txt"), "notes");
+    assert!(!sandbox.exists("notes.txt"));
+}
+
+#[test]
+fn verbose_logs_the_command_and_its_exit_code() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.write("b.txt", "b");
+
+    let quiet = sandbox.run(["a.txt", "--pre-command", "true"]);
+    let verbose = sandbox.run(["b.txt", "--pre-command", "true", "--verbose"]);
+
+    assert!(!stderr(&quiet).contains("Running:"), "{}", stderr(&quiet));
+    assert!(
+        stderr(&verbose).contains("Running: true"),
+        "{}",
+        stderr(&verbose)
+    );
+    assert!(
+        stderr(&verbose).contains("exit code 0"),
+        "{}",
+        stderr(&verbose)
+    );
+}
@@ -0,0 +1,125 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::read_only::ReadOnly;
+use common::{stderr, stdout, Sandbox};
+
+fn stashed(names: &[&str]) -> Sandbox {
+    let sandbox = Sandbox::new();
+    for name in names {
+        sandbox.write(name, *name);
+        sandbox.ok([*name, "--name", name]);
+    }
+    sandbox
+}
+
+fn flagged(sandbox: &Sandbox, uuid: &str) -> bool {
+    sandbox.index()["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|meta| meta["uuid"] == uuid)
+        .map(|meta| meta["removal_failed"] == true)
+        .unwrap()
+}
+
+#[test]
+fn a_read_only_entry_is_kept_and_flagged_when_deleting() {
+    let sandbox = stashed(&["one"]);
+    let uuid = sandbox.uuid_of("one");
+    let entry_dir = sandbox.entry_dir(&uuid);
+
+    {
+        let entries_dir = entry_dir.parent().unwrap();
+        let Some(_read_only) = ReadOnly::new(&[entries_dir]) else {
+            return;
+        };
+        let output = sandbox.fails(["--delete", "one", "--force"]);
+        assert!(
+            stderr(&output).contains("Failed to remove"),
+            "{}",
+            stderr(&output)
+        );
+    }
+
+    assert!(entry_dir.join("manifest.json").is_file());
+    assert!(entry_dir.join("data").join("one").is_file());
+    assert!(flagged(&sandbox, &uuid));
+    let health = sandbox.fails(["--health"]);
+    assert!(
+        stdout(&health).contains("could not be fully removed"),
+        "{}",
+        stdout(&health)
+    );
+
+    sandbox.ok(["--delete", "one", "--force"]);
+
+    assert!(sandbox.entries().is_empty());
+    assert!(!entry_dir.exists());
+}
+
+#[test]
+fn clean_reports_a_read_only_entry_and_removes_the_rest() {
+    let sandbox = stashed(&["one", "two"]);
+    let one = sandbox.uuid_of("one");
+    let two = sandbox.uuid_of("two");
+
+    // Locking the entries directory would keep every entry; lock the
+    // directories holding two's files instead
+    let entry_dir = sandbox.entry_dir(&two);
+    let data_dir = entry_dir.join("data");
+    let output = {
+        let Some(_read_only) = ReadOnly::new(&[&entry_dir, &data_dir]) else {
+            return;
+        };
+        sandbox.fails(["--clean", "0", "--force"])
+    };
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("one"), "{}", stdout(&output));
+    assert!(
+        stderr(&output).contains("Failed to clean 'two'"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!sandbox.entry_dir(&one).exists());
+    let entries = sandbox.entries();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].0, two);
+    assert!(flagged(&sandbox, &two));
+    assert!(sandbox.entry_dir(&two).join("data").join("two").is_file());
+}
+
+#[test]
+fn keep_latest_keeps_an_entry_it_could_not_remove() {
+    let sandbox = stashed(&["one", "two", "three"]);
+    sandbox.set_created("one", "2024-01-01T00:00:00Z");
+    sandbox.set_created("two", "2024-02-01T00:00:00Z");
+    let one = sandbox.uuid_of("one");
+    let two = sandbox.uuid_of("two");
+
+    let entry_dir = sandbox.entry_dir(&two);
+    let data_dir = entry_dir.join("data");
+    let output = {
+        let Some(_read_only) = ReadOnly::new(&[&entry_dir, &data_dir]) else {
+            return;
+        };
+        sandbox.fails(["--clean", "--keep-latest", "1", "--force"])
+    };
+
+    assert!(
+        stderr(&output).contains("Failed to clean 'two'"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!sandbox.entry_dir(&one).exists());
+    let mut names: Vec<String> = sandbox
+        .entries()
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect();
+    names.sort();
+    assert_eq!(names, ["three", "two"]);
+    assert!(flagged(&sandbox, &two));
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::{stderr, Sandbox};
+
+fn pushed(names: &[&str]) -> Sandbox {
+    let sandbox = Sandbox::new();
+    for name in names {
+        let file = format!("{}.txt", name);
+        sandbox.write(&file, *name);
+        sandbox.ok([file.as_str(), "--name", name]);
+    }
+    sandbox
+}
+
+#[test]
+fn renaming_updates_the_index_and_the_manifest() {
+    let sandbox = pushed(&["old"]);
+    let uuid = sandbox.uuid_of("old");
+    let updated = sandbox.manifest(&uuid)["updated"].clone();
+
+    sandbox.ok(["--rename", "old:new"]);
+
+    assert_eq!(sandbox.index()["entries"][0]["name"], "new");
+    let manifest = sandbox.manifest(&uuid);
+    assert_eq!(manifest["name"], "new");
+    assert_ne!(manifest["updated"], updated);
+    let info = sandbox.ok(["--info", "new"]);
+    assert!(info.contains(&uuid), "{}", info);
+    sandbox.fails(["--info", "old"]);
+}
+
+#[test]
+fn empty_names_are_rejected() {
+    let sandbox = pushed(&["old"]);
+    let uuid = sandbox.uuid_of("old");
+
+    for new in ["", "   "] {
+        let output = sandbox.fails(["--rename", "--from-uuid", &uuid, "--to", new]);
+        assert_eq!(output.status.code(), Some(2), "{:?}", new);
+        assert!(
+            stderr(&output).contains("cannot be empty"),
+            "{}",
+            stderr(&output)
+        );
+    }
+
+    assert_eq!(sandbox.manifest(&uuid)["name"], "old");
+    assert_eq!(sandbox.index()["entries"][0]["name"], "old");
+}
+
+#[test]
+fn names_in_use_are_rejected() {
+    let sandbox = pushed(&["first", "second"]);
+    let first = sandbox.uuid_of("first");
+
+    let output = sandbox.fails(["--rename", "first:second"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        stderr(&output).contains("already exists"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(sandbox.manifest(&first)["name"], "first");
+    let mut names: Vec<String> = sandbox
+        .entries()
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect();
+    names.sort();
+    assert_eq!(names, ["first", "second"]);
+}
+
+#[test]
+fn an_entry_can_keep_its_own_name() {
+    let sandbox = pushed(&["same"]);
+
+    sandbox.ok(["--rename", "same:same"]);
+
+    assert_eq!(sandbox.entries()[0].1, "same");
+}
+
+#[test]
+fn names_with_colons_can_be_renamed() {
+    let sandbox = Sandbox::new();
+    sandbox.write("ab.txt", "ab");
+    sandbox.ok(["ab.txt", "--name", "a:b"]);
+    let uuid = sandbox.uuid_of("a:b");
+
+    sandbox.ok(["--rename", r"a\:b:c:d"]);
+
+    assert_eq!(sandbox.index()["entries"][0]["name"], "c:d");
+    assert_eq!(sandbox.manifest(&uuid)["name"], "c:d");
+}
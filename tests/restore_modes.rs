@@ -0,0 +1,121 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::Sandbox;
+use filetime::FileTime;
+use serde_json::Value;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+const STAMP: i64 = 1_500_000_000;
+
+/// Push `tree/` (a 0750 directory holding a 0640 file) and `tool.sh` (0755),
+/// each with an old modification time; returns the entry's manifest
+fn push_tree(sandbox: &Sandbox, extra: &[&str]) -> Value {
+    let file = sandbox.write("tree/data.txt", "data");
+    let tool = sandbox.write("tool.sh", "#!/bin/sh\n");
+    let dir = sandbox.work.join("tree");
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+    fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o750)).unwrap();
+    for path in [&file, &tool, &dir] {
+        filetime::set_file_mtime(path, FileTime::from_unix_time(STAMP, 0)).unwrap();
+    }
+
+    let mut args = vec!["tree", "tool.sh", "--name", "entry"];
+    args.extend_from_slice(extra);
+    sandbox.ok(args);
+    sandbox.manifest(&sandbox.uuid_of("entry"))
+}
+
+/// Every item of `manifest` under `base` has the mode and mtime it records
+fn assert_matches_manifest(base: &Path, manifest: &Value) {
+    for item in manifest["items"].as_array().unwrap() {
+        let path = base.join(item["original_path"].as_str().unwrap());
+        let metadata = fs::metadata(&path).unwrap();
+        assert_eq!(
+            metadata.permissions().mode() & 0o7777,
+            item["permissions"].as_u64().unwrap() as u32 & 0o7777,
+            "mode of {:?}",
+            path
+        );
+        let recorded: chrono::DateTime<chrono::Utc> = item["modified"].as_str().unwrap().parse().unwrap();
+        assert_eq!(
+            FileTime::from_last_modification_time(&metadata).unix_seconds(),
+            recorded.timestamp(),
+            "mtime of {:?}",
+            path
+        );
+    }
+}
+
+#[test]
+fn pop_by_move_restores_modes_and_mtimes() {
+    let sandbox = Sandbox::new();
+    let manifest = push_tree(&sandbox, &[]);
+
+    sandbox.ok(["pop", "entry"]);
+
+    assert_matches_manifest(&sandbox.work, &manifest);
+}
+
+#[test]
+fn pop_as_copy_restores_modes_and_mtimes() {
+    let sandbox = Sandbox::new();
+    let manifest = push_tree(&sandbox, &[]);
+
+    sandbox.ok(["pop", "entry", "--copy"]);
+
+    assert_matches_manifest(&sandbox.work, &manifest);
+    assert_eq!(sandbox.entries().len(), 1);
+}
+
+#[test]
+fn peek_restores_modes_and_mtimes() {
+    let sandbox = Sandbox::new();
+    let manifest = push_tree(&sandbox, &[]);
+
+    sandbox.ok(["--peek", "entry"]);
+
+    assert_matches_manifest(&sandbox.work, &manifest);
+    assert_eq!(sandbox.entries().len(), 1);
+}
+
+#[test]
+fn restore_restores_modes_and_mtimes() {
+    let sandbox = Sandbox::new();
+    let manifest = push_tree(&sandbox, &[]);
+    let elsewhere = sandbox.root().join("elsewhere");
+    fs::create_dir_all(&elsewhere).unwrap();
+
+    let output = sandbox.run_in(&elsewhere, ["entry", "--restore"]);
+
+    assert!(output.status.success(), "{}", common::stderr(&output));
+    assert_matches_manifest(&sandbox.work, &manifest);
+}
+
+#[test]
+fn compressed_entries_restore_modes_and_mtimes() {
+    let sandbox = Sandbox::new();
+    let manifest = push_tree(&sandbox, &["--compress"]);
+
+    sandbox.ok(["--peek", "entry"]);
+
+    assert_matches_manifest(&sandbox.work, &manifest);
+}
+
+#[test]
+fn modes_are_restored_without_preserve_mtime() {
+    let sandbox = Sandbox::new();
+    sandbox.set_config("preserve_mtime", "false");
+    push_tree(&sandbox, &[]);
+    let stashed = sandbox.entry_dir(&sandbox.uuid_of("entry")).join("data").join("tool.sh");
+    fs::set_permissions(&stashed, fs::Permissions::from_mode(0o600)).unwrap();
+
+    sandbox.ok(["--peek", "entry"]);
+
+    let metadata = fs::metadata(sandbox.work.join("tool.sh")).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o755);
+}
@@ -0,0 +1,43 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::Sandbox;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+
+/// Where the stash keeps the first item of entry `name`
+fn blob_of(sandbox: &Sandbox, name: &str) -> PathBuf {
+    let uuid = sandbox.uuid_of(name);
+    let manifest = sandbox.manifest(&uuid);
+    let stashed = manifest["items"][0]["stashed_path"].as_str().unwrap();
+    sandbox.entry_dir(&uuid).join("data").join(stashed)
+}
+
+#[test]
+fn popping_one_of_two_entries_sharing_a_blob_leaves_the_other_intact() {
+    let sandbox = Sandbox::new();
+    sandbox.write("notes.txt", "shared");
+    sandbox.ok(["notes.txt", "--hardlink", "--name", "one"]);
+    sandbox.ok(["notes.txt", "--hardlink", "--name", "two"]);
+    fs::remove_file(sandbox.work.join("notes.txt")).unwrap();
+    assert_eq!(
+        fs::metadata(blob_of(&sandbox, "one")).unwrap().ino(),
+        fs::metadata(blob_of(&sandbox, "two")).unwrap().ino()
+    );
+
+    sandbox.ok(["pop", "one"]);
+    // Written in place: a popped file that was the shared inode would
+    // change the other entry too
+    fs::write(sandbox.work.join("notes.txt"), "edited").unwrap();
+
+    assert_eq!(
+        fs::read_to_string(blob_of(&sandbox, "two")).unwrap(),
+        "shared"
+    );
+    fs::remove_file(sandbox.work.join("notes.txt")).unwrap();
+    sandbox.ok(["pop", "two"]);
+    assert_eq!(sandbox.read("notes.txt"), "shared");
+    assert!(sandbox.entries().is_empty());
+}
@@ -0,0 +1,82 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::Sandbox;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+const LOGICAL_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Bytes the file's blocks take up on disk
+fn disk_usage(path: &Path) -> u64 {
+    fs::metadata(path).unwrap().blocks() * 512
+}
+
+/// A 32 MB file that is a hole apart from a few bytes in the middle.
+/// None when the filesystem fills holes in.
+fn sparse_file(sandbox: &Sandbox, name: &str) -> Option<PathBuf> {
+    let path = sandbox.write(name, "");
+    File::create(&path).unwrap().set_len(LOGICAL_SIZE).unwrap();
+    let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+    file.seek(SeekFrom::Start(LOGICAL_SIZE / 2)).unwrap();
+    file.write_all(b"data").unwrap();
+    (disk_usage(&path) < LOGICAL_SIZE / 2).then_some(path)
+}
+
+#[test]
+fn copied_sparse_files_stay_sparse_in_the_stash() {
+    let sandbox = Sandbox::new();
+    let Some(source) = sparse_file(&sandbox, "disk.img") else {
+        return;
+    };
+    let source_usage = disk_usage(&source);
+
+    sandbox.ok(["disk.img", "--name", "sparse", "--copy"]);
+
+    let uuid = sandbox.uuid_of("sparse");
+    let stashed = sandbox.entry_dir(&uuid).join("data").join("disk.img");
+    assert_eq!(fs::metadata(&stashed).unwrap().len(), LOGICAL_SIZE);
+    assert!(
+        disk_usage(&stashed) <= source_usage + 1024 * 1024,
+        "stashed copy uses {} bytes, the source {}",
+        disk_usage(&stashed),
+        source_usage
+    );
+    let info = sandbox.ok(["--info", "sparse"]);
+    assert!(info.contains("on disk, sparse files"), "{}", info);
+}
+
+#[test]
+fn sparse_files_round_trip() {
+    let sandbox = Sandbox::new();
+    let Some(source) = sparse_file(&sandbox, "disk.img") else {
+        return;
+    };
+    let original = fs::read(&source).unwrap();
+
+    sandbox.ok(["disk.img", "--name", "sparse", "--copy"]);
+    fs::remove_file(sandbox.root().join("work/disk.img")).unwrap();
+    sandbox.ok(["pop", "sparse"]);
+
+    let restored = sandbox.root().join("work/disk.img");
+    assert_eq!(fs::read(&restored).unwrap(), original);
+    assert!(
+        disk_usage(&restored) < LOGICAL_SIZE / 2,
+        "holes were filled in"
+    );
+}
+
+#[test]
+fn zero_byte_files_round_trip() {
+    let sandbox = Sandbox::new();
+    sandbox.write("empty.txt", "");
+
+    sandbox.ok(["empty.txt", "--name", "empty"]);
+
+    assert_eq!(sandbox.index()["entries"][0]["total_size_bytes"], 0);
+    sandbox.ok(["pop", "empty"]);
+    assert_eq!(sandbox.read("empty.txt"), "");
+}
@@ -0,0 +1,37 @@
+mod common;
+
+use common::Sandbox;
+
+fn oldest_line(status: &str) -> &str {
+    status.lines().find(|line| line.starts_with("Oldest:")).unwrap()
+}
+
+#[test]
+fn oldest_follows_the_time_style() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.ok(["a.txt", "--name", "old"]);
+    sandbox.set_created("old", "2020-01-02T12:00:00Z");
+
+    let both = sandbox.ok(["--status"]);
+    let absolute = sandbox.ok(["--status", "--absolute"]);
+    let relative = sandbox.ok(["--status", "--relative"]);
+
+    assert!(oldest_line(&both).contains("2020-01-02") && oldest_line(&both).contains("ago"), "{}", both);
+    assert!(oldest_line(&absolute).contains("2020-01-02") && !oldest_line(&absolute).contains("ago"), "{}", absolute);
+    assert!(!oldest_line(&relative).contains("2020") && oldest_line(&relative).contains("ago"), "{}", relative);
+}
+
+#[test]
+fn counts_undoable_operations() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.ok(["a.txt", "--name", "a"]);
+    sandbox.ok(["pop", "a"]);
+    sandbox.ok(["a.txt", "--name", "a"]);
+    sandbox.ok(["--peek", "a", "--force"]);
+
+    let status = sandbox.ok(["--status", "--porcelain"]);
+
+    assert!(status.lines().any(|line| line == "undoable=3"), "{}", status);
+}
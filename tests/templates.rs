@@ -0,0 +1,119 @@
+mod common;
+
+use common::{stderr, Sandbox};
+
+/// A copied entry `base` of `a.txt` and `cfg/x`, marked as a template
+fn with_template() -> Sandbox {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "first");
+    sandbox.write("cfg/x", "config");
+    sandbox.ok(["a.txt", "cfg", "--name", "base", "--copy"]);
+    sandbox.ok(["--mark-template", "base"]);
+    sandbox
+}
+
+fn is_template(sandbox: &Sandbox, name: &str) -> bool {
+    sandbox.index()["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|meta| meta["name"] == name)
+        .map(|meta| meta["is_template"] == true)
+        .unwrap()
+}
+
+#[test]
+fn marking_flags_the_entry_and_list_shows_it() {
+    let sandbox = with_template();
+
+    assert!(is_template(&sandbox, "base"));
+    let list = sandbox.ok(["--list"]);
+    assert!(list.contains("base [T]"), "{}", list);
+
+    sandbox.ok(["--unmark-template", "base"]);
+
+    assert!(!is_template(&sandbox, "base"));
+    assert!(!sandbox.ok(["--list"]).contains("[T]"));
+}
+
+#[test]
+fn pushing_from_a_template_stashes_the_current_files() {
+    let sandbox = with_template();
+    let template = sandbox.uuid_of("base");
+    sandbox.write("a.txt", "second");
+
+    sandbox.ok(["--template", "base", "--name", "again"]);
+
+    let uuid = sandbox.uuid_of("again");
+    assert_ne!(uuid, template);
+    assert!(!is_template(&sandbox, "again"));
+    assert!(!sandbox.exists("a.txt"));
+    sandbox.ok(["pop", "again"]);
+    assert_eq!(sandbox.read("a.txt"), "second");
+    assert_eq!(sandbox.read("cfg/x"), "config");
+
+    // The template itself is left as it was
+    assert!(is_template(&sandbox, "base"));
+    let data = sandbox.entry_dir(&template).join("data");
+    assert_eq!(
+        std::fs::read_to_string(data.join("a.txt")).unwrap(),
+        "first"
+    );
+}
+
+#[test]
+fn template_pushes_are_named_after_the_template() {
+    let sandbox = with_template();
+
+    sandbox.ok(["--template", "base"]);
+
+    let names: Vec<String> = sandbox
+        .entries()
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect();
+    assert_eq!(names.len(), 2);
+    assert!(
+        names
+            .iter()
+            .any(|name| name.starts_with("base-") && name != "base"),
+        "{:?}",
+        names
+    );
+}
+
+#[test]
+fn missing_paths_are_skipped() {
+    let sandbox = with_template();
+    std::fs::remove_dir_all(sandbox.work.join("cfg")).unwrap();
+
+    let output = sandbox.run(["--template", "base", "--name", "partial"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Skipping cfg"),
+        "{}",
+        stderr(&output)
+    );
+    let uuid = sandbox.uuid_of("partial");
+    assert_eq!(
+        sandbox.manifest(&uuid)["items"].as_array().unwrap().len(),
+        1
+    );
+}
+
+#[test]
+fn only_templates_can_be_pushed_from() {
+    let sandbox = with_template();
+    sandbox.ok(["--unmark-template", "base"]);
+
+    for name in ["base", "nosuch"] {
+        let output = sandbox.fails(["--template", name]);
+        assert_eq!(output.status.code(), Some(3));
+        assert!(
+            stderr(&output).contains("no template by that name"),
+            "{}",
+            stderr(&output)
+        );
+    }
+}
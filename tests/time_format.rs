@@ -0,0 +1,69 @@
+mod common;
+
+use chrono::{DateTime, Local, Utc};
+use common::Sandbox;
+
+const CREATED: &str = "2023-03-04T12:00:00Z";
+
+/// The created time each command shows for the only entry, under `style`
+/// (`--absolute`, `--relative` or nothing)
+fn shown_times(sandbox: &Sandbox, style: &[&str]) -> [String; 3] {
+    let run = |args: &[&str]| sandbox.ok(args.iter().chain(style));
+
+    let list = run(&["--list"]);
+    let list = list.lines().find(|line| line.contains("entry")).unwrap();
+    let list = list.rsplit("  ").next().unwrap().to_string();
+
+    let search = run(&["--search", "entry"]);
+    let search = search.lines().find(|line| line.contains("entry")).unwrap();
+    let search = search.trim_end_matches(')').rsplit(", ").next().unwrap().to_string();
+
+    let info = run(&["--info", "entry"]);
+    let info = info.lines().find_map(|line| line.strip_prefix("Created: ")).unwrap().to_string();
+
+    [list, search, info]
+}
+
+fn sandbox_with_old_entry() -> Sandbox {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "a");
+    sandbox.ok(["a.txt", "--name", "entry"]);
+    sandbox.set_created("entry", CREATED);
+    sandbox
+}
+
+#[test]
+fn absolute_times_match_across_commands() {
+    let sandbox = sandbox_with_old_entry();
+    let expected = CREATED.parse::<DateTime<Utc>>().unwrap().with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string();
+
+    let [list, search, info] = shown_times(&sandbox, &["--absolute"]);
+
+    assert_eq!(list, expected);
+    assert_eq!(search, expected);
+    assert_eq!(info, expected);
+}
+
+#[test]
+fn relative_times_match_across_commands() {
+    let sandbox = sandbox_with_old_entry();
+
+    let [list, search, info] = shown_times(&sandbox, &["--relative"]);
+
+    assert!(info.ends_with(" ago"), "{}", info);
+    assert_eq!(list, info);
+    assert_eq!(search, info);
+}
+
+#[test]
+fn info_shows_both_by_default_and_list_and_search_the_age() {
+    let sandbox = sandbox_with_old_entry();
+    let [absolute, _, _] = shown_times(&sandbox, &["--absolute"]);
+    let [relative, _, _] = shown_times(&sandbox, &["--relative"]);
+
+    let [list, search, info] = shown_times(&sandbox, &[]);
+
+    assert_eq!(list, relative);
+    assert_eq!(search, relative);
+    assert_eq!(info, format!("{} ({})", absolute, relative));
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{stderr, Sandbox};
+use std::fs;
+use std::process::Command;
+
+/// Copy a whole directory as `.`, which has no file name to derive an entry
+/// name from; returns the entry's UUID
+fn unnamed(sandbox: &Sandbox) -> String {
+    let project = sandbox.work.join("project");
+    fs::create_dir_all(&project).unwrap();
+    fs::write(project.join("f.txt"), "contents").unwrap();
+    let output = sandbox.run_in(&project, [".", "--copy"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    sandbox.entries()[0].0.clone()
+}
+
+fn short(uuid: &str) -> &str {
+    &uuid[..6]
+}
+
+#[test]
+fn unnamed_entries_list_as_their_short_uuid() {
+    let sandbox = Sandbox::new();
+    let uuid = unnamed(&sandbox);
+
+    assert_eq!(sandbox.index()["entries"][0]["name"], "");
+    assert_eq!(sandbox.entries(), [(uuid.clone(), String::new())]);
+    let list = sandbox.ok(["--list"]);
+    assert!(list.contains(&format!("1. {}", short(&uuid))), "{}", list);
+}
+
+#[test]
+fn info_and_peek_show_unnamed_entries() {
+    let sandbox = Sandbox::new();
+    let uuid = unnamed(&sandbox);
+
+    let info = sandbox.ok(["--info", &uuid]);
+    assert!(info.contains(&uuid), "{}", info);
+
+    let peek = sandbox.ok(["--peek", &uuid, "--list-only"]);
+    assert!(peek.starts_with(&format!("'{}'", short(&uuid))), "{}", peek);
+}
+
+#[test]
+fn exports_name_unnamed_entries_by_short_uuid() {
+    let sandbox = Sandbox::new();
+    let uuid = unnamed(&sandbox);
+    let dump = sandbox.root().join("dump");
+    let archive = sandbox.root().join("export.tar");
+
+    sandbox.ok(["--dump", "--to", dump.to_str().unwrap()]);
+    sandbox.ok(["--tar", archive.to_str().unwrap()]);
+
+    assert_eq!(
+        fs::read_to_string(dump.join(short(&uuid)).join("f.txt")).unwrap(),
+        "contents"
+    );
+    let listing = Command::new("tar")
+        .arg("-tf")
+        .arg(&archive)
+        .output()
+        .unwrap();
+    let listing = String::from_utf8_lossy(&listing.stdout);
+    assert!(
+        listing
+            .lines()
+            .any(|line| line == format!("{}/data/f.txt", short(&uuid))),
+        "{}",
+        listing
+    );
+}
+
+#[test]
+fn unnamed_entries_can_be_named() {
+    let sandbox = Sandbox::new();
+    let uuid = unnamed(&sandbox);
+
+    sandbox.ok(["--rename", "--from-uuid", &uuid, "--to", "project"]);
+
+    assert_eq!(sandbox.uuid_of("project"), uuid);
+    assert_eq!(sandbox.manifest(&uuid)["name"], "project");
+}
@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+mod common;
+
+use common::{stderr, Sandbox};
+use std::fs::{self, File};
+use std::os::unix::fs::MetadataExt;
+
+/// An empty 8 TB sparse file, far more than the test machine has free.
+/// None when the filesystem can't hold it as a hole.
+fn huge_file(sandbox: &Sandbox, name: &str) -> Option<()> {
+    let path = sandbox.write(name, "");
+    File::create(&path).unwrap().set_len(8 << 40).ok()?;
+    (fs::metadata(&path).unwrap().blocks() == 0).then_some(())
+}
+
+#[test]
+fn verify_dest_refuses_a_pop_that_would_not_fit() {
+    let sandbox = Sandbox::new();
+    if huge_file(&sandbox, "huge.img").is_none() {
+        return;
+    }
+    sandbox.ok(["huge.img", "--no-hash", "--quiet"]);
+
+    let output = sandbox.fails(["pop", "huge.img", "--copy", "--verify-dest"]);
+
+    assert_eq!(output.status.code(), Some(7));
+    assert!(
+        stderr(&output).contains("Not enough space"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!sandbox.exists("huge.img"));
+    assert_eq!(sandbox.entries().len(), 1);
+}
+
+#[test]
+fn check_space_reports_the_shortfall_without_popping() {
+    let sandbox = Sandbox::new();
+    if huge_file(&sandbox, "huge.img").is_none() {
+        return;
+    }
+    sandbox.ok(["huge.img", "--no-hash", "--quiet"]);
+
+    let output = sandbox.fails(["--check-space", "huge.img", "--copy"]);
+
+    assert_eq!(output.status.code(), Some(7));
+    assert!(stderr(&output).contains("short"), "{}", stderr(&output));
+    assert_eq!(sandbox.entries().len(), 1);
+}
+
+#[test]
+fn verify_dest_lets_a_pop_that_fits_through() {
+    let sandbox = Sandbox::new();
+    sandbox.write("a.txt", "hello");
+    sandbox.ok(["a.txt", "--quiet"]);
+
+    sandbox.ok(["--restore", "--verify-dest", "a.txt"]);
+
+    assert_eq!(sandbox.read("a.txt"), "hello");
+    assert!(sandbox.entries().is_empty());
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use common::{stderr, Sandbox};
+
+/// Push `notes.txt` as `notes` once per content, keeping up to `keep` versions
+fn push_versions(sandbox: &Sandbox, contents: &[&str], keep: &str) {
+    for content in contents {
+        sandbox.write("notes.txt", *content);
+        sandbox.ok(["notes.txt", "--name", "notes", "--version-tag", keep]);
+    }
+}
+
+/// Versions of `notes` in the index, lowest first
+fn versions(sandbox: &Sandbox) -> Vec<u64> {
+    let mut versions: Vec<u64> = sandbox.index()["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|meta| meta["name"] == "notes")
+        .map(|meta| meta["version"].as_u64().unwrap())
+        .collect();
+    versions.sort();
+    versions
+}
+
+#[test]
+fn each_push_under_the_name_is_a_new_version() {
+    let sandbox = Sandbox::new();
+
+    push_versions(&sandbox, &["one", "two", "three"], "5");
+
+    assert_eq!(versions(&sandbox), [1, 2, 3]);
+    let list = sandbox.ok(["--list"]);
+    for version in ["notes v1", "notes v2", "notes v3"] {
+        assert!(list.contains(version), "{}", list);
+    }
+}
+
+#[test]
+fn the_name_resolves_to_the_latest_version() {
+    let sandbox = Sandbox::new();
+    push_versions(&sandbox, &["one", "two", "three"], "5");
+
+    sandbox.ok(["pop", "notes"]);
+
+    assert_eq!(sandbox.read("notes.txt"), "three");
+    assert_eq!(versions(&sandbox), [1, 2]);
+}
+
+#[test]
+fn entry_version_pops_that_version() {
+    let sandbox = Sandbox::new();
+    push_versions(&sandbox, &["one", "two", "three"], "5");
+
+    sandbox.ok(["pop", "notes", "--entry-version", "2"]);
+
+    assert_eq!(sandbox.read("notes.txt"), "two");
+    assert_eq!(versions(&sandbox), [1, 3]);
+}
+
+#[test]
+fn a_missing_version_lists_the_ones_there_are() {
+    let sandbox = Sandbox::new();
+    push_versions(&sandbox, &["one", "two"], "5");
+
+    let output = sandbox.fails(["pop", "notes", "--entry-version", "7"]);
+
+    assert_eq!(output.status.code(), Some(3));
+    assert!(
+        stderr(&output).contains("versions: 1, 2"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(versions(&sandbox), [1, 2]);
+}
+
+#[test]
+fn the_oldest_versions_beyond_the_limit_are_deleted() {
+    let sandbox = Sandbox::new();
+    push_versions(&sandbox, &["one", "two"], "2");
+    let oldest = sandbox.index()["entries"][0]["uuid"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    sandbox.write("notes.txt", "three");
+    let output = sandbox.run(["notes.txt", "--name", "notes", "--version-tag", "2"]);
+
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Deleted version 1 of 'notes'"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(versions(&sandbox), [2, 3]);
+    assert!(!sandbox.entry_dir(&oldest).exists());
+}